@@ -2,11 +2,13 @@ mod persist;
 mod story;
 
 pub use persist::{
-    BackerUpper, BackupResult, MemIndex, PersistError, PersistLocation, Shard, Storage,
-    StorageFetch, StorageSummary, StorageWriter, StoryIndex, StoryQuery, StoryScrapePayload,
+    BackerUpper, BackupResult, Durability, ImportResult, MemIndex, MigrationResult, PersistError,
+    PersistLocation, SearchFacets, Shard, ShardLocation, SnapshotResult, Storage, StorageFetch,
+    StorageSummary, StorageWriter, StoryIndex, StoryQuery, StoryScrapePayload,
 };
 pub use story::{
-    Story, StoryEvaluator, StoryIdentifier, StoryRender, StoryScore, StoryScoreConfig, TagSet,
+    EvaluatedStory, ScrapeProvenance, SearchWeights, Story, StoryEngagement, StoryEvaluator,
+    StoryIdScheme, StoryIdentifier, StoryRender, StoryScore, StoryScoreConfig, StoryScorer, TagSet,
     TaggerConfig,
 };
 
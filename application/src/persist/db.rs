@@ -210,6 +210,28 @@ impl DB {
         }
     }
 
+    pub fn delete<T: Serialize>(&self, id: String) -> Result<usize, PersistError> {
+        let sql = format!(
+            "delete from {}.{} where id = ?",
+            Self::schema_for(None),
+            Self::table_for::<T>()
+        );
+        Ok(self
+            .connection
+            .lock()
+            .expect("Poisoned")
+            .execute(&sql, [id])?)
+    }
+
+    /// Write a consistent copy of the database to `path`, which must not exist.
+    pub fn snapshot_to(&self, path: &Path) -> Result<(), PersistError> {
+        self.connection
+            .lock()
+            .expect("Poisoned")
+            .execute("vacuum into ?", [path.to_string_lossy()])?;
+        Ok(())
+    }
+
     pub fn execute_raw(&self, sql: &str) -> Result<(), PersistError> {
         self.connection
             .lock()
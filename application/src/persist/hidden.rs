@@ -0,0 +1,77 @@
+use std::{collections::HashSet, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{PersistError, StoryIdentifier};
+
+use super::{db::DB, PersistLocation};
+
+pub(crate) const HIDDEN_STORIES_FILE: &str = "hidden.sqlite3";
+
+/// Stories that have been manually hidden from the front page and search results (spam, mistaken merges, etc). The
+/// full set is small, so it is cached in memory and written through to the database.
+pub struct HiddenStories {
+    db: DB,
+    ids: HashSet<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct HiddenStory {
+    id: String,
+}
+
+impl HiddenStories {
+    pub fn new(location: &PersistLocation) -> Result<Self, PersistError> {
+        let db = match location {
+            PersistLocation::Memory => DB::open(":memory:")?,
+            PersistLocation::Path(path) | PersistLocation::Sharded(path, _) => {
+                std::fs::create_dir_all(path)?;
+                DB::open(path.join(HIDDEN_STORIES_FILE))?
+            }
+        };
+        db.create_table::<HiddenStory>()?;
+        db.create_unique_index::<HiddenStory>("idx_id", &["id"])?;
+        let ids = db
+            .query_raw::<HiddenStory>(&format!("select * from {}", DB::table_for::<HiddenStory>()))?
+            .into_iter()
+            .map(|hidden| hidden.id)
+            .collect();
+        Ok(Self { db, ids })
+    }
+
+    /// Hide a story, returning false if it was already hidden.
+    pub fn hide(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        let id = id.to_base64();
+        if self.ids.contains(&id) {
+            return Ok(false);
+        }
+        self.db.store(&HiddenStory { id: id.clone() })?;
+        self.ids.insert(id);
+        Ok(true)
+    }
+
+    /// Un-hide a story, returning false if it wasn't hidden.
+    pub fn unhide(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        let id = id.to_base64();
+        if !self.ids.contains(&id) {
+            return Ok(false);
+        }
+        self.db.delete::<HiddenStory>(id.clone())?;
+        self.ids.remove(&id);
+        Ok(true)
+    }
+
+    pub fn contains(&self, id: &StoryIdentifier) -> bool {
+        self.ids.contains(&id.to_base64())
+    }
+
+    /// The base64-encoded IDs of all hidden stories.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.ids.iter().map(String::as_str)
+    }
+
+    /// Copy the database into the snapshot directory at `path`.
+    pub fn snapshot(&self, path: &Path) -> Result<(), PersistError> {
+        self.db.snapshot_to(&path.join(HIDDEN_STORIES_FILE))
+    }
+}
@@ -0,0 +1,111 @@
+//! Query latency benchmarks over a fixed-size synthetic index. These are slow, so they only run when
+//! `ENABLE_SLOW_TESTS` is set, eg: `ENABLE_SLOW_TESTS=1 cargo test --release bench -- --nocapture`.
+use std::time::{Duration, Instant};
+
+use progscrape_scrapers::{hacker_news::HackerNewsStory, StoryDate, StoryUrl, TypedScrape};
+use rstest::*;
+
+use crate::{
+    test::*, MemIndex, PersistLocation, Shard, Storage, StoryEvaluator, StoryIndex, StoryQuery,
+};
+
+/// The number of stories in the benchmark index.
+const STORY_COUNT: usize = 20_000;
+/// The number of times each query is run.
+const ITERATIONS: usize = 200;
+/// The number of stories each query fetches, matching the front page and search pages.
+const FETCH_COUNT: usize = 30;
+
+const WORDS: [&str; 12] = [
+    "rust",
+    "python",
+    "database",
+    "compiler",
+    "linux",
+    "kernel",
+    "release",
+    "security",
+    "browser",
+    "performance",
+    "garbage",
+    "collector",
+];
+
+/// Deterministic scrapes spread over two years of shards.
+fn scrapes() -> impl Iterator<Item = TypedScrape> {
+    let start = StoryDate::year_month_day(2022, 1, 1).expect("Date");
+    (0..STORY_COUNT).map(move |i| {
+        let title = (0..4)
+            .map(|j| WORDS[(i * 7 + j * 5) % WORDS.len()])
+            .collect::<Vec<_>>()
+            .join(" ");
+        let url = StoryUrl::parse(format!("https://domain-{}.com/{}", i % 100, i)).expect("URL");
+        let date = StoryDate::from_seconds(start.timestamp() + (i as i64) * 3_000).expect("Date");
+        let mut scrape = HackerNewsStory::new_with_defaults(i.to_string(), date, title, url);
+        scrape.data.points = (i % 500) as u32;
+        scrape.data.comments = (i % 100) as u32;
+        scrape.into()
+    })
+}
+
+/// Run the query repeatedly, returning the (p50, p99) latencies.
+fn measure<F: FnMut() -> usize>(mut f: F) -> (Duration, Duration) {
+    let mut timings = (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            assert!(f() > 0, "Query returned no stories");
+            start.elapsed()
+        })
+        .collect::<Vec<_>>();
+    timings.sort();
+    (
+        timings[ITERATIONS / 2],
+        timings[(ITERATIONS * 99 / 100).min(ITERATIONS - 1)],
+    )
+}
+
+#[rstest]
+fn bench_queries(
+    _enable_tracing: &bool,
+    enable_slow_tests: &bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !enable_slow_tests {
+        tracing::error!("Ignoring test because enable_slow_tests is not set");
+        return Ok(());
+    }
+    let eval = StoryEvaluator::new_for_test();
+    let mut memindex = MemIndex::default();
+    memindex.insert_scrapes(scrapes())?;
+    let mut index = StoryIndex::new(PersistLocation::Memory)?;
+    index.insert_scrape_collections(&eval, memindex.get_all_stories())?;
+    assert_eq!(STORY_COUNT, index.story_count()?.total.story_count);
+
+    let mut results = vec![];
+    results.push((
+        "query_frontpage_hot_set".to_owned(),
+        measure(|| {
+            index
+                .fetch::<Shard>(StoryQuery::FrontPage(), FETCH_COUNT)
+                .expect("Front page query failed")
+                .len()
+        }),
+    ));
+    for search in ["rust", "compiler", "domain-42.com"] {
+        results.push((
+            format!("query_search({})", search),
+            measure(|| {
+                index
+                    .fetch::<Shard>(StoryQuery::from_search(&eval.tagger, search), FETCH_COUNT)
+                    .expect("Search query failed")
+                    .len()
+            }),
+        ));
+    }
+
+    println!("{:<30} {:>12} {:>12}", "query", "p50", "p99");
+    for (name, (p50, p99)) in results {
+        println!("{:<30} {:>12.3?} {:>12.3?}", name, p50, p99);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use progscrape_scrapers::TypedScrape;
+
+use crate::{PersistError, StoryEvaluator, StoryIndex};
+
+/// The tradeoff between durability and throughput when inserting scrapes. Every commit writes new segments and
+/// reloads the readers of each shard it touches, so committing every insert is the slowest mode, but nothing is lost
+/// if the process dies. Batching buffers scrapes in memory and commits them together, which is much cheaper for many
+/// small inserts, but buffered scrapes are lost on a crash and don't appear in searches until they are committed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "mode")]
+pub enum Durability {
+    /// Commit every insert.
+    #[default]
+    Immediate,
+    /// Buffer inserts, committing once `max_scrapes` are buffered or the oldest has waited for `interval_secs`. The
+    /// interval is only checked on insert, so [`StoryIndex::flush`] should also be called on a timer.
+    Batch {
+        max_scrapes: usize,
+        interval_secs: u64,
+    },
+}
+
+/// Scrapes inserted under [`Durability::Batch`] that haven't been committed yet.
+#[derive(Default)]
+pub(super) struct PendingScrapes {
+    scrapes: Vec<TypedScrape>,
+    since: Option<Instant>,
+}
+
+impl StoryIndex {
+    /// Set how eagerly inserted scrapes are committed. Switching to [`Durability::Immediate`] commits any buffered
+    /// scrapes on the next insert or flush.
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
+
+    /// The number of inserted scrapes waiting to be committed.
+    pub fn pending_scrapes(&self) -> usize {
+        self.pending.scrapes.len()
+    }
+
+    /// Commit any buffered scrapes, returning the number committed.
+    pub fn flush(&mut self, eval: &StoryEvaluator) -> Result<usize, PersistError> {
+        let scrapes = std::mem::take(&mut self.pending).scrapes;
+        let count = scrapes.len();
+        if count > 0 {
+            tracing::info!("Flushing {} buffered scrape(s)", count);
+            self.store_and_index_scrapes(eval, scrapes.into_iter(), false)?;
+        }
+        Ok(count)
+    }
+
+    /// Insert scrapes, or buffer them if batching and the batch isn't yet full or due.
+    pub(super) fn insert_or_buffer_scrapes<I: Iterator<Item = TypedScrape>>(
+        &mut self,
+        eval: &StoryEvaluator,
+        scrapes: I,
+    ) -> Result<(), PersistError> {
+        match self.durability {
+            Durability::Immediate => {
+                let pending = std::mem::take(&mut self.pending).scrapes;
+                self.store_and_index_scrapes(eval, pending.into_iter().chain(scrapes), false)
+            }
+            Durability::Batch {
+                max_scrapes,
+                interval_secs,
+            } => {
+                let since = *self.pending.since.get_or_insert_with(Instant::now);
+                self.pending.scrapes.extend(scrapes);
+                if self.pending.scrapes.len() >= max_scrapes
+                    || since.elapsed() >= Duration::from_secs(interval_secs)
+                {
+                    self.flush(eval)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for StoryIndex {
+    fn drop(&mut self) {
+        if !self.pending.scrapes.is_empty() {
+            tracing::warn!(
+                "Dropping {} buffered scrape(s) that were never flushed",
+                self.pending.scrapes.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PersistLocation, Storage, StorageWriter};
+    use progscrape_scrapers::{hacker_news::HackerNewsStory, StoryDate, StoryUrl};
+
+    fn scrapes(ids: std::ops::Range<usize>) -> impl Iterator<Item = TypedScrape> {
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        ids.map(move |i| {
+            let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+            HackerNewsStory::new_with_defaults(i.to_string(), date, "I love Rust".into(), url)
+                .into()
+        })
+    }
+
+    #[test]
+    fn test_batch_durability() -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let count = |index: &StoryIndex| -> Result<usize, PersistError> {
+            Ok(index.story_count()?.total.story_count)
+        };
+
+        // Every insert is committed by default
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.insert_scrapes(&eval, scrapes(0..1))?;
+        assert_eq!(1, count(&index)?);
+
+        // Batches are deferred until they fill up...
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_durability(Durability::Batch {
+            max_scrapes: 3,
+            interval_secs: 3600,
+        });
+        index.insert_scrapes(&eval, scrapes(0..1))?;
+        index.insert_scrapes(&eval, scrapes(1..2))?;
+        assert_eq!(0, count(&index)?);
+        assert_eq!(2, index.pending_scrapes());
+        index.insert_scrapes(&eval, scrapes(2..3))?;
+        assert_eq!(3, count(&index)?);
+        assert_eq!(0, index.pending_scrapes());
+
+        // ... or are flushed
+        index.insert_scrapes(&eval, scrapes(3..4))?;
+        assert_eq!(3, count(&index)?);
+        assert_eq!(1, index.flush(&eval)?);
+        assert_eq!(4, count(&index)?);
+        assert_eq!(0, index.flush(&eval)?);
+
+        // An elapsed interval commits on the next insert
+        index.set_durability(Durability::Batch {
+            max_scrapes: 100,
+            interval_secs: 0,
+        });
+        index.insert_scrapes(&eval, scrapes(4..5))?;
+        assert_eq!(5, count(&index)?);
+
+        // Leftovers are committed when switching back
+        index.set_durability(Durability::Batch {
+            max_scrapes: 100,
+            interval_secs: 3600,
+        });
+        index.insert_scrapes(&eval, scrapes(5..6))?;
+        index.set_durability(Durability::Immediate);
+        index.insert_scrapes(&eval, scrapes(6..7))?;
+        assert_eq!(7, count(&index)?);
+
+        Ok(())
+    }
+}
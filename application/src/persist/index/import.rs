@@ -0,0 +1,108 @@
+use serde::Serialize;
+
+use progscrape_scrapers::TypedScrape;
+
+use crate::{MemIndex, PersistError, Storage, StoryEvaluator, StoryIndex};
+
+/// Story counts from importing scrapes into an existing index.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ImportResult {
+    pub scrapes: usize,
+    /// The number of stories that weren't already in the index.
+    pub added: usize,
+    /// The number of stories that were merged into ones already in the index.
+    pub merged: usize,
+}
+
+impl StoryIndex {
+    /// Merge a large set of scrapes, such as the legacy import, into the index. Stories that are already indexed gain
+    /// any scrapes they were missing rather than being duplicated, so importing the same scrapes again is harmless.
+    /// Unlike regular scrapes, old stories are added regardless of the maximum story age.
+    pub fn import_scrapes(
+        &mut self,
+        eval: &StoryEvaluator,
+        scrapes: Vec<TypedScrape>,
+    ) -> Result<ImportResult, PersistError> {
+        let mut memindex = MemIndex::default();
+        memindex.insert_scrapes(scrapes.iter().cloned())?;
+        let stories = memindex.get_all_stories().count();
+
+        let before = self.story_count()?.total.story_count;
+        let count = scrapes.len();
+        self.store_and_index_scrapes(eval, scrapes.into_iter(), true)?;
+        let added = self.story_count()?.total.story_count.saturating_sub(before);
+
+        let result = ImportResult {
+            scrapes: count,
+            added,
+            merged: stories.saturating_sub(added),
+        };
+        tracing::info!("Imported {:?}", result);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PersistLocation, Shard, StorageWriter, StoryQuery};
+    use progscrape_scrapers::{
+        hacker_news::HackerNewsStory, reddit::RedditStory, ScrapeConfig, StoryDate, StoryUrl,
+    };
+
+    #[test]
+    fn test_reimport() -> Result<(), Box<dyn std::error::Error>> {
+        // Old stories would normally be dropped as stale
+        let scrape_config = ScrapeConfig {
+            max_story_age_days: Some(30),
+            ..Default::default()
+        };
+        let eval = StoryEvaluator::new(&Default::default(), &Default::default(), &scrape_config);
+        let date = StoryDate::year_month_day(2015, 1, 1).expect("Date");
+        let url = |i: usize| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        let hn = |i: usize| -> TypedScrape {
+            HackerNewsStory::new_with_defaults(i.to_string(), date, "I love Rust".into(), url(i))
+                .into()
+        };
+        let reddit = |i: usize| -> TypedScrape {
+            RedditStory::new_with_defaults(i.to_string(), date, "I love Rust".into(), url(i)).into()
+        };
+
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.insert_scrapes(&eval, (0..3).map(hn))?;
+        assert_eq!(0, index.story_count()?.total.story_count);
+        let result = index.import_scrapes(&eval, (0..3).map(hn).collect())?;
+        assert_eq!(
+            ImportResult {
+                scrapes: 3,
+                added: 3,
+                merged: 0
+            },
+            result
+        );
+
+        // Re-importing an updated set merges into the existing stories, and adds the new one
+        let scrapes = (0..4).map(hn).chain((0..2).map(reddit)).collect();
+        let result = index.import_scrapes(&eval, scrapes)?;
+        assert_eq!(
+            ImportResult {
+                scrapes: 6,
+                added: 1,
+                merged: 3
+            },
+            result
+        );
+        let stories = index.fetch::<Shard>(StoryQuery::ByShard(Shard::from_date_time(date)), 10)?;
+        assert_eq!(4, stories.len());
+        for story in stories {
+            let expected = if story.url == url(0) || story.url == url(1) {
+                2
+            } else {
+                1
+            };
+            assert_eq!(expected, story.scrapes.len(), "{:?}", story.url);
+        }
+
+        Ok(())
+    }
+}
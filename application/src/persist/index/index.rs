@@ -1,30 +1,38 @@
 use itertools::Itertools;
 
-use tantivy::collector::TopDocs;
-use tantivy::query::{AllQuery, BooleanQuery, Occur, PhraseQuery, Query, TermQuery};
-use tantivy::{schema::*, DocAddress, IndexWriter, Searcher};
+use rand::Rng;
+use tantivy::collector::{Count, DocSetCollector, FacetCollector, FilterCollector, TopDocs};
+use tantivy::query::{AllQuery, BooleanQuery, BoostQuery, Occur, PhraseQuery, Query, TermQuery};
+use tantivy::{schema::*, DocAddress, IndexWriter, Searcher, SegmentReader};
 
-use progscrape_scrapers::{ScrapeCollection, StoryDate, StoryUrl, TypedScrape};
+use progscrape_scrapers::{
+    ScrapeCollection, ScrapeId, ScrapeSource, StoryDate, StoryUrl, TypedScrape, TypedScrapeMap,
+};
 
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
+use crate::persist::hidden::HiddenStories;
 use crate::persist::index::indexshard::{StoryIndexShard, StoryLookup, StoryLookupId};
+use crate::persist::pinned::PinnedStories;
+use crate::persist::read_time::ReadTimes;
 use crate::persist::scrapestore::ScrapeStore;
 use crate::persist::shard::{ShardOrder, ShardRange};
-use crate::persist::{Shard, ShardSummary, StorageFetch, StoryQuery};
-use crate::story::{StoryCollector, TagSet};
+use crate::persist::{SearchFacets, Shard, ShardSummary, StorageFetch, StoryQuery};
+use crate::story::{StoryCollector, StoryScrapeId};
 use crate::{
-    timer_end, timer_start, MemIndex, PersistError, PersistLocation, Storage, StorageSummary,
-    StorageWriter, Story, StoryEvaluator, StoryIdentifier,
+    timer_end, timer_start, MemIndex, PersistError, PersistLocation, SearchWeights, Storage,
+    StorageSummary, StorageWriter, Story, StoryEvaluator, StoryIdScheme, StoryIdentifier,
 };
 
+use super::durability::{Durability, PendingScrapes};
 use super::indexshard::StoryInsert;
-use super::schema::StorySchema;
+use super::schema::{StorySchema, FACETS, FACET_DOMAIN, FACET_SOURCE, FACET_TAG, SCHEMA_VERSION};
 
 const STORY_INDEXING_CHUNK_SIZE: usize = 10000;
 const SCRAPE_PROCESSING_CHUNK_SIZE: usize = 1000;
+pub(crate) const SCHEMA_VERSION_FILE: &str = "schema_version";
 
 struct IndexCache {
     cache: HashMap<Shard, Arc<RwLock<StoryIndexShard>>>,
@@ -52,9 +60,24 @@ impl IndexCache {
     }
 }
 
+/// A story in an index that is being rebuilt, which the story rebuilt from its scrapes takes over.
+#[derive(Clone, Debug)]
+pub(crate) struct PreviousStory {
+    pub id: StoryIdentifier,
+    pub first_seen: Option<StoryDate>,
+}
+
 pub struct StoryIndex {
     index_cache: Arc<RwLock<IndexCache>>,
-    scrape_db: ScrapeStore,
+    pub(super) scrape_db: ScrapeStore,
+    pub(super) hidden: HiddenStories,
+    pub(super) pinned: PinnedStories,
+    pub(super) read_times: ReadTimes,
+    search_weights: SearchWeights,
+    id_scheme: StoryIdScheme,
+    pub(super) durability: Durability,
+    pub(super) pending: PendingScrapes,
+    commit_batch_size: Option<usize>,
     schema: StorySchema,
 }
 
@@ -84,16 +107,36 @@ impl WriterProvider {
 
 impl StoryIndex {
     pub fn new(location: PersistLocation) -> Result<Self, PersistError> {
+        Self::check_schema_version(&location, SCHEMA_VERSION)?;
+        Self::open_unchecked(location)
+    }
+
+    /// Open the index without checking its schema version, which is only safe for reading raw scrapes.
+    pub(crate) fn open_unchecked(location: PersistLocation) -> Result<Self, PersistError> {
         // TODO: This start date needs to be dynamic
         let scrape_db = ScrapeStore::new(location.clone())?;
+        let hidden = HiddenStories::new(&location)?;
+        let pinned = PinnedStories::new(&location)?;
+        let read_times = ReadTimes::new(&location)?;
         tracing::info!("Initialized StoryIndex at {:?}", location);
 
         // Determine the min/max shard, if any
         let mut range = ShardRange::default();
-        if let PersistLocation::Path(path) = &location {
-            for d in std::fs::read_dir(path)?.flatten() {
+        for root in location.shard_roots() {
+            if !root.exists() {
+                continue;
+            }
+            for d in std::fs::read_dir(root)?.flatten() {
                 if let Some(s) = d.file_name().to_str() {
                     if let Some(shard) = Shard::from_string(s) {
+                        if location.shard_path(shard).as_deref() != Some(d.path().as_path()) {
+                            tracing::warn!(
+                                "Ignoring shard {} at {:?}, which isn't where it's configured to be",
+                                s,
+                                d.path()
+                            );
+                            continue;
+                        }
                         range.include(shard);
                     }
                 }
@@ -111,12 +154,47 @@ impl StoryIndex {
                 most_recent_story: None,
             })),
             scrape_db,
+            hidden,
+            pinned,
+            read_times,
+            search_weights: Default::default(),
+            id_scheme: Default::default(),
+            durability: Default::default(),
+            pending: Default::default(),
+            commit_batch_size: None,
             schema,
         };
 
         Ok(new)
     }
 
+    /// Ensure that the index at this location was written with the expected schema version, stamping it if it is
+    /// unversioned. Indexes created before versioning was introduced are assumed to have the original schema.
+    pub(crate) fn check_schema_version(
+        location: &PersistLocation,
+        expected: u32,
+    ) -> Result<(), PersistError> {
+        let (PersistLocation::Path(path) | PersistLocation::Sharded(path, _)) = location else {
+            return Ok(());
+        };
+        let version_path = path.join(SCHEMA_VERSION_FILE);
+        if version_path.exists() {
+            let version = std::fs::read_to_string(&version_path)?;
+            let found = version.trim().parse().map_err(|_| {
+                PersistError::UnexpectedError(format!("Invalid schema version '{}'", version))
+            })?;
+            if found != expected {
+                let error = PersistError::SchemaVersionMismatch { found, expected };
+                tracing::error!("Unable to open index at {:?}: {}", path, error);
+                return Err(error);
+            }
+        } else {
+            std::fs::create_dir_all(path)?;
+            std::fs::write(&version_path, expected.to_string())?;
+        }
+        Ok(())
+    }
+
     pub fn shards(&self) -> ShardRange {
         self.index_cache.read().expect("Poisoned").range
     }
@@ -126,6 +204,218 @@ impl StoryIndex {
         lock.get_shard(shard)
     }
 
+    /// Hide a story from the front page and search results, returning false if it was already hidden.
+    pub fn hide_story(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        self.hidden.hide(id)
+    }
+
+    /// Restore a hidden story, returning false if it wasn't hidden.
+    pub fn unhide_story(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        self.hidden.unhide(id)
+    }
+
+    pub fn is_story_hidden(&self, id: &StoryIdentifier) -> bool {
+        self.hidden.contains(id)
+    }
+
+    /// Pin a story to the top of the front page after any already-pinned stories, returning false if it was already
+    /// pinned.
+    pub fn pin_story(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        self.pinned.pin(id)
+    }
+
+    /// Remove a story from the top of the front page, returning false if it wasn't pinned.
+    pub fn unpin_story(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        self.pinned.unpin(id)
+    }
+
+    pub fn is_story_pinned(&self, id: &StoryIdentifier) -> bool {
+        self.pinned.contains(id)
+    }
+
+    /// The IDs of all pinned stories, in the order they were pinned.
+    pub fn pinned_stories(&self) -> Vec<StoryIdentifier> {
+        self.pinned.ids().collect()
+    }
+
+    /// Record the estimated read time of a story's linked article.
+    pub fn set_read_time(
+        &mut self,
+        id: &StoryIdentifier,
+        minutes: u32,
+    ) -> Result<(), PersistError> {
+        self.read_times.set(id, minutes)
+    }
+
+    /// Permanently delete a story's document and its underlying scrapes, returning false if the story doesn't
+    /// exist. If a source still lists the story, a later scrape will re-create it.
+    pub fn delete_story(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        let shard = Shard::from_year_month(id.year(), id.month());
+        let Some((_, doc_address)) = self.with_searcher(shard, self.fetch_by_id(id))??.pop() else {
+            return Ok(false);
+        };
+        let story = self.with_index(shard, |_, index| index.lookup_story(doc_address))??;
+        let term = Term::from_field_text(self.schema.id_field, &id.to_base64());
+        self.with_writers(|provider| {
+            provider.provide(shard, |_, _, writer| {
+                writer.delete_term(term);
+                Ok(())
+            })
+        })?;
+
+        // Confirm that the document is gone before discarding the scrapes it was built from
+        if !self.with_searcher(shard, self.fetch_by_id(id))??.is_empty() {
+            return Err(PersistError::UnexpectedError(format!(
+                "Story {} still present after deletion",
+                id
+            )));
+        }
+        let count = self.scrape_db.delete_scrape_batch(story.scrape_ids)?;
+        tracing::info!("Deleted story {} and {} scrape(s)", id, count);
+        self.hidden.unhide(id)?;
+        self.pinned.unpin(id)?;
+        self.read_times.remove(id)?;
+        Ok(true)
+    }
+
+    /// Set the blend of relevance and recency used to rank search results.
+    pub fn set_search_weights(&mut self, weights: SearchWeights) {
+        self.search_weights = weights;
+    }
+
+    /// Commit bulk inserts of scrape collections (ie: when initializing an index) every `size` stories, bounding the
+    /// memory held by uncommitted writes. If unset, each bulk insert is a single commit.
+    pub fn set_commit_batch_size(&mut self, size: Option<usize>) {
+        self.commit_batch_size = size.filter(|size| *size > 0);
+    }
+
+    /// Set how story IDs are chosen when stories are re-written.
+    pub fn set_id_scheme(&mut self, id_scheme: StoryIdScheme) {
+        self.id_scheme = id_scheme;
+    }
+
+    /// Find the current ID of a story. Under [`StoryIdScheme::Earliest`], merging in an earlier scrape can move a
+    /// story to an earlier date, so if there is no story with this ID we look for one with the same normalized URL in
+    /// the month before it.
+    pub fn resolve_story_id(
+        &self,
+        id: &StoryIdentifier,
+    ) -> Result<Option<StoryIdentifier>, PersistError> {
+        let shard = Shard::from_year_month(id.year(), id.month());
+        let candidates = [shard, shard.sub_months(1)];
+        let shards = self
+            .shards()
+            .iterate(ShardOrder::NewestFirst)
+            .filter(|shard| candidates.contains(shard))
+            .collect_vec();
+        if shards.contains(&shard) && !self.with_searcher(shard, self.fetch_by_id(id))??.is_empty()
+        {
+            return Ok(Some(id.clone()));
+        }
+
+        let Some(date) =
+            StoryDate::year_month_day(id.year() as i32, id.month() as u32, id.day() as u32)
+        else {
+            return Ok(None);
+        };
+        let one_month = Duration::from_secs(60 * 60 * 24 * 30).as_secs() as i64;
+        let lookup = StoryLookupId {
+            url_norm_hash: id.norm.hash(),
+            date: date.timestamp(),
+        };
+        for shard in shards {
+            let result = self.with_index(shard, |_, index| {
+                index.lookup_stories(HashSet::from_iter([lookup]), (-one_month)..one_month)
+            })??;
+            if let Some(StoryLookup::Found(_, doc_address)) = result.into_iter().next() {
+                let story =
+                    self.with_index(shard, |_, index| index.lookup_story(doc_address))??;
+                return Ok(StoryIdentifier::from_base64(story.id));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Re-evaluate and re-write every story that has at least one scrape from the given source, leaving all other
+    /// stories untouched. Returns the number of stories that were re-written.
+    pub fn reingest_source(
+        &mut self,
+        eval: &StoryEvaluator,
+        source: ScrapeSource,
+    ) -> Result<usize, PersistError> {
+        let mut stories = vec![];
+        for shard in self.shards().iterate(ShardOrder::OldestFirst) {
+            for (shard, doc_address) in self.with_searcher(shard, self.fetch_by_segment())? {
+                let story =
+                    self.with_index(shard, |_, index| index.lookup_story(doc_address))??;
+                if story.scrape_ids.iter().any(|id| id.id.source == source) {
+                    stories.push((shard, story));
+                }
+            }
+        }
+
+        let count = stories.len();
+        tracing::info!("Re-ingesting {} story(s) with {:?} scrapes", count, source);
+        let start = timer_start!();
+        self.with_writers(|provider| {
+            for (shard, story) in stories {
+                let id = story.id;
+                let scrapes = self.scrape_db.fetch_scrape_batch(story.scrape_ids)?;
+                let mut collection =
+                    ScrapeCollection::new_from_iter(scrapes.into_values().flatten());
+                let dropped = Self::limit_scrapes(eval, &mut collection);
+                if !dropped.is_empty() {
+                    self.scrape_db
+                        .insert_scrape_batch(collection.scrapes.values())?;
+                    self.scrape_db.delete_scrape_batch(dropped)?;
+                }
+                let mut doc = Self::create_story_insert(eval, &collection);
+                doc.first_seen = story.first_seen;
+                provider.provide(shard, |_, _, writer| {
+                    writer.delete_term(Term::from_field_text(self.schema.id_field, &id));
+                    Ok(())
+                })?;
+                // A stable ID stays in the shard it was created in, as that's where lookups by ID will find it
+                let new_shard = match self.id_scheme {
+                    StoryIdScheme::Earliest => Shard::from_date_time(collection.earliest),
+                    StoryIdScheme::Stable => {
+                        doc.id = id;
+                        shard
+                    }
+                };
+                provider.provide(new_shard, move |_, index, writer| {
+                    index.insert_story_document(writer, doc)?;
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+        timer_end!(start, "Re-ingested {} story(s)", count);
+
+        Ok(count)
+    }
+
+    /// Wraps a query so that it doesn't match any hidden stories.
+    fn exclude_hidden(&self, query: Box<dyn Query>) -> Box<dyn Query> {
+        let mut clauses = self
+            .hidden
+            .ids()
+            .map(|id| {
+                let term = TermQuery::new(
+                    Term::from_field_text(self.schema.id_field, id),
+                    IndexRecordOption::Basic,
+                );
+                (Occur::MustNot, Box::new(term) as Box<dyn Query>)
+            })
+            .collect_vec();
+        if clauses.is_empty() {
+            query
+        } else {
+            clauses.push((Occur::Must, query));
+            Box::new(BooleanQuery::new(clauses))
+        }
+    }
+
     /// Borrow the scrape database for a period of time.
     pub fn with_scrapes<F: FnOnce(&ScrapeStore) -> T, T>(&self, f: F) -> T {
         f(&self.scrape_db)
@@ -143,7 +433,7 @@ impl StoryIndex {
     }
 
     #[inline(always)]
-    fn with_index<F: FnMut(Shard, &StoryIndexShard) -> T, T>(
+    pub(super) fn with_index<F: FnMut(Shard, &StoryIndexShard) -> T, T>(
         &self,
         shard: Shard,
         mut f: F,
@@ -202,7 +492,31 @@ impl StoryIndex {
         )
     }
 
-    fn create_story_insert<'a>(eval: &StoryEvaluator, story: &'a ScrapeCollection) -> StoryInsert {
+    /// Drop scrapes from a story until it is within the configured limit, returning the IDs of the dropped scrapes so
+    /// that they can be removed from the scrape store.
+    fn limit_scrapes(eval: &StoryEvaluator, story: &mut ScrapeCollection) -> Vec<StoryScrapeId> {
+        let Some(max) = eval.extractor.max_scrapes_per_story() else {
+            return vec![];
+        };
+        let before = story
+            .scrapes
+            .values()
+            .map(|scrape| StoryScrapeId {
+                id: scrape.id.clone(),
+                shard: Shard::from_date_time(scrape.date),
+            })
+            .collect_vec();
+        if story.limit(max) == 0 {
+            return vec![];
+        }
+        before
+            .into_iter()
+            .filter(|id| !story.scrapes.contains_key(&id.id))
+            .collect()
+    }
+
+    /// Create the document for a story. The story should already have been limited with [`Self::limit_scrapes`].
+    fn create_story_insert(eval: &StoryEvaluator, story: &ScrapeCollection) -> StoryInsert {
         // TODO: We could be creating the doc directly here instead of allocating
         let extracted = story.extract(&eval.extractor);
         let score = eval.scorer.score(&extracted);
@@ -213,43 +527,85 @@ impl StoryIndex {
             .map(Self::create_scrape_id_from_scrape)
             .collect_vec();
         let title = extracted.title().to_owned();
-        let mut tags = TagSet::new();
-        eval.tagger.tag(&title, &mut tags);
-        for tag in extracted.tags() {
-            tags.add(tag);
-        }
+        let tags = eval.tags(&extracted);
         let url = extracted.url();
         let id = StoryIdentifier::new(story.earliest, extracted.url().normalization()).to_base64();
         let doc = StoryInsert {
             id,
-            host: url.host().to_owned(),
+            host: eval.tagger.canonicalize(url.host()),
             url: url.raw().to_owned(),
             url_norm: url.normalization().string().to_owned(),
             url_norm_hash: url.normalization().hash(),
             score: score as f64,
             date: story.earliest.timestamp(),
             title,
+            sources: extracted.scrapes.keys().map(|id| id.source).collect(),
             scrape_ids,
             tags,
+            // Bulk imports don't know when a story was first seen, so assume it was on time. Migrations carry it over from
+            // the old index where they can.
+            first_seen: story.earliest.timestamp(),
         };
         doc
     }
 
+    /// If adding these scrapes to an existing story would take it over the configured limit, returns the story with
+    /// the scrapes that it should keep, and the IDs of the scrapes that it drops.
+    fn limit_existing_scrapes(
+        &self,
+        eval: &StoryEvaluator,
+        index: &StoryIndexShard,
+        doc: DocAddress,
+        scrapes: &ScrapeCollection,
+    ) -> Result<Option<(ScrapeCollection, Vec<StoryScrapeId>)>, PersistError> {
+        let Some(max) = eval.extractor.max_scrapes_per_story() else {
+            return Ok(None);
+        };
+        let existing = index.lookup_story(doc)?.scrape_ids;
+        let existing_ids: HashSet<_> = existing.iter().map(|id| &id.id).collect();
+        let added = scrapes
+            .scrapes
+            .keys()
+            .filter(|id| !existing_ids.contains(id))
+            .count();
+        if existing.len() + added <= max {
+            return Ok(None);
+        }
+        let existing = self.scrape_db.fetch_scrape_batch(existing)?;
+        let mut story = ScrapeCollection::new_from_iter(
+            existing
+                .into_values()
+                .flatten()
+                .chain(scrapes.scrapes.values().cloned()),
+        );
+        let dropped = Self::limit_scrapes(eval, &mut story);
+        tracing::debug!(
+            "Dropped {} scrape(s) from story over the limit",
+            dropped.len()
+        );
+        Ok(Some((story, dropped)))
+    }
+
     fn insert_scrape_batch<'a, I: Iterator<Item = TypedScrape> + 'a>(
         &mut self,
         eval: &StoryEvaluator,
         scrapes: I,
+        keep_stale: bool,
     ) -> Result<(), PersistError> {
         let one_month = Duration::from_secs(60 * 60 * 24 * 30).as_secs() as i64;
 
         let mut memindex = MemIndex::default();
         memindex.insert_scrapes(scrapes)?;
+        let now = StoryDate::now();
 
-        self.with_writers(|provider| {
-            for scrape in memindex.get_all_stories() {
+        let stale = self.with_writers(|provider| {
+            let mut stale = 0;
+            let mut indexed = vec![];
+            let mut evicted = vec![];
+            for mut scrape in memindex.get_all_stories() {
                 let shard = Shard::from_date_time(scrape.earliest);
                 // TODO: Should be searching multiple shards
-                provider.provide(shard, |_, index, writer| {
+                let dropped = provider.provide(shard, |_, index, writer| {
                     let lookup = StoryLookupId {
                         url_norm_hash: scrape.url().normalization().hash(),
                         date: scrape.earliest.timestamp(),
@@ -259,17 +615,40 @@ impl StoryIndex {
                     let result = index.lookup_stories(lookup, (-one_month)..one_month)?;
                     let lookup = result.into_iter().next().expect("TODO");
                     let insert_type = match lookup {
-                        StoryLookup::Found(_id, doc) => index.add_scrape_id(
-                            writer,
-                            doc,
-                            scrape
-                                .scrapes
-                                .values()
-                                .map(Self::create_scrape_id_from_scrape)
-                                .collect(),
-                        )?,
+                        StoryLookup::Found(_id, doc) => {
+                            let limited = self.limit_existing_scrapes(eval, index, doc, &scrape)?;
+                            let insert_type = index.add_scrape_id(
+                                writer,
+                                doc,
+                                scrape
+                                    .scrapes
+                                    .values()
+                                    .map(Self::create_scrape_id_from_scrape)
+                                    .collect(),
+                                scrape.scrapes.keys().map(|id| id.source).collect(),
+                                limited.as_ref().map(|(story, _)| {
+                                    story
+                                        .scrapes
+                                        .values()
+                                        .map(Self::create_scrape_id_from_scrape)
+                                        .collect()
+                                }),
+                            )?;
+                            // Store the scrapes the story kept, which absorb the engagement of the ones it dropped
+                            if let Some((story, dropped)) = limited {
+                                scrape = story;
+                                evicted.extend(dropped);
+                            }
+                            insert_type
+                        }
                         StoryLookup::Unfound(_id) => {
-                            let doc = Self::create_story_insert(eval, &scrape);
+                            // Old scrapes may only be merged into existing stories
+                            if !keep_stale && eval.extractor.is_stale(scrape.earliest, now) {
+                                return Ok(true);
+                            }
+                            evicted.extend(Self::limit_scrapes(eval, &mut scrape));
+                            let mut doc = Self::create_story_insert(eval, &scrape);
+                            doc.first_seen = now.timestamp();
                             index.insert_story_document(writer, doc)?
                         }
                     };
@@ -279,37 +658,103 @@ impl StoryIndex {
                         insert_type
                     );
 
-                    Ok(())
+                    Ok(false)
                 })?;
+                if dropped {
+                    stale += 1;
+                } else {
+                    indexed.extend(scrape.scrapes.into_values());
+                }
             }
-            Ok(())
+            // Only the scrapes behind indexed stories are stored, so rebuilding the index from the raw scrapes can't
+            // bring back stale stories
+            tracing::info!("Storing {} raw scrape(s)...", indexed.len());
+            self.scrape_db.insert_scrape_batch(indexed.iter())?;
+            // Scrapes dropped to keep stories within the limit aren't referenced by any story
+            if !evicted.is_empty() {
+                let count = self.scrape_db.delete_scrape_batch(evicted)?;
+                tracing::info!("Deleted {} raw scrape(s) over the per-story limit", count);
+            }
+            Ok(stale)
         })?;
+        if stale > 0 {
+            tracing::info!("Dropped {} stale story(s)", stale);
+        }
 
         Ok(())
     }
 
-    /// Insert a list of scrapes into the index.
+    /// Insert a list of scrapes into the index, committing them according to the durability mode.
     fn insert_scrapes<I: Iterator<Item = TypedScrape>>(
         &mut self,
         eval: &StoryEvaluator,
         scrapes: I,
     ) -> Result<(), PersistError> {
-        let v = scrapes.collect_vec();
+        self.insert_or_buffer_scrapes(eval, scrapes)
+    }
 
-        tracing::info!("Storing raw scrapes...");
-        self.scrape_db.insert_scrape_batch(v.iter())?;
+    /// Index the scrapes and store the raw scrapes behind them. Unless `keep_stale` is set, scrapes that would create a
+    /// new story older than the maximum story age are dropped, and aren't stored.
+    pub(super) fn store_and_index_scrapes<I: Iterator<Item = TypedScrape>>(
+        &mut self,
+        eval: &StoryEvaluator,
+        scrapes: I,
+        keep_stale: bool,
+    ) -> Result<(), PersistError> {
+        let (v, blocked): (Vec<_>, Vec<_>) =
+            scrapes.partition(|scrape| !eval.extractor.is_blocked(&scrape.url));
+        if !blocked.is_empty() {
+            tracing::info!("Dropped {} scrape(s) from blocked domains", blocked.len());
+        }
 
         tracing::info!("Indexing scrapes...");
-        self.insert_scrape_batch(eval, v.into_iter())?;
+        self.insert_scrape_batch(eval, v.into_iter(), keep_stale)?;
 
         Ok(())
     }
 
-    fn insert_scrape_collections<I: Iterator<Item = ScrapeCollection>>(
+    /// Index scrape collections as stories, committing in batches of the configured size. Returns the number of
+    /// commits.
+    pub(crate) fn insert_scrape_collections<I: Iterator<Item = ScrapeCollection>>(
         &mut self,
         eval: &StoryEvaluator,
         scrape_collections: I,
-    ) -> Result<(), PersistError> {
+    ) -> Result<usize, PersistError> {
+        self.insert_rebuilt_scrape_collections(eval, scrape_collections.map(|story| (story, None)))
+    }
+
+    /// Index scrape collections as stories in the same way as [`Self::insert_scrape_collections`], where each story
+    /// may replace one from an index that is being rebuilt. The story keeps the time that the previous story was first
+    /// seen, and under [`StoryIdScheme::Stable`] it keeps the previous story's ID.
+    pub(crate) fn insert_rebuilt_scrape_collections<
+        I: Iterator<Item = (ScrapeCollection, Option<PreviousStory>)>,
+    >(
+        &mut self,
+        eval: &StoryEvaluator,
+        scrape_collections: I,
+    ) -> Result<usize, PersistError> {
+        let start = std::time::Instant::now();
+        let batches = scrape_collections.chunks(self.commit_batch_size.unwrap_or(usize::MAX));
+        let (mut total, mut commits) = (0, 0);
+        for batch in &batches {
+            total += self.index_scrape_collections(eval, batch)?;
+            commits += 1;
+            tracing::info!(
+                "Committed {} stories in {} batch(es) ({:.0} stories/s)",
+                total,
+                commits,
+                total as f64 / start.elapsed().as_secs_f64().max(f64::EPSILON)
+            );
+        }
+        Ok(commits)
+    }
+
+    /// Index scrape collections as stories in a single commit, returning the number of stories.
+    fn index_scrape_collections<I: Iterator<Item = (ScrapeCollection, Option<PreviousStory>)>>(
+        &self,
+        eval: &StoryEvaluator,
+        scrape_collections: I,
+    ) -> Result<usize, PersistError> {
         self.with_writers(|provider| {
             let start = timer_start!();
             let mut total = 0;
@@ -319,18 +764,28 @@ impl StoryIndex {
                 let mut count = 0;
                 let mut scrapes_batch = vec![];
 
-                for story in scrape_collections {
+                for (mut story, previous) in scrape_collections {
                     count += 1;
-                    let doc = Self::create_story_insert(eval, &story);
+                    // These scrapes haven't been stored yet, so the dropped ones don't need deleting
+                    Self::limit_scrapes(eval, &mut story);
+                    let mut doc = Self::create_story_insert(eval, &story);
+                    let mut shard = Shard::from_date_time(story.earliest);
+                    if let Some(previous) = previous {
+                        if let Some(first_seen) = previous.first_seen {
+                            doc.first_seen = first_seen.timestamp();
+                        }
+                        // As with re-ingestion, a stable ID stays in the shard it was created in
+                        if self.id_scheme == StoryIdScheme::Stable {
+                            doc.id = previous.id.to_base64();
+                            shard = Shard::from_year_month(previous.id.year(), previous.id.month());
+                        }
+                    }
                     let scrapes = story.scrapes.into_values();
                     scrapes_batch.extend(scrapes);
-                    provider.provide(
-                        Shard::from_date_time(story.earliest),
-                        move |_, index, writer| {
-                            index.insert_story_document(writer, doc)?;
-                            Ok(())
-                        },
-                    )?;
+                    provider.provide(shard, move |_, index, writer| {
+                        index.insert_story_document(writer, doc)?;
+                        Ok(())
+                    })?;
 
                     if scrapes_batch.len() > SCRAPE_PROCESSING_CHUNK_SIZE {
                         self.scrape_db.insert_scrape_batch(scrapes_batch.iter())?;
@@ -344,7 +799,7 @@ impl StoryIndex {
             }
             timer_end!(start, "Indexed total of {} stories", total);
 
-            Ok(())
+            Ok(total)
         })
     }
 
@@ -407,14 +862,27 @@ impl StoryIndex {
         query: Q,
         max: usize,
     ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        let query = self.exclude_hidden(Box::new(query));
+        let weights = self.search_weights;
+        let now = self.most_recent_story()?.timestamp();
         let mut vec = vec![];
         let mut remaining = max;
         for shard in self.shards().iterate(ShardOrder::NewestFirst) {
             if remaining == 0 {
                 break;
             }
-            let docs = self.with_searcher(shard, |shard, searcher, _schema| {
-                let docs = searcher.search(&query, &TopDocs::with_limit(remaining))?;
+            let docs = self.with_searcher(shard, |shard, searcher, schema| {
+                let date_field = schema.date_field;
+                let top = TopDocs::with_limit(remaining).tweak_score(
+                    move |segment_reader: &SegmentReader| {
+                        let dates = segment_reader
+                            .fast_fields()
+                            .i64(date_field)
+                            .expect("Missing date field");
+                        move |doc, score| weights.score(score, now - dates.get_val(doc))
+                    },
+                );
+                let docs = searcher.search(query.as_ref(), &top)?;
                 Result::<_, PersistError>::Ok(docs.into_iter().map(move |x| (shard, x.1)))
             })??;
             vec.extend(docs);
@@ -423,24 +891,16 @@ impl StoryIndex {
         Ok(vec)
     }
 
-    fn fetch_tag_search(
-        &self,
-        tag: &str,
-        max: usize,
-    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+    fn tag_query(&self, tag: &str) -> TermQuery {
         let query = TermQuery::new(
             Term::from_field_text(self.schema.tags_field, tag),
             IndexRecordOption::Basic,
         );
         tracing::debug!("Tag symbol query = {:?}", query);
-        self.fetch_search_query(query, max)
+        query
     }
 
-    fn fetch_domain_search(
-        &self,
-        domain: &str,
-        max: usize,
-    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+    fn domain_query(&self, domain: &str) -> Result<PhraseQuery, PersistError> {
         let host_field = self.schema.host_field;
         // TODO: We probably don't want to re-parse this as a URL, but it's the fastest way to normalize it
         if let Some(url) = StoryUrl::parse(format!("http://{}", domain)) {
@@ -451,17 +911,13 @@ impl StoryIndex {
                 .collect_vec();
             let query = PhraseQuery::new(phrase);
             tracing::debug!("Domain phrase query = {:?}", query);
-            self.fetch_search_query(query, max)
+            Ok(query)
         } else {
             Err(PersistError::UnexpectedError("Invalid domain".into()))
         }
     }
 
-    fn fetch_text_search(
-        &self,
-        search: &str,
-        max: usize,
-    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+    fn text_query(&self, search: &str) -> BooleanQuery {
         let query1 = TermQuery::new(
             Term::from_field_text(self.schema.title_field, search),
             IndexRecordOption::Basic,
@@ -470,19 +926,244 @@ impl StoryIndex {
             Term::from_field_text(self.schema.tags_field, search),
             IndexRecordOption::Basic,
         );
+        // A tag is a stronger signal of what a story is about than a passing mention in its title
+        let query2 = BoostQuery::new(Box::new(query2), self.search_weights.tag_boost);
         let query = BooleanQuery::new(vec![
             (Occur::Should, Box::new(query1)),
             (Occur::Should, Box::new(query2)),
         ]);
         tracing::debug!("Term query = {:?}", query);
-        self.fetch_search_query(query, max)
+        query
+    }
+
+    fn fetch_tag_search(
+        &self,
+        tag: &str,
+        max: usize,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        self.fetch_search_query(self.tag_query(tag), max)
+    }
+
+    fn fetch_domain_search(
+        &self,
+        domain: &str,
+        max: usize,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        self.fetch_search_query(self.domain_query(domain)?, max)
+    }
+
+    fn fetch_text_search(
+        &self,
+        search: &str,
+        max: usize,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        self.fetch_search_query(self.text_query(search), max)
+    }
+
+    /// Count the stories matching a query across all shards by each value of the given root facets, optionally
+    /// limited to stories dated on or after `since`.
+    fn count_facets(
+        &self,
+        query: Box<dyn Query>,
+        roots: &[&str],
+        since: Option<StoryDate>,
+    ) -> Result<HashMap<(String, String), usize>, PersistError> {
+        let mut counts = HashMap::<(String, String), usize>::new();
+        let first_shard = since.map(Shard::from_date_time);
+        let since = since.map(|date| date.timestamp()).unwrap_or(i64::MIN);
+        for shard in self.shards().iterate(ShardOrder::NewestFirst) {
+            if first_shard.is_some_and(|first| shard < first) {
+                break;
+            }
+            self.with_searcher(shard, |_, searcher, schema| {
+                let mut collector = FacetCollector::for_field(schema.facets_field);
+                for facet in roots {
+                    collector.add_facet(Facet::from_path([facet]));
+                }
+                // The date is only a fast field, so it's filtered while collecting rather than in the query
+                let collector = FilterCollector::new(
+                    schema.date_field,
+                    move |date: i64| date >= since,
+                    collector,
+                );
+                let facets = searcher.search(query.as_ref(), &collector)?;
+                for facet in roots {
+                    for (value, count) in facets.get(Facet::from_path([facet])) {
+                        if let [_, value] = value.to_path()[..] {
+                            *counts.entry((facet.to_string(), value.into())).or_default() +=
+                                count as usize;
+                        }
+                    }
+                }
+                Result::<_, PersistError>::Ok(())
+            })??;
+        }
+        Ok(counts)
+    }
+
+    /// Count every story matching a search across all shards by source, domain and tag, keeping the `max` most
+    /// common domains and tags.
+    fn fetch_search_facets(
+        &self,
+        query: StoryQuery,
+        max: usize,
+    ) -> Result<SearchFacets, PersistError> {
+        let query: Box<dyn Query> = match query {
+            StoryQuery::TagSearch(tag) => Box::new(self.tag_query(&tag)),
+            StoryQuery::DomainSearch(domain) => Box::new(self.domain_query(&domain)?),
+            StoryQuery::TextSearch(text) => Box::new(self.text_query(&text)),
+            _ => {
+                return Err(PersistError::UnexpectedError(
+                    "Facets are only available for searches".into(),
+                ))
+            }
+        };
+        let counts = self.count_facets(self.exclude_hidden(query), &FACETS, None)?;
+        let mut facets = SearchFacets::default();
+        for ((facet, value), count) in counts
+            .into_iter()
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+        {
+            match facet.as_str() {
+                FACET_SOURCE => {
+                    if let Some(source) = ScrapeSource::try_from_str(&value) {
+                        facets.sources.push((source, count));
+                    }
+                }
+                FACET_DOMAIN if facets.domains.len() < max => facets.domains.push((value, count)),
+                FACET_TAG if facets.tags.len() < max => facets.tags.push((value, count)),
+                _ => {}
+            }
+        }
+        Ok(facets)
+    }
+
+    /// The `max` most recently ingested stories across all shards, most recent first. Stories can be discovered long
+    /// after their date, so every shard needs to be searched.
+    fn fetch_recently_ingested(
+        &self,
+        max: usize,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        let mut vec = vec![];
+        for shard in self.shards().iterate(ShardOrder::NewestFirst) {
+            let docs = self.with_searcher(shard, |shard, searcher, schema| {
+                let top =
+                    TopDocs::with_limit(max).order_by_fast_field::<i64>(schema.first_seen_field);
+                let docs = searcher.search(&AllQuery, &top)?;
+                Result::<_, PersistError>::Ok(
+                    docs.into_iter()
+                        .map(move |(first_seen, doc)| (first_seen, shard, doc)),
+                )
+            })??;
+            vec.extend(docs);
+        }
+        // Newest first, with newer shards winning ties
+        vec.sort_by_key(|(first_seen, _, _)| std::cmp::Reverse(*first_seen));
+        Ok(vec
+            .into_iter()
+            .take(max)
+            .map(|(_, shard, doc)| (shard, doc))
+            .collect())
+    }
+
+    /// The `max` newest stories by date, newest first. Shards are by story date, so we can stop at the first shard
+    /// that fills the request.
+    fn fetch_newest(&self, max: usize) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        let query = self.exclude_hidden(Box::new(AllQuery));
+        let mut vec = vec![];
+        for shard in self.shards().iterate(ShardOrder::NewestFirst) {
+            if vec.len() >= max {
+                break;
+            }
+            let docs = self.with_searcher(shard, |shard, searcher, schema| {
+                let top = TopDocs::with_limit(max - vec.len())
+                    .order_by_fast_field::<i64>(schema.date_field);
+                let docs = searcher.search(query.as_ref(), &top)?;
+                Result::<_, PersistError>::Ok(
+                    docs.into_iter()
+                        .map(move |(_, doc)| (shard, doc))
+                        .collect_vec(),
+                )
+            })??;
+            vec.extend(docs);
+        }
+        Ok(vec)
     }
 
-    fn fetch_front_page(&self, max_count: usize) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+    /// Pick up to `max` distinct random stories by choosing a random shard, and then a random story within it.
+    fn fetch_random(
+        &self,
+        tag: Option<&str>,
+        max: usize,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        let query: Box<dyn Query> = if let Some(tag) = tag {
+            Box::new(TermQuery::new(
+                Term::from_field_text(self.schema.tags_field, tag),
+                IndexRecordOption::Basic,
+            ))
+        } else {
+            Box::new(AllQuery)
+        };
+        let query = self.exclude_hidden(query);
+        let mut rng = rand::thread_rng();
+        let mut shards = self.shards().iterate(ShardOrder::NewestFirst).collect_vec();
+        let mut v = vec![];
+
+        // Bound the attempts so that a small index can't spin looking for enough distinct stories
+        for _ in 0..max * 4 {
+            if v.len() >= max || shards.is_empty() {
+                break;
+            }
+            let n = rng.gen_range(0..shards.len());
+            let doc = self.with_searcher(shards[n], |shard, searcher, schema| {
+                let count = searcher.search(query.as_ref(), &Count)?;
+                if count == 0 {
+                    return Ok(None);
+                }
+                let top = TopDocs::with_limit(1)
+                    .and_offset(rng.gen_range(0..count))
+                    .order_by_fast_field::<i64>(schema.date_field);
+                let docs = searcher.search(query.as_ref(), &top)?;
+                Result::<_, PersistError>::Ok(docs.into_iter().next().map(|(_, doc)| (shard, doc)))
+            })??;
+            match doc {
+                // Don't revisit shards with no matching stories
+                None => drop(shards.swap_remove(n)),
+                Some(doc) if !v.contains(&doc) => v.push(doc),
+                Some(_) => {}
+            }
+        }
+        Ok(v)
+    }
+
+    /// The sources of a story document, parsed from its scrape IDs.
+    fn doc_sources(
+        searcher: &Searcher,
+        schema: &StorySchema,
+        doc_address: DocAddress,
+    ) -> Result<Vec<ScrapeSource>, PersistError> {
+        let doc = searcher.doc(doc_address)?;
+        Ok(doc
+            .get_all(schema.scrape_field)
+            .filter_map(|value| value.as_text())
+            .filter_map(|id| id.split_once(':'))
+            .filter_map(|(_, id)| ScrapeId::from_string(id.into()))
+            .map(|id| id.source)
+            .collect())
+    }
+
+    fn fetch_front_page(
+        &self,
+        max_count: usize,
+        quotas: Option<&TypedScrapeMap<Option<usize>>>,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
         let mut story_collector: StoryCollector<(Shard, DocAddress)> =
             StoryCollector::new(max_count);
         let mut processed = 0;
         let processing_target = max_count * 2;
+        let quotas = quotas.filter(|quotas| quotas.iter().any(Option::is_some));
+        let mut counts = TypedScrapeMap::<usize>::new();
+        let query = self.exclude_hidden(Box::new(AllQuery));
 
         // Limit how far back we go since the front page _should_ only be one or two shards unless our index is empty.
         for shard in self.shards().iterate(ShardOrder::NewestFirst).take(3) {
@@ -491,21 +1172,47 @@ impl StoryIndex {
                 break;
             }
 
-            self.with_searcher(shard, |shard, searcher, _schema| {
-                let top = TopDocs::with_limit(processing_target - processed)
-                    .order_by_fast_field::<i64>(self.schema.date_field);
-                let docs = searcher.search(&AllQuery {}, &top)?;
-                tracing::info!("Got {} doc(s) from shard {:?}", docs.len(), shard);
-
-                for (_, doc_address) in docs {
-                    processed += 1;
-                    let score = searcher
-                        .segment_reader(doc_address.segment_ord)
-                        .fast_fields()
-                        .f64(self.schema.score_field)?
-                        .get_val(doc_address.doc_id) as f32;
-                    if story_collector.would_accept(score) {
-                        story_collector.accept(score, (shard, doc_address));
+            self.with_searcher(shard, |shard, searcher, schema| {
+                // Stories skipped because their sources are over quota don't count towards the processing target, so
+                // we may need to page through more of the shard
+                let mut offset = 0;
+                while processed < processing_target {
+                    let top = TopDocs::with_limit(processing_target - processed)
+                        .and_offset(offset)
+                        .order_by_fast_field::<i64>(self.schema.date_field);
+                    let docs = searcher.search(query.as_ref(), &top)?;
+                    tracing::info!("Got {} doc(s) from shard {:?}", docs.len(), shard);
+                    if docs.is_empty() {
+                        break;
+                    }
+                    offset += docs.len();
+
+                    for (_, doc_address) in docs {
+                        if let Some(quotas) = quotas {
+                            let sources = Self::doc_sources(searcher, schema, doc_address)?;
+                            let under_quota = sources.is_empty()
+                                || sources.iter().any(|source| {
+                                    quotas
+                                        .get(*source)
+                                        .is_none_or(|quota| *counts.get(*source) < quota)
+                                });
+                            if !under_quota {
+                                continue;
+                            }
+                            for source in sources {
+                                counts.set(source, counts.get(source) + 1);
+                            }
+                        }
+
+                        processed += 1;
+                        let score = searcher
+                            .segment_reader(doc_address.segment_ord)
+                            .fast_fields()
+                            .f64(self.schema.score_field)?
+                            .get_val(doc_address.doc_id) as f32;
+                        if story_collector.would_accept(score) {
+                            story_collector.accept(score, (shard, doc_address));
+                        }
                     }
                 }
 
@@ -521,21 +1228,126 @@ impl StoryIndex {
         Ok(story_collector.to_sorted())
     }
 
-    fn fetch_doc_addresses(
+    fn fetch_since(
         &self,
-        query: StoryQuery,
+        date: StoryDate,
+        after: Option<StoryIdentifier>,
         max: usize,
     ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
-        match query {
-            StoryQuery::ById(id) => self.with_searcher(
-                Shard::from_year_month(id.year(), id.month()),
-                self.fetch_by_id(&id),
+        let start = date.timestamp();
+        let after = after.map(|id| id.to_base64());
+        let first_shard = if u16::try_from(date.year()).is_ok() {
+            Shard::from_date_time(date)
+        } else {
+            Shard::MIN
+        };
+        let mut vec = vec![];
+        for shard in self.shards().iterate(ShardOrder::OldestFirst) {
+            if vec.len() >= max {
+                break;
+            }
+            if shard < first_shard {
+                continue;
+            }
+            let needed = max - vec.len();
+            let mut docs = self.with_searcher(shard, |shard, searcher, schema| {
+                let date_field = schema.date_field;
+                // Stories at the high-water mark's date may be skipped by ID, so leave room for them
+                let skippable = if after.is_some() {
+                    searcher.search(
+                        &AllQuery,
+                        &FilterCollector::new(date_field, move |date: i64| date == start, Count),
+                    )?
+                } else {
+                    0
+                };
+                let limit = needed + skippable;
+                let oldest_first = TopDocs::with_limit(limit).custom_score(
+                    move |segment_reader: &SegmentReader| {
+                        let dates = segment_reader
+                            .fast_fields()
+                            .i64(date_field)
+                            .expect("Missing date field");
+                        move |doc| std::cmp::Reverse(dates.get_val(doc))
+                    },
+                );
+                let mut candidates = searcher
+                    .search(
+                        &AllQuery,
+                        &FilterCollector::new(
+                            date_field,
+                            move |date: i64| date >= start,
+                            oldest_first,
+                        ),
+                    )?
+                    .into_iter()
+                    .map(|(std::cmp::Reverse(date), doc_address)| (date, doc_address))
+                    .collect_vec();
+                // Stories with the same date are ordered by ID, which the limit can't see, so if it cut off part of
+                // the newest date then all of the stories with that date are needed
+                if candidates.len() == limit {
+                    if let Some(&(newest, _)) = candidates.last() {
+                        candidates.retain(|(date, _)| *date < newest);
+                        let ties = searcher.search(
+                            &AllQuery,
+                            &FilterCollector::new(
+                                date_field,
+                                move |date: i64| date == newest,
+                                DocSetCollector,
+                            ),
+                        )?;
+                        candidates
+                            .extend(ties.into_iter().map(|doc_address| (newest, doc_address)));
+                    }
+                }
+
+                let mut docs = vec![];
+                for (date, doc_address) in candidates {
+                    let doc = searcher.doc(doc_address)?;
+                    let id = doc
+                        .get_first(schema.id_field)
+                        .and_then(|id| id.as_text())
+                        .unwrap_or_default()
+                        .to_owned();
+                    if let Some(after) = &after {
+                        if date == start && id <= *after {
+                            continue;
+                        }
+                    }
+                    docs.push((date, id, shard, doc_address));
+                }
+                Result::<_, PersistError>::Ok(docs)
+            })??;
+            docs.sort();
+            vec.extend(docs);
+        }
+        vec.truncate(max);
+        Ok(vec
+            .into_iter()
+            .map(|(_, _, shard, doc_address)| (shard, doc_address))
+            .collect())
+    }
+
+    fn fetch_doc_addresses(
+        &self,
+        query: StoryQuery,
+        max: usize,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        match query {
+            StoryQuery::ById(id) => self.with_searcher(
+                Shard::from_year_month(id.year(), id.month()),
+                self.fetch_by_id(&id),
             )?,
             StoryQuery::ByShard(shard) => Ok(self.with_searcher(shard, self.fetch_by_segment())?),
-            StoryQuery::FrontPage() => self.fetch_front_page(max),
+            StoryQuery::FrontPage() => self.fetch_front_page(max, None),
+            StoryQuery::FrontPageWithQuotas(quotas) => self.fetch_front_page(max, Some(&quotas)),
             StoryQuery::TagSearch(tag) => self.fetch_tag_search(&tag, max),
             StoryQuery::DomainSearch(domain) => self.fetch_domain_search(&domain, max),
             StoryQuery::TextSearch(text) => self.fetch_text_search(&text, max),
+            StoryQuery::Since(date, after) => self.fetch_since(date, after, max),
+            StoryQuery::Random(tag) => self.fetch_random(tag.as_deref(), max),
+            StoryQuery::RecentlyIngested() => self.fetch_recently_ingested(max),
+            StoryQuery::Newest() => self.fetch_newest(max),
         }
     }
 }
@@ -554,7 +1366,8 @@ impl StorageWriter for StoryIndex {
         eval: &StoryEvaluator,
         scrape_collections: I,
     ) -> Result<(), PersistError> {
-        self.insert_scrape_collections(eval, scrape_collections)
+        self.insert_scrape_collections(eval, scrape_collections)?;
+        Ok(())
     }
 }
 
@@ -567,14 +1380,23 @@ impl StorageFetch<Shard> for StoryIndex {
                 let url = StoryUrl::parse(story.url).expect("Failed to parse URL");
                 let date = StoryDate::from_seconds(story.date).expect("Failed to re-parse date");
                 let score = story.score as f32;
-                Result::<_, PersistError>::Ok(Story::new_from_parts(
+                let first_seen = story.first_seen;
+                let id = StoryIdentifier::from_base64(story.id);
+                let mut story = Story::new_from_parts(
                     story.title,
                     url,
                     date,
                     score,
                     story.tags,
                     story.scrape_ids,
-                ))
+                );
+                // A stable ID may not match the story's current date
+                if let Some(id) = id {
+                    story.id = id;
+                }
+                story.read_time = self.read_times.get(&story.id);
+                story.first_seen = StoryDate::from_seconds(first_seen).filter(|_| first_seen > 0);
+                Result::<_, PersistError>::Ok(story)
             })??;
 
             v.push(doc);
@@ -596,11 +1418,13 @@ impl StorageFetch<TypedScrape> for StoryIndex {
                 let url = StoryUrl::parse(story.url).expect("Failed to parse URL");
                 let date = StoryDate::from_seconds(story.date).expect("Failed to re-parse date");
                 let score = story.score as f32;
+                let first_seen = story.first_seen;
+                let id = StoryIdentifier::from_base64(story.id);
 
                 let scrapes = self
                     .scrape_db
                     .fetch_scrape_batch(story.scrape_ids.clone())?;
-                let story = Story::new_from_parts(
+                let mut story = Story::new_from_parts(
                     story.title,
                     url,
                     date,
@@ -608,6 +1432,11 @@ impl StorageFetch<TypedScrape> for StoryIndex {
                     story.tags,
                     scrapes.into_values().flatten(),
                 );
+                if let Some(id) = id {
+                    story.id = id;
+                }
+                story.read_time = self.read_times.get(&story.id);
+                story.first_seen = StoryDate::from_seconds(first_seen).filter(|_| first_seen > 0);
 
                 Result::<_, PersistError>::Ok(story)
             })??;
@@ -663,6 +1492,25 @@ impl Storage for StoryIndex {
     fn fetch_count(&self, query: StoryQuery, max: usize) -> Result<usize, PersistError> {
         Ok(self.fetch_doc_addresses(query, max)?.len())
     }
+
+    fn fetch_facets(&self, query: StoryQuery, max: usize) -> Result<SearchFacets, PersistError> {
+        self.fetch_search_facets(query, max)
+    }
+
+    fn fetch_tag_counts(
+        &self,
+        min_count: usize,
+        since: Option<StoryDate>,
+    ) -> Result<Vec<(String, usize)>, PersistError> {
+        let counts =
+            self.count_facets(self.exclude_hidden(Box::new(AllQuery)), &[FACET_TAG], since)?;
+        Ok(counts
+            .into_iter()
+            .map(|((_, tag), count)| (tag, count))
+            .filter(|(_, count)| *count >= min_count)
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .collect())
+    }
 }
 
 #[cfg(test)]
@@ -671,7 +1519,8 @@ mod test {
 
     use super::*;
     use progscrape_scrapers::{
-        hacker_news::*, lobsters::LobstersStory, reddit::*, ScrapeSource, StoryUrl,
+        hacker_news::*, lobsters::LobstersStory, reddit::*, ScrapeConfig, ScrapeExtractor,
+        ScrapeSource, StoryDuration, StoryUrl,
     };
 
     use crate::{story::TagSet, test::*, MemIndex};
@@ -801,6 +1650,882 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    fn test_index_blocked_domain(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let mut eval = StoryEvaluator::new_for_test();
+        eval.extractor = ScrapeExtractor::new(&ScrapeConfig {
+            blocked_domains: vec!["*.spam.com".into()],
+            ..Default::default()
+        });
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("http://example.com").expect("URL");
+        let blocked = StoryUrl::parse("http://www.blog.spam.com/buy").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("story1", date, "I love Rust", &url),
+                hn_story("story2", date, "Buy now", &blocked),
+            ]
+            .into_iter(),
+        )?;
+
+        let counts = index.story_count()?;
+        assert_eq!(counts.total.story_count, 1);
+        let stories = index.fetch::<Shard>(StoryQuery::ByShard(Shard::from_date_time(date)), 10)?;
+        assert_eq!(stories.len(), 1);
+        assert_eq!("I love Rust", stories[0].title);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_index_stale_scrapes(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let days_ago = |days| {
+            StoryDate::from_seconds(
+                StoryDate::now().timestamp() - StoryDuration::days(days).num_seconds(),
+            )
+            .expect("Date")
+        };
+        let old = days_ago(30);
+        let recent = days_ago(1);
+        let url = |s| StoryUrl::parse(format!("http://{}.com/", s)).expect("URL");
+
+        // This story was indexed before the freshness window was configured
+        index.insert_scrapes(
+            &StoryEvaluator::new_for_test(),
+            [hn_story("story1", old, "Existing story", &url("existing"))].into_iter(),
+        )?;
+
+        let mut eval = StoryEvaluator::new_for_test();
+        eval.extractor = ScrapeExtractor::new(&ScrapeConfig {
+            max_story_age_days: Some(7),
+            ..Default::default()
+        });
+        let stale = hn_story("story2", old, "Old story", &url("old"));
+        let stale_id = stale.id.clone();
+        index.insert_scrapes(
+            &eval,
+            [
+                reddit_story("story1", "rust", old, "Existing story", &url("existing")),
+                stale,
+                hn_story("story3", recent, "Recent story", &url("recent")),
+            ]
+            .into_iter(),
+        )?;
+
+        let counts = index.story_count()?;
+        assert_eq!(counts.total.story_count, 2);
+        let old_stories =
+            index.fetch::<Shard>(StoryQuery::ByShard(Shard::from_date_time(old)), 10)?;
+        assert_eq!(old_stories.len(), 1);
+        assert_eq!("Existing story", old_stories[0].title);
+        assert_eq!(old_stories[0].scrapes.len(), 2);
+        let recent_stories =
+            index.fetch::<Shard>(StoryQuery::ByShard(Shard::from_date_time(recent)), 10)?;
+        assert!(recent_stories
+            .iter()
+            .any(|story| story.title == "Recent story"));
+
+        // The dropped scrape isn't stored, so rebuilding the index from raw scrapes won't revive it
+        let stored =
+            index.with_scrapes(|db| db.fetch_scrape(Shard::from_date_time(old), &stale_id))?;
+        assert!(stored.is_none());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_max_scrapes_per_story(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let mut eval = StoryEvaluator::new_for_test();
+        eval.extractor = ScrapeExtractor::new(&ScrapeConfig {
+            max_scrapes_per_story: Some(5),
+            ..Default::default()
+        });
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("http://example.com/").expect("URL");
+        let cross_post = |i: i64| {
+            let date = StoryDate::from_seconds(date.timestamp() + i * 60).expect("Date");
+            reddit_story(
+                &format!("story{}", i),
+                &format!("sub{}", i),
+                date,
+                "I love Rust",
+                &url,
+            )
+        };
+
+        // A new story is capped as it's created
+        index.insert_scrapes(
+            &eval,
+            [hn_story("hn", date, "I love Rust", &url)]
+                .into_iter()
+                .chain((1..=10).map(cross_post)),
+        )?;
+        let story = |index: &StoryIndex| {
+            let stories = index
+                .fetch::<TypedScrape>(StoryQuery::FrontPage(), 10)
+                .expect("Front page");
+            assert_eq!(1, stories.len());
+            stories[0].scrapes.keys().cloned().collect::<HashSet<_>>()
+        };
+        // Only the scrapes the story kept are stored
+        let stored = |index: &StoryIndex| {
+            index
+                .with_scrapes(|db| db.stats(Shard::from_date_time(date)))
+                .expect("Stats")
+                .count
+        };
+        let scrapes = story(&index);
+        assert_eq!(5, scrapes.len());
+        assert!(scrapes.contains(&ScrapeSource::HackerNews.id("hn")));
+        // The newest cross-posts are the ones kept
+        assert!(scrapes.contains(&ScrapeSource::Reddit.subsource_id("sub10", "story10")));
+        assert!(!scrapes.contains(&ScrapeSource::Reddit.subsource_id("sub1", "story1")));
+        assert_eq!(5, stored(&index));
+
+        // An existing story is capped as scrapes are added
+        for i in 11..=20 {
+            index.insert_scrapes(&eval, [cross_post(i)].into_iter())?;
+        }
+        let scrapes = story(&index);
+        assert_eq!(5, scrapes.len());
+        assert!(scrapes.contains(&ScrapeSource::HackerNews.id("hn")));
+        assert!(scrapes.contains(&ScrapeSource::Reddit.subsource_id("sub20", "story20")));
+        assert!(!scrapes.contains(&ScrapeSource::Reddit.subsource_id("sub10", "story10")));
+        assert_eq!(5, stored(&index));
+        let evicted = ScrapeSource::Reddit.subsource_id("sub10", "story10");
+        assert!(index
+            .with_scrapes(|db| db.fetch_scrape(Shard::from_date_time(date), &evicted))?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_hidden_stories(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            (0..3).map(|i| hn_story(&format!("story{}", i), date, "I love Rust", &url(i))),
+        )?;
+
+        let front_page = |index: &StoryIndex| {
+            index
+                .fetch::<Shard>(StoryQuery::FrontPage(), 10)
+                .expect("Front page")
+                .into_iter()
+                .map(|story| story.id)
+                .collect_vec()
+        };
+        let search = |index: &StoryIndex| {
+            index
+                .fetch_count(StoryQuery::from_search(&eval.tagger, "rust"), 10)
+                .expect("Search")
+        };
+        let stories = front_page(&index);
+        assert_eq!(3, stories.len());
+        assert_eq!(3, search(&index));
+
+        let hidden = stories[0].clone();
+        assert!(index.hide_story(&hidden)?);
+        assert!(!index.hide_story(&hidden)?);
+        assert!(index.is_story_hidden(&hidden));
+        let stories = front_page(&index);
+        assert_eq!(2, stories.len());
+        assert!(!stories.contains(&hidden));
+        assert_eq!(2, search(&index));
+
+        // Hidden stories can still be looked up directly
+        assert!(index
+            .fetch_one::<Shard>(StoryQuery::ById(hidden.clone()))?
+            .is_some());
+
+        assert!(index.unhide_story(&hidden)?);
+        assert!(!index.unhide_story(&hidden)?);
+        let stories = front_page(&index);
+        assert_eq!(3, stories.len());
+        assert!(stories.contains(&hidden));
+        assert_eq!(3, search(&index));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_sharded_location(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let (path, archive) = (dir.path().join("index"), dir.path().join("archive"));
+        let location = PersistLocation::Sharded(
+            path.clone(),
+            vec![format!("..2019-12={}", archive.to_string_lossy()).parse()?],
+        );
+        let eval = StoryEvaluator::new_for_test();
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+
+        let mut index = StoryIndex::new(location.clone())?;
+        index.insert_scrapes(
+            &eval,
+            (0..4).map(|i| {
+                let date = StoryDate::year_month_day(2019 + i / 2, 12, 1).expect("Date");
+                hn_story(&format!("story{}", i), date, "I love Rust", &url(i))
+            }),
+        )?;
+        drop(index);
+        assert!(archive.join("2019-12").exists());
+        assert!(!path.join("2019-12").exists());
+        assert!(path.join("2020-12").exists());
+        assert!(!archive.join("2020-12").exists());
+
+        // Reads span both directories after a re-open
+        let index = StoryIndex::new(location)?;
+        assert_eq!(
+            ShardRange::new_from(
+                Shard::from_year_month(2019, 12)..=Shard::from_year_month(2020, 12)
+            ),
+            index.shards()
+        );
+        assert_eq!(
+            4,
+            index.fetch_count(StoryQuery::from_search(&eval.tagger, "rust"), 100)?
+        );
+        let archived = index
+            .fetch::<TypedScrape>(StoryQuery::ByShard(Shard::from_year_month(2019, 12)), 10)?;
+        assert_eq!(2, archived.len());
+        assert!(archived.iter().all(|story| story.scrapes.len() == 1));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_pinned_stories(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let location = PersistLocation::Path(dir.path().to_owned());
+        let eval = StoryEvaluator::new_for_test();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+
+        let mut index = StoryIndex::new(location.clone())?;
+        index.insert_scrapes(
+            &eval,
+            (0..3).map(|i| hn_story(&format!("story{}", i), date, "I love Rust", &url(i))),
+        )?;
+        let stories = index
+            .fetch::<Shard>(StoryQuery::FrontPage(), 10)?
+            .into_iter()
+            .map(|story| story.id)
+            .collect_vec();
+        assert!(index.pin_story(&stories[2])?);
+        assert!(index.pin_story(&stories[0])?);
+        assert!(!index.pin_story(&stories[2])?);
+        assert!(index.pin_story(&stories[1])?);
+        assert!(index.unpin_story(&stories[0])?);
+        assert!(!index.unpin_story(&stories[0])?);
+        assert!(!index.is_story_pinned(&stories[0]));
+        drop(index);
+
+        // Pins survive a re-open, in the order they were made
+        let mut index = StoryIndex::new(location)?;
+        assert_eq!(
+            vec![stories[2].clone(), stories[1].clone()],
+            index.pinned_stories()
+        );
+        assert!(index.pin_story(&stories[0])?);
+        assert_eq!(
+            vec![stories[2].clone(), stories[1].clone(), stories[0].clone()],
+            index.pinned_stories()
+        );
+        assert!(index.delete_story(&stories[2])?);
+        assert_eq!(
+            vec![stories[1].clone(), stories[0].clone()],
+            index.pinned_stories()
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_delete_story(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("http://example.com/").expect("URL");
+        let other = StoryUrl::parse("http://example.org/").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("story1", date, "I love Rust", &url),
+                reddit_story("story1", "rust", date, "I love Rust", &url),
+                hn_story("story2", date, "I love Rust", &other),
+            ]
+            .into_iter(),
+        )?;
+
+        let stories = index.fetch::<TypedScrape>(StoryQuery::FrontPage(), 10)?;
+        assert_eq!(2, stories.len());
+        let story = stories
+            .into_iter()
+            .find(|story| story.url == url)
+            .expect("Missing story");
+        let id = story.id.clone();
+        index.set_read_time(&id, 5)?;
+        index.hide_story(&id)?;
+
+        assert!(index.delete_story(&id)?);
+        assert!(index
+            .fetch_one::<Shard>(StoryQuery::ById(id.clone()))?
+            .is_none());
+        assert!(!index.is_story_hidden(&id));
+        assert_eq!(1, index.fetch_count(StoryQuery::FrontPage(), 10)?);
+
+        // The story's scrapes are gone as well
+        let shard = Shard::from_date_time(date);
+        for scrape in story.scrapes.values() {
+            assert!(index
+                .with_scrapes(|scrapes| scrapes.fetch_scrape(shard, &scrape.id))?
+                .is_none());
+        }
+
+        // Deleting again finds nothing
+        assert!(!index.delete_story(&id)?);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_reingest_source(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let evaluator = |tags: serde_json::Value| -> Result<StoryEvaluator, serde_json::Error> {
+            Ok(StoryEvaluator::new(
+                &serde_json::from_value(serde_json::json!({ "tags": { "test": tags } }))?,
+                &Default::default(),
+                &ScrapeConfig::default(),
+            ))
+        };
+        let eval = evaluator(serde_json::json!({ "rust": {} }))?;
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("story0", date, "I love Rust", &url(0)),
+                hn_story("story1", date, "I love Rust", &url(1)),
+                reddit_story("story1", "rust", date, "I love Rust", &url(1)),
+                reddit_story("story2", "rust", date, "I love Rust", &url(2)),
+            ]
+            .into_iter(),
+        )?;
+
+        // Only stories with Reddit scrapes are evaluated with the new tags
+        let eval = evaluator(serde_json::json!({ "rust": {}, "love": {} }))?;
+        assert_eq!(2, index.reingest_source(&eval, ScrapeSource::Reddit)?);
+
+        let stories = index.fetch::<Shard>(StoryQuery::FrontPage(), 10)?;
+        assert_eq!(3, stories.len());
+        for story in stories {
+            assert!(story.tags.contains("rust"));
+            let is_hn_only = story.url == url(0);
+            assert_eq!(!is_hn_only, story.tags.contains("love"));
+        }
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_stable_id_scheme(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let url = StoryUrl::parse("http://domain-1.com/").expect("URL");
+        let first = StoryDate::year_month_day(2020, 1, 20).expect("Date failed");
+        let earlier = StoryDate::year_month_day(2020, 1, 10).expect("Date failed");
+
+        let mut ids = vec![];
+        for id_scheme in [StoryIdScheme::Earliest, StoryIdScheme::Stable] {
+            let mut index = StoryIndex::new(PersistLocation::Memory)?;
+            index.set_id_scheme(id_scheme);
+            index.insert_scrapes(
+                &eval,
+                [hn_story("story1", first, "I love Rust", &url)].into_iter(),
+            )?;
+            let id = index.fetch::<Shard>(StoryQuery::FrontPage(), 1)?[0]
+                .id
+                .clone();
+            // An earlier scrape is merged into the story, which is then re-written
+            index.insert_scrapes(
+                &eval,
+                [reddit_story("story1", "rust", earlier, "I love Rust", &url)].into_iter(),
+            )?;
+            assert_eq!(1, index.reingest_source(&eval, ScrapeSource::Reddit)?);
+
+            let stories = index.fetch::<Shard>(StoryQuery::FrontPage(), 10)?;
+            assert_eq!(1, stories.len());
+            assert_eq!(2, stories[0].scrapes.len());
+            let resolved = (
+                index.resolve_story_id(&id)?,
+                index.resolve_story_id(&stories[0].id)?,
+            );
+            ids.push((id, stories[0].id.clone(), resolved));
+        }
+
+        // The earliest scheme moves the story, but the old ID can be resolved to the new one
+        let (old, new, resolved) = &ids[0];
+        assert_ne!(old, new);
+        assert!(new.matches_date(earlier));
+        assert_eq!((Some(new.clone()), Some(new.clone())), *resolved);
+
+        // The stable scheme keeps the ID
+        let (old, new, resolved) = &ids[1];
+        assert_eq!(old, new);
+        assert!(old.matches_date(first));
+        assert_eq!((Some(old.clone()), Some(old.clone())), *resolved);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_search_weights(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let old = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let new = StoryDate::year_month_day(2020, 1, 29).expect("Date failed");
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                // The shorter title is the more relevant match
+                hn_story("story1", old, "Compiler", &url(1)),
+                hn_story(
+                    "story2",
+                    new,
+                    "Notes from a week spent writing a small compiler",
+                    &url(2),
+                ),
+            ]
+            .into_iter(),
+        )?;
+
+        let search = |index: &StoryIndex| {
+            index
+                .fetch::<Shard>(StoryQuery::TextSearch("compiler".into()), 10)
+                .expect("Search")
+                .into_iter()
+                .map(|story| story.url.raw().to_owned())
+                .collect_vec()
+        };
+
+        index.set_search_weights(SearchWeights {
+            relevance: 1.0,
+            recency: 0.0,
+            ..Default::default()
+        });
+        assert_eq!(
+            vec!["http://domain-1.com/", "http://domain-2.com/"],
+            search(&index)
+        );
+
+        index.set_search_weights(SearchWeights {
+            relevance: 1.0,
+            recency: 10.0,
+            ..Default::default()
+        });
+        assert_eq!(
+            vec!["http://domain-2.com/", "http://domain-1.com/"],
+            search(&index)
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_search_tag_boost(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                // Only mentions compilers in passing, but with a short title that matches strongly
+                hn_story("story1", date, "Compiler bugs", &url(1)),
+                // Tagged as being about compilers
+                lobsters_story(
+                    "story2",
+                    date,
+                    "Notes from a week spent writing a small compiler",
+                    &url(2),
+                    vec!["compiler".into()],
+                ),
+            ]
+            .into_iter(),
+        )?;
+
+        let search = |index: &mut StoryIndex, tag_boost| {
+            index.set_search_weights(SearchWeights {
+                tag_boost,
+                ..Default::default()
+            });
+            index
+                .fetch::<Shard>(StoryQuery::TextSearch("compiler".into()), 10)
+                .expect("Search")
+                .into_iter()
+                .map(|story| story.url.raw().to_owned())
+                .collect_vec()
+        };
+
+        assert_eq!(
+            vec!["http://domain-1.com/", "http://domain-2.com/"],
+            search(&mut index, 0.0)
+        );
+        assert_eq!(
+            vec!["http://domain-2.com/", "http://domain-1.com/"],
+            search(&mut index, SearchWeights::default().tag_boost)
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_random(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            (0..10).map(|i| {
+                // Spread the stories across a few shards
+                let date = StoryDate::year_month_day(2020, 1 + i % 3, 1).expect("Date failed");
+                let title = if i == 0 {
+                    "I love Rust"
+                } else {
+                    "I love Python"
+                };
+                hn_story(&format!("story{}", i), date, title, &url(i))
+            }),
+        )?;
+
+        let random = |index: &StoryIndex, tag: Option<&str>| {
+            index
+                .fetch_one::<Shard>(StoryQuery::Random(tag.map(str::to_owned)))
+                .expect("Random")
+                .map(|story| story.id)
+        };
+
+        // Repeated calls return different stories
+        let ids = (0..50)
+            .filter_map(|_| random(&index, None))
+            .collect::<HashSet<_>>();
+        assert!(ids.len() > 1, "{:?}", ids);
+
+        // Multiple stories are distinct
+        let stories = index.fetch::<Shard>(StoryQuery::Random(None), 5)?;
+        assert_eq!(5, stories.iter().map(|story| &story.id).unique().count());
+
+        // Filtered by tag
+        let rust = random(&index, Some("rust")).expect("Missing story");
+        assert_eq!(Some(rust.clone()), random(&index, Some("rust")));
+
+        // Hidden stories are never selected
+        index.hide_story(&rust)?;
+        assert_eq!(None, random(&index, Some("rust")));
+        assert!((0..50).all(|_| random(&index, None) != Some(rust.clone())));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_facets(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let url = |i: u32| StoryUrl::parse(format!("http://domain-{}.com/", i % 2)).expect("URL");
+        let date = |i: u32| StoryDate::year_month_day(2020, 1 + i % 2, 1).expect("Date failed");
+        index.insert_scrapes(
+            &eval,
+            (0..4).map(|i| {
+                let url =
+                    StoryUrl::parse(format!("http://domain-{}.com/{}", i % 2, i)).expect("URL");
+                hn_story(&format!("story{}", i), date(i), "I love Rust", &url)
+            }),
+        )?;
+        // Merge a second source into an existing story
+        index.insert_scrapes(
+            &eval,
+            [reddit_story(
+                "r1",
+                "rust",
+                date(0),
+                "I love Rust",
+                &StoryUrl::parse("http://domain-0.com/0").expect("URL"),
+            )]
+            .into_iter(),
+        )?;
+        index.insert_scrapes(
+            &eval,
+            [hn_story("other", date(0), "I love Python", &url(0))].into_iter(),
+        )?;
+
+        let facets = index.fetch_facets(StoryQuery::TagSearch("rust".into()), 10)?;
+        let count = index.fetch_count(StoryQuery::TagSearch("rust".into()), 100)?;
+        assert_eq!(4, count);
+
+        // Every story has exactly one domain, and one story has two sources
+        assert_eq!(
+            vec![(ScrapeSource::HackerNews, 4), (ScrapeSource::Reddit, 1)],
+            facets.sources
+        );
+        assert_eq!(count, facets.domains.iter().map(|(_, n)| n).sum::<usize>());
+        assert_eq!(
+            vec![
+                ("domain-0.com".to_owned(), 2),
+                ("domain-1.com".to_owned(), 2)
+            ],
+            facets.domains
+        );
+        assert_eq!(Some(&("rust".to_owned(), 4)), facets.tags.first());
+        let tags = index.fetch_tag_counts(1, None)?;
+        assert_eq!(Some(&("rust".to_owned(), 4)), tags.first());
+        assert!(tags.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(
+            vec![("rust".to_owned(), 4)],
+            index.fetch_tag_counts(4, None)?
+        );
+        // Only stories in the window are counted
+        let recent = index.fetch_tag_counts(1, Some(date(1)))?;
+        assert_eq!(Some(&("rust".to_owned(), 2)), recent.first());
+
+        // Hidden stories aren't counted
+        let hidden = index.fetch::<Shard>(StoryQuery::TagSearch("rust".into()), 1)?[0]
+            .id
+            .clone();
+        index.hide_story(&hidden)?;
+        let facets = index.fetch_facets(StoryQuery::TagSearch("rust".into()), 10)?;
+        assert_eq!(3, facets.domains.iter().map(|(_, n)| n).sum::<usize>());
+        assert!(index.fetch_tag_counts(4, None)?.is_empty());
+
+        // Only searches have facets
+        assert!(index.fetch_facets(StoryQuery::FrontPage(), 10).is_err());
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_commit_batches(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let stories = || -> Result<_, PersistError> {
+            let mut memindex = MemIndex::default();
+            memindex.insert_scrapes((0..250).map(|i| {
+                let date = StoryDate::year_month_day(2020, 1 + i % 12, 1).expect("Date");
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                hn_story(&format!("story{}", i), date, "I love Rust", &url)
+            }))?;
+            Ok(memindex.get_all_stories())
+        };
+
+        // A single commit by default
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        assert_eq!(1, index.insert_scrape_collections(&eval, stories()?)?);
+
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_commit_batch_size(Some(100));
+        assert_eq!(3, index.insert_scrape_collections(&eval, stories()?)?);
+        assert_eq!(250, index.story_count()?.total.story_count);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_recently_ingested(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let url = |s: &str| StoryUrl::parse(s).expect("URL");
+
+        // A bulk import of a newer story assumes it was ingested on time...
+        let mut memindex = MemIndex::default();
+        let newer = StoryDate::year_month_day(2020, 6, 1).expect("Date");
+        memindex.insert_scrapes(
+            [hn_story(
+                "new",
+                newer,
+                "Newer story",
+                &url("http://new.com/"),
+            )]
+            .into_iter(),
+        )?;
+        index.insert_scrape_collections(&eval, memindex.get_all_stories())?;
+
+        // ... while an old story discovered now is ingested now
+        let older = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        let before = StoryDate::now();
+        index.insert_scrapes(
+            &eval,
+            [hn_story(
+                "old",
+                older,
+                "Older story",
+                &url("http://old.com/"),
+            )]
+            .into_iter(),
+        )?;
+
+        let stories = index.fetch::<Shard>(StoryQuery::RecentlyIngested(), 10)?;
+        assert_eq!(
+            vec!["Older story", "Newer story"],
+            stories.iter().map(|s| s.title.as_str()).collect_vec()
+        );
+        let first_seen = stories[0].first_seen.expect("Missing ingestion time");
+        assert!(first_seen.timestamp() >= before.timestamp());
+        assert_eq!(Some(newer), stories[1].first_seen);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_newest(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+
+        // Across two shards, with engagement that would rank the older stories first
+        let scrapes = (0..6).map(|i| {
+            let date = StoryDate::year_month_day(2020, 1 + i / 3, 1 + i).expect("Date");
+            let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+            let mut scrape = HackerNewsStory::new_with_defaults(
+                i.to_string(),
+                date,
+                format!("Story {}", i),
+                url,
+            );
+            scrape.data.points = 1000 - i * 100;
+            scrape.data.comments = 500 - i * 50;
+            scrape.into()
+        });
+        index.insert_scrapes(&eval, scrapes)?;
+
+        let titles =
+            |stories: Vec<Story<Shard>>| stories.into_iter().map(|s| s.title).collect_vec();
+        assert_eq!(
+            vec!["Story 5", "Story 4", "Story 3", "Story 2"],
+            titles(index.query_newest(4)?)
+        );
+
+        // Hidden stories are skipped
+        let newest = index.query_newest(1)?.remove(0);
+        index.hide_story(&newest.id)?;
+        assert_eq!(
+            vec!["Story 4", "Story 3", "Story 2", "Story 1", "Story 0"],
+            titles(index.query_newest(10)?)
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_first_seen_merge(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let url = StoryUrl::parse("http://example.com/").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        index.insert_scrapes(
+            &eval,
+            [hn_story("1", date, "I love Rust", &url)].into_iter(),
+        )?;
+        let story = index.fetch::<Shard>(StoryQuery::RecentlyIngested(), 1)?[0].clone();
+        let first_seen = story.first_seen.expect("Missing first seen");
+
+        // Wait so that a fresh timestamp would differ, then merge a later scrape from another source
+        std::thread::sleep(Duration::from_millis(1100));
+        let later = StoryDate::year_month_day(2020, 1, 2).expect("Date");
+        index.insert_scrapes(
+            &eval,
+            [reddit_story("r1", "rust", later, "I love Rust", &url)].into_iter(),
+        )?;
+
+        let merged = index
+            .fetch_one::<Shard>(StoryQuery::ById(story.id.clone()))?
+            .expect("Missing story");
+        assert_eq!(2, merged.scrapes.len());
+        assert_eq!(Some(first_seen), merged.first_seen);
+        assert_eq!(date, merged.date);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_read_times(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            (0..2).map(|i| hn_story(&format!("story{}", i), date, "I love Rust", &url(i))),
+        )?;
+
+        let stories = index.fetch::<Shard>(StoryQuery::FrontPage(), 10)?;
+        assert!(stories.iter().all(|story| story.read_time.is_none()));
+        index.set_read_time(&stories[0].id, 5)?;
+
+        let id = stories[0].id.clone();
+        let story = index
+            .fetch_one::<Shard>(StoryQuery::ById(id.clone()))?
+            .expect("Missing story");
+        assert_eq!(Some(5), story.read_time);
+        assert_eq!(Some(5), story.render(&eval.tagger, 0).read_time);
+        let story = index
+            .fetch_one::<TypedScrape>(StoryQuery::ById(id))?
+            .expect("Missing story");
+        assert_eq!(Some(5), story.read_time);
+        let other = index
+            .fetch_one::<Shard>(StoryQuery::ById(stories[1].id.clone()))?
+            .expect("Missing story");
+        assert_eq!(None, other.read_time);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_front_page_quotas(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let date = |i: i64| StoryDate::from_seconds(1_672_531_200 + i * 60).expect("Date");
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+
+        // Reddit floods the index with newer stories than Hacker News
+        let hn = (0..5).map(|i| hn_story(&format!("hn{}", i), date(i), "HN story", &url(i)));
+        let reddit = (100..150)
+            .map(|i| reddit_story(&format!("r{}", i), "rust", date(i), "Reddit story", &url(i)));
+        index.insert_scrapes(&eval, hn.chain(reddit))?;
+
+        let count_hn = |stories: Vec<Story<Shard>>| {
+            stories
+                .iter()
+                .filter(|story| {
+                    story
+                        .scrapes
+                        .keys()
+                        .any(|id| id.source == ScrapeSource::HackerNews)
+                })
+                .count()
+        };
+
+        // Without quotas, the candidate window is entirely Reddit
+        let stories = index.fetch::<Shard>(StoryQuery::FrontPage(), 10)?;
+        assert_eq!(10, stories.len());
+        assert_eq!(0, count_hn(stories));
+
+        // With a quota on Reddit, Hacker News stories make it into the candidates
+        let mut quotas = TypedScrapeMap::new();
+        quotas.reddit = Some(5);
+        let stories = index.fetch::<Shard>(StoryQuery::FrontPageWithQuotas(quotas), 10)?;
+        assert_eq!(10, stories.len());
+        assert_eq!(5, count_hn(stories));
+
+        Ok(())
+    }
+
     #[rstest]
     fn test_index_scrape_collections(
         _enable_tracing: &bool,
@@ -875,6 +2600,67 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    fn test_fetch_since(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+
+        let batch = |range: std::ops::Range<i64>| {
+            range
+                .map(|i| {
+                    let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                    // Two stories per date to exercise the ID tie-breaker
+                    let date = StoryDate::from_seconds(1_672_531_200 + (i / 2) * 60 * 60 * 24)
+                        .expect("Date failed");
+                    hn_story(&format!("story-{}", i), date, &format!("Title {}", i), &url)
+                })
+                .collect_vec()
+        };
+
+        // First batch spans two months
+        index.insert_scrapes(&eval, batch(0..80).into_iter())?;
+        let first = index.fetch::<Shard>(StoryQuery::Since(StoryDate::MIN, None), 100)?;
+        assert_eq!(80, first.len());
+        assert!(first
+            .iter()
+            .tuple_windows()
+            .all(|(a, b)| (a.date, a.id.to_base64()) < (b.date, b.id.to_base64())));
+
+        // Paging by high-water mark returns the same stories in the same order, including pages that split a date
+        let page_through = |page_size| -> Result<_, PersistError> {
+            let mut paged = vec![];
+            let mut mark = (StoryDate::MIN, None);
+            loop {
+                let page =
+                    index.fetch::<Shard>(StoryQuery::Since(mark.0, mark.1.clone()), page_size)?;
+                if let Some(last) = page.last() {
+                    mark = (last.date, Some(last.id.clone()));
+                    paged.extend(page);
+                } else {
+                    return Ok((paged.into_iter().map(|s| s.id).collect_vec(), mark));
+                }
+            }
+        };
+        let ids = first.iter().map(|s| s.id.clone()).collect_vec();
+        assert_eq!(ids, page_through(1)?.0);
+        let (paged, mark) = page_through(7)?;
+        assert_eq!(ids, paged);
+
+        // Second batch is returned from the high-water mark, and only the second batch
+        index.insert_scrapes(&eval, batch(80..100).into_iter())?;
+        let second = index.fetch::<Shard>(StoryQuery::Since(mark.0, mark.1), 100)?;
+        assert_eq!(
+            (80..100).map(|i| format!("Title {}", i)).collect_vec(),
+            second
+                .iter()
+                .map(|s| s.title.clone())
+                .sorted()
+                .collect_vec()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_findable_by_extracted_tag() -> Result<(), Box<dyn std::error::Error>> {
         let mut index = StoryIndex::new(PersistLocation::Memory)?;
@@ -941,6 +2727,33 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    fn test_schema_version(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path();
+        let location = PersistLocation::Path(path.to_owned());
+
+        // A new index is stamped with the current version, and can be re-opened
+        StoryIndex::new(location.clone())?;
+        assert_eq!(
+            SCHEMA_VERSION.to_string(),
+            std::fs::read_to_string(path.join(SCHEMA_VERSION_FILE))?
+        );
+        StoryIndex::new(location.clone())?;
+
+        // An index from an older version is refused
+        std::fs::write(path.join(SCHEMA_VERSION_FILE), "0")?;
+        assert!(matches!(
+            StoryIndex::new(location),
+            Err(PersistError::SchemaVersionMismatch {
+                found: 0,
+                expected: SCHEMA_VERSION
+            })
+        ));
+
+        Ok(())
+    }
+
     #[rstest]
     fn test_index_lots(
         _enable_tracing: &bool,
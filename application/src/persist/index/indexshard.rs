@@ -3,22 +3,23 @@ use itertools::Itertools;
 use tantivy::directory::{MmapDirectory, RamDirectory};
 use tantivy::query::{BooleanQuery, Occur, Query, RangeQuery, TermQuery};
 use tantivy::tokenizer::{PreTokenizedString, SimpleTokenizer, Tokenizer};
-use tantivy::{doc, Index, IndexReader};
+use tantivy::{doc, Index, IndexReader, TantivyError};
 use tantivy::{
     schema::*, Directory, DocAddress, IndexSettings, IndexSortByField, IndexWriter, Searcher,
 };
 
-use progscrape_scrapers::{ScrapeId, StoryDate};
+use progscrape_scrapers::{ScrapeId, ScrapeSource, StoryDate};
 
 use std::collections::HashSet;
 use std::hash::Hash;
 use std::ops::RangeBounds;
+use std::path::Path;
 
 use crate::persist::{ScrapePersistResult, Shard};
 use crate::story::{StoryScrapeId, TagSet};
 use crate::{PersistError, PersistLocation};
 
-use super::schema::StorySchema;
+use super::schema::{StorySchema, FACET_DOMAIN, FACET_SOURCE, FACET_TAG};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct StoryLookupId {
@@ -43,17 +44,22 @@ pub struct StoryInsert {
     pub date: i64,
     pub score: f64,
     pub tags: TagSet,
+    pub sources: Vec<ScrapeSource>,
     pub scrape_ids: Vec<String>,
+    /// When the story was first added to the index, which may be long after its date for stories discovered late.
+    pub first_seen: i64,
 }
 
 #[derive(Debug)]
 pub struct StoryFetch {
+    pub id: String,
     pub url: String,
     pub title: String,
     pub date: i64,
     pub score: f64,
     pub tags: Vec<String>,
     pub scrape_ids: Vec<StoryScrapeId>,
+    pub first_seen: i64,
 }
 
 /// For performance, we shard stories by time period to allow for more efficient lookup of normalized URLs.
@@ -66,6 +72,9 @@ pub struct StoryIndexShard {
 
 const MEMORY_ARENA_SIZE: usize = 50_000_000;
 
+/// Tantivy's record of the committed segments.
+const TANTIVY_META_FILE: &str = "meta.json";
+
 /// The `StoryIndexShard` manages a single shard of the index.
 impl StoryIndexShard {
     pub fn initialize(
@@ -80,10 +89,10 @@ impl StoryIndexShard {
             }),
             ..Default::default()
         };
-        let (directory, exists): (Box<dyn Directory>, bool) = match location {
-            PersistLocation::Memory => (Box::new(RamDirectory::create()), false),
-            PersistLocation::Path(path) => {
-                let path = path.join(format!("{}/index", shard.to_string()));
+        let (directory, exists): (Box<dyn Directory>, bool) = match location.shard_path(shard) {
+            None => (Box::new(RamDirectory::create()), false),
+            Some(path) => {
+                let path = path.join("index");
                 tracing::info!("Opening index at {}", path.to_string_lossy());
                 std::fs::create_dir_all(&path)?;
                 let dir = MmapDirectory::open(path)?;
@@ -116,6 +125,33 @@ impl StoryIndexShard {
         })
     }
 
+    /// Copy the files of the last commit to `target`, returning the number of files copied. The files come from the
+    /// committed metadata rather than the directory, so in-progress and garbage segments are skipped.
+    pub fn snapshot(&self, target: &Path) -> Result<usize, PersistError> {
+        std::fs::create_dir_all(target)?;
+        let meta = self.index.load_metas()?;
+        let directory = self.index.directory();
+        let mut count = 0;
+        for path in meta
+            .segments
+            .iter()
+            .flat_map(|segment| segment.list_files())
+        {
+            // Not every segment has every component
+            if !directory.exists(&path).map_err(TantivyError::from)? {
+                continue;
+            }
+            // Read the raw file, as `open_read` strips the footer that tantivy validates on open
+            let bytes = directory.atomic_read(&path).map_err(TantivyError::from)?;
+            std::fs::write(target.join(&path), bytes)?;
+            count += 1;
+        }
+        let mut meta = serde_json::to_vec_pretty(&meta)?;
+        meta.push(b'\n');
+        std::fs::write(target.join(TANTIVY_META_FILE), meta)?;
+        Ok(count + 1)
+    }
+
     /// Provides a valid searcher and schema temporarily for the callback function.
     #[inline(always)]
     pub fn with_searcher<F: FnMut(&Searcher, &StorySchema) -> T, T>(
@@ -182,11 +218,28 @@ impl StoryIndexShard {
             self.schema.title_field => doc.title,
             self.schema.date_field => doc.date,
             self.schema.score_field => doc.score,
+            self.schema.first_seen_field => doc.first_seen,
         };
         for id in doc.scrape_ids {
             new_doc.add_text(self.schema.scrape_field, id);
         }
+        for source in doc.sources {
+            new_doc.add_facet(
+                self.schema.facets_field,
+                Facet::from_path([FACET_SOURCE, source.into_str()]),
+            );
+        }
+        if !doc.host.is_empty() {
+            new_doc.add_facet(
+                self.schema.facets_field,
+                Facet::from_path([FACET_DOMAIN, &doc.host]),
+            );
+        }
         for tag in doc.tags {
+            new_doc.add_facet(
+                self.schema.facets_field,
+                Facet::from_path([FACET_TAG, &tag]),
+            );
             new_doc.add_text(self.schema.tags_field, tag);
         }
 
@@ -215,6 +268,8 @@ impl StoryIndexShard {
 
         doc_address: DocAddress,
         mut scrape_ids: HashSet<String>,
+        mut sources: HashSet<ScrapeSource>,
+        retain: Option<HashSet<String>>,
     ) -> Result<ScrapePersistResult, PersistError> {
         let mut doc = self.searcher.doc(doc_address)?;
 
@@ -244,6 +299,39 @@ impl StoryIndexShard {
             doc.add_text(self.schema.scrape_field, id);
         }
 
+        // Drop any scrapes beyond the limit
+        if let Some(retain) = retain {
+            let scrape_field = self.schema.scrape_field;
+            doc = doc
+                .field_values()
+                .iter()
+                .filter(|value| {
+                    value.field() != scrape_field
+                        || value
+                            .value()
+                            .as_text()
+                            .is_some_and(|id| retain.contains(id))
+                })
+                .cloned()
+                .collect_vec()
+                .into();
+        }
+
+        // Count the story under any new sources
+        for value in doc.get_all(self.schema.facets_field) {
+            if let Some(facet) = value.as_facet() {
+                if let [FACET_SOURCE, source] = facet.to_path()[..] {
+                    sources.retain(|s| s.into_str() != source);
+                }
+            }
+        }
+        for source in sources {
+            doc.add_facet(
+                self.schema.facets_field,
+                Facet::from_path([FACET_SOURCE, source.into_str()]),
+            );
+        }
+
         // Re-add the norm hash
         let norm = self
             .searcher
@@ -322,6 +410,7 @@ impl StoryIndexShard {
 
     pub fn lookup_story(&self, doc_address: DocAddress) -> Result<StoryFetch, PersistError> {
         let doc = self.searcher.doc(doc_address)?;
+        let id = self.text_value(&doc, self.schema.id_field);
         let url = self.text_value(&doc, self.schema.url_field);
         let title = self.text_value(&doc, self.schema.title_field);
         let date = self.i64_value(&doc, self.schema.date_field);
@@ -341,13 +430,16 @@ impl StoryIndexShard {
             })
             .collect_vec();
         let tags = self.text_values(&doc, self.schema.tags_field);
+        let first_seen = self.i64_value(&doc, self.schema.first_seen_field);
         Ok(StoryFetch {
+            id,
             url,
             title,
             date,
             score,
             scrape_ids,
             tags,
+            first_seen,
         })
     }
 
@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::persist::shard::ShardOrder;
+use crate::{
+    MemIndex, PersistError, PersistLocation, Shard, ShardLocation, Storage, StoryEvaluator,
+    StoryIdScheme, StoryIdentifier, StoryIndex, StoryQuery,
+};
+
+use super::index::PreviousStory;
+
+/// Story counts from an index migration.
+#[derive(Debug, Serialize)]
+pub struct MigrationResult {
+    /// The number of stories in the old index, if it was readable.
+    pub stories_before: Option<usize>,
+    pub stories_after: usize,
+    /// The number of raw scrapes that were re-indexed.
+    pub scrapes: usize,
+}
+
+/// A path next to the given one, used to stage the migration.
+pub(super) fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// A replacement for an index that is built next to it and swapped in once it is complete. Each directory that may
+/// hold shards is staged next to itself, so shards stored elsewhere stay on their own storage.
+pub(super) struct Staging {
+    /// The staged copy of the main index directory.
+    pub path: PathBuf,
+    pub location: PersistLocation,
+    /// Each staged directory, and the directory it replaces.
+    dirs: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Staging {
+    /// Stage a replacement for the index at `location`, clearing out any earlier attempt.
+    pub fn new(location: &PersistLocation, suffix: &str) -> Result<Self, PersistError> {
+        let dirs = location
+            .shard_roots()
+            .into_iter()
+            .unique()
+            .map(|root| (sibling(root, suffix), root.to_owned()))
+            .collect_vec();
+        let (path, location) = match location {
+            PersistLocation::Memory => {
+                return Err(PersistError::UnexpectedError(
+                    "An in-memory index can't be replaced".into(),
+                ))
+            }
+            PersistLocation::Path(path) => {
+                let path = sibling(path, suffix);
+                (path.clone(), PersistLocation::Path(path))
+            }
+            PersistLocation::Sharded(path, locations) => {
+                let path = sibling(path, suffix);
+                let locations = locations
+                    .iter()
+                    .map(|location| ShardLocation {
+                        path: sibling(&location.path, suffix),
+                        ..location.clone()
+                    })
+                    .collect();
+                (path.clone(), PersistLocation::Sharded(path, locations))
+            }
+        };
+        for (staged, _) in &dirs {
+            if staged.exists() {
+                std::fs::remove_dir_all(staged)?;
+            }
+            std::fs::create_dir_all(staged)?;
+        }
+        Ok(Self {
+            path,
+            location,
+            dirs,
+        })
+    }
+
+    /// Swap every staged directory into place. The old directories are kept until all of them have been replaced, so
+    /// a failure part of the way through can put back the ones that were already swapped.
+    pub fn swap(self) -> Result<(), PersistError> {
+        let mut swapped = vec![];
+        for (staged, path) in &self.dirs {
+            if let Err(e) = Self::swap_dir(staged, path) {
+                for (staged, path) in swapped.into_iter().rev() {
+                    std::fs::rename(path, staged)?;
+                    let old_path = sibling(path, "old");
+                    if old_path.exists() {
+                        std::fs::rename(&old_path, path)?;
+                    }
+                }
+                return Err(e);
+            }
+            swapped.push((staged, path));
+        }
+        for (_, path) in &self.dirs {
+            let old_path = sibling(path, "old");
+            if old_path.exists() {
+                std::fs::remove_dir_all(&old_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Throw away the staged directories.
+    pub fn remove(self) -> Result<(), PersistError> {
+        for (staged, _) in &self.dirs {
+            std::fs::remove_dir_all(staged)?;
+        }
+        Ok(())
+    }
+
+    fn swap_dir(staged: &Path, path: &Path) -> Result<(), PersistError> {
+        let old_path = sibling(path, "old");
+        if old_path.exists() {
+            std::fs::remove_dir_all(&old_path)?;
+        }
+        if path.exists() {
+            std::fs::rename(path, &old_path)?;
+        }
+        if let Err(e) = std::fs::rename(staged, path) {
+            if old_path.exists() {
+                std::fs::rename(&old_path, path)?;
+            }
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+impl StoryIndex {
+    /// Rebuild the index at the given location with the current schema, using only the raw scrapes that it contains.
+    /// The new index is built alongside the old one, with each shard staged next to the directory it belongs in, and
+    /// swapped in once it is complete, so a failed migration leaves the old index untouched. The rebuilt stories keep the times they were first seen, under [`StoryIdScheme::Stable`]
+    /// they keep their IDs, and under either scheme hidden stories, pinned stories and read times follow them.
+    pub fn migrate(
+        location: &PersistLocation,
+        eval: &StoryEvaluator,
+        id_scheme: StoryIdScheme,
+    ) -> Result<MigrationResult, PersistError> {
+        let old = StoryIndex::open_unchecked(location.clone())?;
+        let stories_before = match old.story_count() {
+            Ok(count) => Some(count.total.story_count),
+            Err(e) => {
+                tracing::warn!("Unable to count stories in the old index: {:?}", e);
+                None
+            }
+        };
+
+        // The story that each scrape belonged to, if the old stories are readable
+        let mut previous = HashMap::new();
+        for shard in old.shards().iterate(ShardOrder::OldestFirst) {
+            match old.fetch::<Shard>(StoryQuery::ByShard(shard), usize::MAX) {
+                Ok(stories) => {
+                    for story in stories {
+                        let previous_story = PreviousStory {
+                            id: story.id,
+                            first_seen: story.first_seen,
+                        };
+                        for id in story.scrapes.into_keys() {
+                            previous.insert(id, previous_story.clone());
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!(
+                    "Unable to read the old stories in shard {}: {:?}",
+                    shard.to_string(),
+                    e
+                ),
+            }
+        }
+
+        let mut memindex = MemIndex::default();
+        let mut scrapes = 0;
+        for shard in old.shards().iterate(ShardOrder::OldestFirst) {
+            let mut batch = vec![];
+            old.with_scrapes(|db| {
+                db.fetch_all(
+                    shard,
+                    |scrape| {
+                        batch.push(scrape);
+                        Ok(())
+                    },
+                    |e| tracing::error!("Skipping unreadable scrape: {:?}", e),
+                )
+            })?;
+            tracing::info!(
+                "Read {} scrape(s) from shard {}",
+                batch.len(),
+                shard.to_string()
+            );
+            scrapes += batch.len();
+            memindex.insert_scrapes(batch.into_iter())?;
+        }
+        let hidden = old
+            .hidden
+            .ids()
+            .filter_map(StoryIdentifier::from_base64)
+            .collect_vec();
+        let pinned = old.pinned_stories();
+        let read_times = old
+            .read_times
+            .iter()
+            .filter_map(|(id, minutes)| Some((StoryIdentifier::from_base64(id)?, minutes)))
+            .collect_vec();
+        drop(old);
+
+        let staging = Staging::new(location, "migrate")?;
+        let mut new = StoryIndex::new(staging.location.clone())?;
+        new.set_id_scheme(id_scheme);
+        new.insert_rebuilt_scrape_collections(
+            eval,
+            memindex.get_all_stories().map(|story| {
+                let previous_story = story
+                    .scrapes
+                    .keys()
+                    .find_map(|id| previous.get(id))
+                    .cloned();
+                (story, previous_story)
+            }),
+        )?;
+        let stories_after = new.story_count()?.total.story_count;
+
+        // Hidden stories, pinned stories and read times are keyed by story ID, which a rebuilt story doesn't keep under
+        // the earliest scheme, so map each old ID to the story that took over its scrapes
+        let mut ids = HashMap::new();
+        for shard in new.shards().iterate(ShardOrder::OldestFirst) {
+            for story in new.fetch::<Shard>(StoryQuery::ByShard(shard), usize::MAX)? {
+                for id in story.scrapes.keys() {
+                    if let Some(previous_story) = previous.get(id) {
+                        ids.insert(previous_story.id.clone(), story.id.clone());
+                    }
+                }
+            }
+        }
+        let remap = |id: StoryIdentifier| ids.get(&id).cloned().unwrap_or(id);
+        for id in hidden {
+            new.hide_story(&remap(id))?;
+        }
+        for id in pinned {
+            new.pin_story(&remap(id))?;
+        }
+        for (id, minutes) in read_times {
+            new.set_read_time(&remap(id), minutes)?;
+        }
+        drop(new);
+        staging.swap()?;
+
+        Ok(MigrationResult {
+            stories_before,
+            stories_after,
+            scrapes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::persist::index::index::SCHEMA_VERSION_FILE;
+    use crate::persist::index::schema::SCHEMA_VERSION;
+    use crate::{test::*, StorageWriter};
+    use progscrape_scrapers::{
+        hacker_news::HackerNewsStory, reddit::RedditStory, StoryDate, StoryUrl, TypedScrape,
+    };
+    use rstest::*;
+
+    #[rstest]
+    fn test_migrate(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("index");
+        let location = PersistLocation::Path(path.clone());
+        let eval = StoryEvaluator::new_for_test();
+
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        let mut index = StoryIndex::new(location.clone())?;
+        index.insert_scrapes(
+            &eval,
+            (0..3).map(|i| {
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                HackerNewsStory::new_with_defaults(i.to_string(), date, "I love Rust".into(), url)
+                    .into()
+            }),
+        )?;
+        let story = index.fetch::<Shard>(StoryQuery::FrontPage(), 1)?.remove(0);
+        let (hidden, first_seen) = (story.id, story.first_seen);
+        // Stories are first seen when they're scraped, not when they were posted
+        assert_ne!(Some(date), first_seen);
+        index.hide_story(&hidden)?;
+        drop(index);
+
+        // Simulate an index written by an older release
+        std::fs::write(
+            path.join(SCHEMA_VERSION_FILE),
+            (SCHEMA_VERSION - 1).to_string(),
+        )?;
+        assert!(StoryIndex::new(location.clone()).is_err());
+
+        let res = StoryIndex::migrate(&location, &eval, StoryIdScheme::Earliest)?;
+        assert_eq!(Some(3), res.stories_before);
+        assert_eq!(3, res.stories_after);
+        assert_eq!(3, res.scrapes);
+        let index = StoryIndex::new(location.clone())?;
+        let stories =
+            index.fetch::<TypedScrape>(StoryQuery::ByShard(Shard::from_date_time(date)), 10)?;
+        assert_eq!(3, stories.len());
+        assert!(stories.iter().all(|story| story.scrapes.len() == 1));
+        assert!(stories.iter().all(|story| story.first_seen == first_seen));
+        assert!(index.is_story_hidden(&hidden));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::earliest(StoryIdScheme::Earliest)]
+    #[case::stable(StoryIdScheme::Stable)]
+    fn test_migrate_story_ids(
+        _enable_tracing: &bool,
+        #[case] id_scheme: StoryIdScheme,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("index");
+        let location = PersistLocation::Path(path.clone());
+        let eval = StoryEvaluator::new_for_test();
+
+        // An earlier scrape is merged into the story after it is first indexed, so a rebuild would date it earlier
+        let url = StoryUrl::parse("http://domain-1.com/").expect("URL");
+        let first = StoryDate::year_month_day(2020, 1, 20).expect("Date");
+        let earlier = StoryDate::year_month_day(2020, 1, 10).expect("Date");
+        let mut index = StoryIndex::new(location.clone())?;
+        index.set_id_scheme(id_scheme);
+        index.insert_scrapes(
+            &eval,
+            [HackerNewsStory::new_with_defaults("1", first, "I love Rust", url.clone()).into()]
+                .into_iter(),
+        )?;
+        index.insert_scrapes(
+            &eval,
+            [RedditStory::new_subsource_with_defaults(
+                "1",
+                "rust",
+                earlier,
+                "I love Rust",
+                url.clone(),
+            )
+            .into()]
+            .into_iter(),
+        )?;
+        let id = index.fetch::<Shard>(StoryQuery::FrontPage(), 1)?[0]
+            .id
+            .clone();
+        assert!(id.matches_date(first));
+        index.hide_story(&id)?;
+        index.pin_story(&id)?;
+        index.set_read_time(&id, 5)?;
+        drop(index);
+
+        StoryIndex::migrate(&location, &eval, id_scheme)?;
+        let index = StoryIndex::new(location.clone())?;
+        let story = index
+            .fetch::<Shard>(StoryQuery::ByShard(Shard::from_date_time(first)), 10)?
+            .into_iter()
+            .next()
+            .expect("Missing story");
+        match id_scheme {
+            StoryIdScheme::Earliest => assert!(story.id.matches_date(earlier)),
+            StoryIdScheme::Stable => assert_eq!(id, story.id),
+        }
+        assert!(index.is_story_hidden(&story.id));
+        assert_eq!(vec![story.id], index.pinned_stories());
+        assert_eq!(Some(5), story.read_time);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_migrate_sharded(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let (path, archive) = (dir.path().join("index"), dir.path().join("archive"));
+        let january = Shard::from_year_month(2020, 1);
+        let location = PersistLocation::Sharded(
+            path.clone(),
+            vec![ShardLocation {
+                first: None,
+                last: Some(january),
+                path: archive.clone(),
+            }],
+        );
+        let eval = StoryEvaluator::new_for_test();
+
+        // Stories across the archived shard and the main directory
+        let mut index = StoryIndex::new(location.clone())?;
+        index.insert_scrapes(
+            &eval,
+            (0..4).map(|i| {
+                let date = StoryDate::year_month_day(2020, 1 + i % 2, 1).expect("Date");
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                HackerNewsStory::new_with_defaults(i.to_string(), date, "I love Rust".into(), url)
+                    .into()
+            }),
+        )?;
+        drop(index);
+        std::fs::write(
+            path.join(SCHEMA_VERSION_FILE),
+            (SCHEMA_VERSION - 1).to_string(),
+        )?;
+
+        let res = StoryIndex::migrate(&location, &eval, StoryIdScheme::Earliest)?;
+        assert_eq!(Some(4), res.stories_before);
+        assert_eq!(4, res.stories_after);
+        let index = StoryIndex::new(location.clone())?;
+        assert_eq!(4, index.story_count()?.total.story_count);
+        // The rebuilt archived shard is back on its own storage, with nothing left behind in the main directory
+        assert!(archive.join(january.to_string()).exists());
+        assert!(!path.join(january.to_string()).exists());
+        // Only the index and archive directories remain, without any staged or old copies
+        assert_eq!(2, std::fs::read_dir(dir.path())?.count());
+
+        Ok(())
+    }
+}
@@ -1,5 +1,15 @@
-mod index;
+#[cfg(test)]
+mod bench;
+mod durability;
+mod import;
+pub(crate) mod index;
 mod indexshard;
-mod schema;
+mod migrate;
+pub(crate) mod schema;
+mod snapshot;
 
+pub use durability::Durability;
+pub use import::ImportResult;
 pub use index::StoryIndex;
+pub use migrate::MigrationResult;
+pub use snapshot::SnapshotResult;
@@ -1,5 +1,15 @@
 use tantivy::schema::{Field, Schema, FAST, INDEXED, STORED, STRING, TEXT};
 
+/// The version of the index schema that this build reads and writes. This must be bumped whenever the schema or the
+/// way that stories are indexed changes incompatibly.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// The root facets that each story is counted under, eg: `/source/hacker_news`, `/domain/example.com`, `/tag/rust`.
+pub const FACET_SOURCE: &str = "source";
+pub const FACET_DOMAIN: &str = "domain";
+pub const FACET_TAG: &str = "tag";
+pub const FACETS: [&str; 3] = [FACET_SOURCE, FACET_DOMAIN, FACET_TAG];
+
 #[derive(Clone)]
 pub struct StorySchema {
     pub schema: Schema,
@@ -13,6 +23,8 @@ pub struct StorySchema {
     pub date_field: Field,
     pub scrape_field: Field,
     pub tags_field: Field,
+    pub facets_field: Field,
+    pub first_seen_field: Field,
 }
 
 impl StorySchema {
@@ -28,6 +40,8 @@ impl StorySchema {
         let scrape_field = schema_builder.add_text_field("scrapes", TEXT | STORED);
         let score_field = schema_builder.add_f64_field("score", FAST | STORED);
         let tags_field = schema_builder.add_text_field("tags", TEXT | STORED);
+        let facets_field = schema_builder.add_facet_field("facets", INDEXED | STORED);
+        let first_seen_field = schema_builder.add_i64_field("first_seen", FAST | STORED);
         let schema = schema_builder.build();
 
         Self {
@@ -42,6 +56,8 @@ impl StorySchema {
             date_field,
             scrape_field,
             tags_field,
+            facets_field,
+            first_seen_field,
         }
     }
 }
@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::persist::shard::ShardOrder;
+use crate::{PersistError, PersistLocation, Shard, Storage, StoryIndex};
+
+use super::index::SCHEMA_VERSION_FILE;
+use super::migrate::Staging;
+use super::schema::SCHEMA_VERSION;
+
+/// Written last, so a snapshot without one is incomplete.
+const SNAPSHOT_MANIFEST_FILE: &str = "snapshot.json";
+
+/// The contents of an index snapshot.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotResult {
+    pub shards: usize,
+    pub stories: usize,
+    /// The number of index and database files copied.
+    pub files: usize,
+}
+
+/// Recursively copy a directory.
+fn copy_dir(from: &Path, to: &Path) -> Result<(), PersistError> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()))?;
+        } else {
+            std::fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+impl StoryIndex {
+    /// Copy a consistent view of the index to `target`, which must not exist. Only the files referenced by each
+    /// shard's last commit are copied, and the databases are copied through SQLite, so this is safe while the index
+    /// is being read. Writes require `&mut self`, so none can happen during the snapshot.
+    pub fn snapshot(&self, target: &Path) -> Result<SnapshotResult, PersistError> {
+        if target.exists() {
+            return Err(PersistError::UnexpectedError(format!(
+                "Snapshot path {:?} already exists",
+                target
+            )));
+        }
+        std::fs::create_dir_all(target)?;
+
+        let mut result = SnapshotResult::default();
+        for shard in self.shards().iterate(ShardOrder::OldestFirst) {
+            let path = target.join(shard.to_string());
+            result.files +=
+                self.with_index(shard, |_, index| index.snapshot(&path.join("index")))??;
+            self.scrape_db.snapshot(shard, &path)?;
+            result.files += 1;
+            result.shards += 1;
+        }
+        self.hidden.snapshot(target)?;
+        self.pinned.snapshot(target)?;
+        self.read_times.snapshot(target)?;
+        result.files += 3;
+        std::fs::write(target.join(SCHEMA_VERSION_FILE), SCHEMA_VERSION.to_string())?;
+        result.stories = self.story_count()?.total.story_count;
+
+        serde_json::to_writer_pretty(
+            std::fs::File::create(target.join(SNAPSHOT_MANIFEST_FILE))?,
+            &result,
+        )?;
+        tracing::info!("Wrote snapshot of {:?} to {:?}", result, target);
+        Ok(result)
+    }
+
+    /// Replace the index at `location` with a snapshot, placing each shard in the directory that the location keeps it
+    /// in. The snapshot is checked against the current schema version and opened to confirm that it contains the
+    /// stories it claims to before it is swapped in, so a bad snapshot leaves the existing index untouched. The index
+    /// must not be open while it is restored.
+    pub fn restore_snapshot(
+        snapshot: &Path,
+        location: &PersistLocation,
+    ) -> Result<SnapshotResult, PersistError> {
+        let manifest = snapshot.join(SNAPSHOT_MANIFEST_FILE);
+        if !manifest.exists() {
+            return Err(PersistError::UnexpectedError(format!(
+                "{:?} is not a complete snapshot",
+                snapshot
+            )));
+        }
+        let expected: SnapshotResult = serde_json::from_reader(std::fs::File::open(manifest)?)?;
+        let version = std::fs::read_to_string(snapshot.join(SCHEMA_VERSION_FILE))?;
+        let found = version.trim().parse().map_err(|_| {
+            PersistError::UnexpectedError(format!("Invalid schema version '{}'", version))
+        })?;
+        if found != SCHEMA_VERSION {
+            return Err(PersistError::SchemaVersionMismatch {
+                found,
+                expected: SCHEMA_VERSION,
+            });
+        }
+
+        let staging = Staging::new(location, "restore")?;
+        for entry in std::fs::read_dir(snapshot)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name == SNAPSHOT_MANIFEST_FILE {
+                continue;
+            }
+            let target = name
+                .to_str()
+                .and_then(Shard::from_string)
+                .and_then(|shard| staging.location.shard_path(shard))
+                .unwrap_or_else(|| staging.path.join(&name));
+            if entry.file_type()?.is_dir() {
+                copy_dir(&entry.path(), &target)?;
+            } else {
+                std::fs::copy(entry.path(), target)?;
+            }
+        }
+        let restored = StoryIndex::new(staging.location.clone())?;
+        let stories = restored.story_count()?.total.story_count;
+        let shards = restored.shards().iterate(ShardOrder::OldestFirst).count();
+        drop(restored);
+        if stories != expected.stories || shards != expected.shards {
+            staging.remove()?;
+            return Err(PersistError::UnexpectedError(format!(
+                "Snapshot contains {} stories in {} shard(s), expected {:?}",
+                stories, shards, expected
+            )));
+        }
+        staging.swap()?;
+
+        tracing::info!("Restored snapshot {:?} to {:?}", snapshot, location);
+        Ok(expected)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ShardLocation, StorageWriter, StoryEvaluator, StoryQuery};
+    use progscrape_scrapers::{hacker_news::HackerNewsStory, StoryDate, StoryUrl, TypedScrape};
+
+    #[test]
+    fn test_snapshot_restore() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let (path, snapshot, restored, archive) = (
+            dir.path().join("index"),
+            dir.path().join("snap"),
+            dir.path().join("restored"),
+            dir.path().join("archive"),
+        );
+        let eval = StoryEvaluator::new_for_test();
+
+        let mut index = StoryIndex::new(PersistLocation::Path(path.clone()))?;
+        // Stories across two shards
+        index.insert_scrapes(
+            &eval,
+            (0..6).map(|i| {
+                let date = StoryDate::year_month_day(2020, 1 + i % 2, 1).expect("Date");
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                HackerNewsStory::new_with_defaults(i.to_string(), date, "I love Rust".into(), url)
+                    .into()
+            }),
+        )?;
+        let hidden = index.fetch::<Shard>(StoryQuery::FrontPage(), 1)?[0]
+            .id
+            .clone();
+        index.hide_story(&hidden)?;
+        let result = index.snapshot(&snapshot)?;
+        assert_eq!(2, result.shards);
+        assert_eq!(6, result.stories);
+        assert!(index.snapshot(&snapshot).is_err());
+
+        // Changes after the snapshot aren't in it
+        index.insert_scrapes(
+            &eval,
+            [HackerNewsStory::new_with_defaults(
+                "later",
+                StoryDate::year_month_day(2020, 1, 2).expect("Date"),
+                "I love Rust",
+                StoryUrl::parse("http://later.com/").expect("URL"),
+            )
+            .into()]
+            .into_iter(),
+        )?;
+        drop(index);
+
+        // Restore over the existing index, and into a new location that keeps the older shard elsewhere
+        let location = PersistLocation::Path(path.clone());
+        let january = Shard::from_year_month(2020, 1);
+        let sharded = PersistLocation::Sharded(
+            restored.clone(),
+            vec![ShardLocation {
+                first: None,
+                last: Some(january),
+                path: archive.clone(),
+            }],
+        );
+        assert_eq!(result, StoryIndex::restore_snapshot(&snapshot, &location)?);
+        StoryIndex::restore_snapshot(&snapshot, &sharded)?;
+        assert!(archive.join(january.to_string()).exists());
+        assert!(!restored.join(january.to_string()).exists());
+        for location in [&location, &sharded] {
+            let index = StoryIndex::new(location.clone())?;
+            // One of the stories is hidden from search
+            assert_eq!(
+                5,
+                index.fetch_count(StoryQuery::from_search(&eval.tagger, "rust"), 100)?
+            );
+            let shard = index
+                .fetch::<TypedScrape>(StoryQuery::ByShard(Shard::from_year_month(2020, 2)), 10)?;
+            assert_eq!(3, shard.len());
+            assert!(shard.iter().all(|story| story.scrapes.len() == 1));
+            assert!(index.is_story_hidden(&hidden));
+        }
+
+        // An incomplete snapshot is refused and leaves the index alone
+        std::fs::remove_file(snapshot.join(SNAPSHOT_MANIFEST_FILE))?;
+        assert!(StoryIndex::restore_snapshot(&snapshot, &location).is_err());
+        assert_eq!(
+            6,
+            StoryIndex::new(location)?.story_count()?.total.story_count
+        );
+
+        Ok(())
+    }
+}
@@ -1,19 +1,25 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use crate::story::{Story, StoryEvaluator, StoryIdentifier, StoryTagger};
-use progscrape_scrapers::{ScrapeCollection, StoryDate, TypedScrape};
+use progscrape_scrapers::{ScrapeCollection, ScrapeSource, StoryDate, TypedScrape, TypedScrapeMap};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 mod backerupper;
 mod db;
+mod hidden;
 mod index;
 mod memindex;
+mod pinned;
+mod read_time;
 mod scrapestore;
 mod shard;
 
 pub use backerupper::{BackerUpper, BackupResult};
-pub use index::StoryIndex;
+pub use index::{Durability, ImportResult, MigrationResult, SnapshotResult, StoryIndex};
 pub use memindex::MemIndex;
 pub use shard::Shard;
 
@@ -35,6 +41,8 @@ pub enum PersistError {
     SerdeError(#[from] serde_rusqlite::Error),
     #[error("I/O error")]
     IOError(#[from] std::io::Error),
+    #[error("Index schema version {found} doesn't match the expected version {expected}, the index must be rebuilt with the `migrate` command")]
+    SchemaVersionMismatch { found: u32, expected: u32 },
     #[error("Unexpected error")]
     UnexpectedError(String),
 }
@@ -51,6 +59,14 @@ pub struct StorageSummary {
     pub total: ShardSummary,
 }
 
+/// Counts of the stories matching a search by source, domain and tag, most common first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchFacets {
+    pub sources: Vec<(ScrapeSource, usize)>,
+    pub domains: Vec<(String, usize)>,
+    pub tags: Vec<(String, usize)>,
+}
+
 /// The type of story fetch to perform.
 pub enum StoryQuery {
     /// A single story.
@@ -59,12 +75,24 @@ pub enum StoryQuery {
     ByShard(Shard),
     /// Front page stories.
     FrontPage(),
+    /// Front page stories, considering at most the given number of candidates from each source (`None` for no limit)
+    /// so that a single source can't crowd out the others.
+    FrontPageWithQuotas(TypedScrapeMap<Option<usize>>),
     /// Stories matching a tag query.
     TagSearch(String),
     /// Stories matching a domain query.
     DomainSearch(String),
     /// Stories matching a text search.
     TextSearch(String),
+    /// Stories on or after the given date, oldest first. If a story ID is provided, only stories strictly after that
+    /// story (ordered by date, then ID) are returned, making the pair usable as a high-water mark.
+    Since(StoryDate, Option<StoryIdentifier>),
+    /// Randomly selected stories, optionally with the given tag.
+    Random(Option<String>),
+    /// The most recently ingested stories, newest first, including hidden stories.
+    RecentlyIngested(),
+    /// The newest stories by date, newest first, regardless of score.
+    Newest(),
 }
 
 impl StoryQuery {
@@ -73,7 +101,7 @@ impl StoryQuery {
         if let Some(tag) = tagger.check_tag_search(search) {
             StoryQuery::TagSearch(tag.to_string())
         } else if search.contains('.') {
-            StoryQuery::DomainSearch(search.to_string())
+            StoryQuery::DomainSearch(tagger.canonicalize(search))
         } else {
             StoryQuery::TextSearch(search.to_string())
         }
@@ -104,6 +132,18 @@ pub trait Storage: Send + Sync {
     /// Count the docs matching the query, at most max.
     fn fetch_count(&self, query: StoryQuery, max: usize) -> Result<usize, PersistError>;
 
+    /// Count all the stories matching a search by source, domain and tag, keeping the `max` most common domains and
+    /// tags. Only tag, domain and text searches are supported.
+    fn fetch_facets(&self, query: StoryQuery, max: usize) -> Result<SearchFacets, PersistError>;
+
+    /// Count the stories carrying each tag, most common first, skipping tags on fewer than `min_count` stories. Only
+    /// stories dated on or after `since` are counted, or the whole index if it is `None`.
+    fn fetch_tag_counts(
+        &self,
+        min_count: usize,
+        since: Option<StoryDate>,
+    ) -> Result<Vec<(String, usize)>, PersistError>;
+
     /// Fetch a list of stories with the specified payload type.
     #[inline(always)]
     fn fetch<S: StoryScrapePayload>(
@@ -130,6 +170,14 @@ pub trait Storage: Send + Sync {
             .into_iter()
             .next())
     }
+
+    /// Fetch the `count` newest stories strictly by date, newest first.
+    fn query_newest(&self, count: usize) -> Result<Vec<Story<Shard>>, PersistError>
+    where
+        Self: StorageFetch<Shard>,
+    {
+        self.fetch::<Shard>(StoryQuery::Newest(), count)
+    }
 }
 
 pub trait StorageWriter: Storage {
@@ -162,13 +210,89 @@ pub enum PersistLocation {
     Memory,
     /// At a given path.
     Path(PathBuf),
+    /// At a given path, with some ranges of shards stored in other directories.
+    Sharded(PathBuf, Vec<ShardLocation>),
 }
 
 impl PersistLocation {
+    /// At the given path, with any ranges of shards in `shard_locations` stored in their own directories.
+    pub fn with_shard_locations(path: PathBuf, shard_locations: Vec<ShardLocation>) -> Self {
+        if shard_locations.is_empty() {
+            PersistLocation::Path(path)
+        } else {
+            PersistLocation::Sharded(path, shard_locations)
+        }
+    }
+
     pub fn join<P: AsRef<std::path::Path>>(&self, p: P) -> PersistLocation {
         match self {
             PersistLocation::Memory => PersistLocation::Memory,
-            PersistLocation::Path(path) => PersistLocation::Path(path.join(p)),
+            PersistLocation::Path(path) | PersistLocation::Sharded(path, _) => {
+                PersistLocation::Path(path.join(p))
+            }
+        }
+    }
+
+    /// The directory holding a shard's files, or `None` if it's in memory.
+    pub fn shard_path(&self, shard: Shard) -> Option<PathBuf> {
+        match self {
+            PersistLocation::Memory => None,
+            PersistLocation::Path(path) => Some(path.join(shard.to_string())),
+            PersistLocation::Sharded(path, locations) => {
+                let root = locations
+                    .iter()
+                    .find(|location| location.contains(shard))
+                    .map_or(path, |location| &location.path);
+                Some(root.join(shard.to_string()))
+            }
+        }
+    }
+
+    /// Every directory that may hold shards.
+    pub fn shard_roots(&self) -> Vec<&Path> {
+        match self {
+            PersistLocation::Memory => vec![],
+            PersistLocation::Path(path) => vec![path],
+            PersistLocation::Sharded(path, locations) => std::iter::once(path.as_path())
+                .chain(locations.iter().map(|location| location.path.as_path()))
+                .collect(),
+        }
+    }
+}
+
+/// A range of shards stored outside of the main index directory, such as older shards on slower storage. Written as
+/// `FIRST..LAST=DIR`, where either end of the range may be left open (eg: `..2019-12=/mnt/archive`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardLocation {
+    pub first: Option<Shard>,
+    pub last: Option<Shard>,
+    pub path: PathBuf,
+}
+
+impl ShardLocation {
+    pub fn contains(&self, shard: Shard) -> bool {
+        self.first.is_none_or(|first| shard >= first) && self.last.is_none_or(|last| shard <= last)
+    }
+}
+
+impl FromStr for ShardLocation {
+    type Err = PersistError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || PersistError::UnexpectedError(format!("Invalid shard location '{}'", s));
+        let (range, path) = s.split_once('=').ok_or_else(invalid)?;
+        let (first, last) = range.split_once("..").ok_or_else(invalid)?;
+        let parse = |shard: &str| match shard.trim() {
+            "" => Ok(None),
+            shard => Shard::from_string(shard).map(Some).ok_or_else(invalid),
+        };
+        if path.is_empty() {
+            return Err(invalid());
         }
+        Ok(Self {
+            first: parse(first)?,
+            last: parse(last)?,
+            path: path.into(),
+        })
     }
 }
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{PersistError, StoryIdentifier};
+
+use super::{db::DB, PersistLocation};
+
+pub(crate) const PINNED_STORIES_FILE: &str = "pinned.sqlite3";
+
+/// Stories that have been manually pinned to the top of the front page, in the order they were pinned. Like hidden
+/// stories, the full set is small, so it is cached in memory and written through to the database.
+pub struct PinnedStories {
+    db: DB,
+    ids: Vec<String>,
+    next_position: i64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PinnedStory {
+    id: String,
+    position: i64,
+}
+
+impl PinnedStories {
+    pub fn new(location: &PersistLocation) -> Result<Self, PersistError> {
+        let db = match location {
+            PersistLocation::Memory => DB::open(":memory:")?,
+            PersistLocation::Path(path) | PersistLocation::Sharded(path, _) => {
+                std::fs::create_dir_all(path)?;
+                DB::open(path.join(PINNED_STORIES_FILE))?
+            }
+        };
+        db.create_table::<PinnedStory>()?;
+        db.create_unique_index::<PinnedStory>("idx_id", &["id"])?;
+        let pinned = db.query_raw::<PinnedStory>(&format!(
+            "select * from {} order by position",
+            DB::table_for::<PinnedStory>()
+        ))?;
+        let next_position = pinned.last().map(|pinned| pinned.position + 1).unwrap_or(0);
+        let ids = pinned.into_iter().map(|pinned| pinned.id).collect();
+        Ok(Self {
+            db,
+            ids,
+            next_position,
+        })
+    }
+
+    /// Pin a story after any already-pinned stories, returning false if it was already pinned.
+    pub fn pin(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        let id = id.to_base64();
+        if self.ids.contains(&id) {
+            return Ok(false);
+        }
+        self.db.store(&PinnedStory {
+            id: id.clone(),
+            position: self.next_position,
+        })?;
+        self.next_position += 1;
+        self.ids.push(id);
+        Ok(true)
+    }
+
+    /// Un-pin a story, returning false if it wasn't pinned.
+    pub fn unpin(&mut self, id: &StoryIdentifier) -> Result<bool, PersistError> {
+        let id = id.to_base64();
+        let Some(index) = self.ids.iter().position(|pinned| *pinned == id) else {
+            return Ok(false);
+        };
+        self.db.delete::<PinnedStory>(id)?;
+        self.ids.remove(index);
+        Ok(true)
+    }
+
+    pub fn contains(&self, id: &StoryIdentifier) -> bool {
+        self.ids.contains(&id.to_base64())
+    }
+
+    /// The IDs of all pinned stories, in the order they were pinned.
+    pub fn ids(&self) -> impl Iterator<Item = StoryIdentifier> + '_ {
+        self.ids.iter().filter_map(StoryIdentifier::from_base64)
+    }
+
+    /// Copy the database into the snapshot directory at `path`.
+    pub fn snapshot(&self, path: &Path) -> Result<(), PersistError> {
+        self.db.snapshot_to(&path.join(PINNED_STORIES_FILE))
+    }
+}
@@ -0,0 +1,76 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{PersistError, StoryIdentifier};
+
+use super::{db::DB, PersistLocation};
+
+pub(crate) const READ_TIMES_FILE: &str = "read_time.sqlite3";
+
+/// Estimated read times of linked articles, in minutes. These are computed from a separate fetch of each article
+/// rather than from scrapes, so they're stored alongside the index and cached in memory.
+pub struct ReadTimes {
+    db: DB,
+    minutes: HashMap<String, u32>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ReadTime {
+    id: String,
+    minutes: u32,
+}
+
+impl ReadTimes {
+    pub fn new(location: &PersistLocation) -> Result<Self, PersistError> {
+        let db = match location {
+            PersistLocation::Memory => DB::open(":memory:")?,
+            PersistLocation::Path(path) | PersistLocation::Sharded(path, _) => {
+                std::fs::create_dir_all(path)?;
+                DB::open(path.join(READ_TIMES_FILE))?
+            }
+        };
+        db.create_table::<ReadTime>()?;
+        db.create_unique_index::<ReadTime>("idx_id", &["id"])?;
+        let minutes = db
+            .query_raw::<ReadTime>(&format!("select * from {}", DB::table_for::<ReadTime>()))?
+            .into_iter()
+            .map(|read_time| (read_time.id, read_time.minutes))
+            .collect();
+        Ok(Self { db, minutes })
+    }
+
+    pub fn set(&mut self, id: &StoryIdentifier, minutes: u32) -> Result<(), PersistError> {
+        let id = id.to_base64();
+        self.db.store(&ReadTime {
+            id: id.clone(),
+            minutes,
+        })?;
+        self.minutes.insert(id, minutes);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &StoryIdentifier) -> Result<(), PersistError> {
+        let id = id.to_base64();
+        if self.minutes.remove(&id).is_some() {
+            self.db.delete::<ReadTime>(id)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: &StoryIdentifier) -> Option<u32> {
+        self.minutes.get(&id.to_base64()).copied()
+    }
+
+    /// The base64-encoded IDs of all stories with a read time, and their read times.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.minutes
+            .iter()
+            .map(|(id, minutes)| (id.as_str(), *minutes))
+    }
+
+    /// Copy the database into the snapshot directory at `path`.
+    pub fn snapshot(&self, path: &Path) -> Result<(), PersistError> {
+        self.db.snapshot_to(&path.join(READ_TIMES_FILE))
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    path::Path,
     sync::{Arc, RwLock},
 };
 
@@ -10,6 +11,8 @@ use crate::{story::StoryScrapeId, PersistError};
 
 use super::{db::DB, shard::Shard, PersistLocation};
 
+const SCRAPES_FILE: &str = "scrapes.sqlite3";
+
 /// Long-term persistence for raw scrape data.
 pub struct ScrapeStore {
     location: PersistLocation,
@@ -45,11 +48,11 @@ impl ScrapeStore {
         let db = if let Some(db) = lock.get(&shard) {
             db
         } else {
-            let db = match self.location.join(shard.to_string()) {
-                PersistLocation::Memory => DB::open(":memory:")?,
-                PersistLocation::Path(ref path) => {
-                    std::fs::create_dir_all(path)?;
-                    let path = path.join("scrapes.sqlite3");
+            let db = match self.location.shard_path(shard) {
+                None => DB::open(":memory:")?,
+                Some(path) => {
+                    std::fs::create_dir_all(&path)?;
+                    let path = path.join(SCRAPES_FILE);
                     tracing::info!("Opening scrape database at {}", path.to_string_lossy());
                     let db = DB::open(path)?;
                     // Force each DB into WAL mode
@@ -64,6 +67,12 @@ impl ScrapeStore {
         Ok(db.clone())
     }
 
+    /// Copy the database for a shard into the snapshot directory for that shard.
+    pub fn snapshot(&self, shard: Shard, path: &Path) -> Result<(), PersistError> {
+        self.open_shard(shard)?
+            .snapshot_to(&path.join(SCRAPES_FILE))
+    }
+
     pub fn insert_scrape(&self, scrape: &TypedScrape) -> Result<(), PersistError> {
         self.insert_scrape_batch([scrape].into_iter())
     }
@@ -126,6 +135,19 @@ impl ScrapeStore {
         Ok(map)
     }
 
+    /// Permanently delete a batch of scrapes, returning the number that were deleted.
+    pub fn delete_scrape_batch<I: IntoIterator<Item = StoryScrapeId>>(
+        &self,
+        iter: I,
+    ) -> Result<usize, PersistError> {
+        let mut count = 0;
+        for id in iter {
+            let db = self.open_shard(id.shard)?;
+            count += db.delete::<ScrapeCacheEntry>(id.id.to_string())?;
+        }
+        Ok(count)
+    }
+
     /// Fetch all the scrapes, passing them to a given callback (or the error to an error callback).
     pub fn fetch_all<F: FnMut(TypedScrape) -> Result<(), PersistError>, FE: FnMut(PersistError)>(
         &self,
@@ -134,7 +156,10 @@ impl ScrapeStore {
         mut fe: FE,
     ) -> Result<(), PersistError> {
         let db = self.open_shard(shard)?;
-        let sql = format!("select * from {} order by date", DB::table_for::<ScrapeCacheEntry>());
+        let sql = format!(
+            "select * from {} order by date",
+            DB::table_for::<ScrapeCacheEntry>()
+        );
         db.query_raw_callback(&sql, |scrape: ScrapeCacheEntry| {
             match serde_json::from_str(&scrape.json) {
                 Ok(typed_scrape) => f(typed_scrape)?,
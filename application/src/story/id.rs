@@ -4,6 +4,18 @@ use std::fmt::Display;
 
 use progscrape_scrapers::{StoryDate, StoryUrlNorm};
 
+/// How a story's ID is chosen when it is re-written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoryIdScheme {
+    /// The ID follows the earliest scrape, so re-writing a story that has had an earlier scrape merged into it changes
+    /// its ID.
+    #[default]
+    Earliest,
+    /// The ID is fixed when the story is first indexed, from its normalized URL and the date it was first seen.
+    Stable,
+}
+
 /// Uniquely identifies a story within the index.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct StoryIdentifier {
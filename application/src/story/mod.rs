@@ -3,7 +3,10 @@
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
-use progscrape_scrapers::{ScrapeConfig, ScrapeExtractor, ScrapeId, StoryDate, StoryUrl};
+use progscrape_scrapers::{
+    ExtractedScrapeCollection, ScrapeCollection, ScrapeConfig, ScrapeExtractor, ScrapeId,
+    StoryDate, StoryUrl, TypedScrape,
+};
 use std::collections::{HashMap, HashSet};
 
 mod collector;
@@ -16,9 +19,9 @@ use crate::persist::Shard;
 
 pub use self::{
     collector::StoryCollector,
-    id::StoryIdentifier,
-    render::{StoryFullRender, StoryRender},
-    scorer::{StoryScore, StoryScoreConfig, StoryScorer},
+    id::{StoryIdScheme, StoryIdentifier},
+    render::{ScrapeProvenance, StoryEngagement, StoryFullRender, StoryRender},
+    scorer::{SearchWeights, StoryScore, StoryScoreConfig, StoryScorer},
     tagger::{StoryTagger, TaggerConfig},
 };
 
@@ -38,6 +41,48 @@ impl StoryEvaluator {
         }
     }
 
+    /// Computes the tags for an extracted story from its title and the tags provided by its sources.
+    pub fn tags(&self, extracted: &ExtractedScrapeCollection) -> TagSet {
+        let mut keyword = TagSet::new();
+        self.tagger.tag(extracted.title(), &mut keyword);
+        let mut source = TagSet::new();
+        for tag in extracted.tags() {
+            let tag = self.tagger.canonicalize(&tag);
+            if !self.tagger.is_stopword(&tag) {
+                source.add(tag);
+            }
+        }
+        let self_post = extracted
+            .scrapes
+            .values()
+            .any(|(core, _)| core.is_self_post());
+        if let Some(tag) = self.tagger.discussion_tag() {
+            if self_post {
+                source.add(tag);
+            }
+        }
+        if let Some(tag) = self.tagger.content_type(extracted.url(), self_post) {
+            source.add(tag);
+        }
+        self.tagger.limit_tags(source, keyword)
+    }
+
+    /// Evaluates a single scrape as if it were being inserted into the index as a new story.
+    pub fn evaluate(&self, scrape: &TypedScrape) -> EvaluatedStory {
+        let collection = ScrapeCollection::new_from_one(scrape.clone());
+        let extracted = collection.extract(&self.extractor);
+        let url = extracted.url();
+        let mut tags = self.tags(&extracted).collect();
+        tags.sort();
+        EvaluatedStory {
+            title: extracted.title().to_owned(),
+            url: url.to_string(),
+            url_norm: url.normalization().string().to_owned(),
+            score: self.scorer.score(&extracted),
+            tags,
+        }
+    }
+
     #[cfg(test)]
     pub fn new_for_test() -> Self {
         Self::new(
@@ -48,6 +93,18 @@ impl StoryEvaluator {
     }
 }
 
+/// The result of evaluating a single scrape with the `StoryEvaluator`.
+#[derive(Clone, Debug, Serialize)]
+pub struct EvaluatedStory {
+    pub title: String,
+    pub url: String,
+    pub url_norm: String,
+    /// The provisional score, which doesn't include the age of the story.
+    pub score: f32,
+    /// The derived tags, in sorted order.
+    pub tags: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct StoryScrapeId {
     pub id: ScrapeId,
@@ -70,6 +127,12 @@ pub struct Story<S> {
     pub title: String,
     pub tags: TagSet,
     pub scrapes: HashMap<ScrapeId, S>,
+    /// The estimated read time of the linked article in minutes, if it has been fetched.
+    #[serde(default)]
+    pub read_time: Option<u32>,
+    /// When the story was first added to the index, if known.
+    #[serde(default)]
+    pub first_seen: Option<StoryDate>,
 }
 
 impl<S> Story<S> {
@@ -89,6 +152,8 @@ impl<S> Story<S> {
             date,
             score,
             scrapes: HashMap::from_iter(scrapes.into_iter().map(|x| x.into())),
+            read_time: None,
+            first_seen: None,
         }
     }
 
@@ -104,7 +169,7 @@ impl<S> Story<S> {
     }
 
     pub fn render(&self, tagger: &StoryTagger, order: usize) -> StoryRender {
-        let mut tags = vec![self.url.host().to_owned()];
+        let mut tags = vec![tagger.canonicalize(self.url.host())];
         tags.extend(tagger.make_display_tags(self.tags.dump()));
         let mut comment_links = HashMap::new();
         for (id, _) in &self.scrapes {
@@ -120,10 +185,72 @@ impl<S> Story<S> {
             date: self.date,
             tags,
             comment_links,
+            engagement: Default::default(),
+            submitter: None,
+            read_time: self.read_time,
+            first_seen: self.first_seen,
+            icon_url: None,
+            scrape_sources: self
+                .scrapes
+                .keys()
+                .map(|id| ScrapeProvenance::new(id, None))
+                .sorted()
+                .collect(),
         }
     }
 }
 
+impl Story<TypedScrape> {
+    /// Renders this story along with the engagement metrics and submitter reported by each of its sources.
+    pub fn render_with_engagement(&self, tagger: &StoryTagger, order: usize) -> StoryRender {
+        let mut render = self.render(tagger, order);
+        render.scrape_sources = self
+            .scrapes
+            .values()
+            .map(|scrape| ScrapeProvenance::new(&scrape.id, scrape.scraped_at))
+            .sorted()
+            .collect();
+        for scrape in self.scrapes.values() {
+            render
+                .engagement
+                .entry(scrape.id.source.into_str().to_string())
+                .or_default()
+                .merge(scrape.into());
+            if let TypedScrape::Lobsters(lobsters) = scrape {
+                if render.submitter.is_none() {
+                    render.submitter = lobsters.data.submitter.clone();
+                }
+            }
+        }
+        render
+    }
+
+    /// The points and comments of this story summed across its sources, using the highest metrics reported by each
+    /// source. Metrics that no source reports are left empty.
+    pub fn total_engagement(&self) -> StoryEngagement {
+        let mut by_source = HashMap::<_, StoryEngagement>::new();
+        for scrape in self.scrapes.values() {
+            by_source
+                .entry(scrape.id.source)
+                .or_default()
+                .merge(scrape.into());
+        }
+        let sum = |a: Option<u32>, b: Option<u32>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        by_source
+            .into_values()
+            .fold(StoryEngagement::default(), |total, engagement| {
+                StoryEngagement {
+                    points: sum(total.points, engagement.points),
+                    comments: sum(total.comments, engagement.comments),
+                    upvote_ratio: None,
+                }
+            })
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TagSet {
     set: HashSet<String>,
@@ -146,6 +273,14 @@ impl TagSet {
         self.set.contains(tag.as_ref())
     }
 
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
     pub fn add(&mut self, tag: impl AsRef<str>) {
         self.set.insert(tag.as_ref().to_ascii_lowercase());
     }
@@ -177,3 +312,217 @@ impl TagAcceptor for TagSet {
 pub trait TagAcceptor {
     fn tag(&mut self, s: &str);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use progscrape_scrapers::{
+        hacker_news::HackerNewsStory, lobsters::LobstersStory, reddit::RedditStory,
+    };
+
+    #[test]
+    fn test_evaluate_hacker_news() {
+        let eval = StoryEvaluator::new_for_test();
+        let url = StoryUrl::parse("https://www.example.com/posts/rust?utm_source=hn").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        let mut story = HackerNewsStory::new_with_defaults("1", date, "I love Rust", url.clone());
+        story.data.points = 100;
+        story.data.comments = 50;
+        let evaluated = eval.evaluate(&story.into());
+
+        assert_eq!("I love Rust", evaluated.title);
+        assert_eq!(url.to_string(), evaluated.url);
+        assert_eq!(url.normalization().string(), evaluated.url_norm);
+        assert_eq!(vec!["rust".to_owned()], evaluated.tags);
+        assert!(evaluated.score > 0.0, "{}", evaluated.score);
+    }
+
+    #[test]
+    fn test_render_engagement() {
+        let eval = StoryEvaluator::new_for_test();
+        let url = StoryUrl::parse("https://www.example.com/rust").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        let mut hn = HackerNewsStory::new_with_defaults("1", date, "I love Rust", url.clone());
+        hn.data.points = 100;
+        hn.data.comments = 50;
+        let mut reddit =
+            RedditStory::new_subsource_with_defaults("2", "rust", date, "I love Rust", url.clone());
+        reddit.data.score = 20;
+        reddit.data.num_comments = 5;
+        reddit.data.upvote_ratio = 0.9;
+        let scrapes: [TypedScrape; 2] = [hn.into(), reddit.into()];
+        let story = Story::new_from_parts(
+            "I love Rust".into(),
+            url,
+            date,
+            0.0,
+            ["rust".to_owned()],
+            scrapes.map(|scrape| (scrape.id.clone(), scrape)),
+        );
+
+        let render = story.render_with_engagement(&eval.tagger, 0);
+        assert_eq!(
+            StoryEngagement {
+                points: Some(100),
+                comments: Some(50),
+                upvote_ratio: None,
+            },
+            render.engagement["hacker_news"]
+        );
+        assert_eq!(
+            StoryEngagement {
+                points: Some(20),
+                comments: Some(5),
+                upvote_ratio: Some(0.9),
+            },
+            render.engagement["reddit"]
+        );
+        assert_eq!(None, render.submitter);
+    }
+
+    #[test]
+    fn test_render_submitter() {
+        let eval = StoryEvaluator::new_for_test();
+        let url = StoryUrl::parse("https://www.example.com/rust").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        let mut lobsters = LobstersStory::new_with_defaults("1", date, "I love Rust", url.clone());
+        lobsters.data.submitter = Some("ferris".into());
+        let scrapes: [TypedScrape; 1] = [lobsters.into()];
+        let story = Story::new_from_parts(
+            "I love Rust".into(),
+            url,
+            date,
+            0.0,
+            ["rust".to_owned()],
+            scrapes.map(|scrape| (scrape.id.clone(), scrape)),
+        );
+
+        assert_eq!(
+            Some("ferris"),
+            story
+                .render_with_engagement(&eval.tagger, 0)
+                .submitter
+                .as_deref()
+        );
+        assert_eq!(None, story.render(&eval.tagger, 0).submitter);
+    }
+
+    #[test]
+    fn test_render_provenance() {
+        let eval = StoryEvaluator::new_for_test();
+        let url = StoryUrl::parse("https://www.example.com/rust").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        let scraped_at = StoryDate::year_month_day(2020, 1, 2).expect("Date");
+        let reddit =
+            RedditStory::new_subsource_with_defaults("2", "rust", date, "I love Rust", url.clone());
+        let mut hn = HackerNewsStory::new_with_defaults("1", date, "I love Rust", url.clone());
+        hn.scraped_at = Some(scraped_at);
+        let (hn_url, reddit_url) = (hn.id.comments_url(), reddit.id.comments_url());
+        let scrapes: [TypedScrape; 2] = [reddit.into(), hn.into()];
+        let story = Story::new_from_parts(
+            "I love Rust".into(),
+            url,
+            date,
+            0.0,
+            ["rust".to_owned()],
+            scrapes.map(|scrape| (scrape.id.clone(), scrape)),
+        );
+
+        // Ordered by source, with fetch times from the full scrapes
+        let render = story.render_with_engagement(&eval.tagger, 0);
+        assert_eq!(
+            vec![
+                ScrapeProvenance {
+                    source: "hacker_news".into(),
+                    subsource: None,
+                    url: hn_url,
+                    scraped_at: Some(scraped_at),
+                },
+                ScrapeProvenance {
+                    source: "reddit".into(),
+                    subsource: Some("rust".into()),
+                    url: reddit_url,
+                    scraped_at: None,
+                },
+            ],
+            render.scrape_sources
+        );
+
+        // The basic render has the same scrapes, without fetch times
+        let render = story.render(&eval.tagger, 0);
+        assert_eq!(2, render.scrape_sources.len());
+        assert!(render
+            .scrape_sources
+            .iter()
+            .all(|provenance| provenance.scraped_at.is_none()));
+    }
+
+    #[rstest::rstest]
+    #[case::ask_hn(HackerNewsStory::new_with_defaults("1", date(), "Ask HN: Rust?", url("https://news.ycombinator.com/item?id=1")).into(), true)]
+    #[case::hn_link(HackerNewsStory::new_with_defaults("1", date(), "Rust", url("https://example.com/rust")).into(), false)]
+    #[case::reddit_self(RedditStory::new_subsource_with_defaults("2", "rust", date(), "Help with Rust", url("https://www.reddit.com/r/rust/comments/2/help_with_rust/")).into(), true)]
+    #[case::reddit_to_hn(RedditStory::new_subsource_with_defaults("2", "rust", date(), "Rust", url("https://news.ycombinator.com/item?id=1")).into(), false)]
+    #[case::lobsters_text(LobstersStory::new_with_defaults("abc", date(), "What are you doing this week?", url("https://lobste.rs/s/abc/what_are_you_doing_this_week")).into(), true)]
+    #[case::lobsters_link(LobstersStory::new_with_defaults("abc", date(), "Rust", url("https://example.com/rust")).into(), false)]
+    fn test_discussion_tag(#[case] scrape: TypedScrape, #[case] discussion: bool) {
+        let eval = StoryEvaluator::new_for_test();
+        let evaluated = eval.evaluate(&scrape);
+        assert_eq!(
+            discussion,
+            evaluated.tags.contains(&"discussion".to_owned()),
+            "{:?}",
+            evaluated.tags
+        );
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let eval = StoryEvaluator::new_for_test();
+        let mut lobsters = LobstersStory::new_with_defaults(
+            "1",
+            date(),
+            "This week in programming",
+            url("https://example.com/weekly"),
+        );
+        lobsters.data.tags = vec!["news".into(), "rust".into(), "programming".into()];
+        let evaluated = eval.evaluate(&lobsters.into());
+
+        // "news" is a stopword, but "rust" has a tag rule of its own
+        assert!(!evaluated.tags.contains(&"news".to_owned()));
+        assert!(evaluated.tags.contains(&"rust".to_owned()));
+        assert!(evaluated.tags.contains(&"programming".to_owned()));
+    }
+
+    #[rstest::rstest]
+    #[case::video("https://www.youtube.com/watch?v=abc", Some("typevideo"))]
+    #[case::pdf_extension("https://example.com/papers/Paper.PDF", Some("typepdf"))]
+    #[case::code("https://github.com/rust-lang/rust", Some("typecode"))]
+    #[case::code_subdomain("https://gist.github.com/someone/abc", Some("typecode"))]
+    #[case::discussion("https://news.ycombinator.com/item?id=1", Some("typediscussion"))]
+    #[case::host_suffix("https://notyoutube.com/watch?v=abc", None)]
+    #[case::pdf_in_path("https://example.com/pdf/viewer", None)]
+    fn test_content_type_tag(#[case] story_url: &str, #[case] expected: Option<&str>) {
+        let eval = StoryEvaluator::new_for_test();
+        let story = HackerNewsStory::new_with_defaults("1", date(), "A story", url(story_url));
+        let evaluated = eval.evaluate(&story.into());
+        let types = ["typevideo", "typepdf", "typecode", "typediscussion"];
+        assert_eq!(
+            expected.into_iter().collect_vec(),
+            evaluated
+                .tags
+                .iter()
+                .filter(|tag| types.contains(&tag.as_str()))
+                .collect_vec(),
+            "{:?}",
+            evaluated.tags
+        );
+    }
+
+    fn date() -> StoryDate {
+        StoryDate::year_month_day(2020, 1, 1).expect("Date")
+    }
+
+    fn url(url: &str) -> StoryUrl {
+        StoryUrl::parse(url).expect("URL")
+    }
+}
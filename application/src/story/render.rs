@@ -17,6 +17,94 @@ pub struct StoryRender {
     pub score: f32,
     pub tags: Vec<String>,
     pub comment_links: HashMap<String, String>,
+    /// Engagement metrics for each source, keyed by source name. Only available when rendered from full scrapes.
+    #[serde(default)]
+    pub engagement: HashMap<String, StoryEngagement>,
+    /// The user who submitted the story, for sources that report one. Only available when rendered from full scrapes.
+    #[serde(default)]
+    pub submitter: Option<String>,
+    /// The estimated read time of the linked article in minutes, if it has been fetched.
+    #[serde(default)]
+    pub read_time: Option<u32>,
+    /// When the story was first added to the index, if known.
+    #[serde(default)]
+    pub first_seen: Option<StoryDate>,
+    /// The URL of an icon for the story's domain, resolved by the web frontend.
+    #[serde(default)]
+    pub icon_url: Option<String>,
+    /// Each of the scrapes that make up this story, ordered by source.
+    #[serde(default)]
+    pub scrape_sources: Vec<ScrapeProvenance>,
+}
+
+/// Where one of a story's scrapes came from.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct ScrapeProvenance {
+    pub source: String,
+    pub subsource: Option<String>,
+    /// The story's page on the source.
+    pub url: String,
+    /// When the scrape was last fetched. Only available when rendered from full scrapes, and only for scrapes that
+    /// were fetched after this was recorded.
+    pub scraped_at: Option<StoryDate>,
+}
+
+impl ScrapeProvenance {
+    pub fn new(id: &ScrapeId, scraped_at: Option<StoryDate>) -> Self {
+        Self {
+            source: id.source.into_str().to_string(),
+            subsource: id.subsource.clone(),
+            url: id.comments_url(),
+            scraped_at,
+        }
+    }
+}
+
+/// Engagement metrics as reported by one source. Sources measure engagement differently (Hacker News points
+/// aren't comparable to Reddit scores), so metrics that a source doesn't report are left empty.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct StoryEngagement {
+    pub points: Option<u32>,
+    pub comments: Option<u32>,
+    pub upvote_ratio: Option<f32>,
+}
+
+impl StoryEngagement {
+    /// Combines the metrics of two scrapes from the same source, keeping the highest of each.
+    pub fn merge(&mut self, other: StoryEngagement) {
+        self.points = self.points.max(other.points);
+        self.comments = self.comments.max(other.comments);
+        self.upvote_ratio = match (self.upvote_ratio, other.upvote_ratio) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+impl From<&TypedScrape> for StoryEngagement {
+    fn from(scrape: &TypedScrape) -> Self {
+        match scrape {
+            TypedScrape::HackerNews(hn) => Self {
+                points: Some(hn.data.points),
+                comments: Some(hn.data.comments),
+                ..Default::default()
+            },
+            TypedScrape::Reddit(reddit) => Self {
+                points: Some(reddit.data.score),
+                comments: Some(reddit.data.num_comments),
+                upvote_ratio: Some(reddit.data.upvote_ratio),
+            },
+            TypedScrape::Lobsters(lobsters) => Self {
+                points: Some(lobsters.data.score),
+                comments: Some(lobsters.data.num_comments),
+                ..Default::default()
+            },
+            TypedScrape::Slashdot(slashdot) => Self {
+                comments: Some(slashdot.data.num_comments),
+                ..Default::default()
+            },
+        }
+    }
 }
 
 /// Fully-rendered story, suitable for display on admin screens.
@@ -6,11 +6,130 @@ use progscrape_scrapers::{
 
 use super::Story;
 
-#[derive(Clone, Default, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StoryScoreConfig {
     age_breakpoint_days: [u32; 2],
     hour_scores: [f32; 3],
     service_rank: TypedScrapeMap<f32>,
+    /// The weight of text relevance (BM25) when ranking search results.
+    #[serde(default = "default_search_relevance_weight")]
+    search_relevance_weight: f32,
+    /// The weight of recency when ranking search results. Recency decays from 1 for the newest story, halving every
+    /// week.
+    #[serde(default)]
+    search_recency_weight: f32,
+    /// How much more a search term matching a story's tag counts than the same term in its title.
+    #[serde(default = "default_search_tag_boost")]
+    search_tag_boost: f32,
+    /// Adjustments for low-quality titles, all of which are off by default.
+    #[serde(default)]
+    title_quality: TitleQualityConfig,
+}
+
+/// Penalties for titles that tend to be clickbait. Each penalty is applied only if it is non-zero.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TitleQualityConfig {
+    /// Titles with fewer words than this are penalized.
+    short_title_words: usize,
+    short_title_penalty: f32,
+    /// Titles where at least this fraction of letters are uppercase are penalized.
+    all_caps_ratio: f32,
+    all_caps_penalty: f32,
+    /// Titles with more than this many `!` or `?` characters are penalized.
+    max_punctuation: usize,
+    punctuation_penalty: f32,
+}
+
+impl TitleQualityConfig {
+    /// The fraction of letters in the title that are uppercase, or zero if it has no letters.
+    fn caps_ratio(title: &str) -> f32 {
+        let (letters, upper) = title
+            .chars()
+            .filter(|c| c.is_alphabetic())
+            .fold((0, 0), |(letters, upper), c| {
+                (letters + 1, upper + c.is_uppercase() as usize)
+            });
+        if letters == 0 {
+            0.0
+        } else {
+            upper as f32 / letters as f32
+        }
+    }
+
+    fn score<T: FnMut(StoryScore, f32)>(&self, title: &str, mut accum: T) {
+        if self.short_title_penalty != 0.0
+            && title.split_whitespace().count() < self.short_title_words
+        {
+            accum(StoryScore::ShortTitle, -self.short_title_penalty);
+        }
+        if self.all_caps_penalty != 0.0 && Self::caps_ratio(title) >= self.all_caps_ratio {
+            accum(StoryScore::AllCapsTitle, -self.all_caps_penalty);
+        }
+        if self.punctuation_penalty != 0.0
+            && title.chars().filter(|c| matches!(c, '!' | '?')).count() > self.max_punctuation
+        {
+            accum(StoryScore::ExcessivePunctuation, -self.punctuation_penalty);
+        }
+    }
+}
+
+fn default_search_relevance_weight() -> f32 {
+    1.0
+}
+
+fn default_search_tag_boost() -> f32 {
+    2.0
+}
+
+impl Default for StoryScoreConfig {
+    fn default() -> Self {
+        Self {
+            age_breakpoint_days: Default::default(),
+            hour_scores: Default::default(),
+            service_rank: Default::default(),
+            search_relevance_weight: default_search_relevance_weight(),
+            search_recency_weight: 0.0,
+            search_tag_boost: default_search_tag_boost(),
+            title_quality: Default::default(),
+        }
+    }
+}
+
+impl StoryScoreConfig {
+    pub fn search_weights(&self) -> SearchWeights {
+        SearchWeights {
+            relevance: self.search_relevance_weight,
+            recency: self.search_recency_weight,
+            tag_boost: self.search_tag_boost,
+        }
+    }
+}
+
+/// The blend of relevance and recency used to rank search results.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchWeights {
+    pub relevance: f32,
+    pub recency: f32,
+    /// The factor applied to text search matches on tags, relative to matches on titles.
+    pub tag_boost: f32,
+}
+
+impl Default for SearchWeights {
+    fn default() -> Self {
+        StoryScoreConfig::default().search_weights()
+    }
+}
+
+impl SearchWeights {
+    /// The number of seconds over which recency halves.
+    const RECENCY_HALF_LIFE: f32 = 7.0 * 24.0 * 60.0 * 60.0;
+
+    /// Blend a relevance score with the age of a story in seconds.
+    pub fn score(&self, relevance: f32, age_seconds: i64) -> f32 {
+        let recency = 0.5_f32.powf(age_seconds.max(0) as f32 / Self::RECENCY_HALF_LIFE);
+        self.relevance * relevance + self.recency * recency
+    }
 }
 
 pub enum StoryScoreType {
@@ -25,6 +144,9 @@ pub enum StoryScore {
     SourceCount,
     LongRedditTitle,
     LongTitle,
+    ShortTitle,
+    AllCapsTitle,
+    ExcessivePunctuation,
     ImageLink,
     SelfLink,
     PoorUpvoteRatio,
@@ -147,6 +269,14 @@ impl StoryScorer {
             }
         }
 
+        if let Some(slashdot) = service_scrapes.slashdot.and_then(|t| t.1.slashdot()) {
+            if slashdot.data.num_comments < 10 {
+                accum(CommentCount, -5.0);
+            } else if slashdot.data.num_comments > 100 {
+                accum(CommentCount, 5.0);
+            }
+        }
+
         // Penalize Reddit self links
         if url.host().contains("reddit.com") {
             accum(SelfLink, -20.0);
@@ -157,6 +287,8 @@ impl StoryScorer {
             accum(LongTitle, -15.0);
         }
 
+        self.config.title_quality.score(title, &mut accum);
+
         if url.host().contains("gfycat")
             || url.host().contains("imgur")
             || url.host().contains("i.reddit.com")
@@ -192,6 +324,68 @@ impl StoryScorer {
 #[cfg(test)]
 mod test {
     use super::*;
+    use progscrape_scrapers::{
+        hacker_news::HackerNewsStory, ScrapeCollection, ScrapeConfig, ScrapeExtractor, StoryUrl,
+    };
+
+    fn score_title(config: &StoryScoreConfig, title: &str) -> f32 {
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let scrapes = ScrapeCollection::new_from_one(
+            HackerNewsStory::new_with_defaults("1", date, title, url).into(),
+        );
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        StoryScorer::new(config).score(&scrapes.extract(&extractor))
+    }
+
+    #[test]
+    fn test_all_caps_title() {
+        let normal = "Announcing a new release of the Rust compiler";
+        let caps = "ANNOUNCING A NEW RELEASE OF THE RUST COMPILER";
+
+        // Off by default
+        let config = StoryScoreConfig::default();
+        assert_eq!(score_title(&config, normal), score_title(&config, caps));
+
+        let config = StoryScoreConfig {
+            title_quality: TitleQualityConfig {
+                all_caps_ratio: 0.8,
+                all_caps_penalty: 10.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            score_title(&config, normal) - 10.0,
+            score_title(&config, caps)
+        );
+
+        // A few acronyms don't make a title all-caps
+        let acronyms = "Announcing a new release of the LLVM and GCC compilers";
+        assert_eq!(score_title(&config, normal), score_title(&config, acronyms));
+    }
+
+    #[test]
+    fn test_title_quality() {
+        let config = StoryScoreConfig {
+            title_quality: TitleQualityConfig {
+                short_title_words: 3,
+                short_title_penalty: 5.0,
+                max_punctuation: 1,
+                punctuation_penalty: 3.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let base = score_title(&config, "A perfectly reasonable title");
+        assert_eq!(base - 5.0, score_title(&config, "Wow"));
+        assert_eq!(base, score_title(&config, "Is this a reasonable title?"));
+        assert_eq!(
+            base - 3.0,
+            score_title(&config, "You won't believe this!!!")
+        );
+        assert_eq!(base - 8.0, score_title(&config, "Really?!"));
+    }
 
     /// Make sure that the scores are decreasing.
     #[test]
@@ -200,6 +394,7 @@ mod test {
             age_breakpoint_days: [1, 30],
             hour_scores: [-5.0, -3.0, -0.1],
             service_rank: TypedScrapeMap::new_with_all(1.0),
+            ..Default::default()
         };
         let mut last_score = f32::MAX;
         let scorer = StoryScorer::new(&config);
@@ -1,6 +1,8 @@
 use std::collections::{HashMap, HashSet};
 
+use indexmap::IndexMap;
 use itertools::Itertools;
+use progscrape_scrapers::StoryUrl;
 use serde::{Deserialize, Serialize};
 
 use super::{TagAcceptor, TagSet};
@@ -25,9 +27,53 @@ pub struct TagConfig {
     symbol: bool,
 }
 
+/// The links that have a content type.
+#[derive(Default, Serialize, Deserialize)]
+pub struct ContentTypeConfig {
+    /// Hosts whose links have this type, including their subdomains.
+    #[serde(default)]
+    hosts: Vec<String>,
+    /// File extensions of links with this type, without the dot.
+    #[serde(default)]
+    extensions: Vec<String>,
+    /// Stories that link back to their source's own comments have this type.
+    #[serde(default)]
+    self_posts: bool,
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct TaggerConfig {
-    tags: HashMap<String, HashMap<String, TagConfig>>,
+    /// Tags are kept in config order, which is also their priority when a story has too many of them.
+    tags: IndexMap<String, IndexMap<String, TagConfig>>,
+    /// The maximum number of tags shown for a story, including its domain. If unset, stories are not limited.
+    #[serde(default)]
+    max_tags_per_story: Option<usize>,
+    /// Variant spellings of tags and domains, mapped to the canonical tag or domain that is stored in their place.
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    /// The tag applied to stories that link back to their source's own comments (ie: Ask HN, Reddit self-posts), if
+    /// any.
+    #[serde(default)]
+    discussion_tag: Option<String>,
+    /// Generic words that are never taken as tags from a story's sources (ie: Lobsters tags or Reddit flair). Tags
+    /// that match one of the rules in `tags` are kept.
+    #[serde(default)]
+    stopwords: HashSet<String>,
+    /// Content types that stories are tagged with based on their URL, displayed and searched as `type:<name>`. A
+    /// story has at most one, the first in config order that matches.
+    #[serde(default)]
+    content_types: IndexMap<String, ContentTypeConfig>,
+}
+
+#[derive(Debug)]
+struct ContentType {
+    /// The indexed tag, which must be a single token.
+    internal: String,
+    /// The displayed tag, eg: `type:video`.
+    display: String,
+    hosts: Vec<String>,
+    extensions: Vec<String>,
+    self_posts: bool,
 }
 
 #[derive(Debug)]
@@ -77,6 +123,15 @@ pub struct StoryTagger {
     backward: HashMap<String, String>,
     ///
     symbols: HashMap<String, usize>,
+    /// The config order of each output tag, including implied tags.
+    priority: HashMap<String, usize>,
+    max_tags_per_story: Option<usize>,
+    /// Maps lowercase variants to their canonical tag.
+    aliases: HashMap<String, String>,
+    discussion_tag: Option<String>,
+    /// Lowercase words that aren't accepted as source tags.
+    stopwords: HashSet<String>,
+    content_types: Vec<ContentType>,
 }
 
 impl StoryTagger {
@@ -122,7 +177,38 @@ impl StoryTagger {
             records: vec![],
             symbols: HashMap::new(),
             exclusions: HashMap::new(),
+            priority: HashMap::new(),
+            max_tags_per_story: config.max_tags_per_story,
+            aliases: config
+                .aliases
+                .iter()
+                .map(|(alias, tag)| (alias.to_lowercase(), tag.to_lowercase()))
+                .collect(),
+            discussion_tag: config.discussion_tag.as_ref().map(|tag| tag.to_lowercase()),
+            stopwords: config.stopwords.iter().map(|s| s.to_lowercase()).collect(),
+            content_types: vec![],
         };
+        for (name, content_type) in &config.content_types {
+            let name = name.to_lowercase();
+            let content_type = ContentType {
+                internal: "type".to_owned() + &name.replace(|c: char| !c.is_alphanumeric(), ""),
+                display: format!("type:{}", name),
+                hosts: content_type
+                    .hosts
+                    .iter()
+                    .map(|s| s.to_lowercase())
+                    .collect(),
+                extensions: content_type
+                    .extensions
+                    .iter()
+                    .map(|s| s.trim_start_matches('.').to_lowercase())
+                    .collect(),
+                self_posts: content_type.self_posts,
+            };
+            new.backward
+                .insert(content_type.internal.clone(), content_type.display.clone());
+            new.content_types.push(content_type);
+        }
         for tags in config.tags.values() {
             for (tag, tags) in tags {
                 let (primary, all_tags) = Self::compute_all_tags(tag, &tags.alt, &tags.alts);
@@ -143,6 +229,11 @@ impl StoryTagger {
                     },
                     implies: tags.implies.clone().into_iter().collect(),
                 };
+                for output in std::iter::once(&record.output).chain(&record.implies) {
+                    new.priority
+                        .entry(output.clone())
+                        .or_insert(new.records.len());
+                }
                 if let Some(internal) = &tags.internal {
                     new.backward.insert(internal.clone(), tag.clone());
                 }
@@ -167,6 +258,56 @@ impl StoryTagger {
         new
     }
 
+    /// The tag for stories that are discussions rather than links, if configured.
+    pub fn discussion_tag(&self) -> Option<&str> {
+        self.discussion_tag.as_deref()
+    }
+
+    /// The content type tag for a story's URL, if any. `self_post` is set for stories that link back to their source's
+    /// own comments.
+    pub fn content_type(&self, url: &StoryUrl, self_post: bool) -> Option<&str> {
+        let host = url.host();
+        let extension = url.extension();
+        self.content_types
+            .iter()
+            .find(|content_type| {
+                (content_type.self_posts && self_post)
+                    || content_type.hosts.iter().any(|h| {
+                        host == h
+                            || host
+                                .strip_suffix(h.as_str())
+                                .is_some_and(|sub| sub.ends_with('.'))
+                    })
+                    || extension
+                        .as_ref()
+                        .is_some_and(|extension| content_type.extensions.contains(extension))
+            })
+            .map(|content_type| content_type.internal.as_str())
+    }
+
+    /// Is this tag a stopword that shouldn't be accepted from a story's sources? Tags with a rule of their own are
+    /// never stopwords.
+    pub fn is_stopword(&self, tag: &str) -> bool {
+        self.stopwords.contains(&tag.to_lowercase()) && self.check_tag_search(tag).is_none()
+    }
+
+    /// Map a tag or domain to its canonical form, if it has an alias.
+    pub fn canonicalize(&self, tag: &str) -> String {
+        let lowercase = tag.to_lowercase();
+        match self.aliases.get(&lowercase) {
+            Some(canonical) => canonical.clone(),
+            None => lowercase,
+        }
+    }
+
+    /// Output a matched tag record and everything it implies.
+    fn emit<T: TagAcceptor>(&self, rec: &TagRecord, tags: &mut T) {
+        tags.tag(&self.canonicalize(&rec.output));
+        for implies in &rec.implies {
+            tags.tag(&self.canonicalize(implies));
+        }
+    }
+
     pub fn tag<T: TagAcceptor>(&self, s: &str, tags: &mut T) {
         let s = s.to_lowercase();
 
@@ -185,10 +326,7 @@ impl StoryTagger {
         for (symbol, rec) in &self.symbols {
             if s.contains(symbol) {
                 s = s.replace(symbol, " ");
-                tags.tag(&self.records[*rec].output);
-                for implies in &self.records[*rec].implies {
-                    tags.tag(implies);
-                }
+                self.emit(&self.records[*rec], tags);
             }
         }
 
@@ -218,42 +356,68 @@ impl StoryTagger {
             }
             for (multi, rec) in &self.forward_multi {
                 if multi.chomp(&mut tokens) {
-                    let rec = &self.records[*rec];
-                    tags.tag(&rec.output);
-                    for implies in &rec.implies {
-                        tags.tag(implies);
-                    }
+                    self.emit(&self.records[*rec], tags);
                     continue 'outer;
                 }
             }
             if let Some(rec) = self.forward.get(&tokens[0]) {
                 if !mutes.contains_key(&tokens[0]) {
-                    let rec = &self.records[*rec];
-                    tags.tag(&rec.output);
-                    for implies in &rec.implies {
-                        tags.tag(implies);
-                    }
+                    self.emit(&self.records[*rec], tags);
                 }
             }
             tokens = &tokens[1..];
         }
     }
 
+    /// Combine a story's source and keyword tags, truncating them to the configured maximum. The story's domain is
+    /// always displayed first so it takes one slot, then source tags are kept in alphabetical order, then keyword tags
+    /// in config order.
+    pub fn limit_tags(&self, source: TagSet, keyword: TagSet) -> TagSet {
+        let Some(max) = self.max_tags_per_story else {
+            return TagSet::from_iter(source.into_iter().chain(keyword));
+        };
+        // Tags without a priority aren't from the config, so they're sorted last
+        let mut keyword = keyword.collect();
+        keyword.sort_by_cached_key(|tag| {
+            let priority = self.priority.get(tag).copied().unwrap_or(usize::MAX);
+            (priority, tag.clone())
+        });
+        let mut tags = TagSet::new();
+        for tag in source.collect().into_iter().chain(keyword) {
+            if tags.len() + 1 >= max {
+                break;
+            }
+            tags.add(tag);
+        }
+        tags
+    }
+
     /// Identify any tags in the search term and return the appropriate search term to use. If the search term is a symbol,
     /// we must use its internal version (ie: cplusplus -> c++, c -> clanguage).
     pub fn check_tag_search(&self, search: &str) -> Option<&str> {
         let lowercase = search.to_lowercase();
-        if let Some(idx) = self.symbols.get(&lowercase) {
+        let alias = self.aliases.get(&lowercase);
+        let lowercase = alias.unwrap_or(&lowercase);
+        if let Some(idx) = self.symbols.get(lowercase) {
             return Some(&self.records[*idx].output);
         }
-        if let Some(idx) = self.forward.get(&lowercase) {
+        if let Some(idx) = self.forward.get(lowercase) {
             return Some(&self.records[*idx].output);
         }
-        if let Some((k, _)) = self.backward.get_key_value(&lowercase) {
+        if let Some((k, _)) = self.backward.get_key_value(lowercase) {
             return Some(k.as_str());
         }
-
-        None
+        if let Some(content_type) = self
+            .content_types
+            .iter()
+            .find(|content_type| &content_type.display == lowercase)
+        {
+            return Some(&content_type.internal);
+        }
+        // An alias may point at a tag without a rule of its own, but domain aliases are left to the domain search
+        alias
+            .filter(|alias| !alias.contains('.'))
+            .map(String::as_str)
     }
 
     /// Given an iterator of raw, indexed tags, output an iterator that is suitable for display purposes (ie: cplusplus -> c++).
@@ -299,6 +463,14 @@ pub(crate) mod test {
     #[fixture]
     pub(crate) fn tagger_config() -> TaggerConfig {
         serde_json::from_value(json!({
+            "discussion_tag": "discussion",
+            "stopwords": ["news", "rust"],
+            "content_types": {
+                "video": {"hosts": ["youtube.com", "youtu.be", "vimeo.com"]},
+                "pdf": {"extensions": ["pdf"]},
+                "code": {"hosts": ["github.com", "gitlab.com"]},
+                "discussion": {"self_posts": true}
+            },
             "tags": {
                 "testing": {
                     "video(s)": {"hosts": ["youtube.com", "vimeo.com"]},
@@ -428,4 +600,106 @@ pub(crate) mod test {
             s
         );
     }
+
+    #[rstest]
+    #[case("https://www.youtube.com/watch?v=abc", false, Some("type:video"))]
+    #[case("https://m.youtube.com/watch?v=abc", false, Some("type:video"))]
+    #[case("https://vimeo.com/123", false, Some("type:video"))]
+    #[case("https://notyoutube.com/watch", false, None)]
+    #[case(
+        "https://example.com/papers/paper.PDF?download=1",
+        false,
+        Some("type:pdf")
+    )]
+    #[case("https://github.com/rust-lang/rust", false, Some("type:code"))]
+    #[case(
+        "https://news.ycombinator.com/item?id=1",
+        true,
+        Some("type:discussion")
+    )]
+    #[case("https://example.com/blog/post.html", false, None)]
+    fn test_content_type(
+        tagger: StoryTagger,
+        #[case] url: &str,
+        #[case] self_post: bool,
+        #[case] expected: Option<&str>,
+    ) {
+        let url = progscrape_scrapers::StoryUrl::parse(url).expect("URL");
+        let tag = tagger.content_type(&url, self_post);
+        assert_eq!(
+            expected.map(str::to_owned),
+            tag.and_then(|tag| tagger.make_display_tags([tag]).next())
+        );
+        // The displayed tag can be searched for
+        if let Some(expected) = expected {
+            assert_eq!(tag, tagger.check_tag_search(expected));
+        }
+    }
+
+    /// A story matching many rules keeps its source tags, then the earliest keyword tags in config order.
+    #[rstest]
+    #[case(None, &["lobsters-tag", "neovim", "rust", "video", "vim", "zig"])]
+    #[case(Some(5), &["lobsters-tag", "rust", "video", "zig"])]
+    #[case(Some(3), &["lobsters-tag", "zig"])]
+    #[case(Some(1), &[])]
+    fn test_max_tags_per_story(#[case] max: Option<usize>, #[case] expected: &[&str]) {
+        // Parse from a string, as `json!` doesn't preserve the config order
+        let config = r#"{
+            "tags": {
+                "languages": {
+                    "zig": {},
+                    "rust": {}
+                },
+                "general": {
+                    "video(s)": {},
+                    "neovim": {"implies": "vim"},
+                    "vim": {}
+                }
+            }
+        }"#;
+        let mut config: TaggerConfig =
+            serde_json::from_str(config).expect("Failed to parse test config");
+        config.max_tags_per_story = max;
+        let tagger = StoryTagger::new(&config);
+        let mut keyword = TagSet::new();
+        tagger.tag("Neovim videos about Rust and Zig", &mut keyword);
+        let source = TagSet::from_iter(["lobsters-tag"]);
+        assert_eq!(
+            expected.to_vec(),
+            tagger.limit_tags(source, keyword).collect()
+        );
+    }
+
+    #[rstest]
+    #[case("JS is everywhere", &["javascript"])]
+    #[case("Rust and JS", &["javascript", "rust"])]
+    #[case("Ecmascript is everywhere", &["javascript"])]
+    #[case("Golang is fun", &["golang"])]
+    fn test_aliases(#[case] s: &str, #[case] tags: &[&str]) {
+        let mut config = tagger_config();
+        config.tags["testing"].insert("js".into(), Default::default());
+        config.tags["testing"].insert("ecmascript".into(), Default::default());
+        config.aliases = serde_json::from_value(json!({
+            "JS": "javascript",
+            "ecmascript": "javascript",
+            "youtu.be": "youtube.com",
+        }))
+        .expect("Failed to parse aliases");
+        let tagger = StoryTagger::new(&config);
+
+        let mut tag_set = TagSet::new();
+        tagger.tag(s, &mut tag_set);
+        assert_eq!(
+            tag_set.collect(),
+            tags.to_vec(),
+            "while checking tags for {}",
+            s
+        );
+
+        // Domains and searches are canonicalized too
+        assert_eq!("youtube.com", tagger.canonicalize("YOUTU.BE"));
+        assert_eq!("example.com", tagger.canonicalize("example.com"));
+        assert_eq!(Some("javascript"), tagger.check_tag_search("js"));
+        assert_eq!(None, tagger.check_tag_search("youtu.be"));
+    }
 }
@@ -0,0 +1,73 @@
+//! Saved HTTP responses that can stand in for the live network when scraping.
+use std::{
+    collections::HashMap,
+    fs::{read_to_string, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use crate::{ScrapeError, ScraperHttpResponseInput};
+
+/// The name of the manifest file within an archive directory.
+pub const ARCHIVE_MANIFEST: &str = "manifest.json";
+
+/// A directory of saved HTTP responses, used to replay a known set of scrapes deterministically. The directory
+/// contains a `manifest.json` mapping each URL to a file (relative to the directory) holding the response body.
+pub struct ScrapeArchive {
+    root: PathBuf,
+    manifest: HashMap<String, String>,
+}
+
+impl ScrapeArchive {
+    /// Open the archive at the given path, loading its manifest.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ScrapeError> {
+        let root = path.as_ref().to_owned();
+        let reader = BufReader::new(File::open(root.join(ARCHIVE_MANIFEST))?);
+        let manifest = serde_json::from_reader(reader)?;
+        Ok(Self { root, manifest })
+    }
+
+    /// Does this archive contain a response for the given URL?
+    pub fn contains(&self, url: &str) -> bool {
+        self.manifest.contains_key(url)
+    }
+
+    /// Load the saved response for a URL. URLs missing from the manifest are reported as an HTTP 404, mirroring what
+    /// a fetch of an unknown page would return.
+    pub fn fetch(&self, url: &str) -> Result<ScraperHttpResponseInput, ScrapeError> {
+        if let Some(file) = self.manifest.get(url) {
+            Ok(ScraperHttpResponseInput::Ok(read_to_string(
+                self.root.join(file),
+            )?))
+        } else {
+            tracing::warn!("URL {} not found in scrape archive", url);
+            Ok(ScraperHttpResponseInput::HTTPError(
+                404,
+                "Not Found".to_owned(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backends::test::load_file;
+
+    #[test]
+    fn test_archive_fetch() {
+        let archive = ScrapeArchive::open("testdata").expect("Failed to open archive");
+        assert!(archive.contains("https://news.ycombinator.com/news"));
+        match archive
+            .fetch("https://news.ycombinator.com/news")
+            .expect("Failed to fetch")
+        {
+            ScraperHttpResponseInput::Ok(s) => assert_eq!(s, load_file("hn1.html")),
+            _ => panic!("Expected a successful response"),
+        }
+        assert!(matches!(
+            archive.fetch("https://example.com/missing"),
+            Ok(ScraperHttpResponseInput::HTTPError(404, _))
+        ));
+    }
+}
@@ -0,0 +1,57 @@
+//! Extraction of the main text of a linked article, used to estimate how long the article takes to read.
+use tl::ParserOptions;
+
+use crate::backends::utils::html::{html_tag_iterator, unescape_entities};
+
+/// Count the words in the main text of an HTML page. The main text is taken to be the paragraphs of the page's
+/// `<article>`, falling back to its `<main>` and then to every paragraph on the page. Returns `None` if the page
+/// can't be parsed or has no text.
+pub fn article_word_count(html: &str) -> Option<usize> {
+    let dom = tl::parse(html, ParserOptions::default()).ok()?;
+    let p = dom.parser();
+    let root = ["article", "main"]
+        .into_iter()
+        .find_map(|selector| html_tag_iterator(p, dom.query_selector(selector)).next());
+    let paragraphs = match root {
+        Some(root) => html_tag_iterator(p, root.query_selector(p, "p")).collect::<Vec<_>>(),
+        None => html_tag_iterator(p, dom.query_selector("p")).collect(),
+    };
+    let count = paragraphs
+        .into_iter()
+        .map(|paragraph| {
+            unescape_entities(&paragraph.inner_text(p))
+                .split_whitespace()
+                .count()
+        })
+        .sum();
+    if count == 0 {
+        None
+    } else {
+        Some(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backends::test::load_file;
+
+    #[test]
+    fn test_article_word_count() {
+        // Only the article's paragraphs count, not the navigation, scripts or footer
+        assert_eq!(Some(503), article_word_count(&load_file("article1.html")));
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        assert_eq!(
+            Some(3),
+            article_word_count("<main><p>One two</p><p>three</p></main>")
+        );
+        assert_eq!(
+            Some(2),
+            article_word_count("<div><p>One</p></div><p>two</p>")
+        );
+        assert_eq!(None, article_word_count("<div>No paragraphs</div>"));
+    }
+}
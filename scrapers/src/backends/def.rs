@@ -62,12 +62,76 @@ pub struct ScrapeCore<'a> {
     pub rank: Option<usize>,
 }
 
+impl<'a> ScrapeCore<'a> {
+    /// Does this scrape link back to its own source's comments (ie: an Ask HN or a Reddit self-post), rather than to
+    /// an external story?
+    pub fn is_self_post(&self) -> bool {
+        if self.source.source == ScrapeSource::Other {
+            return false;
+        }
+        let comments = self
+            .source
+            .source
+            .comments_url(&self.source.id, self.source.subsource.as_deref());
+        StoryUrl::parse(comments)
+            .map(|comments| comments.host() == self.url.host())
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ScrapeShared {
     pub id: ScrapeId,
     pub url: StoryUrl,
     pub raw_title: String,
     pub date: StoryDate,
+    /// When this scrape was last fetched. Scrapes stored before this was recorded don't have it.
+    #[serde(default)]
+    pub scraped_at: Option<StoryDate>,
+    /// The date couldn't be parsed, so it is the time of the scrape instead. See `DateFallback`.
+    #[serde(default)]
+    pub date_estimated: bool,
+}
+
+/// What to do with a scrape whose date can't be parsed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateFallback {
+    /// Drop the scrape with a warning.
+    #[default]
+    Reject,
+    /// Use the time of the scrape, and mark the date as estimated. This suits sources where a story's rank matters
+    /// more than its date.
+    Now,
+}
+
+impl DateFallback {
+    /// Apply the fallback to the result of parsing a date, returning the date and whether it was estimated.
+    pub fn resolve<E>(self, date: Result<StoryDate, E>) -> Result<(StoryDate, bool), E> {
+        match (date, self) {
+            (Ok(date), _) => Ok((date, false)),
+            (Err(_), DateFallback::Now) => Ok((StoryDate::now(), true)),
+            (Err(e), DateFallback::Reject) => Err(e),
+        }
+    }
+}
+
+impl ScrapeShared {
+    /// Check that this scrape has an ID, a URL with a host and a date. A date at or before the Unix epoch is treated
+    /// as missing, since that's what an absent or zeroed timestamp parses to.
+    pub fn validate(&self) -> Vec<ScrapeViolation> {
+        let mut violations = vec![];
+        if self.id.id.trim().is_empty() {
+            violations.push(ScrapeViolation::MissingId);
+        }
+        if self.url.host().is_empty() {
+            violations.push(ScrapeViolation::InvalidUrl(self.url.raw().to_owned()));
+        }
+        if self.date.timestamp() <= 0 {
+            violations.push(ScrapeViolation::MissingDate);
+        }
+        violations
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -92,7 +156,19 @@ impl<T: ScrapeStory> std::ops::DerefMut for GenericScrape<T> {
 }
 
 impl<T: ScrapeStory> GenericScrape<T> {
-    pub fn merge_generic(&mut self, _other: Self) {}
+    pub fn merge_generic(&mut self, other: Self) {
+        // Never replace a title with an empty one, but do fill in a title that was empty
+        if self.shared.raw_title.trim().is_empty() && !other.shared.raw_title.trim().is_empty() {
+            self.shared.raw_title = other.shared.raw_title;
+        }
+        // Likewise, replace an estimated date with a real one
+        if self.shared.date_estimated && !other.shared.date_estimated {
+            self.shared.date = other.shared.date;
+            self.shared.date_estimated = false;
+        }
+        self.shared.scraped_at = self.shared.scraped_at.max(other.shared.scraped_at);
+        self.data.merge(other.data);
+    }
 }
 
 macro_rules! scrape_story {
@@ -107,7 +183,7 @@ macro_rules! scrape_story {
             pub fn new<'a, S: Clone + Into<Cow<'a, str>>>(id: S, date: StoryDate, raw_title: S, url: StoryUrl, $( $id: $type ),*) -> GenericScrape<$name> {
                 GenericScrape {
                     shared: ScrapeShared {
-                        id: ScrapeId::new(<$name as ScrapeStory>::TYPE, None, id.into().into()), date, raw_title: raw_title.into().into(), url
+                        id: ScrapeId::new(<$name as ScrapeStory>::TYPE, None, id.into().into()), date, raw_title: raw_title.into().into(), url, scraped_at: None, date_estimated: false
                     },
                     data: $name {
                         $($id),*
@@ -119,7 +195,7 @@ macro_rules! scrape_story {
             pub fn new_subsource<'a, S: Clone + Into<Cow<'a, str>>>(id: S, subsource: S, date: StoryDate, raw_title: S, url: StoryUrl, $( $id: $type ),*) -> GenericScrape<$name> {
                 GenericScrape {
                     shared: ScrapeShared {
-                        id: ScrapeId::new(<$name as ScrapeStory>::TYPE, Some(subsource.into().into()), id.into().into()), date, raw_title: raw_title.into().into(), url
+                        id: ScrapeId::new(<$name as ScrapeStory>::TYPE, Some(subsource.into().into()), id.into().into()), date, raw_title: raw_title.into().into(), url, scraped_at: None, date_estimated: false
                     },
                     data: $name {
                         $($id),*
@@ -131,7 +207,7 @@ macro_rules! scrape_story {
             pub fn new_with_defaults<'a, S: Clone + Into<Cow<'a, str>>>(id: S, date: StoryDate, raw_title: S, url: StoryUrl) -> GenericScrape<$name> {
                 GenericScrape {
                     shared: ScrapeShared {
-                        id: ScrapeId::new(<$name as ScrapeStory>::TYPE, None, id.into().into()), date, raw_title: raw_title.into().into(), url
+                        id: ScrapeId::new(<$name as ScrapeStory>::TYPE, None, id.into().into()), date, raw_title: raw_title.into().into(), url, scraped_at: None, date_estimated: false
                     },
                     data: $name {
                         $($id : Default::default() ),*
@@ -143,7 +219,7 @@ macro_rules! scrape_story {
             pub fn new_subsource_with_defaults<'a, S: Clone + Into<Cow<'a, str>>>(id: S, subsource: S, date: StoryDate, raw_title: S, url: StoryUrl) -> GenericScrape<$name> {
                 GenericScrape {
                     shared: ScrapeShared {
-                        id: ScrapeId::new(<$name as ScrapeStory>::TYPE, Some(subsource.into().into()), id.into().into()), date, raw_title: raw_title.into().into(), url
+                        id: ScrapeId::new(<$name as ScrapeStory>::TYPE, Some(subsource.into().into()), id.into().into()), date, raw_title: raw_title.into().into(), url, scraped_at: None, date_estimated: false
                     },
                     data: $name {
                         $($id : Default::default() ),*
@@ -1,5 +1,6 @@
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{
     borrow::{Borrow, Cow},
     collections::HashMap,
@@ -7,8 +8,8 @@ use std::{
 use tl::{HTMLTag, Parser, ParserOptions};
 
 use super::{
-    scrape_story, utils::html::*, GenericScrape, ScrapeConfigSource, ScrapeCore, ScrapeShared,
-    ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
+    scrape_story, utils::html::*, DateFallback, GenericScrape, ScrapeConfigSource, ScrapeCore,
+    ScrapeShared, ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
 };
 use crate::types::*;
 
@@ -28,6 +29,25 @@ impl ScrapeSourceDef for HackerNews {
 pub struct HackerNewsConfig {
     homepage: String,
     pages: Vec<String>,
+    /// Whether to scrape the HTML pages or the Algolia search API.
+    #[serde(default)]
+    format: HackerNewsFormat,
+    /// The Algolia search URL for the front page (eg: `https://hn.algolia.com/api/v1/search?tags=front_page`), used
+    /// in place of the pages when the format is `api`.
+    #[serde(default)]
+    api: String,
+    /// What to do with stories whose date can't be parsed.
+    #[serde(default)]
+    date_fallback: DateFallback,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HackerNewsFormat {
+    #[default]
+    Html,
+    /// The Algolia HN search API, which is less likely to break than the markup.
+    Api,
 }
 
 impl ScrapeConfigSource for HackerNewsConfig {
@@ -36,10 +56,14 @@ impl ScrapeConfigSource for HackerNewsConfig {
     }
 
     fn provide_urls(&self, _: Vec<String>) -> Vec<String> {
-        self.pages
-            .iter()
-            .map(|s| format!("{}{}", self.homepage, s))
-            .collect_vec()
+        match self.format {
+            HackerNewsFormat::Html => self
+                .pages
+                .iter()
+                .map(|s| format!("{}{}", self.homepage, s))
+                .collect_vec(),
+            HackerNewsFormat::Api => vec![self.api.clone()],
+        }
     }
 }
 
@@ -76,7 +100,8 @@ struct HackerNewsInfoLine {
     id: String,
     comments: u32,
     points: u32,
-    date: StoryDate,
+    /// Kept as a result, so that the configured fallback can be applied.
+    date: Result<StoryDate, String>,
 }
 
 #[derive(Debug)]
@@ -133,7 +158,7 @@ impl HackerNewsScraper {
                 .ok_or_else(|| "Failed to get age title".to_string())?
                 + "Z";
             let date = StoryDate::parse_from_rfc3339(&date)
-                .ok_or_else(|| "Failed to map date".to_string())?;
+                .ok_or_else(|| "Failed to map date".to_string());
             let mut comments = None;
             for node in html_tag_iterator(p, node.query_selector(p, "a")) {
                 let text = node.inner_text(p);
@@ -162,6 +187,55 @@ impl HackerNewsScraper {
         };
     }
 
+    fn scrape_api(
+        &self,
+        args: &HackerNewsConfig,
+        input: &str,
+    ) -> Result<(Vec<GenericScrape<HackerNewsStory>>, Vec<String>), ScrapeError> {
+        let root: Value = serde_json::from_str(input)?;
+        let hits = root["hits"].as_array().ok_or_else(|| {
+            ScrapeError::StructureError("Hacker News API response has no hits".to_owned())
+        })?;
+        let mut warnings = vec![];
+        let mut stories = vec![];
+        // Hits are in front page order
+        for (position, hit) in hits.iter().enumerate() {
+            match Self::map_api_story(args, hit, position as u32 + 1) {
+                Ok(story) => stories.push(story),
+                Err(e) => warnings.push(e),
+            }
+        }
+        Ok((stories, warnings))
+    }
+
+    fn map_api_story(
+        args: &HackerNewsConfig,
+        hit: &Value,
+        position: u32,
+    ) -> Result<GenericScrape<HackerNewsStory>, String> {
+        let string = |key: &str| hit[key].as_str().filter(|s| !s.is_empty());
+        let id = string("objectID").ok_or("Missing field \"objectID\"")?;
+        let raw_title = string("title").ok_or("Missing field \"title\"")?;
+        // Text posts (ie: Ask HN) have no URL of their own, so they link to the story on HN
+        let url = match string("url") {
+            Some(url) => url.to_owned(),
+            None => format!("https://news.ycombinator.com/item?id={}", id),
+        };
+        let url = StoryUrl::parse(&url).ok_or(format!("Failed to parse URL {}", url))?;
+        let (date, date_estimated) = args.date_fallback.resolve(
+            hit["created_at_i"]
+                .as_i64()
+                .and_then(StoryDate::from_seconds)
+                .or_else(|| string("created_at").and_then(StoryDate::parse_from_rfc3339))
+                .ok_or("Unmappable date"),
+        )?;
+        let points = hit["points"].as_u64().unwrap_or_default() as u32;
+        let comments = hit["num_comments"].as_u64().unwrap_or_default() as u32;
+        let mut story = HackerNewsStory::new(id, date, raw_title, url, points, comments, position);
+        story.date_estimated = date_estimated;
+        Ok(story)
+    }
+
     fn tags_from_title(
         &self,
         _args: &<HackerNews as ScrapeSourceDef>::Config,
@@ -191,9 +265,12 @@ impl Scraper for HackerNewsScraper {
 
     fn scrape(
         &self,
-        _args: &HackerNewsConfig,
+        args: &HackerNewsConfig,
         input: &str,
     ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
+        if args.format == HackerNewsFormat::Api {
+            return self.scrape_api(args, input);
+        }
         let dom = tl::parse(input, ParserOptions::default())?;
         let p = dom.parser();
         let mut errors = vec![];
@@ -228,10 +305,18 @@ impl Scraper for HackerNewsScraper {
                     comments,
                     ..
                 } = info;
+                let (date, date_estimated) = match args.date_fallback.resolve(date) {
+                    Ok(date) => date,
+                    Err(e) => {
+                        errors.push(format!("{} for id {}", e, k));
+                        continue;
+                    }
+                };
                 let id = k;
-                stories.push(HackerNewsStory::new(
-                    id, date, raw_title, url, points, comments, position,
-                ));
+                let mut story =
+                    HackerNewsStory::new(id, date, raw_title, url, points, comments, position);
+                story.date_estimated = date_estimated;
+                stories.push(story);
             } else {
                 errors.push(format!("Unmatched story/info for id {}", k));
             }
@@ -260,3 +345,117 @@ impl Scraper for HackerNewsScraper {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backends::test::load_file;
+
+    #[test]
+    fn test_parse_api() {
+        let config = HackerNewsConfig {
+            format: HackerNewsFormat::Api,
+            ..Default::default()
+        };
+        let scraper = HackerNewsScraper::default();
+        let (stories, warnings) = scraper
+            .scrape(&config, &load_file("hn-algolia1.json"))
+            .expect("Failed to scrape");
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(6, stories.len());
+
+        // The API produces the same stories as the HTML of the same front page
+        let (html, _) = scraper
+            .scrape(&HackerNewsConfig::default(), &load_file("hn1.html"))
+            .expect("Failed to scrape");
+        for story in &stories {
+            let expected = html
+                .iter()
+                .find(|s| s.shared.id == story.shared.id)
+                .expect("Missing story");
+            assert_eq!(expected.shared.raw_title, story.shared.raw_title);
+            assert_eq!(expected.shared.url, story.shared.url);
+            assert_eq!(expected.shared.date, story.shared.date);
+            assert_eq!(expected.data.points, story.data.points);
+            assert_eq!(expected.data.comments, story.data.comments);
+        }
+        // Positions match where the API has the same ordering as the page
+        for story in &stories[..5] {
+            let expected = html.iter().find(|s| s.shared.id == story.shared.id);
+            assert_eq!(expected.map(|s| s.data.position), Some(story.data.position));
+        }
+
+        // Text posts link to the story on HN
+        assert_eq!(
+            "https://news.ycombinator.com/item?id=34100102",
+            stories[5].shared.url.raw()
+        );
+    }
+
+    #[test]
+    fn test_parse_api_warnings() {
+        let config = HackerNewsConfig {
+            format: HackerNewsFormat::Api,
+            api: "https://hn.algolia.com/api/v1/search?tags=front_page".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["https://hn.algolia.com/api/v1/search?tags=front_page"],
+            config.provide_urls(vec![])
+        );
+
+        let input = r#"{"hits": [{"objectID": "1", "url": "https://example.com/"}]}"#;
+        let (stories, warnings) = HackerNewsScraper::default()
+            .scrape(&config, input)
+            .expect("Failed to scrape");
+        assert!(stories.is_empty());
+        assert_eq!(1, warnings.len());
+
+        assert!(HackerNewsScraper::default()
+            .scrape(&config, r#"{"message": "rate limited"}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn test_empty_title() {
+        let input = r#"{"hits": [
+            {"objectID": "1", "title": "A story", "url": "http://example.com/1", "created_at_i": 1671821650},
+            {"objectID": "2", "title": "  ", "url": "http://example.com/2", "created_at_i": 1671821650}
+        ]}"#;
+        let mut config = crate::ScrapeConfig::default();
+        config.hacker_news.format = HackerNewsFormat::Api;
+        let (scrapes, warnings) = crate::backends::scrape(&config, ScrapeSource::HackerNews, input)
+            .expect("Scrape failed");
+        assert_eq!(1, scrapes.len());
+        assert_eq!("A story", scrapes[0].raw_title);
+        assert_eq!(vec!["Empty title for hacker_news-2"], warnings);
+
+        config.allow_empty_titles = true;
+        let (scrapes, warnings) = crate::backends::scrape(&config, ScrapeSource::HackerNews, input)
+            .expect("Scrape failed");
+        assert_eq!(2, scrapes.len());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_validate_scrapes() {
+        let input = r#"{"hits": [
+            {"objectID": "1", "title": "A story", "url": "http://example.com/1", "created_at_i": 1671821650},
+            {"objectID": "2", "title": "No date", "url": "http://example.com/2", "created_at_i": 0}
+        ]}"#;
+        let mut config = crate::ScrapeConfig::default();
+        config.hacker_news.format = HackerNewsFormat::Api;
+        let (scrapes, warnings) = crate::backends::scrape(&config, ScrapeSource::HackerNews, input)
+            .expect("Scrape failed");
+        assert_eq!(2, scrapes.len());
+        assert!(warnings.is_empty());
+
+        config.validate_scrapes = true;
+        let (scrapes, warnings) = crate::backends::scrape(&config, ScrapeSource::HackerNews, input)
+            .expect("Scrape failed");
+        assert_eq!(2, scrapes.len());
+        assert!(scrapes[0].validate().is_empty());
+        assert_eq!(vec![ScrapeViolation::MissingDate], scrapes[1].validate());
+        assert_eq!(vec!["Missing date for hacker_news-2"], warnings);
+    }
+}
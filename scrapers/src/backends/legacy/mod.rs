@@ -56,8 +56,11 @@ fn make_lobsters(
     lobsters::LobstersStory::new_with_defaults(id, date, raw_title, url)
 }
 
+const LEGACY_FILE_1: &str = "scrapers/import/old.json.gz";
+const LEGACY_FILE_2: &str = "scrapers/import/stories-progscrape-hr.gz";
+
 fn import_legacy_1(root: &Path) -> Result<impl Iterator<Item = TypedScrape>, LegacyError> {
-    let f = BufReader::new(File::open(root.join("scrapers/import/old.json.gz"))?);
+    let f = BufReader::new(File::open(root.join(LEGACY_FILE_1))?);
     let mut decoder = BufReader::new(GzDecoder::new(f));
     let mut out = vec![];
     loop {
@@ -105,9 +108,7 @@ fn import_legacy_1(root: &Path) -> Result<impl Iterator<Item = TypedScrape>, Leg
 }
 
 fn import_legacy_2(root: &Path) -> Result<impl Iterator<Item = TypedScrape>, LegacyError> {
-    let f = BufReader::new(File::open(
-        root.join("scrapers/import/stories-progscrape-hr.gz"),
-    )?);
+    let f = BufReader::new(File::open(root.join(LEGACY_FILE_2))?);
     let mut decoder = BufReader::new(GzDecoder::new(f));
     let mut out = vec![];
     'outer: loop {
@@ -162,10 +163,26 @@ fn import_legacy_2(root: &Path) -> Result<impl Iterator<Item = TypedScrape>, Leg
     Ok(out.into_iter())
 }
 
+/// Is the cache at least as new as all of the legacy files it was built from? Any file that can't be checked makes the
+/// cache stale.
+fn is_cache_fresh(root: &Path, cache_file: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let Some(cache) = modified(cache_file) else {
+        return false;
+    };
+    [LEGACY_FILE_1, LEGACY_FILE_2]
+        .iter()
+        .all(|file| modified(&root.join(file)).is_some_and(|modified| modified <= cache))
+}
+
+/// Read all of the legacy scrapes. The parsed scrapes are cached, and the cache is rebuilt whenever the legacy files
+/// are updated.
 pub fn import_legacy(root: &Path) -> Result<Vec<TypedScrape>, LegacyError> {
     let cache_file = root.to_owned().join("target/legacycache.bin");
     tracing::info!("Reading cache '{:?}'...", cache_file);
-    if let Ok(f) = File::open(&cache_file) {
+    if !is_cache_fresh(root, &cache_file) {
+        tracing::info!("Cache missing or older than the legacy files");
+    } else if let Ok(f) = File::open(&cache_file) {
         if let Ok(value) = serde_cbor::from_reader::<Vec<_>, _>(BufReader::new(f)) {
             tracing::info!("Cache OK");
             return Ok(value);
@@ -202,4 +219,26 @@ mod test {
         assert!(!import_legacy(Path::new(".."))?.is_empty());
         Ok(())
     }
+
+    #[test]
+    fn test_cache_freshness() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("scrapers/import"))?;
+        let cache = root.join("cache.bin");
+        let touch = |path: &Path, time| -> std::io::Result<()> {
+            File::create(path)?.set_modified(std::time::UNIX_EPOCH + time)
+        };
+        let day = std::time::Duration::from_secs(60 * 60 * 24);
+
+        assert!(!is_cache_fresh(root, &cache));
+        touch(&root.join(LEGACY_FILE_1), day)?;
+        touch(&root.join(LEGACY_FILE_2), day)?;
+        touch(&cache, day * 2)?;
+        assert!(is_cache_fresh(root, &cache));
+        // Updating either legacy file invalidates the cache
+        touch(&root.join(LEGACY_FILE_2), day * 3)?;
+        assert!(!is_cache_fresh(root, &cache));
+        Ok(())
+    }
 }
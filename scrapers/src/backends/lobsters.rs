@@ -4,6 +4,7 @@ use super::*;
 
 use roxmltree::Document;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 pub struct Lobsters {}
 
@@ -20,7 +21,21 @@ impl ScrapeSourceDef for Lobsters {
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct LobstersConfig {
     feed: String,
+    /// The format of the feed. The JSON feed (eg: `hottest.json`) includes scores and comment counts.
+    #[serde(default)]
+    format: LobstersFormat,
     tag_denylist: HashSet<String>,
+    /// What to do with stories whose date can't be parsed.
+    #[serde(default)]
+    date_fallback: DateFallback,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LobstersFormat {
+    #[default]
+    Rss,
+    Json,
 }
 
 impl ScrapeConfigSource for LobstersConfig {
@@ -39,6 +54,7 @@ scrape_story! {
         position: u32,
         score: u32,
         tags: Vec<String>,
+        submitter: Option<String>,
     }
 }
 
@@ -48,21 +64,107 @@ impl ScrapeStory for LobstersStory {
     fn merge(&mut self, other: LobstersStory) {
         self.score = std::cmp::max(self.score, other.score);
         self.num_comments = std::cmp::max(self.num_comments, other.num_comments);
+        if self.submitter.is_none() {
+            self.submitter = other.submitter;
+        }
     }
 }
 
 #[derive(Default)]
 pub struct LobstersScraper {}
 
-impl Scraper for LobstersScraper {
-    type Config = <Lobsters as ScrapeSourceDef>::Config;
-    type Output = <Lobsters as ScrapeSourceDef>::Scrape;
+impl LobstersScraper {
+    /// Lobsters reports authors as `user@users.lobste.rs (user)`, so we prefer the name in parentheses and fall back
+    /// to the local part of the address.
+    fn parse_author(author: &str) -> Option<String> {
+        let author = author.trim();
+        let name = match (author.find('('), author.rfind(')')) {
+            (Some(start), Some(end)) if start < end => &author[start + 1..end],
+            _ => author.split('@').next().unwrap_or_default(),
+        }
+        .trim();
+        if name.is_empty() {
+            None
+        } else {
+            Some(name.to_owned())
+        }
+    }
 
-    fn scrape(
+    fn scrape_json(
         &self,
-        _args: &Self::Config,
+        args: &LobstersConfig,
         input: &str,
-    ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
+    ) -> Result<(Vec<GenericScrape<LobstersStory>>, Vec<String>), ScrapeError> {
+        let root: Value = serde_json::from_str(input)?;
+        let items = root.as_array().ok_or_else(|| {
+            ScrapeError::StructureError("Lobsters JSON was not an array".to_owned())
+        })?;
+        let mut warnings = vec![];
+        let mut stories = vec![];
+        for (position, item) in items.iter().enumerate() {
+            match Self::map_json_story(args, item, position as u32 + 1) {
+                Ok(story) => stories.push(story),
+                Err(e) => warnings.push(e),
+            }
+        }
+        Ok((stories, warnings))
+    }
+
+    fn map_json_story(
+        args: &LobstersConfig,
+        item: &Value,
+        position: u32,
+    ) -> Result<GenericScrape<LobstersStory>, String> {
+        let string = |key: &str| item[key].as_str().filter(|s| !s.is_empty());
+        let id = string("short_id").ok_or("Missing field \"short_id\"")?;
+        let raw_title = string("title").ok_or("Missing field \"title\"")?;
+        // Text posts have no URL of their own, so they link to the story on Lobsters
+        let url = string("url")
+            .or(string("short_id_url"))
+            .and_then(StoryUrl::parse)
+            .ok_or("Unmappable URL")?;
+        let (date, date_estimated) = args.date_fallback.resolve(
+            string("created_at")
+                .and_then(StoryDate::parse_from_rfc3339)
+                .ok_or("Unmappable date"),
+        )?;
+        // Scores can go negative when a story is flagged
+        let score = item["score"].as_i64().unwrap_or_default().max(0) as u32;
+        let num_comments = item["comment_count"].as_u64().unwrap_or_default() as u32;
+        let tags = item["tags"]
+            .as_array()
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(|tag| tag.as_str().map(str::to_owned))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Older versions of the API return the full user rather than the username
+        let submitter = match &item["submitter_user"] {
+            Value::String(user) => Some(user.as_str()),
+            user => user["username"].as_str(),
+        }
+        .and_then(Self::parse_author);
+        let mut story = LobstersStory::new(
+            id,
+            date,
+            raw_title,
+            url,
+            num_comments,
+            position,
+            score,
+            tags,
+            submitter,
+        );
+        story.date_estimated = date_estimated;
+        Ok(story)
+    }
+
+    fn scrape_rss(
+        &self,
+        args: &LobstersConfig,
+        input: &str,
+    ) -> Result<(Vec<GenericScrape<LobstersStory>>, Vec<String>), ScrapeError> {
         let doc = Document::parse(input)?;
         let rss = doc.root_element();
         let mut warnings = vec![];
@@ -77,8 +179,9 @@ impl Scraper for LobstersScraper {
                     let mut raw_title = None;
                     let mut id = None;
                     let mut url = None;
-                    let mut date = None;
+                    let mut date = Err("Missing date");
                     let mut tags = vec![];
+                    let mut submitter = None;
                     for subitem in item.children() {
                         if !subitem.is_element() {
                             continue;
@@ -91,9 +194,12 @@ impl Scraper for LobstersScraper {
                                 })
                             }
                             "link" => url = subitem.text().and_then(StoryUrl::parse),
-                            "author" => {}
+                            "author" => submitter = subitem.text().and_then(Self::parse_author),
                             "pubDate" => {
-                                date = subitem.text().and_then(StoryDate::parse_from_rfc2822)
+                                date = subitem
+                                    .text()
+                                    .and_then(StoryDate::parse_from_rfc2822)
+                                    .ok_or("Unmappable date")
                             }
                             "comments" => {}
                             "category" => drop(subitem.text().map(|s| tags.push(s.to_owned()))),
@@ -101,13 +207,14 @@ impl Scraper for LobstersScraper {
                             x => warnings.push(format!("Unknown sub-node '{}'", x)),
                         }
                     }
-                    if let (Some(raw_title), Some(id), Some(url), Some(date)) =
+                    let date = args.date_fallback.resolve(date);
+                    if let (Some(raw_title), Some(id), Some(url), Ok((date, date_estimated))) =
                         (raw_title, id, url, date)
                     {
                         let position = position as u32 + 1;
                         let num_comments = 0;
                         let score = 0;
-                        stories.push(LobstersStory::new(
+                        let mut story = LobstersStory::new(
                             id,
                             date,
                             raw_title,
@@ -116,7 +223,10 @@ impl Scraper for LobstersScraper {
                             position,
                             score,
                             tags,
-                        ));
+                            submitter,
+                        );
+                        story.date_estimated = date_estimated;
+                        stories.push(story);
                     } else {
                         warnings.push("Story did not contain all required fields".to_string());
                     }
@@ -125,6 +235,22 @@ impl Scraper for LobstersScraper {
         }
         Ok((stories, warnings))
     }
+}
+
+impl Scraper for LobstersScraper {
+    type Config = <Lobsters as ScrapeSourceDef>::Config;
+    type Output = <Lobsters as ScrapeSourceDef>::Scrape;
+
+    fn scrape(
+        &self,
+        args: &Self::Config,
+        input: &str,
+    ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
+        match args.format {
+            LobstersFormat::Rss => self.scrape_rss(args, input),
+            LobstersFormat::Json => self.scrape_json(args, input),
+        }
+    }
 
     fn extract_core<'a>(
         &self,
@@ -149,3 +275,108 @@ impl Scraper for LobstersScraper {
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("waddlesplash@users.lobste.rs (waddlesplash)", Some("waddlesplash"))]
+    #[case("tomscii@users.lobste.rs", Some("tomscii"))]
+    #[case("tomscii", Some("tomscii"))]
+    #[case("", None)]
+    #[case("()", None)]
+    fn test_parse_author(#[case] author: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            expected.map(str::to_owned),
+            LobstersScraper::parse_author(author)
+        );
+    }
+
+    #[rstest]
+    fn test_parse_submitter(#[values("lobsters1.rss", "lobsters2.rss")] file: &str) {
+        let (stories, warnings) = LobstersScraper::default()
+            .scrape(
+                &LobstersConfig::default(),
+                &crate::backends::test::load_file(file),
+            )
+            .expect("Failed to scrape");
+        assert!(warnings.is_empty());
+        assert!(!stories.is_empty());
+        assert!(stories.iter().all(|story| story.data.submitter.is_some()));
+        if file == "lobsters1.rss" {
+            assert_eq!(Some("waddlesplash"), stories[0].data.submitter.as_deref());
+        }
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let config = LobstersConfig {
+            format: LobstersFormat::Json,
+            ..Default::default()
+        };
+        let (stories, warnings) = LobstersScraper::default()
+            .scrape(&config, &crate::backends::test::load_file("lobsters1.json"))
+            .expect("Failed to scrape");
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(4, stories.len());
+
+        let story = &stories[0];
+        assert_eq!("xf162v", story.shared.id.id);
+        assert_eq!("Haiku R1/beta4 has been released", story.shared.raw_title);
+        assert_eq!(
+            "https://www.haiku-os.org/news/2022-12-23_haiku_r1_beta4/",
+            story.shared.url.raw()
+        );
+        assert_eq!(
+            StoryDate::parse_from_rfc2822("Fri, 23 Dec 2022 13:04:48 -0600"),
+            Some(story.shared.date)
+        );
+        assert_eq!(54, story.data.score);
+        assert_eq!(12, story.data.num_comments);
+        assert_eq!(1, story.data.position);
+        assert_eq!(vec!["release", "osdev"], story.data.tags);
+        assert_eq!(Some("waddlesplash"), story.data.submitter.as_deref());
+
+        // The older object form of the submitter
+        assert_eq!(Some("tomscii"), stories[1].data.submitter.as_deref());
+        // Text posts link to the story itself
+        assert_eq!("https://lobste.rs/s/k2lmnp", stories[2].shared.url.raw());
+        assert_eq!(4, stories[3].data.position);
+    }
+
+    #[test]
+    fn test_parse_json_warnings() {
+        let config = LobstersConfig {
+            format: LobstersFormat::Json,
+            ..Default::default()
+        };
+        let input = r#"[{"short_id": "abcdef", "url": "https://example.com/"}]"#;
+        let (stories, warnings) = LobstersScraper::default()
+            .scrape(&config, input)
+            .expect("Failed to scrape");
+        assert!(stories.is_empty());
+        assert_eq!(1, warnings.len());
+
+        assert!(LobstersScraper::default()
+            .scrape(&config, r#"{"error": "rate limited"}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn test_missing_author() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0"><channel><item>
+<title>A story</title>
+<link>https://example.com/</link>
+<guid isPermaLink="false">https://lobste.rs/s/abcdef</guid>
+<pubDate>Fri, 23 Dec 2022 13:04:48 -0600</pubDate>
+</item></channel></rss>"#;
+        let (stories, _) = LobstersScraper::default()
+            .scrape(&LobstersConfig::default(), input)
+            .expect("Failed to scrape");
+        assert_eq!(1, stories.len());
+        assert_eq!(None, stories[0].data.submitter);
+    }
+}
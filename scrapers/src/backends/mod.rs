@@ -1,8 +1,9 @@
 use serde::{ser::SerializeMap, Deserialize, Serialize};
-use std::{borrow::Cow, fmt::Debug};
+use std::{borrow::Cow, collections::HashMap, fmt::Debug};
 
-pub use self::def::ScrapeCore;
 pub(crate) use self::def::*;
+pub use self::def::{DateFallback, ScrapeCore};
+use crate::collections::{TitleStrategy, UrlStrategy};
 use crate::types::*;
 
 mod def;
@@ -11,7 +12,7 @@ pub mod legacy;
 pub mod lobsters;
 pub mod reddit;
 pub mod slashdot;
-mod utils;
+pub(crate) mod utils;
 
 macro_rules! scrapers {
     ($($package:ident :: $name:ident ,)*) => {
@@ -28,8 +29,32 @@ macro_rules! scrapers {
                 $(
                     ScrapeSource::$name => {
                         let scraper = <$package::$name as ScrapeSourceDef>::Scraper::default();
-                        let (res, warnings) = scraper.scrape(&config.$package, input)?;
-                        Ok((res.into_iter().map(|x| x.into()).collect(), warnings))
+                        let (res, mut warnings) = scraper.scrape(&config.$package, input)?;
+                        let mut scrapes = vec![];
+                        for x in res {
+                            let mut x: TypedScrape = x.into();
+                            if !config.keep_title_whitespace {
+                                x.raw_title = utils::html::clean_title(&x.raw_title);
+                            }
+                            if !config.allow_empty_titles && x.raw_title.trim().is_empty() {
+                                warnings.push(format!("Empty title for {}", x.id));
+                                continue;
+                            }
+                            if config.validate_scrapes {
+                                for violation in x.validate() {
+                                    tracing::warn!(id = %x.id, %violation, "Scrape failed validation");
+                                    warnings.push(format!("{} for {}", violation, x.id));
+                                }
+                            }
+                            x.scraped_at = Some(StoryDate::now());
+                            x.url = x
+                                .url
+                                .clone()
+                                .rewrite_host(&config.host_rewrites)
+                                .keep_query_params(&config.query_param_allowlist);
+                            scrapes.push(x);
+                        }
+                        Ok((scrapes, warnings))
                     },
                 )*
                 ScrapeSource::Other => unreachable!(),
@@ -43,8 +68,61 @@ macro_rules! scrapers {
                 #[doc="Configuration for the "]
                 #[doc=stringify!($name)]
                 #[doc=" backend."]
-                pub $package : <$package :: $name as ScrapeSourceDef>::Config
-            ),*
+                pub $package : <$package :: $name as ScrapeSourceDef>::Config,
+            )*
+            /// How to choose between differing titles for the same story.
+            #[serde(default)]
+            pub title_strategy: TitleStrategy,
+            /// How to choose between differing URLs for the same story.
+            #[serde(default)]
+            pub url_strategy: UrlStrategy,
+            /// Domains whose stories are never indexed. Entries of the form `*.example.com` match the domain and all
+            /// of its subdomains.
+            #[serde(default)]
+            pub blocked_domains: Vec<String>,
+            /// Alternate frontends and mirrors, mapped to the canonical host that their URLs are rewritten to.
+            #[serde(default)]
+            pub host_rewrites: HashMap<String, String>,
+            /// Query params that identify the article on a host (ie: `?id=123`). For the hosts listed here, only these
+            /// params are kept when normalizing URLs, even if they would otherwise be stripped as tracking params.
+            #[serde(default)]
+            pub query_param_allowlist: HashMap<String, Vec<String>>,
+            /// The maximum number of concurrent fetches for each source. Sources not listed here use
+            /// `DEFAULT_MAX_CONCURRENCY`.
+            #[serde(default)]
+            pub max_concurrency: HashMap<ScrapeSource, usize>,
+            /// Whether each source is scraped. Sources not listed here are enabled, so a source can be paused (ie:
+            /// when its format breaks) without removing its configuration.
+            #[serde(default)]
+            pub enabled: HashMap<ScrapeSource, bool>,
+            /// Suffixes stripped from the titles of each source (ie: a site name), in order. The raw title is kept.
+            #[serde(default)]
+            pub title_suffixes: HashMap<ScrapeSource, Vec<crate::TitleSuffix>>,
+            /// Scrapes older than this many days are dropped, unless they belong to a story that is already indexed.
+            /// If unset, scrapes of any age are accepted.
+            #[serde(default)]
+            pub max_story_age_days: Option<u32>,
+            /// Fetch every URL demanded for a source, even if the same URL is demanded more than once. By default,
+            /// repeated subsources and URLs are collapsed so that each URL is fetched once per scrape.
+            #[serde(default)]
+            pub keep_duplicate_demands: bool,
+            /// Keep titles exactly as scraped. By default, control characters are stripped from titles and runs of
+            /// whitespace are collapsed to a single space.
+            #[serde(default)]
+            pub keep_title_whitespace: bool,
+            /// Keep scrapes whose title is empty or whitespace-only. By default these are rejected with a warning,
+            /// as they are almost always a parsing glitch and make for an unusable story.
+            #[serde(default)]
+            pub allow_empty_titles: bool,
+            /// Check that every scrape has an ID, a valid URL and a date, reporting any that don't as warnings. The
+            /// scrapes are kept, but a sudden run of these means that a source's format has changed.
+            #[serde(default)]
+            pub validate_scrapes: bool,
+            /// The most scrapes kept for a single story. The newest scrape from each source is always kept, then the
+            /// newest of the rest, and the engagement of any others is merged into a kept scrape from the same source.
+            /// If unset, every scrape is kept.
+            #[serde(default)]
+            pub max_scrapes_per_story: Option<usize>,
         }
 
         impl ScrapeConfig {
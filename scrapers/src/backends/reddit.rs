@@ -4,8 +4,8 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use super::{
-    scrape_story, utils::html::unescape_entities, GenericScrape, ScrapeConfigSource, ScrapeCore,
-    ScrapeShared, ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
+    scrape_story, utils::html::unescape_entities, DateFallback, GenericScrape, ScrapeConfigSource,
+    ScrapeCore, ScrapeShared, ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
 };
 use crate::types::*;
 
@@ -31,6 +31,17 @@ pub struct RedditConfig {
     subreddit_batch: usize,
     limit: usize,
     subreddits: HashMap<String, SubredditConfig>,
+    /// Posts with an upvote ratio below this are dropped, as they tend to be controversial or spam. Posts without an
+    /// upvote ratio are always kept.
+    #[serde(default)]
+    min_upvote_ratio: f32,
+    /// Keep post IDs as they appear in the response. By default they are lowercased, as Reddit's base36 IDs are
+    /// case-insensitive but don't always have the same case across endpoints.
+    #[serde(default)]
+    keep_id_case: bool,
+    /// What to do with posts whose date can't be parsed.
+    #[serde(default)]
+    date_fallback: DateFallback,
 }
 
 impl ScrapeConfigSource for RedditConfig {
@@ -155,8 +166,17 @@ impl RedditScraper {
         }
     }
 
+    fn optional_float(&self, data: &Value, key: &str) -> Result<Option<f64>, String> {
+        if data[key].is_null() {
+            Ok(None)
+        } else {
+            self.require_float(data, key).map(Some)
+        }
+    }
+
     fn map_story(
         &self,
+        args: &RedditConfig,
         child: &Value,
         positions: &mut HashMap<String, u32>,
     ) -> Result<GenericScrape<<Self as Scraper>::Output>, String> {
@@ -167,7 +187,10 @@ impl RedditScraper {
             return Err(format!("Unknown story type: {:?}", kind));
         };
 
-        let id = self.require_string(data, "id")?;
+        let mut id = self.require_string(data, "id")?;
+        if !args.keep_id_case {
+            id.make_ascii_lowercase();
+        }
         let subreddit = self.require_string(data, "subreddit")?.to_ascii_lowercase();
         if let Some(true) = data["stickied"].as_bool() {
             return Err(format!("Ignoring stickied story {}/{}", subreddit, id));
@@ -177,9 +200,15 @@ impl RedditScraper {
             .and_modify(|n| *n += 1)
             .or_default()
             + 1;
-        let seconds: i64 = self.require_integer(data, "created_utc")?;
-        let millis = seconds * 1000;
-        let date = StoryDate::from_millis(millis).ok_or_else(|| "Unmappable date".to_string())?;
+        let (date, date_estimated) =
+            args.date_fallback
+                .resolve(
+                    self.require_integer(data, "created_utc")
+                        .and_then(|seconds: i64| {
+                            StoryDate::from_millis(seconds * 1000)
+                                .ok_or_else(|| "Unmappable date".to_string())
+                        }),
+                )?;
         let url = StoryUrl::parse(unescape_entities(&self.require_string(data, "url")?))
             .ok_or_else(|| "Unmappable URL".to_string())?;
         let raw_title = unescape_entities(&self.require_string(data, "title")?);
@@ -187,9 +216,19 @@ impl RedditScraper {
         let score = self.require_integer(data, "score")?;
         let downvotes = self.require_integer(data, "downs")?;
         let upvotes = self.require_integer(data, "ups")?;
-        let upvote_ratio = self.require_float(data, "upvote_ratio")? as f32;
+        // A missing upvote ratio is treated as uncontroversial
+        let upvote_ratio = self
+            .optional_float(data, "upvote_ratio")?
+            .map(|ratio| ratio as f32)
+            .unwrap_or(1.0);
+        if upvote_ratio < args.min_upvote_ratio {
+            return Err(format!(
+                "Ignoring story {}/{} with upvote ratio {}",
+                subreddit, id, upvote_ratio
+            ));
+        }
         let flair = unescape_entities(&self.optional_string(data, "link_flair_text")?);
-        let story = RedditStory::new_subsource(
+        let mut story = RedditStory::new_subsource(
             id,
             subreddit,
             date,
@@ -203,6 +242,7 @@ impl RedditScraper {
             score,
             upvote_ratio,
         );
+        story.date_estimated = date_estimated;
         Ok(story)
     }
 }
@@ -213,7 +253,7 @@ impl Scraper for RedditScraper {
 
     fn scrape(
         &self,
-        _args: &RedditConfig,
+        args: &RedditConfig,
         input: &str,
     ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
         let root: Value = serde_json::from_str(input)?;
@@ -235,7 +275,7 @@ impl Scraper for RedditScraper {
             let mut errors = vec![];
             let mut positions = HashMap::new();
             for child in children {
-                match self.map_story(child, &mut positions) {
+                match self.map_story(args, child, &mut positions) {
                     Ok(story) => vec.push(story),
                     Err(e) => errors.push(e),
                 }
@@ -275,3 +315,121 @@ impl Scraper for RedditScraper {
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::TypedScrape;
+    use serde_json::json;
+
+    fn child(id: &str, upvote_ratio: Option<f64>) -> Value {
+        let mut data = json!({
+            "id": id,
+            "subreddit": "programming",
+            "created_utc": 1672531200,
+            "url": format!("https://example.com/{}", id),
+            "title": "Title",
+            "num_comments": 1,
+            "score": 1,
+            "downs": 0,
+            "ups": 1,
+        });
+        if let Some(upvote_ratio) = upvote_ratio {
+            data["upvote_ratio"] = json!(upvote_ratio);
+        }
+        json!({ "kind": "t3", "data": data })
+    }
+
+    #[test]
+    fn test_min_upvote_ratio() {
+        let input = json!({ "data": { "children": [
+            child("low", Some(0.3)),
+            child("high", Some(0.9)),
+            child("missing", None),
+        ] } })
+        .to_string();
+
+        // Nothing is filtered by default
+        let (stories, errors) = RedditScraper::default()
+            .scrape(&RedditConfig::default(), &input)
+            .expect("Failed to scrape");
+        assert_eq!(3, stories.len());
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let config = RedditConfig {
+            min_upvote_ratio: 0.5,
+            ..Default::default()
+        };
+        let (stories, errors) = RedditScraper::default()
+            .scrape(&config, &input)
+            .expect("Failed to scrape");
+        let ids = stories
+            .iter()
+            .map(|story| story.shared.id.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["high", "missing"], ids);
+        assert_eq!(1, errors.len());
+    }
+
+    #[test]
+    fn test_date_fallback() {
+        let mut undated = child("undated", None);
+        undated["data"]["created_utc"] = json!("yesterday");
+        let input = json!({ "data": { "children": [child("dated", None), undated] } }).to_string();
+
+        // Dropped by default
+        let (stories, errors) = RedditScraper::default()
+            .scrape(&RedditConfig::default(), &input)
+            .expect("Failed to scrape");
+        assert_eq!(1, stories.len());
+        assert_eq!(1, errors.len());
+
+        let config = RedditConfig {
+            date_fallback: DateFallback::Now,
+            ..Default::default()
+        };
+        let before = StoryDate::now();
+        let (mut stories, errors) = RedditScraper::default()
+            .scrape(&config, &input)
+            .expect("Failed to scrape");
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(!stories[0].date_estimated);
+        assert!(stories[1].date_estimated);
+        assert!(stories[1].date >= before);
+
+        // A real date replaces an estimated one
+        let dated = stories.remove(0);
+        let date = dated.date;
+        stories[0].merge_generic(dated);
+        assert!(!stories[0].date_estimated);
+        assert_eq!(date, stories[0].date);
+    }
+
+    #[test]
+    fn test_id_case() {
+        let mut upper = child("ABC12", None);
+        // The same post, from another endpoint
+        upper["data"]["url"] = json!("https://example.com/abc12");
+        let input = json!({ "data": { "children": [child("abc12", None), upper] } }).to_string();
+
+        let (stories, _) = RedditScraper::default()
+            .scrape(&RedditConfig::default(), &input)
+            .expect("Failed to scrape");
+        let collection =
+            crate::ScrapeCollection::new_from_iter(stories.into_iter().map(TypedScrape::from));
+        assert_eq!(1, collection.scrapes.len());
+        assert_eq!(
+            "abc12",
+            collection.scrapes.keys().next().expect("Scrape").id
+        );
+
+        let config = RedditConfig {
+            keep_id_case: true,
+            ..Default::default()
+        };
+        let (stories, _) = RedditScraper::default()
+            .scrape(&config, &input)
+            .expect("Failed to scrape");
+        assert_eq!("ABC12", stories[1].shared.id.id);
+    }
+}
@@ -10,8 +10,8 @@ use tl::{HTMLTag, Parser, ParserOptions};
 use crate::types::*;
 
 use super::{
-    scrape_story, utils::html::*, GenericScrape, ScrapeConfigSource, ScrapeCore, ScrapeShared,
-    ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
+    scrape_story, utils::html::*, DateFallback, GenericScrape, ScrapeConfigSource, ScrapeCore,
+    ScrapeShared, ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
 };
 
 pub struct Slashdot {}
@@ -30,6 +30,9 @@ impl ScrapeSourceDef for Slashdot {
 pub struct SlashdotConfig {
     homepage: String,
     tag_allowlist: HashSet<String>,
+    /// What to do with stories whose date can't be parsed.
+    #[serde(default)]
+    date_fallback: DateFallback,
 }
 
 impl ScrapeConfigSource for SlashdotConfig {
@@ -45,6 +48,9 @@ impl ScrapeConfigSource for SlashdotConfig {
 scrape_story! {
     SlashdotStory {
         num_comments: u32,
+        position: u32,
+        // Slashdot's popularity rating, where 1 is the most popular (zero if missing)
+        popularity: u32,
         tags: Vec<String>,
     }
 }
@@ -54,6 +60,9 @@ impl ScrapeStory for SlashdotStory {
 
     fn merge(&mut self, other: Self) {
         self.num_comments = std::cmp::max(self.num_comments, other.num_comments);
+        if other.popularity != 0 && (self.popularity == 0 || other.popularity < self.popularity) {
+            self.popularity = other.popularity;
+        }
     }
 }
 
@@ -90,8 +99,10 @@ impl SlashdotScraper {
     }
 
     fn map_story(
+        args: &SlashdotConfig,
         p: &Parser,
         article: &HTMLTag,
+        position: u32,
     ) -> Result<GenericScrape<<Self as Scraper>::Output>, String> {
         let title = find_first(p, article, ".story-title").ok_or("Missing .story-title")?;
         let mut links = html_tag_iterator(p, title.query_selector(p, "a"));
@@ -127,6 +138,16 @@ impl SlashdotScraper {
             0
         };
 
+        // The popularity "beaker" has a class of pop1 (most popular) through pop7
+        let popularity = find_first(p, article, ".icon-beaker")
+            .and_then(|beaker| get_attribute(p, beaker, "class"))
+            .and_then(|class| {
+                class
+                    .split_ascii_whitespace()
+                    .find_map(|class| class.strip_prefix("pop")?.parse().ok())
+            })
+            .unwrap_or_default();
+
         let topics = find_first(p, article, ".topic").ok_or_else(|| "Mising topics".to_string())?;
         let mut tags = vec![];
         for topic in html_tag_iterator(p, topics.query_selector(p, "img")) {
@@ -139,16 +160,22 @@ impl SlashdotScraper {
 
         let date =
             find_first(p, article, "time").ok_or_else(|| "Could not locate time".to_string())?;
-        let date = Self::parse_time(&date.inner_text(p))?;
+        let (date, date_estimated) = args
+            .date_fallback
+            .resolve(Self::parse_time(&date.inner_text(p)))?;
 
-        Ok(SlashdotStory::new(
+        let mut story = SlashdotStory::new(
             id,
             date,
             raw_title,
             url,
             num_comments,
+            position,
+            popularity,
             tags,
-        ))
+        );
+        story.date_estimated = date_estimated;
+        Ok(story)
     }
 }
 
@@ -158,7 +185,7 @@ impl Scraper for SlashdotScraper {
 
     fn scrape(
         &self,
-        _args: &Self::Config,
+        args: &Self::Config,
         input: &str,
     ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
         let dom = tl::parse(input, ParserOptions::default())?;
@@ -167,7 +194,7 @@ impl Scraper for SlashdotScraper {
         let mut v = vec![];
 
         for article in html_tag_iterator(p, dom.query_selector("article.article")) {
-            match Self::map_story(p, article) {
+            match Self::map_story(args, p, article, v.len() as u32 + 1) {
                 Ok(s) => v.push(s),
                 Err(e) => errors.push(e),
             }
@@ -193,7 +220,7 @@ impl Scraper for SlashdotScraper {
             date: input.shared.date,
             title: &input.shared.raw_title,
             url: &input.shared.url,
-            rank: None,
+            rank: (input.data.position as usize).checked_sub(1),
             tags,
         }
     }
@@ -213,4 +240,22 @@ pub mod test {
     fn test_date_parse(#[case] s: &str) {
         SlashdotScraper::parse_time(s).expect("Expected this to parse");
     }
+
+    #[rstest]
+    fn test_parse_comments_and_popularity(
+        #[values("slashdot1.html", "slashdot2.html", "slashdot3.html")] file: &str,
+    ) {
+        let config = SlashdotConfig::default();
+        let (stories, _) = SlashdotScraper::default()
+            .scrape(&config, &crate::backends::test::load_file(file))
+            .expect("Failed to scrape");
+        assert!(!stories.is_empty());
+        assert!(stories.iter().any(|story| story.data.num_comments > 0));
+        assert!(stories.iter().all(|story| story.data.popularity > 0));
+        for (i, story) in stories.iter().enumerate() {
+            assert_eq!(i as u32 + 1, story.data.position);
+            let core = SlashdotScraper::default().extract_core(&config, story);
+            assert_eq!(Some(i), core.rank);
+        }
+    }
 }
@@ -90,6 +90,18 @@ pub fn unescape_entities(input: &str) -> String {
     s
 }
 
+/// Tidy up a scraped title: control characters are stripped, and runs of whitespace (including tabs, newlines and
+/// non-breaking spaces) are collapsed to a single space and trimmed from the ends. Other characters, like zero-width
+/// joiners in emoji, are kept.
+pub fn clean_title(input: &str) -> String {
+    input
+        .split(char::is_whitespace)
+        .map(|word| word.chars().filter(|c| !c.is_control()).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -112,4 +124,16 @@ mod test {
     fn test_bad_escape(#[case] a: &str) {
         assert_eq!(unescape_entities(a), a.to_owned());
     }
+
+    #[rstest]
+    #[case("Show HN: Rust", "Show HN: Rust")]
+    #[case("  Show HN:  Rust  ", "Show HN: Rust")]
+    #[case("\u{00a0}Show\u{00a0}HN:\u{00a0}\u{00a0}Rust\u{00a0}", "Show HN: Rust")]
+    #[case("Show\tHN:\r\n\tRust", "Show HN: Rust")]
+    #[case("Show\u{0}\u{7} HN:\u{1b} Rust\u{7f}", "Show HN: Rust")]
+    #[case("\u{7} \u{0}", "")]
+    #[case("Ünïcödé — 👩\u{200d}💻 “Rust”", "Ünïcödé — 👩\u{200d}💻 “Rust”")]
+    fn test_clean_title(#[case] a: &str, #[case] b: &str) {
+        assert_eq!(clean_title(a), b);
+    }
 }
@@ -6,10 +6,42 @@ use std::{
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
-use crate::{backends::ScrapeCore, ScrapeExtractor, ScrapeId, StoryDate, StoryUrl, TypedScrape};
+use crate::{
+    backends::ScrapeCore, ScrapeExtractor, ScrapeId, ScrapeSource, StoryDate, StoryUrl, TypedScrape,
+};
+
+/// How we choose a single title for a story that has scrapes with differing titles. Ties are always broken by the
+/// earliest scrape, then by scrape ID, so the choice is deterministic.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleStrategy {
+    /// Prefer titles from sources in the given order. Sources not in the list are least preferred.
+    PreferSource(Vec<ScrapeSource>),
+    /// Prefer the longest title.
+    Longest,
+    /// Prefer the shortest title.
+    Shortest,
+    /// Prefer the title from the earliest scrape.
+    #[default]
+    Earliest,
+}
+
+/// How we choose a single URL for a story that has scrapes with differing URLs (ie: one with tracking params, or a
+/// shortened link). Ties are broken the same way as for titles.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlStrategy {
+    /// Prefer URLs from sources in the given order. Sources not in the list are least preferred.
+    PreferSource(Vec<ScrapeSource>),
+    /// Prefer the shortest URL, which is usually the canonical one.
+    Shortest,
+    /// Prefer the URL from the earliest scrape, so that later scrapes never replace it.
+    #[default]
+    Earliest,
+}
 
 /// Collection of scrapes, which can also extract the best title, etc.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ScrapeCollection {
     pub earliest: StoryDate,
 
@@ -47,6 +79,52 @@ impl ScrapeCollection {
         }
     }
 
+    /// Drop scrapes until at most `max` remain, returning the number dropped. The newest scrape from each source is
+    /// always kept, even if that's more than `max`, and the remaining space goes to the newest of the rest. Each
+    /// dropped scrape is merged into the kept scrape from its source so that its engagement still counts. The
+    /// `earliest` date is unchanged, as it identifies the story.
+    pub fn limit(&mut self, max: usize) -> usize {
+        if self.scrapes.len() <= max {
+            return 0;
+        }
+        let newest_first = self
+            .scrapes
+            .values()
+            .sorted_by(|a, b| b.date.cmp(&a.date).then_with(|| a.id.cmp(&b.id)))
+            .map(|scrape| scrape.id.clone())
+            .collect_vec();
+        let mut keep = vec![];
+        let mut newest_by_source = HashMap::new();
+        for id in &newest_first {
+            if let Entry::Vacant(entry) = newest_by_source.entry(id.source) {
+                entry.insert(id.clone());
+                keep.push(id.clone());
+            }
+        }
+        for id in &newest_first {
+            if keep.len() >= max {
+                break;
+            }
+            if !keep.contains(id) {
+                keep.push(id.clone());
+            }
+        }
+
+        let mut dropped = 0;
+        for id in newest_first {
+            if keep.contains(&id) {
+                continue;
+            }
+            if let Some(scrape) = self.scrapes.remove(&id) {
+                if let Some(target) = self.scrapes.get_mut(&newest_by_source[&id.source]) {
+                    target.merge(scrape);
+                }
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+
     pub fn url(&self) -> &StoryUrl {
         &self
             .scrapes
@@ -64,6 +142,8 @@ impl ScrapeCollection {
         ExtractedScrapeCollection {
             earliest: self.earliest,
             scrapes: HashMap::from_iter(iter),
+            title_strategy: extractor.title_strategy().clone(),
+            url_strategy: extractor.url_strategy().clone(),
         }
     }
 }
@@ -72,28 +152,52 @@ impl ScrapeCollection {
 pub struct ExtractedScrapeCollection<'a> {
     pub earliest: StoryDate,
     pub scrapes: HashMap<&'a ScrapeId, (ScrapeCore<'a>, &'a TypedScrape)>,
+    title_strategy: TitleStrategy,
+    url_strategy: UrlStrategy,
 }
 
 impl<'a> ExtractedScrapeCollection<'a> {
     pub fn title(&'a self) -> &'a str {
-        // TODO: Best title
-        self.scrapes
-            .iter()
-            .next()
-            .expect("Expected at least one scrape")
-            .1
-             .0
-            .title
+        // Empty titles are only chosen if there is nothing else
+        let (titled, untitled): (Vec<_>, Vec<_>) = self
+            .scrapes
+            .values()
+            .map(|(core, _)| core)
+            .sorted_by_key(|core| (core.date, core.source))
+            .partition(|core| !core.title.trim().is_empty());
+        let mut candidates = if titled.is_empty() { untitled } else { titled }.into_iter();
+        let len = |core: &&ScrapeCore| core.title.chars().count();
+        let best = match &self.title_strategy {
+            TitleStrategy::Earliest => candidates.next(),
+            TitleStrategy::Longest => candidates.min_by_key(|core| std::cmp::Reverse(len(core))),
+            TitleStrategy::Shortest => candidates.min_by_key(len),
+            TitleStrategy::PreferSource(sources) => candidates.min_by_key(|core| {
+                sources
+                    .iter()
+                    .position(|source| *source == core.source.source)
+                    .unwrap_or(sources.len())
+            }),
+        };
+        best.expect("Expected at least one scrape").title
     }
 
     pub fn url(&'a self) -> &'a StoryUrl {
-        self.scrapes
-            .iter()
-            .next()
-            .expect("Expected at least one scrape")
-            .1
-             .0
-            .url
+        let mut candidates = self
+            .scrapes
+            .values()
+            .map(|(core, _)| core)
+            .sorted_by_key(|core| (core.date, core.source));
+        let best = match &self.url_strategy {
+            UrlStrategy::Earliest => candidates.next(),
+            UrlStrategy::Shortest => candidates.min_by_key(|core| core.url.raw().len()),
+            UrlStrategy::PreferSource(sources) => candidates.min_by_key(|core| {
+                sources
+                    .iter()
+                    .position(|source| *source == core.source.source)
+                    .unwrap_or(sources.len())
+            }),
+        };
+        best.expect("Expected at least one scrape").url
     }
 
     pub fn tags<'b>(&'b self) -> Vec<Cow<'a, str>> {
@@ -103,33 +207,168 @@ impl<'a> ExtractedScrapeCollection<'a> {
         }
         tags.into_iter().cloned().collect_vec()
     }
-    // /// Choose a title based on source priority, with preference for shorter titles if the priority is the same.
-    // fn title_choice(&self) -> (ScrapeSource, Cow<str>) {
-    //     let title_score = |source: &ScrapeSource| {
-    //         match source {
-    //             // HN is moderated and titles are high quality
-    //             ScrapeSource::HackerNews => 0,
-    //             ScrapeSource::Lobsters => 1,
-    //             ScrapeSource::Slashdot => 2,
-    //             // User-submitted titles are generally just OK
-    //             ScrapeSource::Reddit => 3,
-    //             ScrapeSource::Other => 99,
-    //         }
-    //     };
-    //     let mut best_title = (99, &ScrapeSource::Other, Cow::Borrowed("Unknown title"));
-    //     for (id, scrape) in &self.scrapes {
-    //         let scrape = extractor.extract(scrape);
-    //         let score = title_score(&id.source);
-    //         if score < best_title.0 {
-    //             best_title = (score, &id.source, scrape.title);
-    //             continue;
-    //         }
-    //         let title = &scrape.title;
-    //         if score == best_title.0 && title.len() < best_title.2.len() {
-    //             best_title = (score, &id.source, scrape.title);
-    //             continue;
-    //         }
-    //     }
-    //     (*best_title.1, best_title.2)
-    // }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{hacker_news::HackerNewsStory, reddit::RedditStory, ScrapeConfig};
+    use rstest::*;
+
+    fn collection() -> ScrapeCollection {
+        let url = StoryUrl::parse("https://example.com/story").expect("Failed to parse URL");
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Failed to create date");
+        let later = StoryDate::year_month_day(2023, 1, 2).expect("Failed to create date");
+        let hn = HackerNewsStory::new_with_defaults(
+            "1",
+            date,
+            "Example story: now with a longer title",
+            url.clone(),
+        );
+        let reddit = RedditStory::new_subsource_with_defaults(
+            "2",
+            "programming",
+            later,
+            "Example story",
+            url,
+        );
+        ScrapeCollection::new_from_iter([hn.into(), reddit.into()].into_iter())
+    }
+
+    #[rstest]
+    #[case(TitleStrategy::Earliest, "Example story: now with a longer title")]
+    #[case(TitleStrategy::Longest, "Example story: now with a longer title")]
+    #[case(TitleStrategy::Shortest, "Example story")]
+    #[case(TitleStrategy::PreferSource(vec![ScrapeSource::Reddit]), "Example story")]
+    #[case(TitleStrategy::PreferSource(vec![ScrapeSource::HackerNews, ScrapeSource::Reddit]), "Example story: now with a longer title")]
+    #[case(TitleStrategy::PreferSource(vec![ScrapeSource::Lobsters]), "Example story: now with a longer title")]
+    fn test_title_strategy(#[case] title_strategy: TitleStrategy, #[case] expected: &str) {
+        let config = ScrapeConfig {
+            title_strategy,
+            ..Default::default()
+        };
+        let extractor = ScrapeExtractor::new(&config);
+        let collection = collection();
+        // Repeat to ensure that the choice doesn't depend on hash iteration order
+        for _ in 0..10 {
+            assert_eq!(collection.extract(&extractor).title(), expected);
+        }
+    }
+
+    #[rstest]
+    #[case(UrlStrategy::Earliest, "https://example.com/story")]
+    #[case(UrlStrategy::Shortest, "https://bit.ly/abc")]
+    #[case(UrlStrategy::PreferSource(vec![ScrapeSource::HackerNews]), "https://example.com/story")]
+    #[case(UrlStrategy::PreferSource(vec![ScrapeSource::Reddit]), "https://bit.ly/abc")]
+    fn test_url_strategy(#[case] url_strategy: UrlStrategy, #[case] expected: &str) {
+        let url = |url| StoryUrl::parse(url).expect("Failed to parse URL");
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Failed to create date");
+        let later = StoryDate::year_month_day(2023, 1, 2).expect("Failed to create date");
+        let config = ScrapeConfig {
+            url_strategy,
+            ..Default::default()
+        };
+        let extractor = ScrapeExtractor::new(&config);
+
+        // A later scrape of a shortened link
+        let mut collection = ScrapeCollection::new_from_one(
+            HackerNewsStory::new_with_defaults(
+                "1",
+                date,
+                "Example story",
+                url("https://example.com/story"),
+            )
+            .into(),
+        );
+        collection.merge(
+            RedditStory::new_subsource_with_defaults(
+                "2",
+                "programming",
+                later,
+                "Example story",
+                url("https://bit.ly/abc"),
+            )
+            .into(),
+        );
+        // Repeat to ensure that the choice doesn't depend on hash iteration order
+        for _ in 0..10 {
+            assert_eq!(collection.extract(&extractor).url().raw(), expected);
+        }
+    }
+
+    #[test]
+    fn test_merge_empty_title() {
+        let url = StoryUrl::parse("https://example.com/story").expect("Failed to parse URL");
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Failed to create date");
+        let story =
+            |title| HackerNewsStory::new_with_defaults("1", date, title, url.clone()).into();
+
+        // A re-scrape with an empty title doesn't replace the existing one
+        let mut collection = ScrapeCollection::new_from_one(story("Example story"));
+        collection.merge(story(" "));
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        assert_eq!("Example story", collection.extract(&extractor).title());
+
+        // ...but an empty title is filled in by a later scrape
+        let mut collection = ScrapeCollection::new_from_one(story(""));
+        collection.merge(story("Example story"));
+        assert_eq!("Example story", collection.extract(&extractor).title());
+
+        // An empty title from another source is never chosen over a real one
+        let later = StoryDate::year_month_day(2023, 1, 2).expect("Failed to create date");
+        let mut collection = ScrapeCollection::new_from_one(
+            RedditStory::new_subsource_with_defaults("2", "programming", date, " ", url.clone())
+                .into(),
+        );
+        collection.merge(
+            HackerNewsStory::new_with_defaults("1", later, "Example story", url.clone()).into(),
+        );
+        assert_eq!("Example story", collection.extract(&extractor).title());
+    }
+
+    #[test]
+    fn test_limit() {
+        let url = StoryUrl::parse("https://example.com/story").expect("Failed to parse URL");
+        let date = |day| StoryDate::year_month_day(2023, 1, day).expect("Failed to create date");
+        let reddit = |day: u32, score| {
+            let mut story = RedditStory::new_subsource_with_defaults(
+                day.to_string(),
+                format!("sub{}", day),
+                date(day),
+                "Example story".into(),
+                url.clone(),
+            );
+            story.data.score = score;
+            story.into()
+        };
+        let hn = HackerNewsStory::new_with_defaults("1", date(1), "Example story", url.clone());
+        let mut collection = ScrapeCollection::new_from_iter(
+            [hn.into()]
+                .into_iter()
+                .chain((2..=6).map(|day| reddit(day, if day == 2 { 1000 } else { 10 }))),
+        );
+
+        assert_eq!(0, collection.limit(6));
+        assert_eq!(3, collection.limit(3));
+        let mut ids = collection.scrapes.keys().cloned().collect_vec();
+        ids.sort();
+        assert_eq!(
+            vec![
+                ScrapeSource::HackerNews.id("1"),
+                ScrapeSource::Reddit.subsource_id("sub5", "5"),
+                ScrapeSource::Reddit.subsource_id("sub6", "6"),
+            ],
+            ids
+        );
+        // The oldest scrape is kept as the story's date, and its engagement is merged into the newest Reddit scrape
+        assert_eq!(date(1), collection.earliest);
+        let newest = collection.scrapes[&ScrapeSource::Reddit.subsource_id("sub6", "6")]
+            .reddit()
+            .expect("Reddit");
+        assert_eq!(1000, newest.data.score);
+
+        // One scrape from each source is kept, even beyond the limit
+        assert_eq!(1, collection.limit(1));
+        assert_eq!(2, collection.scrapes.len());
+    }
 }
@@ -1 +1,2 @@
+pub mod similarity;
 pub mod urlnormalizer;
@@ -0,0 +1,75 @@
+use std::collections::HashSet;
+
+use itertools::Itertools;
+
+/// Computes a similarity score between two sequences of URL normalization tokens (the host, followed by the path and
+/// query tokens). URLs on different hosts are never similar. Otherwise, we score the length of the common prefix of
+/// the remaining tokens, giving partial credit when one diverging token is a prefix of the other (ie: an article slug
+/// with and without a trailing ID).
+pub fn path_prefix_similarity<'a>(
+    a: impl IntoIterator<Item = &'a str>,
+    b: impl IntoIterator<Item = &'a str>,
+) -> f32 {
+    let a = a.into_iter().collect_vec();
+    let b = b.into_iter().collect_vec();
+    match (a.split_first(), b.split_first()) {
+        (Some((host_a, a)), Some((host_b, b))) if host_a == host_b => {
+            let len = a.len().max(b.len());
+            if len == 0 {
+                return 1.0;
+            }
+            let common = a.iter().zip(b.iter()).take_while(|(a, b)| a == b).count();
+            let partial = match (a.get(common), b.get(common)) {
+                (Some(a), Some(b)) if a.starts_with(b) || b.starts_with(a) => 0.5,
+                _ => 0.0,
+            };
+            (common as f32 + partial) / len as f32
+        }
+        _ => 0.0,
+    }
+}
+
+/// Computes the Jaccard similarity of the lowercased words in two titles.
+pub fn title_similarity(a: &str, b: &str) -> f32 {
+    fn words(s: &str) -> HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    }
+    let a = words(a);
+    let b = words(b);
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("x.com:a:b:c:", "x.com:a:b:c:", 1.0)]
+    #[case("x.com:", "x.com:", 1.0)]
+    #[case("x.com:a:b:", "x.com:a:c:", 0.5)]
+    #[case("x.com:a:story:", "x.com:a:story-1234:", 0.75)]
+    #[case("x.com:a:b:", "y.com:a:b:", 0.0)]
+    fn test_path_prefix_similarity(#[case] a: &str, #[case] b: &str, #[case] expected: f32) {
+        fn tokens(s: &str) -> impl Iterator<Item = &str> {
+            s.split(':').filter(|s| !s.is_empty())
+        }
+        assert_eq!(path_prefix_similarity(tokens(a), tokens(b)), expected);
+    }
+
+    #[rstest]
+    #[case("Hello, World", "hello world", 1.0)]
+    #[case("Hello world", "Goodbye world", 1.0 / 3.0)]
+    #[case("Hello", "Goodbye", 0.0)]
+    #[case("", "", 0.0)]
+    fn test_title_similarity(#[case] a: &str, #[case] b: &str, #[case] expected: f32) {
+        assert_eq!(title_similarity(a, b), expected);
+    }
+}
@@ -74,6 +74,15 @@ lazy_static! {
 
 /// Generates a stream of token bits that can be used to compare whether URLs are "normalized-equal", that is: whether two URLs normalize to the same stream of tokens.
 pub fn token_stream(url: &Url) -> impl Iterator<Item = CompareToken> {
+    token_stream_keeping(url, None)
+}
+
+/// Generates the normalization token stream, but if `keep` is provided, only those query params are kept (even if
+/// they would otherwise be ignored) and all others are stripped.
+fn token_stream_keeping<'a>(
+    url: &'a Url,
+    keep: Option<&[String]>,
+) -> impl Iterator<Item = CompareToken<'a>> {
     let mut out = vec![];
     let host = url.host_str().unwrap_or_default();
     if let Some(stripped) = WWW_PREFIX.find_at(host, 0) {
@@ -119,7 +128,11 @@ pub fn token_stream(url: &Url) -> impl Iterator<Item = CompareToken> {
         }
         query_pairs.sort();
         for (key, value) in query_pairs {
-            if !QUERY_PARAM_REGEX.is_match(key) {
+            let kept = match keep {
+                Some(keep) => keep.iter().any(|param| param == key),
+                None => !QUERY_PARAM_REGEX.is_match(key),
+            };
+            if kept {
                 out.push(CompareToken(key));
                 out.push(CompareToken(value));
             }
@@ -144,8 +157,17 @@ pub fn urls_are_same(a: &Url, b: &Url) -> bool {
 }
 
 pub fn url_normalization_string(url: &Url) -> String {
+    normalization_string(url, token_stream(url))
+}
+
+/// The normalization string for a URL, keeping only the given query params if provided.
+pub fn url_normalization_string_keeping(url: &Url, keep: Option<&[String]>) -> String {
+    normalization_string(url, token_stream_keeping(url, keep))
+}
+
+fn normalization_string<'a>(url: &Url, tokens: impl Iterator<Item = CompareToken<'a>>) -> String {
     let mut s = String::with_capacity(url.as_str().len());
-    for bit in token_stream(url) {
+    for bit in tokens {
         s += bit.0;
         s.push(':');
     }
@@ -287,6 +309,32 @@ mod test {
         assert!(!urls_are_same(&a, &b), "{} != {}", a, b);
     }
 
+    #[rstest]
+    // Only the kept params are significant
+    #[case(
+        "http://x.com/post?id=1&utm_source=foo",
+        "http://x.com/post?id=1",
+        true
+    )]
+    #[case(
+        "http://x.com/post?id=1&ref=home",
+        "http://x.com/post?id=1&ref=feed",
+        true
+    )]
+    #[case("http://x.com/post?id=1", "http://x.com/post?id=2", false)]
+    // Kept params override the ignored ones
+    #[case("http://x.com/post?gclid=1", "http://x.com/post?gclid=2", false)]
+    fn test_url_normalization_keeping(#[case] a: &str, #[case] b: &str, #[case] same: bool) {
+        let keep = ["id".to_owned(), "gclid".to_owned()];
+        let a = Url::parse(a).unwrap();
+        let b = Url::parse(b).unwrap();
+        assert_eq!(
+            same,
+            url_normalization_string_keeping(&a, Some(&keep))
+                == url_normalization_string_keeping(&b, Some(&keep))
+        );
+    }
+
     // TODO: Known failures
     // http://apenwarr.ca/log/?m=201407#01 http://apenwarr.ca/log/?m=201407#14
     // https://www.google.com/trends/explore#q=golang https://www.google.com/trends/explore#q=rustlang
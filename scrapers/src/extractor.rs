@@ -1,17 +1,185 @@
-use crate::backends::{ScrapeConfig, ScrapeCore, TypedScrape};
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backends::{ScrapeConfig, ScrapeCore, TypedScrape},
+    ScrapeSource, StoryDate, StoryDuration, StoryUrl, TitleStrategy, UrlStrategy,
+};
+
+/// A suffix to strip from a source's titles: either a literal string, or `{"regex": "..."}` for a pattern that is
+/// matched at the end of the title.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TitleSuffix {
+    Literal(String),
+    Regex { regex: String },
+}
+
+impl TitleSuffix {
+    fn compile(&self) -> Result<Regex, regex::Error> {
+        match self {
+            TitleSuffix::Literal(suffix) => Regex::new(&format!("{}\\z", regex::escape(suffix))),
+            TitleSuffix::Regex { regex } => Regex::new(&format!("(?:{})\\z", regex)),
+        }
+    }
+}
 
 pub struct ScrapeExtractor {
     config: ScrapeConfig,
+    title_suffixes: HashMap<ScrapeSource, Vec<Regex>>,
 }
 
 impl ScrapeExtractor {
     pub fn new(config: &ScrapeConfig) -> Self {
+        let mut title_suffixes = HashMap::new();
+        for (source, suffixes) in &config.title_suffixes {
+            let suffixes = suffixes
+                .iter()
+                .filter_map(|suffix| match suffix.compile() {
+                    Ok(regex) => Some(regex),
+                    Err(e) => {
+                        tracing::error!("Invalid title suffix {:?}: {:?}", suffix, e);
+                        None
+                    }
+                })
+                .collect();
+            title_suffixes.insert(*source, suffixes);
+        }
         Self {
             config: config.clone(),
+            title_suffixes,
         }
     }
 
     pub fn extract<'a>(&self, scrape: &'a TypedScrape) -> ScrapeCore<'a> {
-        scrape.extract(&self.config)
+        let mut core = scrape.extract(&self.config);
+        core.title = self.strip_title_suffixes(core.source.source, core.title);
+        core
+    }
+
+    /// Strip the configured suffixes for this source from a title. A title that would be left empty is returned as-is.
+    fn strip_title_suffixes<'a>(&self, source: ScrapeSource, title: &'a str) -> &'a str {
+        let Some(suffixes) = self.title_suffixes.get(&source) else {
+            return title;
+        };
+        let mut stripped = title;
+        for suffix in suffixes {
+            if let Some(m) = suffix.find(stripped) {
+                stripped = stripped[..m.start()].trim_end();
+            }
+        }
+        if stripped.is_empty() {
+            title
+        } else {
+            stripped
+        }
+    }
+
+    pub fn title_strategy(&self) -> &TitleStrategy {
+        &self.config.title_strategy
+    }
+
+    pub fn url_strategy(&self) -> &UrlStrategy {
+        &self.config.url_strategy
+    }
+
+    /// Is this URL's normalized host on the configured list of blocked domains?
+    pub fn is_blocked(&self, url: &StoryUrl) -> bool {
+        let host = url.host();
+        self.config.blocked_domains.iter().any(|domain| {
+            if let Some(domain) = domain.strip_prefix("*.") {
+                host == domain
+                    || host
+                        .strip_suffix(domain)
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+            } else {
+                host == domain
+            }
+        })
+    }
+
+    /// The most scrapes kept for a single story, if limited.
+    pub fn max_scrapes_per_story(&self) -> Option<usize> {
+        self.config.max_scrapes_per_story
+    }
+
+    /// Is a story from this date too old to be newly indexed, according to the configured maximum story age?
+    pub fn is_stale(&self, date: StoryDate, now: StoryDate) -> bool {
+        self.config
+            .max_story_age_days
+            .is_some_and(|days| now - date > StoryDuration::days(days as i64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("http://example.com/story", true)]
+    #[case("http://www.example.com/story", true)]
+    #[case("http://news.example.com/story", false)]
+    #[case("http://paywall.com/story", true)]
+    #[case("http://a.b.paywall.com/story", true)]
+    #[case("http://notpaywall.com/story", false)]
+    #[case("http://example.org/story", false)]
+    fn test_is_blocked(#[case] url: &str, #[case] blocked: bool) {
+        let config = ScrapeConfig {
+            blocked_domains: vec!["example.com".into(), "*.paywall.com".into()],
+            ..Default::default()
+        };
+        let url = StoryUrl::parse(url).expect("Failed to parse URL");
+        assert_eq!(blocked, ScrapeExtractor::new(&config).is_blocked(&url));
+    }
+
+    #[rstest]
+    #[case::slashdot(
+        ScrapeSource::Slashdot,
+        "Rust 2.0 Released - Slashdot",
+        "Rust 2.0 Released"
+    )]
+    #[case::slashdot_regex(
+        ScrapeSource::Slashdot,
+        "Rust 2.0 Released (Updated) - Slashdot",
+        "Rust 2.0 Released"
+    )]
+    #[case::slashdot_unchanged(ScrapeSource::Slashdot, "Rust 2.0 Released", "Rust 2.0 Released")]
+    #[case::slashdot_only_suffix(ScrapeSource::Slashdot, "- Slashdot", "- Slashdot")]
+    #[case::hacker_news(
+        ScrapeSource::HackerNews,
+        "Rust 2.0 Released - Slashdot",
+        "Rust 2.0 Released - Slashdot"
+    )]
+    fn test_title_suffixes(
+        #[case] source: ScrapeSource,
+        #[case] title: &str,
+        #[case] expected: &str,
+    ) {
+        let suffixes = serde_json::from_value(serde_json::json!([
+            " - Slashdot",
+            {"regex": "(?i)\\s*\\((updated|video)\\)"}
+        ]))
+        .expect("Failed to parse suffixes");
+        let config = ScrapeConfig {
+            title_suffixes: HashMap::from([(ScrapeSource::Slashdot, suffixes)]),
+            ..Default::default()
+        };
+        let url = StoryUrl::parse("http://example.com/").expect("URL");
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let scrape: TypedScrape = match source {
+            ScrapeSource::Slashdot => {
+                crate::slashdot::SlashdotStory::new_with_defaults("1", date, title, url).into()
+            }
+            _ => {
+                crate::hacker_news::HackerNewsStory::new_with_defaults("1", date, title, url).into()
+            }
+        };
+
+        let core = ScrapeExtractor::new(&config).extract(&scrape);
+        assert_eq!(expected, core.title);
+        assert_eq!(title, scrape.raw_title);
     }
 }
@@ -1,3 +1,5 @@
+mod archive;
+mod article;
 mod backends;
 mod collections;
 mod datasci;
@@ -5,10 +7,14 @@ mod extractor;
 mod scrapers;
 mod types;
 
+pub use archive::*;
+pub use article::article_word_count;
 pub use backends::export::*;
 pub use backends::legacy::{import_legacy, LegacyError};
-pub use backends::{ScrapeConfig, ScrapeCore, ScrapeSource, TypedScrape, TypedScrapeMap};
-pub use collections::{ExtractedScrapeCollection, ScrapeCollection};
+pub use backends::{
+    DateFallback, ScrapeConfig, ScrapeCore, ScrapeSource, TypedScrape, TypedScrapeMap,
+};
+pub use collections::{ExtractedScrapeCollection, ScrapeCollection, TitleStrategy, UrlStrategy};
 pub use extractor::*;
 pub use scrapers::*;
 pub use types::*;
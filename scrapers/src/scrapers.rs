@@ -1,10 +1,13 @@
 ///! Public interface for the collection of scrapers.
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use serde::Serialize;
 
 use crate::{backends::scrape, ScrapeConfig, ScrapeSource, TypedScrape};
 
+/// The maximum number of concurrent fetches for a source that doesn't configure its own limit.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 2;
+
 /// Accumulates the URLs required to scrape for all the services.
 #[derive(Serialize)]
 pub struct ScraperPossibilities {
@@ -15,12 +18,16 @@ pub struct ScraperPossibilities {
 pub enum ScraperHttpResponseInput {
     HTTPError(u16, String),
     Ok(String),
+    /// The resource is unchanged since the previous fetch (ie: HTTP 304).
+    NotModified,
 }
 
 #[derive(Serialize)]
 pub enum ScraperHttpResult {
     Err(ScraperHttpResponseInput, String),
     Ok(String, Vec<TypedScrape>),
+    /// The resource is unchanged since the previous fetch, so there was nothing to scrape.
+    NotModified,
 }
 
 pub struct Scrapers {
@@ -56,17 +63,53 @@ impl Scrapers {
         vec![]
     }
 
-    /// Given a source and subsources, compute the set of URLs to fetch.
+    /// The maximum number of URLs to fetch concurrently for the given source.
+    pub fn max_concurrency(&self, source: ScrapeSource) -> usize {
+        self.config
+            .max_concurrency
+            .get(&source)
+            .copied()
+            .unwrap_or(DEFAULT_MAX_CONCURRENCY)
+    }
+
+    /// Is the given source enabled for scraping?
+    pub fn is_enabled(&self, source: ScrapeSource) -> bool {
+        self.config.enabled.get(&source).copied().unwrap_or(true)
+    }
+
+    /// Given a source and subsources, compute the set of URLs to fetch. Disabled sources have nothing to fetch.
+    /// Unless configured otherwise, repeated subsources and URLs are dropped, keeping the first of each.
     pub fn compute_scrape_url_demands(
         &self,
         source: ScrapeSource,
         subsources: Vec<String>,
     ) -> Vec<String> {
-        if let Some(scrape) = self.config.get(source) {
-            scrape.provide_urls(subsources)
-        } else {
-            vec![]
+        if !self.is_enabled(source) {
+            return vec![];
+        }
+        let Some(scrape) = self.config.get(source) else {
+            return vec![];
+        };
+        if self.config.keep_duplicate_demands {
+            return scrape.provide_urls(subsources);
         }
+
+        let requested = subsources.len();
+        let subsources = dedupe(subsources);
+        let mut urls = scrape.provide_urls(subsources.clone());
+        let provided = urls.len();
+        urls = dedupe(urls);
+        if subsources.len() < requested || urls.len() < provided {
+            tracing::info!(
+                "Collapsed duplicate scrape demands for {:?}: {} subsource(s) to {}, {} URL(s) to {}",
+                source,
+                requested,
+                subsources.len(),
+                provided,
+                urls.len()
+            );
+        }
+        urls
     }
 
     /// Given the result of fetching a URL, returns the scraped stories.
@@ -85,6 +128,103 @@ impl Scrapers {
             error @ ScraperHttpResponseInput::HTTPError(..) => {
                 ScraperHttpResult::Err(error, "HTTP Error".to_string())
             }
+            ScraperHttpResponseInput::NotModified => ScraperHttpResult::NotModified,
         }
     }
 }
+
+/// Drop repeated items, keeping the first of each in its original position.
+fn dedupe(items: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    items
+        .into_iter()
+        .filter(|item| seen.insert(item.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_disabled_source() {
+        let mut config = ScrapeConfig::default();
+        config.enabled.insert(ScrapeSource::HackerNews, true);
+        config.enabled.insert(ScrapeSource::Lobsters, false);
+        let scrapers = Scrapers::new(&config);
+
+        // Sources are enabled unless configured otherwise
+        assert!(scrapers.is_enabled(ScrapeSource::HackerNews));
+        assert!(scrapers.is_enabled(ScrapeSource::Slashdot));
+        assert!(!scrapers.is_enabled(ScrapeSource::Lobsters));
+
+        let subsources = scrapers.compute_scrape_subsources(ScrapeSource::Lobsters);
+        assert!(scrapers
+            .compute_scrape_url_demands(ScrapeSource::Lobsters, subsources.clone())
+            .is_empty());
+
+        // The same source contributes demands when it's re-enabled
+        config.enabled.remove(&ScrapeSource::Lobsters);
+        let scrapers = Scrapers::new(&config);
+        assert!(!scrapers
+            .compute_scrape_url_demands(ScrapeSource::Lobsters, subsources)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_demands() -> Result<(), serde_json::Error> {
+        let mut config = ScrapeConfig {
+            hacker_news: serde_json::from_value(serde_json::json!({
+                "homepage": "https://news.ycombinator.com/",
+                "pages": ["", "news?p=2", "", "news?p=3", "news?p=2"]
+            }))?,
+            reddit: serde_json::from_value(serde_json::json!({
+                "api": "https://www.reddit.com/r/${subreddits}.json",
+                "subreddit_batch": 2,
+                "limit": 10,
+                "subreddits": {}
+            }))?,
+            ..Default::default()
+        };
+        let subsources = ["rust", "programming", "rust", "golang", "programming"]
+            .map(str::to_owned)
+            .to_vec();
+        let scrapers = Scrapers::new(&config);
+
+        // Overlapping pages are only fetched once, in their original order
+        assert_eq!(
+            vec![
+                "https://news.ycombinator.com/",
+                "https://news.ycombinator.com/news?p=2",
+                "https://news.ycombinator.com/news?p=3",
+            ],
+            scrapers.compute_scrape_url_demands(ScrapeSource::HackerNews, vec![])
+        );
+
+        // Subsources are collapsed before batching, so no subreddit is requested twice
+        assert_eq!(
+            vec![
+                "https://www.reddit.com/r/rust+programming.json?limit=10",
+                "https://www.reddit.com/r/golang.json?limit=10",
+            ],
+            scrapers.compute_scrape_url_demands(ScrapeSource::Reddit, subsources.clone())
+        );
+
+        // Duplicates are kept if configured
+        config.keep_duplicate_demands = true;
+        let scrapers = Scrapers::new(&config);
+        assert_eq!(
+            5,
+            scrapers
+                .compute_scrape_url_demands(ScrapeSource::HackerNews, vec![])
+                .len()
+        );
+        assert_eq!(
+            3,
+            scrapers
+                .compute_scrape_url_demands(ScrapeSource::Reddit, subsources)
+                .len()
+        );
+        Ok(())
+    }
+}
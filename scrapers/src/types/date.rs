@@ -51,6 +51,12 @@ impl StoryDate {
             .ok()
             .map(|x| Self::new(x.into()))
     }
+    pub fn to_rfc2822(&self) -> String {
+        self.internal_date.to_rfc2822()
+    }
+    pub fn to_rfc3339(&self) -> String {
+        self.internal_date.to_rfc3339()
+    }
     pub fn year(&self) -> i32 {
         self.internal_date.year()
     }
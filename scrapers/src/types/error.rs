@@ -1,3 +1,4 @@
+use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,3 +14,14 @@ pub enum ScrapeError {
     #[error("Structure error")]
     StructureError(String),
 }
+
+/// A scraped story that is missing a field that every story should have, usually because the source's format drifted.
+#[derive(Error, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum ScrapeViolation {
+    #[error("Missing ID")]
+    MissingId,
+    #[error("Invalid URL '{0}'")]
+    InvalidUrl(String),
+    #[error("Missing date")]
+    MissingDate,
+}
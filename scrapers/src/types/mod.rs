@@ -5,7 +5,7 @@ mod url;
 
 pub use self::{
     date::{StoryDate, StoryDuration},
-    error::ScrapeError,
+    error::{ScrapeError, ScrapeViolation},
     id::ScrapeId,
     url::{StoryUrl, StoryUrlNorm},
 };
@@ -1,5 +1,5 @@
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, HashMap},
     fmt::Display,
     hash::{Hash, Hasher},
 };
@@ -7,7 +7,12 @@ use std::{
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use crate::datasci::urlnormalizer::{url_normalization_string, url_normalized_host};
+use crate::datasci::{
+    similarity::{path_prefix_similarity, title_similarity},
+    urlnormalizer::{
+        url_normalization_string, url_normalization_string_keeping, url_normalized_host,
+    },
+};
 
 /// Story-specific URL that caches the normalization information and other important parts of the URL.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -67,6 +72,38 @@ impl StoryUrl {
         None
     }
 
+    /// Rewrite this URL's host if its normalized host is a key in `rewrites` (ie: an alternate frontend or mirror of
+    /// another site), re-computing the normalization so that the rewritten URL dedups against the canonical host.
+    pub fn rewrite_host(self, rewrites: &HashMap<String, String>) -> Self {
+        if let Some(host) = rewrites.get(&self.host) {
+            if let Ok(mut url) = Url::parse(&self.url) {
+                if url.set_host(Some(host)).is_ok() {
+                    if let Some(url) = Self::parse(url) {
+                        return url;
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Re-compute the normalization of this URL if its normalized host is a key in `allowlist`, keeping only the
+    /// listed query params. This is for sites that identify an article by a query param (ie: `?id=123`), where other
+    /// params (ie: session or referrer) would otherwise split the same article into multiple stories.
+    pub fn keep_query_params(self, allowlist: &HashMap<String, Vec<String>>) -> Self {
+        if let Some(params) = allowlist.get(&self.host) {
+            if let Ok(url) = Url::parse(&self.url) {
+                return Self {
+                    norm_str: StoryUrlNorm {
+                        norm: url_normalization_string_keeping(&url, Some(params)),
+                    },
+                    ..self
+                };
+            }
+        }
+        self
+    }
+
     pub fn host(&self) -> &str {
         &self.host
     }
@@ -75,9 +112,30 @@ impl StoryUrl {
         &self.url
     }
 
+    /// The lowercase file extension of the last path segment, if it has one (ie: `pdf` for `/papers/paper.pdf`).
+    pub fn extension(&self) -> Option<String> {
+        let url = Url::parse(&self.url).ok()?;
+        let (_, extension) = url.path_segments()?.next_back()?.rsplit_once('.')?;
+        (!extension.is_empty()).then(|| extension.to_lowercase())
+    }
+
     pub fn normalization(&self) -> &StoryUrlNorm {
         &self.norm_str
     }
+
+    /// Fuzzy check for whether two URLs (and their titles) are likely to be the same story, beyond what normalization
+    /// can safely merge. Returns a confidence between 0.0 and 1.0, combining the common path prefix of the URLs and
+    /// the similarity of the titles. This is only a suggestion and is never used to merge stories automatically.
+    pub fn is_same_story(&self, title: &str, other: &StoryUrl, other_title: &str) -> f32 {
+        if self.norm_str == other.norm_str {
+            return 1.0;
+        }
+        if self.host != other.host {
+            return 0.0;
+        }
+        let path = path_prefix_similarity(self.norm_str.tokens(), other.norm_str.tokens());
+        (path + title_similarity(title, other_title)) / 2.0
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -120,4 +178,133 @@ impl StoryUrlNorm {
     pub fn string(&self) -> &str {
         &self.norm
     }
+
+    /// The individual tokens that make up this normalization string.
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.norm.split(':').filter(|s| !s.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(
+        "https://example.com/2023/01/great-article",
+        "Great article about Rust",
+        "https://www.example.com/2023/01/great-article-12345",
+        "Great article about Rust"
+    )]
+    #[case(
+        "https://example.com/blog/post/why-we-rewrote-it",
+        "Why we rewrote it in Rust",
+        "https://example.com/blog/post/why-we-rewrote-it/comments",
+        "Why we rewrote it in Rust (2023)"
+    )]
+    fn test_is_same_story_near_duplicate(
+        #[case] a: &str,
+        #[case] title_a: &str,
+        #[case] b: &str,
+        #[case] title_b: &str,
+    ) {
+        let a = StoryUrl::parse(a).expect("Failed to parse URL");
+        let b = StoryUrl::parse(b).expect("Failed to parse URL");
+        let confidence = a.is_same_story(title_a, &b, title_b);
+        assert!(confidence > 0.75, "Confidence was {}", confidence);
+        assert_eq!(confidence, b.is_same_story(title_b, &a, title_a));
+    }
+
+    #[rstest]
+    #[case(
+        "https://example.com/2023/01/great-article",
+        "Great article about Rust",
+        "https://example.org/2023/01/great-article",
+        "Great article about Rust"
+    )]
+    #[case(
+        "https://example.com/2023/01/great-article",
+        "Great article about Rust",
+        "https://example.com/2022/12/something-else",
+        "Something else entirely"
+    )]
+    #[case(
+        "https://github.com/rust-lang/rust",
+        "The Rust programming language",
+        "https://github.com/golang/go",
+        "The Go programming language"
+    )]
+    fn test_is_same_story_different(
+        #[case] a: &str,
+        #[case] title_a: &str,
+        #[case] b: &str,
+        #[case] title_b: &str,
+    ) {
+        let a = StoryUrl::parse(a).expect("Failed to parse URL");
+        let b = StoryUrl::parse(b).expect("Failed to parse URL");
+        let confidence = a.is_same_story(title_a, &b, title_b);
+        assert!(confidence < 0.5, "Confidence was {}", confidence);
+    }
+
+    #[test]
+    fn test_is_same_story_identical() {
+        let a = StoryUrl::parse("https://example.com/a?utm_source=x").expect("Failed to parse URL");
+        let b = StoryUrl::parse("https://www.example.com/a").expect("Failed to parse URL");
+        assert_eq!(a.is_same_story("A", &b, "B"), 1.0);
+    }
+
+    #[rstest]
+    #[case(
+        "https://old.reddit.com/r/rust/comments/abc/story/",
+        "https://www.reddit.com/r/rust/comments/abc/story/"
+    )]
+    #[case(
+        "https://nitter.net/rustlang/status/1",
+        "https://twitter.com/rustlang/status/1"
+    )]
+    #[case("https://example.com/a", "https://example.com/a")]
+    fn test_rewrite_host(#[case] url: &str, #[case] expected: &str) {
+        let rewrites = HashMap::from_iter([
+            ("old.reddit.com".to_owned(), "www.reddit.com".to_owned()),
+            ("nitter.net".to_owned(), "twitter.com".to_owned()),
+        ]);
+        let url = StoryUrl::parse(url)
+            .expect("Failed to parse URL")
+            .rewrite_host(&rewrites);
+        let expected = StoryUrl::parse(expected).expect("Failed to parse URL");
+        assert_eq!(url.raw(), expected.raw());
+        assert_eq!(url.host(), expected.host());
+        assert_eq!(url.normalization(), expected.normalization());
+    }
+
+    #[test]
+    fn test_keep_query_params() {
+        let allowlist =
+            HashMap::from_iter([("news.example.com".to_owned(), vec!["id".to_owned()])]);
+        let parse = |url| {
+            StoryUrl::parse(url)
+                .expect("Failed to parse URL")
+                .keep_query_params(&allowlist)
+        };
+
+        // The id is preserved while utm and other params are stripped
+        let a = parse("https://news.example.com/item?id=123&utm_source=hn&ref=rss");
+        let b = parse("https://www.news.example.com/item?id=123");
+        assert_eq!(a.normalization(), b.normalization());
+        assert_eq!(
+            "https://news.example.com/item?id=123&utm_source=hn&ref=rss",
+            a.raw()
+        );
+        assert_ne!(
+            a.normalization(),
+            parse("https://news.example.com/item?id=456").normalization()
+        );
+
+        // Other hosts keep their non-tracking params
+        assert_ne!(
+            parse("https://example.com/item?id=123&ref=rss").normalization(),
+            parse("https://example.com/item?id=123").normalization()
+        );
+    }
 }
@@ -0,0 +1,138 @@
+//! Decoding of fetched responses that aren't UTF-8. Some older feeds are served as ISO-8859-1 or Windows-1252, which
+//! would be mangled if we assumed UTF-8.
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+
+use crate::web::WebError;
+
+/// HTML and XML declare their charset near the start of the document, so that's all we search.
+const SNIFF_LENGTH: usize = 1024;
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct CharsetConfig {
+    /// The label of the charset used for responses that don't declare one, such as `utf-8` or `windows-1252`.
+    pub fallback: String,
+}
+
+impl Default for CharsetConfig {
+    fn default() -> Self {
+        Self {
+            fallback: "utf-8".into(),
+        }
+    }
+}
+
+impl CharsetConfig {
+    /// The encoding for the configured fallback, failing if the label isn't a known charset.
+    pub fn fallback_encoding(&self) -> Result<&'static Encoding, WebError> {
+        Encoding::for_label(self.fallback.trim().as_bytes()).ok_or_else(|| {
+            WebError::InvalidConfig(format!("Unknown fallback charset '{}'", self.fallback))
+        })
+    }
+}
+
+/// Read the `charset` parameter of a `Content-Type` header.
+fn content_type_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("charset") {
+            Encoding::for_label(value.trim().trim_matches('"').as_bytes())
+        } else {
+            None
+        }
+    })
+}
+
+/// Look for a charset declared by the document itself, either in an HTML `<meta charset>` (or the older
+/// `http-equiv` form), or in an XML declaration.
+fn sniff_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(&body[..body.len().min(SNIFF_LENGTH)]).to_ascii_lowercase();
+    let declared = |tag: &str, attr: &str| {
+        head.match_indices(tag).find_map(|(start, _)| {
+            let tag = &head[start..];
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            let value = tag[tag.find(attr)? + attr.len()..].trim_start_matches(['"', '\'', ' ']);
+            let end = value
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+                .unwrap_or(value.len());
+            Encoding::for_label(&value.as_bytes()[..end])
+        })
+    };
+    declared("<meta", "charset=").or_else(|| declared("<?xml", "encoding="))
+}
+
+/// Decode a response body to a string. The charset is taken from the `Content-Type` header, then from the document,
+/// and finally from the fallback. A byte order mark overrides all of these. Anything that can't be decoded is
+/// replaced, with a warning.
+pub fn decode(content_type: Option<&str>, body: &[u8], fallback: &'static Encoding) -> String {
+    let encoding = content_type
+        .and_then(content_type_charset)
+        .or_else(|| sniff_charset(body))
+        .unwrap_or(fallback);
+    let (text, used, had_errors) = encoding.decode(body);
+    if had_errors {
+        tracing::warn!(
+            "Response contained invalid {} and was decoded lossily",
+            used.name()
+        );
+    }
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    /// "Café “quoted” — 5€" in Windows-1252.
+    const WINDOWS_1252: &[u8] = b"Caf\xe9 \x93quoted\x94 \x97 5\x80";
+    const DECODED: &str = "Café “quoted” — 5€";
+
+    #[rstest]
+    #[case(Some("text/plain; charset=windows-1252"), WINDOWS_1252.to_vec())]
+    #[case(Some("text/plain; Charset=\"ISO-8859-1\""), WINDOWS_1252.to_vec())]
+    #[case(Some("text/html"), [b"<html><head><meta charset=\"windows-1252\"></head>".as_slice(), WINDOWS_1252].concat())]
+    #[case(None, [b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=cp1252\">".as_slice(), WINDOWS_1252].concat())]
+    #[case(None, [b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>".as_slice(), WINDOWS_1252].concat())]
+    fn test_decode_windows_1252(#[case] content_type: Option<&str>, #[case] body: Vec<u8>) {
+        let utf8 = encoding_rs::UTF_8;
+        assert!(decode(content_type, &body, utf8).ends_with(DECODED));
+    }
+
+    #[test]
+    fn test_decode_fallback() -> Result<(), WebError> {
+        // Undeclared bodies use the fallback, and invalid UTF-8 is replaced
+        let config = CharsetConfig::default();
+        assert_eq!(
+            "Caf\u{FFFD} \u{FFFD}quoted\u{FFFD} \u{FFFD} 5\u{FFFD}",
+            decode(None, WINDOWS_1252, config.fallback_encoding()?)
+        );
+        let config = CharsetConfig {
+            fallback: "windows-1252".into(),
+        };
+        assert_eq!(
+            DECODED,
+            decode(
+                Some("text/plain"),
+                WINDOWS_1252,
+                config.fallback_encoding()?
+            )
+        );
+        // A declared charset overrides the fallback
+        assert_eq!(
+            "Café",
+            decode(
+                Some("text/plain; charset=utf-8"),
+                "Café".as_bytes(),
+                config.fallback_encoding()?
+            )
+        );
+
+        let config = CharsetConfig {
+            fallback: "klingon".into(),
+        };
+        assert!(config.fallback_encoding().is_err());
+        Ok(())
+    }
+}
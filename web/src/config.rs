@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 /// Root configuration for the application.
@@ -7,4 +9,88 @@ pub struct Config {
     pub tagger: progscrape_application::TaggerConfig,
     pub scrape: progscrape_scrapers::ScrapeConfig,
     pub cron: crate::cron::CronConfig,
+    /// Limits for the store of scrapes that failed to parse.
+    #[serde(default)]
+    pub dead_letter: crate::dead_letter::DeadLetterConfig,
+    /// Storing the raw response behind each scrape so that it can be replayed.
+    #[serde(default)]
+    pub raw_responses: crate::raw_responses::RawResponseConfig,
+    /// The name of a theme under `resource/themes/` that overrides the default templates and static files.
+    #[serde(default)]
+    pub theme: Option<String>,
+    /// Limits on the candidates for the hot set.
+    #[serde(default)]
+    pub hot_set: crate::index::HotSetConfig,
+    /// Batching limits for the background commit of scrapes.
+    #[serde(default)]
+    pub ingest: crate::index::IngestConfig,
+    /// Limits on concurrent queries, shared by pages, the API and admin.
+    #[serde(default)]
+    pub query_limit: crate::index::QueryLimitConfig,
+    /// The default filter for the front page.
+    #[serde(default)]
+    pub front_page: crate::web::FrontPageConfig,
+    /// The chronological `/new` page and feed.
+    #[serde(default)]
+    pub new_feed: crate::web::NewFeedConfig,
+    /// The index of every tag at `/tags`.
+    #[serde(default)]
+    pub tags: crate::web::TagsConfig,
+    /// The public URL of the site, used by the `absolute_url` template filter for feeds and sitemaps.
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    /// Thresholds for the `compact_number` template filter.
+    #[serde(default)]
+    pub compact_number: crate::filters::CompactNumberConfig,
+    /// Fetching linked articles to estimate their read time.
+    #[serde(default)]
+    pub read_time: crate::read_time::ReadTimeConfig,
+    /// Resolving link shorteners to the URLs they redirect to while scraping.
+    #[serde(default)]
+    pub redirects: crate::redirects::RedirectConfig,
+    /// How scraped responses that don't declare a charset are decoded.
+    #[serde(default)]
+    pub charset: crate::charset::CharsetConfig,
+    /// An outbound proxy for scrape traffic.
+    #[serde(default)]
+    pub proxy: crate::web::ProxyConfig,
+    /// Where story domain icons are loaded from.
+    #[serde(default)]
+    pub icons: crate::icons::IconConfig,
+    /// Limits on user-provided searches.
+    #[serde(default)]
+    pub search: crate::web::SearchConfig,
+    /// The header used to tag requests with an ID for tracing.
+    #[serde(default)]
+    pub request_id: crate::request_id::RequestIdConfig,
+    /// Requests that take longer than this are cut off with a 503. Scrapes, backups and re-ingestion are exempt, as
+    /// they are expected to run for a long time. If unset, requests aren't timed out.
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>,
+    /// How story IDs, and so their admin pages, are kept when stories are re-ingested. Links to IDs that have changed
+    /// are redirected.
+    #[serde(default)]
+    pub id_scheme: progscrape_application::StoryIdScheme,
+    /// Whether inserted scrapes are committed as soon as the ingest queue has collected them, or held in the queue and
+    /// committed in larger batches. Batching is faster, but queued scrapes are lost if the server dies, and inserts
+    /// wait until their batch is committed. Batching replaces the limits in `ingest`.
+    #[serde(default)]
+    pub durability: progscrape_application::Durability,
+    /// The rules served from `/robots.txt`.
+    #[serde(default)]
+    pub robots: crate::web::RobotsConfig,
+    /// Queries run at startup before the server reports that it's ready.
+    #[serde(default)]
+    pub warmup: crate::index::WarmupConfig,
+    /// Friendlier labels for the domains that stories are displayed with. Hosts are matched as displayed, which is
+    /// without any `www.` prefix. Only the label changes: URLs, deduplication and domain searches are unaffected.
+    #[serde(default)]
+    pub domain_aliases: HashMap<String, String>,
+    /// The protocols the server speaks. Only read at startup.
+    #[serde(default)]
+    pub server: crate::web::ServerConfig,
+}
+
+fn default_base_url() -> String {
+    "https://www.progscrape.com/".into()
 }
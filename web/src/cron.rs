@@ -1,9 +1,11 @@
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     ops::RangeInclusive,
     time::{Duration, Instant, SystemTime},
 };
 
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone, Timelike, Utc};
+use itertools::Itertools;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
@@ -39,10 +41,161 @@ impl CronInterval {
     }
 }
 
+/// The furthest ahead that we'll look for the next run of a schedule.
+const MAX_SCHEDULE_DAYS: i64 = 366 * 5;
+
+/// A standard five-field cron expression (`minute hour day-of-month month day-of-week`), evaluated in UTC. Each field
+/// may be `*`, a value, a range (`1-5`), a step (`*/15`, `0-30/10`) or a comma-separated list of these. Days of the
+/// week run from 0 (Sunday) to 7 (also Sunday).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    /// Was the day of the month restricted? If both days of the month and the week are, either may match.
+    days_restricted: bool,
+    /// Was the day of the week restricted?
+    weekdays_restricted: bool,
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>, String> {
+    let mut values = vec![];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("Invalid step '{}'", step))?,
+            ),
+            None => (part, 1),
+        };
+        let parse = |s: &str| {
+            s.parse::<u32>()
+                .ok()
+                .filter(|n| (min..=max).contains(n))
+                .ok_or_else(|| format!("Invalid value '{}' (expected {}-{})", s, min, max))
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            // A step from a single value runs to the end of the range
+            None if part.contains('/') => (parse(range)?, max),
+            None => (parse(range)?, parse(range)?),
+        };
+        if start > end {
+            return Err(format!("Invalid range '{}'", range));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort();
+    values.dedup();
+    Ok(values)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "Invalid cron expression '{}': expected five fields",
+                expression
+            ));
+        };
+        let error = |e| format!("Invalid cron expression '{}': {}", expression, e);
+        let days_restricted = days != "*";
+        let weekdays_restricted = weekdays != "*";
+        let mut weekdays = parse_cron_field(weekdays, 0, 7).map_err(error)?;
+        // Sunday is both 0 and 7
+        if weekdays.last() == Some(&7) {
+            weekdays.pop();
+            if weekdays.first() != Some(&0) {
+                weekdays.insert(0, 0);
+            }
+        }
+        let schedule = Self {
+            expression: expression.to_owned(),
+            minutes: parse_cron_field(minutes, 0, 59).map_err(error)?,
+            hours: parse_cron_field(hours, 0, 23).map_err(error)?,
+            days: parse_cron_field(days, 1, 31).map_err(error)?,
+            months: parse_cron_field(months, 1, 12).map_err(error)?,
+            weekdays,
+            days_restricted,
+            weekdays_restricted,
+        };
+        // Catch schedules that can never fire, like the 31st of February
+        if schedule.next_after(Utc::now()).is_none() {
+            return Err(error("the schedule never runs".into()));
+        }
+        Ok(schedule)
+    }
+
+    fn matches_day(&self, date: DateTime<Utc>) -> bool {
+        if !self.months.contains(&date.month()) {
+            return false;
+        }
+        let day = self.days.contains(&date.day());
+        let weekday = self
+            .weekdays
+            .contains(&date.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first time strictly after `after` that matches this schedule.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // Start from the next whole minute
+        let after = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let midnight = Utc.from_utc_datetime(&after.date_naive().and_hms_opt(0, 0, 0)?);
+        for day in 0..MAX_SCHEDULE_DAYS {
+            let date = midnight + ChronoDuration::days(day);
+            if !self.matches_day(date) {
+                continue;
+            }
+            for hour in &self.hours {
+                for minute in &self.minutes {
+                    let time = date
+                        + ChronoDuration::hours(*hour as i64)
+                        + ChronoDuration::minutes(*minute as i64);
+                    if time >= after {
+                        return Some(time);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(value: CronSchedule) -> Self {
+        value.expression
+    }
+}
+
+/// A job runs either at a fixed interval (with jitter), or on a cron `schedule`.
 #[derive(Serialize, Deserialize)]
 pub struct CronJob {
     url: String,
-    interval: (usize, CronInterval),
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interval: Option<(usize, CronInterval)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schedule: Option<CronSchedule>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -51,6 +204,39 @@ pub struct CronConfig {
     pub jitter: (i8, i8),
     pub history_age: (usize, CronInterval),
     pub history_count: usize,
+    /// The delay before the first retry of a failed task, doubling with each subsequent failure.
+    #[serde(default = "default_retry_backoff")]
+    pub retry_backoff: (usize, CronInterval),
+    /// The most tasks that run at once. Any others that are due wait for a running task to finish.
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+}
+
+fn default_max_concurrent() -> usize {
+    1
+}
+
+fn default_retry_backoff() -> (usize, CronInterval) {
+    (30, CronInterval::Second)
+}
+
+impl CronConfig {
+    /// Ensure that every job has exactly one of an interval or a schedule. Cron expressions themselves are validated
+    /// when the config is loaded.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, job) in self.jobs.iter().sorted_by_key(|(name, _)| *name) {
+            match (&job.interval, &job.schedule) {
+                (Some(_), None) | (None, Some(_)) => {}
+                _ => {
+                    return Err(format!(
+                        "Cron job '{}' must have exactly one of an interval or a schedule",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for CronConfig {
@@ -60,6 +246,8 @@ impl Default for CronConfig {
             jitter: (0, 0),
             history_age: (1, CronInterval::Minute),
             history_count: 10,
+            retry_backoff: default_retry_backoff(),
+            max_concurrent: default_max_concurrent(),
         }
     }
 }
@@ -113,14 +301,23 @@ impl CronHistory {
 pub struct Cron {
     queue: Vec<CronTask>,
     jitter_fraction: Option<RangeInclusive<f32>>,
+    /// The URLs of tasks returned from `tick` that haven't been reported yet.
+    running: BTreeSet<String>,
+    max_concurrent: Option<usize>,
 }
 
 #[derive(Clone)]
 pub struct CronTask {
     name: String,
     url: String,
+    /// The cron expression for scheduled tasks.
+    schedule: Option<String>,
     last: Option<Instant>,
     next: Instant,
+    /// The number of consecutive failures of this task.
+    failures: usize,
+    /// Is the next run of this task a retry of a failure?
+    retrying: bool,
 }
 
 fn approximate_instant_as_unix_time(when: Instant) -> u64 {
@@ -147,17 +344,23 @@ impl Serialize for CronTask {
         struct Temp<'a> {
             name: &'a str,
             url: &'a str,
+            schedule: Option<&'a str>,
             next: u64,
             last: u64,
+            failures: usize,
+            retrying: bool,
         }
         Temp {
             name: &self.name,
             url: &self.url,
+            schedule: self.schedule.as_deref(),
             next: approximate_instant_as_unix_time(self.next),
             last: self
                 .last
                 .map(approximate_instant_as_unix_time)
                 .unwrap_or_default(),
+            failures: self.failures,
+            retrying: self.retrying,
         }
         .serialize(serializer)
     }
@@ -178,6 +381,8 @@ impl Cron {
         Self {
             queue: vec![],
             jitter_fraction: Some(start..=end),
+            running: Default::default(),
+            max_concurrent: None,
         }
     }
 
@@ -186,6 +391,8 @@ impl Cron {
         Self {
             queue: vec![],
             jitter_fraction: None,
+            running: Default::default(),
+            max_concurrent: None,
         }
     }
 
@@ -201,6 +408,30 @@ impl Cron {
         }
     }
 
+    /// When a job should next run: either after its interval (with jitter), or at the next time on its schedule.
+    fn next_run(&self, job: &CronJob, now: Instant) -> Instant {
+        if let Some(schedule) = &job.schedule {
+            let wall = DateTime::<Utc>::from(SystemTime::UNIX_EPOCH)
+                + ChronoDuration::seconds(approximate_instant_as_unix_time(now) as i64);
+            if let Some(next) = schedule.next_after(wall) {
+                return now + (next - wall).to_std().unwrap_or_default();
+            }
+            tracing::error!("Cron schedule '{}' has no next run", schedule.expression);
+            return now + CronInterval::Week.as_duration(1);
+        }
+        now + self.jitter(job.interval.unwrap_or((1, CronInterval::Hour)))
+    }
+
+    /// Limit the number of tasks that can run at once. If unset, every due task is returned from `tick`.
+    pub fn set_max_concurrent(&mut self, max_concurrent: Option<usize>) {
+        self.max_concurrent = max_concurrent;
+    }
+
+    /// The URLs of the tasks that are currently running.
+    pub fn running(&self) -> Vec<String> {
+        self.running.iter().cloned().collect()
+    }
+
     /// Trigger a task to run at the next call to `tick`.
     pub fn trigger(&mut self, job_name: String) -> bool {
         for job in self.queue.iter_mut() {
@@ -212,14 +443,24 @@ impl Cron {
         false
     }
 
+    /// Return the URLs of the tasks that are due, each of which must be passed to `report` once it has run. Tasks
+    /// that are still running aren't returned again, and if the concurrency limit is reached, due tasks stay queued
+    /// for a later tick.
     pub fn tick(&mut self, jobs: &HashMap<String, CronJob>, now: Instant) -> Vec<String> {
-        // Drain the queue of any ready items
-        let mut ready = HashSet::new();
+        // Drain the queue of any ready items, remembering their failure counts
+        let mut ready = HashMap::new();
         let mut ret = vec![];
         let mut remaining = HashMap::<_, _>::from_iter(jobs.iter());
+        let mut slots = self
+            .max_concurrent
+            .map(|max| max.saturating_sub(self.running.len()));
+        let running = &self.running;
         self.queue.retain(|task| {
-            if task.next <= now {
-                ready.insert(task.name.clone());
+            if task.next <= now && slots != Some(0) && !running.contains(&task.url) {
+                if let Some(slots) = &mut slots {
+                    *slots -= 1;
+                }
+                ready.insert(task.name.clone(), task.failures);
                 ret.push(task.url.clone());
                 false
             } else {
@@ -230,7 +471,7 @@ impl Cron {
 
         // If we find a job in the config and it isn't already in the queue, add it in
         for (name, job) in remaining {
-            let last = if ready.contains(name) {
+            let last = if ready.contains_key(name) {
                 Some(now)
             } else {
                 None
@@ -238,14 +479,50 @@ impl Cron {
             self.queue.push(CronTask {
                 name: name.clone(),
                 url: job.url.clone(),
-                next: now + self.jitter(job.interval),
+                schedule: job.schedule.clone().map(String::from),
+                next: self.next_run(job, now),
                 last,
+                failures: ready.get(name).copied().unwrap_or_default(),
+                retrying: false,
             });
         }
 
+        self.running.extend(ret.iter().cloned());
         ret
     }
 
+    /// Report the outcome of a task returned from `tick`. A failed task is retried after an exponential backoff (with
+    /// jitter) from `backoff`, but never later than its next scheduled run. Success resets the failure count.
+    pub fn report(
+        &mut self,
+        backoff: (usize, CronInterval),
+        url: &str,
+        success: bool,
+        now: Instant,
+    ) {
+        self.running.remove(url);
+        let Some(index) = self.queue.iter().position(|task| task.url == url) else {
+            return;
+        };
+        if success {
+            let task = &mut self.queue[index];
+            task.failures = 0;
+            task.retrying = false;
+            return;
+        }
+
+        let failures = self.queue[index].failures + 1;
+        // Cap the exponent so that the backoff can't overflow (it's capped by the next scheduled run anyway)
+        let multiplier = 1 << (failures - 1).min(16);
+        let retry = now + self.jitter((backoff.0.saturating_mul(multiplier), backoff.1));
+        let task = &mut self.queue[index];
+        task.failures = failures;
+        if retry < task.next {
+            task.next = retry;
+            task.retrying = true;
+        }
+    }
+
     pub fn inspect(&self) -> Vec<CronTask> {
         self.queue.to_vec()
     }
@@ -262,7 +539,8 @@ mod test {
             "job".into(),
             CronJob {
                 url: "/1".into(),
-                interval: (1, CronInterval::Minute),
+                interval: Some((1, CronInterval::Minute)),
+                schedule: None,
             },
         );
         let mut cron = Cron::new();
@@ -287,6 +565,222 @@ mod test {
         assert_eq!(cron.tick(&jobs, now).len(), 0);
     }
 
+    #[test]
+    fn test_cron_retry() {
+        let mut jobs = HashMap::new();
+        jobs.insert(
+            "job".into(),
+            CronJob {
+                url: "/1".into(),
+                interval: Some((1, CronInterval::Hour)),
+                schedule: None,
+            },
+        );
+        let backoff = (1, CronInterval::Minute);
+        let mut cron = Cron::new();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(cron.tick(&jobs, start).len(), 0);
+
+        // First run fails, so we retry after one minute
+        let run = at(3600);
+        assert_eq!(cron.tick(&jobs, run), vec!["/1".to_owned()]);
+        cron.report(backoff, "/1", false, run);
+        let task = &cron.inspect()[0];
+        assert_eq!((1, true), (task.failures, task.retrying));
+        assert_eq!(task.next, run + Duration::from_secs(60));
+        assert_eq!(cron.tick(&jobs, at(3600 + 59)).len(), 0);
+
+        // Second run fails, so we retry after two minutes
+        let run = at(3600 + 60);
+        assert_eq!(cron.tick(&jobs, run).len(), 1);
+        cron.report(backoff, "/1", false, run);
+        let task = &cron.inspect()[0];
+        assert_eq!((2, true), (task.failures, task.retrying));
+        assert_eq!(task.next, run + Duration::from_secs(120));
+
+        // Third run succeeds, so we're back on the regular schedule
+        let run = at(3600 + 60 + 120);
+        assert_eq!(cron.tick(&jobs, run).len(), 1);
+        cron.report(backoff, "/1", true, run);
+        let task = &cron.inspect()[0];
+        assert_eq!((0, false), (task.failures, task.retrying));
+        assert_eq!(task.next, run + Duration::from_secs(3600));
+
+        // Backoff is capped at the next scheduled run
+        for _ in 0..10 {
+            cron.report(backoff, "/1", false, run);
+        }
+        let task = &cron.inspect()[0];
+        assert_eq!(10, task.failures);
+        assert!(task.next <= run + Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_cron_max_concurrent() {
+        let jobs = HashMap::from_iter((0..5).map(|i| {
+            (
+                format!("job{}", i),
+                CronJob {
+                    url: format!("/{}", i),
+                    interval: Some((1, CronInterval::Minute)),
+                    schedule: None,
+                },
+            )
+        }));
+        let backoff = (1, CronInterval::Minute);
+        let mut cron = Cron::new();
+        cron.set_max_concurrent(Some(2));
+        let start = Instant::now();
+        assert_eq!(cron.tick(&jobs, start).len(), 0);
+
+        // Five tasks are due, but only two can run at once
+        let now = start + Duration::from_secs(61);
+        let mut first = cron.tick(&jobs, now);
+        first.sort();
+        assert_eq!(2, first.len());
+        assert_eq!(first, cron.running());
+        assert_eq!(cron.tick(&jobs, now).len(), 0);
+
+        // Each finished task frees a slot for one that's waiting, until none are left
+        let mut ran = first.clone();
+        cron.report(backoff, &first[0], true, now);
+        let next = cron.tick(&jobs, now);
+        assert_eq!(1, next.len());
+        ran.extend(next);
+        let mut running = cron.running();
+        while !running.is_empty() {
+            assert!(running.len() <= 2);
+            for url in &running {
+                cron.report(backoff, url, true, now);
+            }
+            running = cron.tick(&jobs, now);
+            ran.extend(running.clone());
+        }
+        ran.sort();
+        assert_eq!(vec!["/0", "/1", "/2", "/3", "/4"], ran);
+
+        // Nothing runs twice, and everything is scheduled again
+        assert!(cron.inspect().iter().all(|task| task.next > now));
+    }
+
+    #[test]
+    fn test_schedule() {
+        let at = |s: &str| {
+            DateTime::parse_from_rfc3339(s)
+                .expect("Date")
+                .with_timezone(&Utc)
+        };
+        let runs = |expression: &str, from: &str, count: usize| {
+            let schedule = CronSchedule::parse(expression).expect("Schedule");
+            let mut time = at(from);
+            (0..count)
+                .map(|_| {
+                    time = schedule.next_after(time).expect("Next run");
+                    time.to_rfc3339()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Every 15 minutes, never firing twice at the same time
+        assert_eq!(
+            vec![
+                "2023-01-02T10:15:00+00:00",
+                "2023-01-02T10:30:00+00:00",
+                "2023-01-02T10:45:00+00:00",
+                "2023-01-02T11:00:00+00:00"
+            ],
+            runs("*/15 * * * *", "2023-01-02T10:07:30Z", 4)
+        );
+        assert_eq!(
+            vec!["2023-01-02T10:30:00+00:00"],
+            runs("*/15 * * * *", "2023-01-02T10:15:00Z", 1)
+        );
+        // Hourly at a fixed minute
+        assert_eq!(
+            vec!["2023-01-02T11:05:00+00:00", "2023-01-02T12:05:00+00:00"],
+            runs("5 * * * *", "2023-01-02T10:05:00Z", 2)
+        );
+        // Weekdays at 9am, skipping the weekend (2023-01-06 is a Friday)
+        assert_eq!(
+            vec!["2023-01-09T09:00:00+00:00", "2023-01-10T09:00:00+00:00"],
+            runs("0 9 * * 1-5", "2023-01-06T10:00:00Z", 2)
+        );
+        // Sunday as 7, and either the day of the month or week when both are restricted
+        assert_eq!(
+            vec!["2023-01-08T00:00:00+00:00", "2023-01-13T00:00:00+00:00"],
+            runs("0 0 13 * 7", "2023-01-06T10:00:00Z", 2)
+        );
+        // Lists and year boundaries
+        assert_eq!(
+            vec!["2024-01-01T00:00:00+00:00", "2024-07-01T00:00:00+00:00"],
+            runs("0 0 1 1,7 *", "2023-07-01T00:00:00Z", 2)
+        );
+    }
+
+    #[test]
+    fn test_schedule_invalid() {
+        for expression in [
+            "* * * *",
+            "61 * * * *",
+            "* 24 * * *",
+            "a * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "0 0 31 2 *",
+        ] {
+            assert!(
+                CronSchedule::parse(expression).is_err(),
+                "{} should be invalid",
+                expression
+            );
+        }
+
+        // Invalid expressions fail to load with the config
+        let job = serde_json::from_str::<CronJob>(r#"{"url": "/1", "schedule": "* * *"}"#);
+        assert!(job.is_err());
+        let job = serde_json::from_str::<CronJob>(r#"{"url": "/1", "schedule": "*/5 * * * *"}"#)
+            .expect("Job");
+        assert_eq!(
+            r#"{"url":"/1","schedule":"*/5 * * * *"}"#,
+            serde_json::to_string(&job).expect("JSON")
+        );
+
+        // A job needs exactly one of an interval or schedule
+        let mut config = CronConfig::default();
+        config.jobs.insert(
+            "job".into(),
+            CronJob {
+                url: "/1".into(),
+                interval: None,
+                schedule: None,
+            },
+        );
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let mut jobs = HashMap::new();
+        jobs.insert(
+            "job".into(),
+            CronJob {
+                url: "/1".into(),
+                interval: None,
+                schedule: Some(CronSchedule::parse("* * * * *").expect("Schedule")),
+            },
+        );
+        let mut cron = Cron::new_with_jitter(-20..=20);
+        let now = Instant::now();
+        assert_eq!(cron.tick(&jobs, now).len(), 0);
+
+        // The next run is at the top of the next minute, unaffected by jitter
+        let next = cron.inspect()[0].next;
+        assert!(next > now && next <= now + Duration::from_secs(61));
+        assert_eq!(cron.tick(&jobs, next - Duration::from_secs(1)).len(), 0);
+        assert_eq!(cron.tick(&jobs, next), vec!["/1".to_owned()]);
+    }
+
     #[test]
     fn test_history() {
         let mut history = CronHistory::default();
@@ -0,0 +1,166 @@
+//! A bounded store of scrapes that failed to parse, kept so that we can diagnose changes to a source's format.
+use std::collections::{HashMap, VecDeque};
+
+use progscrape_scrapers::{
+    ScrapeSource, ScraperHttpResponseInput, ScraperHttpResult, StoryDate, StoryDuration,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::cron::CronInterval;
+
+#[derive(Serialize, Deserialize)]
+pub struct DeadLetterConfig {
+    /// The maximum number of failures to keep.
+    pub max_count: usize,
+    /// Failures older than this are discarded.
+    pub max_age: (usize, CronInterval),
+    /// The raw input of each failure is truncated to this many bytes.
+    pub max_input_length: usize,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            max_count: 100,
+            max_age: (7, CronInterval::Day),
+            max_input_length: 16 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct DeadLetter {
+    pub date: StoryDate,
+    pub source: ScrapeSource,
+    pub url: String,
+    pub error: String,
+    /// The raw input, truncated to `DeadLetterConfig::max_input_length`.
+    pub input: String,
+    /// The length of the raw input before truncation.
+    pub input_length: usize,
+}
+
+#[derive(Default)]
+pub struct DeadLetterStore {
+    letters: VecDeque<DeadLetter>,
+}
+
+impl DeadLetterStore {
+    /// Record any scrapes that were fetched successfully but failed to parse. HTTP errors are not recorded.
+    pub fn insert_failures(
+        &mut self,
+        config: &DeadLetterConfig,
+        source: ScrapeSource,
+        scrapes: &HashMap<String, ScraperHttpResult>,
+        now: StoryDate,
+    ) {
+        for (url, result) in scrapes {
+            if let ScraperHttpResult::Err(ScraperHttpResponseInput::Ok(input), error) = result {
+                tracing::warn!("Failed to parse scrape of {}: {}", url, error);
+                let mut len = input.len().min(config.max_input_length);
+                while !input.is_char_boundary(len) {
+                    len -= 1;
+                }
+                self.letters.push_back(DeadLetter {
+                    date: now,
+                    source,
+                    url: url.clone(),
+                    error: error.clone(),
+                    input: input[..len].to_owned(),
+                    input_length: input.len(),
+                });
+            }
+        }
+
+        let max_age =
+            StoryDuration::seconds(config.max_age.1.as_duration(config.max_age.0).as_secs() as i64);
+        while let Some(letter) = self.letters.front() {
+            if self.letters.len() > config.max_count || now - letter.date > max_age {
+                self.letters.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// All of the failures, newest first.
+    pub fn entries(&self) -> Vec<DeadLetter> {
+        self.letters.iter().rev().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use progscrape_scrapers::{ScrapeConfig, Scrapers};
+
+    #[test]
+    fn test_broken_scrape() {
+        let scrapers = Scrapers::new(&ScrapeConfig::default());
+        let url = "https://www.reddit.com/r/rust/.json".to_owned();
+        let input = format!("{{\"broken\": \"{}", "x".repeat(100));
+        let scrapes = HashMap::from_iter([(
+            url.clone(),
+            scrapers.scrape_http_result(
+                ScrapeSource::Reddit,
+                ScraperHttpResponseInput::Ok(input.clone()),
+            ),
+        )]);
+
+        let config = DeadLetterConfig {
+            max_input_length: 10,
+            ..Default::default()
+        };
+        let mut store = DeadLetterStore::default();
+        let now = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        store.insert_failures(&config, ScrapeSource::Reddit, &scrapes, now);
+
+        let entries = store.entries();
+        assert_eq!(1, entries.len());
+        assert_eq!(url, entries[0].url);
+        assert_eq!(ScrapeSource::Reddit, entries[0].source);
+        assert_eq!(&input[..10], entries[0].input);
+        assert_eq!(input.len(), entries[0].input_length);
+        assert!(!entries[0].error.is_empty());
+
+        // HTTP errors aren't format drift, so they aren't recorded
+        let errors = HashMap::from_iter([(
+            url,
+            scrapers.scrape_http_result(
+                ScrapeSource::Reddit,
+                ScraperHttpResponseInput::HTTPError(500, "Error".into()),
+            ),
+        )]);
+        store.insert_failures(&config, ScrapeSource::Reddit, &errors, now);
+        assert_eq!(1, store.entries().len());
+
+        // Old failures age out
+        let later = StoryDate::year_month_day(2023, 2, 1).expect("Date");
+        store.insert_failures(&config, ScrapeSource::Reddit, &HashMap::new(), later);
+        assert!(store.entries().is_empty());
+    }
+
+    #[test]
+    fn test_max_count() {
+        let scrapers = Scrapers::new(&ScrapeConfig::default());
+        let config = DeadLetterConfig {
+            max_count: 5,
+            ..Default::default()
+        };
+        let mut store = DeadLetterStore::default();
+        let now = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        for i in 0..10 {
+            let scrapes = HashMap::from_iter([(
+                format!("https://www.reddit.com/{}", i),
+                scrapers.scrape_http_result(
+                    ScrapeSource::Reddit,
+                    ScraperHttpResponseInput::Ok("{".into()),
+                ),
+            )]);
+            store.insert_failures(&config, ScrapeSource::Reddit, &scrapes, now);
+        }
+        let entries = store.entries();
+        assert_eq!(5, entries.len());
+        assert_eq!("https://www.reddit.com/9", entries[0].url);
+    }
+}
@@ -0,0 +1,119 @@
+//! Archival snapshots of the stories from a single day.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use progscrape_application::{Storage, Story, StoryIndex, StoryQuery};
+use progscrape_scrapers::{StoryDate, StoryDuration, TypedScrape};
+
+use crate::web::WebError;
+
+/// The number of stories fetched from the index at a time while exporting.
+const EXPORT_BATCH_SIZE: usize = 100;
+
+/// Parse a `YYYY-MM-DD` day.
+pub fn parse_day(day: &str) -> Result<StoryDate, WebError> {
+    StoryDate::from_string(&format!("{} 00:00:00", day.trim()), "%Y-%m-%d %H:%M:%S").ok_or_else(
+        || WebError::ArgumentsInvalid(format!("Invalid date '{}', expected YYYY-MM-DD", day)),
+    )
+}
+
+/// Write every story from the given day, with its scrapes, to `out` as a JSON array. Stories are fetched and written
+/// in batches so that a busy day doesn't need to fit in memory. Returns the number of stories written.
+pub fn export_day(index: &StoryIndex, day: StoryDate, out: &Path) -> Result<usize, WebError> {
+    let end = StoryDate::from_seconds(day.timestamp() + StoryDuration::days(1).num_seconds())
+        .unwrap_or(StoryDate::MAX);
+    let fetch = |after: Option<&Story<TypedScrape>>| -> Result<Vec<Story<TypedScrape>>, WebError> {
+        let query = match after {
+            Some(story) => StoryQuery::Since(story.date, Some(story.id.clone())),
+            None => StoryQuery::Since(day, None),
+        };
+        let mut stories = index.fetch::<TypedScrape>(query, EXPORT_BATCH_SIZE)?;
+        stories.retain(|story| story.date < end);
+        Ok(stories)
+    };
+
+    // Check for an empty day before creating the output
+    let mut stories = fetch(None)?;
+    if stories.is_empty() {
+        return Err(WebError::ArgumentsInvalid(format!(
+            "No stories found for {}-{:02}-{:02}",
+            day.year(),
+            day.month(),
+            day.day()
+        )));
+    }
+
+    let mut writer = BufWriter::new(File::create(out)?);
+    let mut count = 0;
+    writer.write_all(b"[")?;
+    while !stories.is_empty() {
+        for story in &stories {
+            if count > 0 {
+                writer.write_all(b",")?;
+            }
+            writer.write_all(b"\n")?;
+            serde_json::to_writer(&mut writer, story)?;
+            count += 1;
+        }
+        stories = fetch(stories.last())?;
+    }
+    writer.write_all(b"\n]\n")?;
+    writer.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use progscrape_application::{PersistLocation, StorageWriter};
+    use progscrape_scrapers::{hacker_news::HackerNewsStory, StoryUrl};
+
+    #[test]
+    fn test_parse_day() {
+        assert_eq!(
+            StoryDate::year_month_day(2023, 1, 2),
+            parse_day("2023-01-02").ok()
+        );
+        assert!(parse_day("2023-13-02").is_err());
+        assert!(parse_day("yesterday").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_day() -> Result<(), WebError> {
+        let resources = crate::resource::start_watcher("../resource").await?;
+        let eval = resources.story_evaluator();
+        let dir = tempfile::tempdir()?;
+        let out = dir.path().join("export.json");
+
+        let mut index = StoryIndex::new(PersistLocation::Path(dir.path().join("index")))?;
+        // More stories than a single batch, with neighbours on the days either side
+        let scrapes = (0..EXPORT_BATCH_SIZE * 2 + 10).map(|i| {
+            let day = 1 + (i % 3) as u32;
+            let date = StoryDate::year_month_day(2023, 1, day).expect("Date");
+            let date = StoryDate::from_seconds(date.timestamp() + i as i64 * 60).expect("Date");
+            let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+            HackerNewsStory::new_with_defaults(i.to_string(), date, "Title".into(), url).into()
+        });
+        index.insert_scrapes(&eval, scrapes)?;
+
+        let day = parse_day("2023-01-02")?;
+        let count = export_day(&index, day, &out)?;
+        let stories: Vec<Story<TypedScrape>> = serde_json::from_reader(File::open(&out)?)?;
+        assert_eq!(70, count);
+        assert_eq!(count, stories.len());
+        assert!(stories
+            .iter()
+            .all(|story| story.date.day() == 2 && story.date >= day));
+        assert!(stories.iter().all(|story| story.scrapes.len() == 1));
+
+        // An empty day is an error and writes nothing
+        std::fs::remove_file(&out)?;
+        assert!(matches!(
+            export_day(&index, parse_day("2023-02-01")?, &out),
+            Err(WebError::ArgumentsInvalid(_))
+        ));
+        assert!(!out.exists());
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use num_format::ToFormattedString;
 use progscrape_scrapers::{StoryDate, StoryDuration};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use super::static_files::StaticFileRegistry;
@@ -26,6 +27,72 @@ impl tera::Filter for CommaFilter {
     }
 }
 
+/// The values at or above which numbers are rendered with k/M/B suffixes by the `compact_number` filter.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompactNumberConfig {
+    pub thousands: i64,
+    pub millions: i64,
+    pub billions: i64,
+}
+
+impl Default for CompactNumberConfig {
+    fn default() -> Self {
+        Self {
+            thousands: 1_000,
+            millions: 1_000_000,
+            billions: 1_000_000_000,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CompactNumberFilter {
+    config: CompactNumberConfig,
+}
+
+impl CompactNumberFilter {
+    pub fn new(config: CompactNumberConfig) -> Self {
+        Self { config }
+    }
+
+    fn format(&self, n: i64) -> String {
+        let config = &self.config;
+        let abs = n.unsigned_abs();
+        let (divisor, suffix) = if abs >= config.billions.unsigned_abs() {
+            (1_000_000_000, "B")
+        } else if abs >= config.millions.unsigned_abs() {
+            (1_000_000, "M")
+        } else if abs >= config.thousands.unsigned_abs() {
+            (1_000, "k")
+        } else {
+            return n.to_string();
+        };
+        // Avoid rendering values like 999,999 as "1000.0k"
+        let value = n as f64 / divisor as f64;
+        let (value, suffix) = match suffix {
+            "k" if value.abs() >= 999.95 => (value / 1000.0, "M"),
+            "M" if value.abs() >= 999.95 => (value / 1000.0, "B"),
+            _ => (value, suffix),
+        };
+        format!("{:.1}{}", value, suffix)
+    }
+}
+
+impl tera::Filter for CompactNumberFilter {
+    fn filter(
+        &self,
+        value: &Value,
+        _args: &std::collections::HashMap<String, Value>,
+    ) -> tera::Result<Value> {
+        Ok(self
+            .format(value.as_i64().unwrap_or_else(|| {
+                tracing::warn!("Invalid input to compact_number filter");
+                0
+            }))
+            .into())
+    }
+}
+
 #[derive(Default)]
 pub struct AbsoluteTimeFilter {}
 
@@ -44,6 +111,25 @@ impl tera::Filter for AbsoluteTimeFilter {
     }
 }
 
+/// Formats a timestamp as an RFC 2822 date, as required by RSS.
+#[derive(Default)]
+pub struct Rfc2822TimeFilter {}
+
+impl tera::Filter for Rfc2822TimeFilter {
+    fn filter(
+        &self,
+        value: &Value,
+        _args: &std::collections::HashMap<String, Value>,
+    ) -> tera::Result<Value> {
+        let date = value.as_i64().and_then(StoryDate::from_seconds);
+        if let Some(date) = date {
+            Ok(date.to_rfc2822().into())
+        } else {
+            Err("Invalid date arguments".to_string().into())
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct RelativeTimeFilter {}
 
@@ -154,3 +240,93 @@ impl tera::Filter for StaticFileFilter {
         Ok(s.into())
     }
 }
+
+pub struct AbsoluteUrlFilter {
+    base_url: url::Url,
+}
+
+impl AbsoluteUrlFilter {
+    pub fn new(mut base_url: url::Url) -> Self {
+        // Ensure that paths are resolved underneath the base URL's path, rather than replacing its last segment
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        Self { base_url }
+    }
+
+    pub fn absolute(&self, path: &str) -> Result<url::Url, url::ParseError> {
+        self.base_url.join(path.trim_start_matches('/'))
+    }
+}
+
+impl tera::Filter for AbsoluteUrlFilter {
+    fn filter(
+        &self,
+        value: &Value,
+        _args: &std::collections::HashMap<String, Value>,
+    ) -> tera::Result<Value> {
+        if let Some(path) = value.as_str() {
+            match self.absolute(path) {
+                Ok(url) => Ok(url.as_str().into()),
+                Err(e) => Err(format!("Invalid path '{}': {}", path, e).into()),
+            }
+        } else {
+            Err("Invalid path arguments".to_string().into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(0, "0")]
+    #[case(999, "999")]
+    #[case(1000, "1.0k")]
+    #[case(12345, "12.3k")]
+    #[case(999_999, "1.0M")]
+    #[case(1_500_000, "1.5M")]
+    #[case(2_000_000_000, "2.0B")]
+    #[case(-1500, "-1.5k")]
+    fn test_compact_number(#[case] n: i64, #[case] expected: &str) {
+        assert_eq!(expected, CompactNumberFilter::default().format(n));
+    }
+
+    #[rstest]
+    #[case(9999, "9999")]
+    #[case(10000, "10.0k")]
+    fn test_compact_number_threshold(#[case] n: i64, #[case] expected: &str) {
+        let filter = CompactNumberFilter::new(CompactNumberConfig {
+            thousands: 10_000,
+            ..Default::default()
+        });
+        assert_eq!(expected, filter.format(n));
+    }
+
+    #[rstest]
+    #[case("https://www.progscrape.com", "/", "https://www.progscrape.com/")]
+    #[case(
+        "https://www.progscrape.com/",
+        "/feed.xml",
+        "https://www.progscrape.com/feed.xml"
+    )]
+    #[case(
+        "https://www.progscrape.com/",
+        "tag/rust/feed.xml",
+        "https://www.progscrape.com/tag/rust/feed.xml"
+    )]
+    #[case(
+        "https://example.com/progscrape",
+        "/?search=rust",
+        "https://example.com/progscrape/?search=rust"
+    )]
+    fn test_absolute_url(#[case] base: &str, #[case] path: &str, #[case] expected: &str) {
+        let filter = AbsoluteUrlFilter::new(url::Url::parse(base).expect("Invalid base URL"));
+        assert_eq!(
+            expected,
+            filter.absolute(path).expect("Invalid path").as_str()
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Resolves the icon displayed beside each story from the story's domain.
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IconConfig {
+    /// An icon service URL template where `{domain}` is replaced with the story's domain. If unset, the domain's
+    /// own `/favicon.ico` is used.
+    pub service: Option<String>,
+    /// Domains that are known to have no icon.
+    pub exclude: Vec<String>,
+}
+
+/// Maps domains to icon URLs, caching each resolution.
+#[derive(Default)]
+pub struct IconResolver {
+    config: IconConfig,
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl IconResolver {
+    pub fn new(config: &IconConfig) -> Self {
+        Self {
+            config: config.clone(),
+            cache: Default::default(),
+        }
+    }
+
+    /// The icon URL for a domain, or `None` if the domain has no icon.
+    pub fn resolve(&self, domain: &str) -> Option<String> {
+        let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+        let mut cache = self.cache.lock().expect("Poisoned icon cache");
+        cache
+            .entry(domain)
+            .or_insert_with_key(|domain| self.resolve_uncached(domain))
+            .clone()
+    }
+
+    fn resolve_uncached(&self, domain: &str) -> Option<String> {
+        if domain.is_empty() || self.config.exclude.iter().any(|x| x == domain) {
+            return None;
+        }
+        let url = match &self.config.service {
+            Some(service) => service.replace("{domain}", domain),
+            None => format!("https://{}/favicon.ico", domain),
+        };
+        // Domains that can't form a valid URL (eg: stray whitespace or ports) get no icon
+        match url::Url::parse(&url) {
+            Ok(url) if url.host_str().is_some() => Some(url.into()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("example.com", Some("https://example.com/favicon.ico"))]
+    #[case("Example.COM.", Some("https://example.com/favicon.ico"))]
+    #[case("", None)]
+    #[case("bad domain", None)]
+    #[case("noicon.com", None)]
+    fn test_resolve(#[case] domain: &str, #[case] expected: Option<&str>) {
+        let resolver = IconResolver::new(&IconConfig {
+            exclude: vec!["noicon.com".into()],
+            ..Default::default()
+        });
+        assert_eq!(expected.map(str::to_owned), resolver.resolve(domain));
+        // Cached resolutions are stable
+        assert_eq!(expected.map(str::to_owned), resolver.resolve(domain));
+    }
+
+    #[test]
+    fn test_service() {
+        let resolver = IconResolver::new(&IconConfig {
+            service: Some("https://icons.duckduckgo.com/ip3/{domain}.ico".into()),
+            ..Default::default()
+        });
+        assert_eq!(
+            Some("https://icons.duckduckgo.com/ip3/github.com.ico".to_owned()),
+            resolver.resolve("github.com")
+        );
+    }
+}
@@ -1,20 +1,155 @@
 use std::{
-    path::Path,
-    sync::{Arc, RwLock},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use progscrape_application::{
-    BackerUpper, BackupResult, PersistError, PersistLocation, Shard, Storage, StorageFetch,
-    StorageSummary, StorageWriter, Story, StoryEvaluator, StoryIndex, StoryQuery,
-    StoryScrapePayload,
+    BackerUpper, BackupResult, Durability, ImportResult, PersistError, PersistLocation,
+    SearchFacets, SearchWeights, Shard, ShardLocation, Storage, StorageFetch, StorageSummary,
+    StorageWriter, Story, StoryEngagement, StoryEvaluator, StoryIdScheme, StoryIdentifier,
+    StoryIndex, StoryQuery, StoryScrapePayload,
 };
-use progscrape_scrapers::{StoryDate, TypedScrape};
+use progscrape_scrapers::{ScrapeSource, StoryDate, TypedScrape, TypedScrapeMap};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
 
 use crate::web::WebError;
 
+/// The number of candidate stories considered for the hot set.
+const HOT_SET_CANDIDATES: usize = 500;
+
+/// The file in the persistence path where the hot set is saved between restarts.
+const HOT_SET_FILE: &str = "hot_set.json";
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct HotSetConfig {
+    /// The maximum number of hot set candidates from each source. Sources without a quota are unlimited.
+    #[serde(default)]
+    pub source_quotas: TypedScrapeMap<Option<usize>>,
+    /// Save the hot set this often (and on shutdown) so that a restarted server can serve it while the hot set is
+    /// recomputed. If unset, the hot set isn't saved.
+    #[serde(default)]
+    pub persist_interval_secs: Option<u64>,
+}
+
+/// Queries run at startup to populate the OS page cache and the index caches before the server reports that it's
+/// ready for traffic.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarmupConfig {
+    pub enabled: bool,
+    /// Representative searches to run after the hot set has been computed.
+    pub searches: Vec<String>,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            searches: vec!["rust".into()],
+        }
+    }
+}
+
+/// The number of search results fetched for each warm-up search.
+const WARMUP_SEARCH_RESULTS: usize = 30;
+
+/// A hot set story as saved to disk. The rest of the story is re-fetched from the index when it is loaded.
+#[derive(Serialize, Deserialize)]
+struct PersistedHotSetStory {
+    id: StoryIdentifier,
+    score: f32,
+}
+
+/// Limits on how long scrapes wait in the ingest queue before they are committed together.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IngestConfig {
+    /// A batch is committed as soon as it holds at least this many scrapes.
+    pub max_batch_scrapes: usize,
+    /// A batch is committed no later than this many milliseconds after its first scrape was queued.
+    pub max_batch_delay_ms: u64,
+}
+
+impl IngestConfig {
+    /// The size and age at which a batch is committed. Batching durability replaces the limits with its own, which
+    /// are usually much larger, so scrapes wait longer in the queue to be committed together.
+    fn batch_limits(&self, durability: Durability) -> (usize, Duration) {
+        match durability {
+            Durability::Immediate => (
+                self.max_batch_scrapes,
+                Duration::from_millis(self.max_batch_delay_ms),
+            ),
+            Durability::Batch {
+                max_scrapes,
+                interval_secs,
+            } => (max_scrapes, Duration::from_secs(interval_secs)),
+        }
+    }
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_scrapes: 1000,
+            max_batch_delay_ms: 250,
+        }
+    }
+}
+
+/// Limits on concurrent queries. Searches are CPU-bound, so a flood of them could otherwise starve the rest of the
+/// server.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryLimitConfig {
+    /// The most queries run at once.
+    pub max_concurrent: usize,
+    /// How long a query waits for another to finish before failing with a 503.
+    pub max_wait_ms: u64,
+}
+
+impl Default for QueryLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 8,
+            max_wait_ms: 2000,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct QueryLimit {
+    semaphore: Arc<Semaphore>,
+    max_wait: Duration,
+}
+
+impl From<&QueryLimitConfig> for QueryLimit {
+    fn from(config: &QueryLimitConfig) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            max_wait: Duration::from_millis(config.max_wait_ms),
+        }
+    }
+}
+
+type IngestResult = oneshot::Sender<Result<(), PersistError>>;
+
+enum IngestMessage {
+    Insert(Arc<StoryEvaluator>, Vec<TypedScrape>, IngestResult),
+    Flush(IngestResult),
+}
+
 pub struct Index<S: StorageWriter> {
     pub storage: Arc<RwLock<S>>,
     pub hot_set: Arc<RwLock<Vec<Story<Shard>>>>,
+    /// The engagement of each hot set story, for the front page sorts that need it.
+    hot_set_engagement: Arc<RwLock<HashMap<StoryIdentifier, StoryEngagement>>>,
+    ingest: mpsc::Sender<IngestMessage>,
+    ingest_config: Arc<RwLock<IngestConfig>>,
+    durability: Arc<RwLock<Durability>>,
+    query_limit: Arc<RwLock<QueryLimit>>,
+    hot_set_path: Option<PathBuf>,
 }
 
 impl<S: StorageWriter> Clone for Index<S> {
@@ -22,6 +157,12 @@ impl<S: StorageWriter> Clone for Index<S> {
         Self {
             storage: self.storage.clone(),
             hot_set: self.hot_set.clone(),
+            hot_set_engagement: self.hot_set_engagement.clone(),
+            ingest: self.ingest.clone(),
+            ingest_config: self.ingest_config.clone(),
+            durability: self.durability.clone(),
+            query_limit: self.query_limit.clone(),
+            hot_set_path: self.hot_set_path.clone(),
         }
     }
 }
@@ -51,17 +192,237 @@ macro_rules! async_run_write {
 }
 
 impl Index<StoryIndex> {
+    /// Open the index at `path`, with any shards in `shard_locations` stored in their own directories.
     pub fn initialize_with_persistence<P: AsRef<Path>>(
         path: P,
+        shard_locations: Vec<ShardLocation>,
     ) -> Result<Index<StoryIndex>, WebError> {
-        let index = StoryIndex::new(PersistLocation::Path(path.as_ref().to_owned()))?;
-        let hot_set = index.fetch(StoryQuery::FrontPage(), 500)?;
+        let location =
+            PersistLocation::with_shard_locations(path.as_ref().to_owned(), shard_locations);
+        let index = StoryIndex::new(location)?;
+        let hot_set_path = path.as_ref().join(HOT_SET_FILE);
+        let hot_set = match Self::load_hot_set(&index, &hot_set_path) {
+            Ok(Some(hot_set)) => {
+                tracing::info!("Loaded {} hot set story(s) from disk", hot_set.len());
+                hot_set
+            }
+            Ok(None) => index.fetch(StoryQuery::FrontPage(), HOT_SET_CANDIDATES)?,
+            Err(e) => {
+                tracing::error!("Failed to load the saved hot set, recomputing: {:?}", e);
+                index.fetch(StoryQuery::FrontPage(), HOT_SET_CANDIDATES)?
+            }
+        };
+        Ok(Self::new_with_hot_set(index, hot_set, Some(hot_set_path))?)
+    }
+
+    /// Wrap an existing `StoryIndex`, computing the initial hot set.
+    #[cfg(test)]
+    pub fn new(index: StoryIndex) -> Result<Index<StoryIndex>, PersistError> {
+        let hot_set = index.fetch(StoryQuery::FrontPage(), HOT_SET_CANDIDATES)?;
+        Self::new_with_hot_set(index, hot_set, None)
+    }
+
+    fn new_with_hot_set(
+        index: StoryIndex,
+        hot_set: Vec<Story<Shard>>,
+        hot_set_path: Option<PathBuf>,
+    ) -> Result<Index<StoryIndex>, PersistError> {
+        let storage = Arc::new(RwLock::new(index));
+        let ingest_config = Arc::<RwLock<IngestConfig>>::default();
+        let durability = Arc::<RwLock<Durability>>::default();
+        let (ingest, rx) = mpsc::channel();
+        {
+            let (storage, ingest_config, durability) =
+                (storage.clone(), ingest_config.clone(), durability.clone());
+            std::thread::Builder::new()
+                .name("ingest".into())
+                .spawn(move || Self::run_ingest(storage, ingest_config, durability, rx))?;
+        }
         Ok(Index {
-            storage: Arc::new(RwLock::new(index)),
+            storage,
             hot_set: Arc::new(RwLock::new(hot_set)),
+            hot_set_engagement: Default::default(),
+            ingest,
+            ingest_config,
+            durability,
+            query_limit: Arc::new(RwLock::new((&QueryLimitConfig::default()).into())),
+            hot_set_path,
         })
     }
 
+    /// Load a saved hot set, skipping any stories that have since been hidden or deleted.
+    fn load_hot_set(
+        index: &StoryIndex,
+        path: &Path,
+    ) -> Result<Option<Vec<Story<Shard>>>, PersistError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let persisted: Vec<PersistedHotSetStory> =
+            serde_json::from_reader(std::io::BufReader::new(std::fs::File::open(path)?))?;
+        let mut hot_set = vec![];
+        for story in persisted {
+            if index.is_story_hidden(&story.id) {
+                continue;
+            }
+            if let Some(mut loaded) = index.fetch_one::<Shard>(StoryQuery::ById(story.id))? {
+                loaded.score = story.score;
+                hot_set.push(loaded);
+            }
+        }
+        Ok(Some(hot_set))
+    }
+
+    /// Save the current hot set to disk, if this index has a persistence path.
+    pub async fn persist_hot_set(&self) -> Result<(), PersistError> {
+        let Some(path) = self.hot_set_path.clone() else {
+            return Ok(());
+        };
+        let persisted = self
+            .hot_set
+            .read()
+            .expect("Failed to lock hot set")
+            .iter()
+            .map(|story| PersistedHotSetStory {
+                id: story.id.clone(),
+                score: story.score,
+            })
+            .collect::<Vec<_>>();
+        tokio::task::spawn_blocking(move || {
+            // Write to a temporary file first so that a crash can't leave a partial hot set behind
+            let tmp = path.with_extension("json.tmp");
+            std::fs::write(&tmp, serde_json::to_vec(&persisted)?)?;
+            std::fs::rename(&tmp, &path)?;
+            Result::<_, PersistError>::Ok(())
+        })
+        .await
+        .map_err(|_| PersistError::UnexpectedError("Hot set persist panicked".into()))?
+    }
+
+    /// Update the batching limits of the ingest queue. These take effect from the next batch.
+    pub fn set_ingest_config(&self, config: &IngestConfig) {
+        *self
+            .ingest_config
+            .write()
+            .expect("Failed to lock ingest config") = config.clone();
+    }
+
+    /// Set how long the ingest queue holds scrapes before committing them. The index itself always commits each batch
+    /// before the waiting inserts return, so that they are never told that buffered scrapes were committed.
+    pub fn set_durability(&self, durability: Durability) {
+        *self.durability.write().expect("Failed to lock durability") = durability;
+    }
+
+    /// Update the limit on concurrent queries. Queries that are already running or waiting count against the old limit.
+    pub fn set_query_limit(&self, config: &QueryLimitConfig) {
+        *self
+            .query_limit
+            .write()
+            .expect("Failed to lock query limit") = config.into();
+    }
+
+    /// Wait for a query slot, giving up if none is free in time. The slot is held until the permit is dropped.
+    async fn query_permit(&self) -> Result<OwnedSemaphorePermit, WebError> {
+        let limit = self
+            .query_limit
+            .read()
+            .expect("Failed to lock query limit")
+            .clone();
+        match tokio::time::timeout(limit.max_wait, limit.semaphore.acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => {
+                tracing::warn!("Timed out waiting for a query slot");
+                Err(WebError::Overloaded)
+            }
+        }
+    }
+
+    /// The background commit thread. Queued scrapes are collected into a batch until it is large enough or old
+    /// enough, and the batch is then inserted under a single write lock and commit. The thread exits once every
+    /// `Index` is dropped and the queue has drained.
+    fn run_ingest(
+        storage: Arc<RwLock<StoryIndex>>,
+        config: Arc<RwLock<IngestConfig>>,
+        durability: Arc<RwLock<Durability>>,
+        rx: mpsc::Receiver<IngestMessage>,
+    ) {
+        while let Ok(first) = rx.recv() {
+            let (max_batch_scrapes, max_batch_delay) = config
+                .read()
+                .expect("Failed to lock ingest config")
+                .batch_limits(*durability.read().expect("Failed to lock durability"));
+            let deadline = Instant::now() + max_batch_delay;
+            let mut eval = None;
+            let mut scrapes = vec![];
+            let mut waiting = vec![];
+            let mut next = Some(first);
+            while let Some(message) = next.take() {
+                match message {
+                    IngestMessage::Insert(batch_eval, batch, tx) => {
+                        // Callers share the evaluator from the current resources, so the most recent one wins
+                        eval = Some(batch_eval);
+                        scrapes.extend(batch);
+                        waiting.push(tx);
+                    }
+                    IngestMessage::Flush(tx) => {
+                        waiting.push(tx);
+                        break;
+                    }
+                }
+                if scrapes.len() >= max_batch_scrapes {
+                    break;
+                }
+                next = rx
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok();
+            }
+
+            let res = match eval {
+                Some(eval) if !scrapes.is_empty() => {
+                    tracing::info!("Committing batch of {} scrape(s)", scrapes.len());
+                    storage
+                        .write()
+                        .expect("Failed to lock storage for write")
+                        .insert_scrapes(&eval, scrapes.into_iter())
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = &res {
+                tracing::error!("Failed to commit batch: {:?}", e);
+            }
+            for tx in waiting {
+                let res = match &res {
+                    Ok(()) => Ok(()),
+                    Err(e) => Err(PersistError::UnexpectedError(format!(
+                        "Batch commit failed: {:?}",
+                        e
+                    ))),
+                };
+                // The caller may have gone away, which is fine
+                let _ = tx.send(res);
+            }
+        }
+        tracing::info!("Ingest queue closed");
+    }
+
+    /// Send a message to the commit thread and wait for its batch to be committed.
+    async fn send_ingest(
+        &self,
+        message: impl FnOnce(IngestResult) -> IngestMessage,
+    ) -> Result<(), PersistError> {
+        let (tx, rx) = oneshot::channel();
+        self.ingest
+            .send(message(tx))
+            .map_err(|_| PersistError::UnexpectedError("Ingest queue closed".into()))?;
+        rx.await
+            .map_err(|_| PersistError::UnexpectedError("Ingest queue closed".into()))?
+    }
+
+    /// Wait for every scrape queued so far to be committed.
+    pub async fn flush(&self) -> Result<(), PersistError> {
+        self.send_ingest(IngestMessage::Flush).await
+    }
+
     /// Back up the current index to the given path. The return value of this function is a little convoluted because we
     /// don't necessarily want to fail the whole operation.
     pub fn backup(
@@ -83,24 +444,219 @@ impl Index<StoryIndex> {
         Ok(results)
     }
 
-    pub async fn refresh_hot_set(&self) -> Result<(), PersistError> {
-        let v = self.fetch(StoryQuery::FrontPage(), 500).await?;
-        *self.hot_set.write().expect("Failed to lock hot set") = v.clone();
+    pub async fn refresh_hot_set(&self, config: &HotSetConfig) -> Result<(), PersistError> {
+        let query = StoryQuery::FrontPageWithQuotas(config.source_quotas.clone());
+        let stories = self
+            .fetch_unlimited::<TypedScrape>(query, HOT_SET_CANDIDATES)
+            .await?;
+        let engagement = stories
+            .iter()
+            .map(|story| (story.id.clone(), story.total_engagement()))
+            .collect();
+        // The hot set only keeps the shard of each scrape, which is derived from its date as it is in the index
+        let v = stories
+            .into_iter()
+            .map(|story| Story {
+                id: story.id,
+                score: story.score,
+                date: story.date,
+                url: story.url,
+                title: story.title,
+                tags: story.tags,
+                scrapes: story
+                    .scrapes
+                    .into_iter()
+                    .map(|(id, scrape)| (id, Shard::from_date_time(scrape.date)))
+                    .collect(),
+                read_time: story.read_time,
+                first_seen: story.first_seen,
+            })
+            .collect();
+        *self.hot_set.write().expect("Failed to lock hot set") = v;
+        *self
+            .hot_set_engagement
+            .write()
+            .expect("Failed to lock hot set engagement") = engagement;
         Ok(())
     }
 
+    /// The engagement of a hot set story, as of the last time the hot set was computed.
+    pub fn with_hot_set_engagement<T>(
+        &self,
+        f: impl FnOnce(&HashMap<StoryIdentifier, StoryEngagement>) -> T,
+    ) -> T {
+        f(&self
+            .hot_set_engagement
+            .read()
+            .expect("Failed to lock hot set engagement"))
+    }
+
+    /// Compute the hot set and run the warm-up searches, returning how long that took.
+    pub async fn warm_up(
+        &self,
+        eval: &StoryEvaluator,
+        config: &WarmupConfig,
+        hot_set: &HotSetConfig,
+    ) -> Result<Duration, PersistError> {
+        let start = Instant::now();
+        self.refresh_hot_set(hot_set).await?;
+        for search in &config.searches {
+            self.fetch_unlimited::<Shard>(
+                StoryQuery::from_search(&eval.tagger, search),
+                WARMUP_SEARCH_RESULTS,
+            )
+            .await?;
+        }
+        Ok(start.elapsed())
+    }
+
     pub async fn hot_set(&self) -> Result<Vec<Story<Shard>>, PersistError> {
         let v = self.hot_set.read().expect("Failed to lock hot set").clone();
         Ok(v)
     }
 
+    /// Queue scrapes for insertion, returning once the batch containing them has been committed.
     pub async fn insert_scrapes<I: Iterator<Item = TypedScrape> + Send + 'static>(
         &self,
         eval: Arc<StoryEvaluator>,
         scrapes: I,
     ) -> Result<(), PersistError> {
+        let scrapes = scrapes.collect();
+        self.send_ingest(|tx| IngestMessage::Insert(eval, scrapes, tx))
+            .await
+    }
+
+    /// Hide a story from the front page and search results, removing it from the hot set immediately.
+    pub async fn hide_story(&self, id: StoryIdentifier) -> Result<bool, PersistError> {
+        let hidden = id.clone();
+        let res = async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.hide_story(&hidden)
+        })?;
+        self.hot_set
+            .write()
+            .expect("Failed to lock hot set")
+            .retain(|story| story.id != id);
+        Ok(res)
+    }
+
+    /// Restore a hidden story. It will reappear on the front page once the hot set is refreshed.
+    pub async fn unhide_story(&self, id: StoryIdentifier) -> Result<bool, PersistError> {
+        async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.unhide_story(&id)
+        })
+    }
+
+    /// Permanently delete a story and its scrapes, removing it from the hot set immediately. Returns false if the
+    /// story doesn't exist.
+    pub async fn delete_story(&self, id: StoryIdentifier) -> Result<bool, PersistError> {
+        let deleted = id.clone();
+        let res = async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.delete_story(&deleted)
+        })?;
+        self.hot_set
+            .write()
+            .expect("Failed to lock hot set")
+            .retain(|story| story.id != id);
+        Ok(res)
+    }
+
+    /// Merge a large set of scrapes into the index without duplicating the stories it already has.
+    pub async fn import_scrapes(
+        &self,
+        eval: Arc<StoryEvaluator>,
+        scrapes: Vec<TypedScrape>,
+    ) -> Result<ImportResult, PersistError> {
         async_run_write!(self.storage, move |storage: &mut StoryIndex| {
-            storage.insert_scrapes(&eval, scrapes)
+            storage.import_scrapes(&eval, scrapes)
+        })
+    }
+
+    /// Re-evaluate and re-write the stories that have scrapes from a single source, returning the number of stories
+    /// affected.
+    pub async fn reingest_source(
+        &self,
+        eval: Arc<StoryEvaluator>,
+        source: ScrapeSource,
+    ) -> Result<usize, PersistError> {
+        async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.reingest_source(&eval, source)
+        })
+    }
+
+    /// Update the blend of relevance and recency used to rank search results.
+    pub async fn set_search_weights(&self, weights: SearchWeights) -> Result<(), PersistError> {
+        async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.set_search_weights(weights);
+            Ok(())
+        })
+    }
+
+    pub async fn set_id_scheme(&self, id_scheme: StoryIdScheme) -> Result<(), PersistError> {
+        async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.set_id_scheme(id_scheme);
+            Ok(())
+        })
+    }
+
+    /// The current ID of a story, following it if its ID has changed.
+    pub async fn resolve_story_id(
+        &self,
+        id: StoryIdentifier,
+    ) -> Result<Option<StoryIdentifier>, PersistError> {
+        async_run!(self.storage, move |storage: &StoryIndex| {
+            storage.resolve_story_id(&id)
+        })
+    }
+
+    pub async fn set_read_time(
+        &self,
+        id: StoryIdentifier,
+        minutes: u32,
+    ) -> Result<(), PersistError> {
+        async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.set_read_time(&id, minutes)
+        })
+    }
+
+    /// Pin a story to the top of the front page. Pins are applied whenever the front page is rendered, so this takes
+    /// effect immediately.
+    pub async fn pin_story(&self, id: StoryIdentifier) -> Result<bool, PersistError> {
+        async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.pin_story(&id)
+        })
+    }
+
+    pub async fn unpin_story(&self, id: StoryIdentifier) -> Result<bool, PersistError> {
+        async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.unpin_story(&id)
+        })
+    }
+
+    pub async fn is_story_pinned(&self, id: StoryIdentifier) -> Result<bool, PersistError> {
+        async_run!(self.storage, move |storage: &StoryIndex| {
+            Ok(storage.is_story_pinned(&id))
+        })
+    }
+
+    /// The pinned stories, in the order they were pinned, skipping any that are hidden or no longer exist.
+    pub async fn pinned_stories(&self) -> Result<Vec<Story<Shard>>, PersistError> {
+        async_run!(self.storage, |storage: &StoryIndex| {
+            let mut stories = vec![];
+            for id in storage.pinned_stories() {
+                if storage.is_story_hidden(&id) {
+                    continue;
+                }
+                if let Some(story) = storage.fetch_one::<Shard>(StoryQuery::ById(id))? {
+                    stories.push(story);
+                }
+            }
+            Ok(stories)
+        })
+    }
+
+    pub async fn is_story_hidden(&self, id: StoryIdentifier) -> Result<bool, PersistError> {
+        async_run!(self.storage, move |storage: &StoryIndex| {
+            Ok(storage.is_story_hidden(&id))
         })
     }
 
@@ -116,10 +672,46 @@ impl Index<StoryIndex> {
         })
     }
 
+    pub async fn fetch_facets(
+        &self,
+        query: StoryQuery,
+        max: usize,
+    ) -> Result<SearchFacets, WebError> {
+        let _permit = self.query_permit().await?;
+        Ok(async_run!(self.storage, |storage: &StoryIndex| {
+            storage.fetch_facets(query, max)
+        })?)
+    }
+
+    pub async fn fetch_tag_counts(
+        &self,
+        min_count: usize,
+        since: Option<StoryDate>,
+    ) -> Result<Vec<(String, usize)>, WebError> {
+        let _permit = self.query_permit().await?;
+        Ok(async_run!(self.storage, |storage: &StoryIndex| {
+            storage.fetch_tag_counts(min_count, since)
+        })?)
+    }
+
+    /// Run a query, waiting for a free slot under the query limit.
     pub async fn fetch<S: StoryScrapePayload + 'static>(
         &self,
         query: StoryQuery,
         max: usize,
+    ) -> Result<Vec<Story<S>>, WebError>
+    where
+        StoryIndex: StorageFetch<S>,
+    {
+        let _permit = self.query_permit().await?;
+        Ok(self.fetch_unlimited(query, max).await?)
+    }
+
+    /// Run a query outside of the query limit, for our own queries that shouldn't fail under load.
+    async fn fetch_unlimited<S: StoryScrapePayload + 'static>(
+        &self,
+        query: StoryQuery,
+        max: usize,
     ) -> Result<Vec<Story<S>>, PersistError>
     where
         StoryIndex: StorageFetch<S>,
@@ -129,6 +721,13 @@ impl Index<StoryIndex> {
         })
     }
 
+    pub async fn query_newest(&self, count: usize) -> Result<Vec<Story<Shard>>, WebError> {
+        let _permit = self.query_permit().await?;
+        Ok(async_run!(self.storage, |storage: &StoryIndex| {
+            storage.query_newest(count)
+        })?)
+    }
+
     pub async fn fetch_one<S: StoryScrapePayload + 'static>(
         &self,
         query: StoryQuery,
@@ -141,3 +740,214 @@ impl Index<StoryIndex> {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use progscrape_scrapers::{hacker_news::HackerNewsStory, StoryUrl};
+
+    fn scrape(i: usize) -> TypedScrape {
+        let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        HackerNewsStory::new_with_defaults(i.to_string(), date, format!("Title {}", i), url).into()
+    }
+
+    #[tokio::test]
+    async fn test_ingest_batch() -> Result<(), WebError> {
+        let resources = crate::resource::start_watcher("../resource").await?;
+        let index = Index::new(StoryIndex::new(PersistLocation::Memory)?)?;
+        let eval = resources.story_evaluator();
+
+        // A burst that fills the batch is committed without waiting for the delay
+        index.set_ingest_config(&IngestConfig {
+            max_batch_scrapes: 10,
+            max_batch_delay_ms: 60_000,
+        });
+        let burst = (0..10).map(|i| {
+            let (index, eval) = (index.clone(), eval.clone());
+            tokio::spawn(async move { index.insert_scrapes(eval, [scrape(i)].into_iter()).await })
+        });
+        let results =
+            tokio::time::timeout(Duration::from_secs(10), futures::future::join_all(burst))
+                .await
+                .expect("Burst was not committed");
+        for res in results {
+            res.expect("Insert panicked")?;
+        }
+        assert_eq!(10, index.story_count().await?.total.story_count);
+
+        // A partial batch is committed once the delay expires
+        index.set_ingest_config(&IngestConfig {
+            max_batch_scrapes: 1000,
+            max_batch_delay_ms: 100,
+        });
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            index.insert_scrapes(eval.clone(), [scrape(10)].into_iter()),
+        )
+        .await
+        .expect("Partial batch was not committed")?;
+        assert_eq!(11, index.story_count().await?.total.story_count);
+
+        index.flush().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_durability() -> Result<(), WebError> {
+        let resources = crate::resource::start_watcher("../resource").await?;
+        let index = Index::new(StoryIndex::new(PersistLocation::Memory)?)?;
+        let eval = resources.story_evaluator();
+        index.set_durability(Durability::Batch {
+            max_scrapes: 3,
+            interval_secs: 3600,
+        });
+
+        // Inserts wait in the queue until the batch is full...
+        let insert = |i| {
+            let (index, eval) = (index.clone(), eval.clone());
+            tokio::spawn(async move { index.insert_scrapes(eval, [scrape(i)].into_iter()).await })
+        };
+        let pending = [insert(0), insert(1)];
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(pending.iter().all(|insert| !insert.is_finished()));
+        assert_eq!(0, index.story_count().await?.total.story_count);
+
+        // ... and are only told it's done once it has been committed
+        insert(2).await.expect("Insert panicked")?;
+        for insert in pending {
+            insert.await.expect("Insert panicked")?;
+        }
+        assert_eq!(3, index.story_count().await?.total.story_count);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persisted_hot_set() -> Result<(), WebError> {
+        let resources = crate::resource::start_watcher("../resource").await?;
+        let eval = resources.story_evaluator();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path();
+
+        let index = Index::initialize_with_persistence(path, vec![])?;
+        index
+            .insert_scrapes(eval.clone(), (0..3).map(scrape))
+            .await?;
+        index.refresh_hot_set(&Default::default()).await?;
+        assert_eq!(3, index.hot_set().await?.len());
+
+        // Keep a subset of the hot set so that we can tell a loaded hot set from a recomputed one
+        let kept = {
+            let mut hot_set = index.hot_set.write().expect("Failed to lock hot set");
+            hot_set.truncate(2);
+            hot_set[0].score = 1234.0;
+            hot_set
+                .iter()
+                .map(|story| story.id.clone())
+                .collect::<Vec<_>>()
+        };
+        index.persist_hot_set().await?;
+        index.flush().await?;
+        drop(index);
+
+        let index = Index::initialize_with_persistence(path, vec![])?;
+        let hot_set = index.hot_set().await?;
+        assert_eq!(
+            kept,
+            hot_set
+                .iter()
+                .map(|story| story.id.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1234.0, hot_set[0].score);
+        // The rest of each story comes from the index
+        assert!(hot_set
+            .iter()
+            .all(|story| story.title.starts_with("Title ")));
+
+        // The fresh hot set replaces the loaded one
+        index.refresh_hot_set(&Default::default()).await?;
+        assert_eq!(3, index.hot_set().await?.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_warm_up() -> Result<(), WebError> {
+        let resources = crate::resource::start_watcher("../resource").await?;
+        let index = Index::new(StoryIndex::new(PersistLocation::Memory)?)?;
+        let eval = resources.story_evaluator();
+        index
+            .insert_scrapes(eval.clone(), (0..10).map(scrape))
+            .await?;
+
+        let config = WarmupConfig {
+            enabled: true,
+            searches: vec!["title".into(), "tag:rust".into()],
+        };
+        index
+            .warm_up(&eval, &config, &HotSetConfig::default())
+            .await?;
+        assert_eq!(10, index.hot_set().await?.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_limit() -> Result<(), WebError> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let resources = crate::resource::start_watcher("../resource").await?;
+        let index = Index::new(StoryIndex::new(PersistLocation::Memory)?)?;
+        index
+            .insert_scrapes(resources.story_evaluator(), (0..10).map(scrape))
+            .await?;
+        index.set_query_limit(&QueryLimitConfig {
+            max_concurrent: 2,
+            max_wait_ms: 10_000,
+        });
+
+        // Every query completes, but no more than two ever run at once
+        let (running, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let queries = (0..10).map(|_| {
+            let (index, running, peak) = (index.clone(), running.clone(), peak.clone());
+            tokio::spawn(async move {
+                let _permit = index.query_permit().await?;
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                let stories = index.fetch_unlimited::<Shard>(StoryQuery::FrontPage(), 10);
+                let stories = stories.await?;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Result::<_, WebError>::Ok(stories.len())
+            })
+        });
+        for res in futures::future::join_all(queries).await {
+            assert_eq!(10, res.expect("Query panicked")?);
+        }
+        assert_eq!(2, peak.load(Ordering::SeqCst));
+
+        // A query that can't get a slot in time fails
+        index.set_query_limit(&QueryLimitConfig {
+            max_concurrent: 1,
+            max_wait_ms: 10,
+        });
+        let permit = index.query_permit().await?;
+        assert!(matches!(
+            index.fetch::<Shard>(StoryQuery::FrontPage(), 10).await,
+            Err(WebError::Overloaded)
+        ));
+        drop(permit);
+        assert_eq!(
+            10,
+            index
+                .fetch::<Shard>(StoryQuery::FrontPage(), 10)
+                .await?
+                .len()
+        );
+
+        Ok(())
+    }
+}
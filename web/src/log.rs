@@ -0,0 +1,185 @@
+//! Logging setup, with support for JSON lines output for log aggregation.
+use std::fmt;
+
+use progscrape_scrapers::StoryDate;
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable output.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Initialize the global subscriber with the given filter and output format.
+pub fn init(format: LogFormat, env_filter: EnvFilter) {
+    match format {
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(env_filter).init(),
+        LogFormat::Json => tracing_subscriber::fmt()
+            .with_env_filter(env_filter)
+            .fmt_fields(JsonFields)
+            .event_format(JsonFormat)
+            .init(),
+    }
+}
+
+/// Collects the fields of an event or span into a JSON map.
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl<'a> Visit for JsonVisitor<'a> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
+
+fn write_json(writer: &mut Writer<'_>, value: &impl serde::Serialize) -> fmt::Result {
+    let json = serde_json::to_string(value).map_err(|_| fmt::Error)?;
+    writer.write_str(&json)
+}
+
+/// Formats span fields as a JSON object so they can be embedded in each event's span context.
+pub struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: tracing_subscriber::field::RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut map = Map::new();
+        fields.record(&mut JsonVisitor(&mut map));
+        write_json(&mut writer, &map)
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let mut map: Map<String, Value> = serde_json::from_str(&current.fields).unwrap_or_default();
+        fields.record(&mut JsonVisitor(&mut map));
+        current.fields = serde_json::to_string(&map).map_err(|_| fmt::Error)?;
+        Ok(())
+    }
+}
+
+/// Formats each event as a single line of JSON containing the timestamp, level, target, fields and enclosing spans.
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+
+        let mut spans = vec![];
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                let mut json = Map::new();
+                json.insert("name".into(), span.name().into());
+                if let Some(formatted) = span.extensions().get::<FormattedFields<N>>() {
+                    if let Ok(Value::Object(span_fields)) = serde_json::from_str(&formatted.fields)
+                    {
+                        json.extend(span_fields);
+                    }
+                }
+                spans.push(Value::Object(json));
+            }
+        }
+
+        let mut line = Map::new();
+        line.insert("timestamp".into(), StoryDate::now().to_rfc3339().into());
+        line.insert("level".into(), metadata.level().as_str().into());
+        line.insert("target".into(), metadata.target().into());
+        line.insert("fields".into(), Value::Object(fields));
+        if let Some(span) = spans.last() {
+            line.insert("span".into(), span.clone());
+        }
+        line.insert("spans".into(), Value::Array(spans));
+        write_json(&mut writer, &line)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().expect("Poisoned").write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new("info"))
+            .fmt_fields(JsonFields)
+            .event_format(JsonFormat)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", path = "/feed");
+            let _guard = span.enter();
+            tracing::info!(count = 3, "Served \"stories\"");
+            tracing::debug!("Filtered out");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().expect("Poisoned").clone()).expect("UTF-8");
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(1, lines.len(), "{}", output);
+        let json: Value = serde_json::from_str(lines[0]).expect("Invalid JSON");
+        assert_eq!("INFO", json["level"]);
+        assert_eq!(module_path!(), json["target"]);
+        assert_eq!("Served \"stories\"", json["fields"]["message"]);
+        assert_eq!(3, json["fields"]["count"]);
+        assert_eq!("request", json["span"]["name"]);
+        assert_eq!("/feed", json["spans"][0]["path"]);
+    }
+}
@@ -7,8 +7,9 @@ use std::time::Instant;
 use clap::{Parser, Subcommand};
 use config::Config;
 use progscrape_application::{
-    MemIndex, PersistLocation, Storage, StorageWriter, StoryEvaluator, StoryIndex,
+    MemIndex, PersistLocation, ShardLocation, Storage, StorageWriter, StoryEvaluator, StoryIndex,
 };
+use progscrape_scrapers::{ScrapeArchive, ScrapeSource};
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::EnvFilter;
 use web::WebError;
@@ -17,12 +18,22 @@ use crate::auth::Auth;
 use crate::index::Index;
 
 mod auth;
+mod charset;
 mod config;
 mod cron;
+mod dead_letter;
+mod export;
 mod filters;
+mod icons;
 mod index;
+mod log;
+mod raw_responses;
+mod read_time;
+mod redirects;
+mod request_id;
 mod resource;
 mod serve_static_files;
+mod source_status;
 mod static_files;
 mod web;
 
@@ -37,6 +48,15 @@ struct Args {
     )]
     log: Option<String>,
 
+    #[arg(
+        long,
+        env = "SERVER_LOG_FORMAT",
+        value_enum,
+        default_value_t,
+        help = "Logging output format"
+    )]
+    log_format: log::LogFormat,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -49,6 +69,13 @@ pub enum Command {
 
         #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Backup output path")]
         backup_path: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
     },
     Serve {
         #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
@@ -57,6 +84,13 @@ pub enum Command {
         #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Backup output path")]
         backup_path: Option<PathBuf>,
 
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
+
         #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
         root: Option<PathBuf>,
 
@@ -76,13 +110,132 @@ pub enum Command {
             help = "Fixed authorization value for testing purposes"
         )]
         fixed_auth_value: Option<String>,
+
+        #[arg(
+            long,
+            env = "SCRAPE_ARCHIVE",
+            value_name = "DIR",
+            value_hint = clap::ValueHint::DirPath,
+            help = "Replay scrapes from a saved HTTP archive rather than the network"
+        )]
+        scrape_archive: Option<PathBuf>,
     },
     Initialize {
         #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
         persist_path: PathBuf,
 
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
+        root: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "STORIES",
+            help = "Commit the index every this many stories, rather than once at the end"
+        )]
+        commit_batch_size: Option<usize>,
+    },
+    /// Rebuild the index with the current schema from the scrapes it contains.
+    Migrate {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
+        root: Option<PathBuf>,
+    },
+    /// Copy a consistent snapshot of the index's files, which is much faster to restore than re-ingesting a backup.
+    Snapshot {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Snapshot output path")]
+        out: PathBuf,
+    },
+    /// Replace the index with a snapshot, once it has been validated.
+    Restore {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Snapshot path")]
+        snapshot: PathBuf,
+    },
+    /// Re-evaluate and re-write only the stories with scrapes from one source, eg: after fixing its scraper.
+    Reingest {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
+
         #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
         root: Option<PathBuf>,
+
+        #[arg(long, help = "The source to re-ingest (eg: reddit)")]
+        source: String,
+    },
+    /// Re-read the legacy import and merge it into an existing index, without duplicating the stories it already has.
+    ImportLegacy {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
+        root: Option<PathBuf>,
+    },
+    /// Write all of the stories from a single day, with their scrapes, to a JSON file for archival.
+    ExportDay {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(
+            long,
+            value_name = "FIRST..LAST=DIR",
+            help = "Store a range of shards in another directory"
+        )]
+        shard_location: Vec<ShardLocation>,
+
+        #[arg(long, value_name = "DATE", help = "The day to export (YYYY-MM-DD)")]
+        date: String,
+
+        #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath, help = "Output path")]
+        out: PathBuf,
     },
 }
 
@@ -115,15 +268,16 @@ async fn go() -> Result<(), WebError> {
             .from_env()?
     };
 
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    log::init(args.log_format, env_filter);
     tracing::info!("Logging initialized");
 
     match args.command {
         Command::Backup {
             persist_path,
             backup_path,
+            shard_location,
         } => {
-            let index = Index::initialize_with_persistence(persist_path)?;
+            let index = Index::initialize_with_persistence(persist_path, shard_location)?;
             index.backup(&backup_path)?;
         }
         Command::Serve {
@@ -133,11 +287,13 @@ async fn go() -> Result<(), WebError> {
             fixed_auth_value,
             listen_port,
             backup_path,
+            shard_location,
+            scrape_archive,
         } => {
             let persist_path = persist_path
                 .unwrap_or("target/index".into())
                 .canonicalize()?;
-            let index = Index::initialize_with_persistence(persist_path)?;
+            let index = Index::initialize_with_persistence(persist_path, shard_location)?;
             let root_path = root.unwrap_or(".".into()).canonicalize()?;
             let listen_port = listen_port
                 .map(|s| s.parse().expect("Failed to parse socket address"))
@@ -153,9 +309,20 @@ async fn go() -> Result<(), WebError> {
                     ));
                 }
             };
-            web::start_server(&root_path, backup_path, listen_port, index, auth).await?;
+            let archive = if let Some(scrape_archive) = scrape_archive {
+                tracing::info!("Replaying scrapes from archive {:?}", scrape_archive);
+                Some(ScrapeArchive::open(scrape_archive)?)
+            } else {
+                None
+            };
+            web::start_server(&root_path, backup_path, listen_port, index, archive, auth).await?;
         }
-        Command::Initialize { root, persist_path } => {
+        Command::Initialize {
+            root,
+            persist_path,
+            commit_batch_size,
+            shard_location,
+        } => {
             if persist_path.exists() {
                 return Err(WebError::ArgumentsInvalid(format!(
                     "Path {} must not exist",
@@ -182,7 +349,11 @@ async fn go() -> Result<(), WebError> {
 
             // Now, import those stories
             let story_start = Instant::now();
-            let mut index = StoryIndex::new(PersistLocation::Path(persist_path))?;
+            let mut index = StoryIndex::new(PersistLocation::with_shard_locations(
+                persist_path,
+                shard_location,
+            ))?;
+            index.set_commit_batch_size(commit_batch_size);
             index.insert_scrape_collections(&eval, memindex.get_all_stories())?;
             let story_index_time = story_start.elapsed();
 
@@ -200,6 +371,137 @@ async fn go() -> Result<(), WebError> {
                 story_index_time.as_secs()
             );
         }
+        Command::Migrate {
+            root,
+            persist_path,
+            shard_location,
+        } => {
+            let persist_path = persist_path.canonicalize()?;
+            let resource_path = root.unwrap_or(".".into()).canonicalize()?.join("resource");
+            let reader = BufReader::new(File::open(resource_path.join("config/config.json"))?);
+            let config: Config = serde_json::from_reader(reader)?;
+            let eval = StoryEvaluator::new(&config.tagger, &config.score, &config.scrape);
+
+            let start = Instant::now();
+            let location = PersistLocation::with_shard_locations(persist_path, shard_location);
+            let result = StoryIndex::migrate(&location, &eval, config.id_scheme)?;
+            tracing::info!(
+                "Completed migration in {}s: {} scrape(s), {} stories before, {} stories after",
+                start.elapsed().as_secs(),
+                result.scrapes,
+                result
+                    .stories_before
+                    .map(|count| count.to_string())
+                    .unwrap_or("unknown".into()),
+                result.stories_after
+            );
+        }
+        Command::Snapshot {
+            persist_path,
+            out,
+            shard_location,
+        } => {
+            let persist_path = persist_path.canonicalize()?;
+            let index = StoryIndex::new(PersistLocation::with_shard_locations(
+                persist_path,
+                shard_location,
+            ))?;
+            let start = Instant::now();
+            let result = index.snapshot(&out)?;
+            tracing::info!(
+                "Wrote snapshot of {} stories in {} shard(s) to {:?} in {}s",
+                result.stories,
+                result.shards,
+                out,
+                start.elapsed().as_secs()
+            );
+        }
+        Command::Restore {
+            persist_path,
+            snapshot,
+            shard_location,
+        } => {
+            let start = Instant::now();
+            let location = PersistLocation::with_shard_locations(persist_path, shard_location);
+            let result = StoryIndex::restore_snapshot(&snapshot, &location)?;
+            tracing::info!(
+                "Restored {} stories in {} shard(s) from {:?} in {}s",
+                result.stories,
+                result.shards,
+                snapshot,
+                start.elapsed().as_secs()
+            );
+        }
+        Command::Reingest {
+            root,
+            persist_path,
+            source,
+            shard_location,
+        } => {
+            let source = ScrapeSource::try_from_str(&source).ok_or_else(|| {
+                WebError::ArgumentsInvalid(format!("Unknown source '{}'", source))
+            })?;
+            let persist_path = persist_path.canonicalize()?;
+            let resource_path = root.unwrap_or(".".into()).canonicalize()?.join("resource");
+            let reader = BufReader::new(File::open(resource_path.join("config/config.json"))?);
+            let config: Config = serde_json::from_reader(reader)?;
+            let eval = StoryEvaluator::new(&config.tagger, &config.score, &config.scrape);
+
+            let start = Instant::now();
+            let mut index = StoryIndex::new(PersistLocation::with_shard_locations(
+                persist_path,
+                shard_location,
+            ))?;
+            index.set_id_scheme(config.id_scheme);
+            let count = index.reingest_source(&eval, source)?;
+            tracing::info!(
+                "Re-ingested {} story(s) from {:?} in {}s",
+                count,
+                source,
+                start.elapsed().as_secs()
+            );
+        }
+        Command::ImportLegacy {
+            root,
+            persist_path,
+            shard_location,
+        } => {
+            let persist_path = persist_path.canonicalize()?;
+            let root = root.unwrap_or(".".into()).canonicalize()?;
+            let reader = BufReader::new(File::open(root.join("resource/config/config.json"))?);
+            let config: Config = serde_json::from_reader(reader)?;
+            let eval = StoryEvaluator::new(&config.tagger, &config.score, &config.scrape);
+
+            let start = Instant::now();
+            let scrapes = progscrape_scrapers::import_legacy(&root)?;
+            let mut index = StoryIndex::new(PersistLocation::with_shard_locations(
+                persist_path,
+                shard_location,
+            ))?;
+            let result = index.import_scrapes(&eval, scrapes)?;
+            tracing::info!(
+                "Imported {} legacy scrape(s) in {}s: {} story(s) added, {} merged",
+                result.scrapes,
+                start.elapsed().as_secs(),
+                result.added,
+                result.merged
+            );
+        }
+        Command::ExportDay {
+            persist_path,
+            date,
+            out,
+            shard_location,
+        } => {
+            let day = export::parse_day(&date)?;
+            let persist_path = persist_path.canonicalize()?;
+            let index = StoryIndex::new(PersistLocation::with_shard_locations(
+                persist_path,
+                shard_location,
+            ))?;
+            let count = export::export_day(&index, day, &out)?;
+            tracing::info!("Exported {} story(s) from {} to {:?}", count, date, out);
+        }
     };
     Ok(())
 }
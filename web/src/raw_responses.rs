@@ -0,0 +1,245 @@
+//! An opt-in store of the raw responses behind each scrape, so that a bad scrape can be replayed through the scrapers
+//! offline. The directory doubles as a scrape archive: its manifest maps each URL to the most recent response, so it
+//! can be replayed with `--scrape-archive`.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use progscrape_scrapers::{
+    ScrapeSource, ScraperHttpResponseInput, ScraperHttpResult, StoryDate, StoryDuration,
+    ARCHIVE_MANIFEST,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{cron::CronInterval, web::WebError};
+
+/// The list of stored responses, oldest first.
+const RAW_RESPONSE_INDEX: &str = "responses.json";
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct RawResponseConfig {
+    /// The directory that responses are stored in. Nothing is stored unless this is set.
+    pub path: Option<PathBuf>,
+    /// The maximum number of responses to keep.
+    pub max_count: usize,
+    /// Responses older than this are discarded.
+    pub max_age: (usize, CronInterval),
+    /// The oldest responses are discarded once the total size of the store exceeds this many bytes.
+    pub max_bytes: u64,
+}
+
+impl Default for RawResponseConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_count: 500,
+            max_age: (3, CronInterval::Day),
+            max_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RawResponse {
+    /// The name of the file holding the response, made from the source and time of the scrape.
+    pub file: String,
+    pub source: ScrapeSource,
+    pub date: StoryDate,
+    pub url: String,
+    pub length: u64,
+}
+
+/// The responses in the configured directory. The index is read from disk when the store is first used, or when the
+/// configured directory changes.
+#[derive(Default)]
+pub struct RawResponseStore {
+    root: Option<PathBuf>,
+    responses: Vec<RawResponse>,
+}
+
+impl RawResponseStore {
+    /// Load the index for `root`, if it isn't already loaded.
+    fn load(&mut self, root: &Path) -> Result<(), WebError> {
+        if self.root.as_deref() == Some(root) {
+            return Ok(());
+        }
+        let index = root.join(RAW_RESPONSE_INDEX);
+        self.responses = if index.exists() {
+            serde_json::from_reader(std::io::BufReader::new(std::fs::File::open(index)?))?
+        } else {
+            vec![]
+        };
+        self.root = Some(root.to_owned());
+        Ok(())
+    }
+
+    /// Store the body of every successful fetch, whether or not it could be scraped, then discard the oldest
+    /// responses until the store is within its limits. Returns the number of responses stored.
+    pub fn insert(
+        &mut self,
+        config: &RawResponseConfig,
+        source: ScrapeSource,
+        scrapes: &HashMap<String, ScraperHttpResult>,
+        now: StoryDate,
+    ) -> Result<usize, WebError> {
+        let Some(root) = &config.path else {
+            return Ok(0);
+        };
+        std::fs::create_dir_all(root)?;
+        self.load(root)?;
+
+        let mut count = 0;
+        for (url, result) in scrapes.iter().sorted_by_key(|(url, _)| *url) {
+            let (ScraperHttpResult::Ok(input, _)
+            | ScraperHttpResult::Err(ScraperHttpResponseInput::Ok(input), _)) = result
+            else {
+                continue;
+            };
+            // Several URLs may be fetched for a source in the same second
+            let file = (0..)
+                .map(|n| format!("{}-{}-{}.txt", source.into_str(), now.timestamp(), n))
+                .find(|file| !root.join(file).exists())
+                .expect("File name");
+            std::fs::write(root.join(&file), input)?;
+            self.responses.push(RawResponse {
+                file,
+                source,
+                date: now,
+                url: url.clone(),
+                length: input.len() as u64,
+            });
+            count += 1;
+        }
+
+        let max_age =
+            StoryDuration::seconds(config.max_age.1.as_duration(config.max_age.0).as_secs() as i64);
+        let mut total = self.responses.iter().map(|r| r.length).sum::<u64>();
+        while let Some(response) = self.responses.first() {
+            if self.responses.len() > config.max_count
+                || total > config.max_bytes
+                || now - response.date > max_age
+            {
+                total -= response.length;
+                let response = self.responses.remove(0);
+                if let Err(e) = std::fs::remove_file(root.join(&response.file)) {
+                    tracing::warn!("Failed to remove raw response {}: {:?}", response.file, e);
+                }
+            } else {
+                break;
+            }
+        }
+
+        self.write_index(root)?;
+        Ok(count)
+    }
+
+    /// Write the index, and the archive manifest pointing each URL at its most recent response.
+    fn write_index(&self, root: &Path) -> Result<(), WebError> {
+        let manifest: HashMap<_, _> = self
+            .responses
+            .iter()
+            .map(|response| (&response.url, &response.file))
+            .collect();
+        serde_json::to_writer(
+            std::fs::File::create(root.join(ARCHIVE_MANIFEST))?,
+            &manifest,
+        )?;
+        serde_json::to_writer(
+            std::fs::File::create(root.join(RAW_RESPONSE_INDEX))?,
+            &self.responses,
+        )?;
+        Ok(())
+    }
+
+    /// All of the stored responses, newest first.
+    pub fn entries(&mut self, config: &RawResponseConfig) -> Result<Vec<RawResponse>, WebError> {
+        let Some(root) = &config.path else {
+            return Ok(vec![]);
+        };
+        self.load(root)?;
+        Ok(self.responses.iter().rev().cloned().collect())
+    }
+
+    /// Read a stored response. Only files in the index can be read.
+    pub fn read(&mut self, config: &RawResponseConfig, file: &str) -> Result<String, WebError> {
+        let Some(root) = &config.path else {
+            return Err(WebError::NotFound);
+        };
+        self.load(root)?;
+        if !self.responses.iter().any(|response| response.file == file) {
+            return Err(WebError::NotFound);
+        }
+        Ok(std::fs::read_to_string(root.join(file))?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use progscrape_scrapers::{ScrapeArchive, ScrapeConfig, Scrapers};
+
+    const HN_INPUT: &str = include_str!("../../scrapers/testdata/hn1.html");
+
+    #[test]
+    fn test_store_and_replay() -> Result<(), WebError> {
+        let dir = tempfile::tempdir()?;
+        let config = RawResponseConfig {
+            path: Some(dir.path().to_owned()),
+            max_count: 2,
+            ..Default::default()
+        };
+        let scrapers = Scrapers::new(&ScrapeConfig::default());
+        let url = "https://news.ycombinator.com/news".to_owned();
+        let scrape = |input: &str| {
+            HashMap::from_iter([(
+                url.clone(),
+                scrapers.scrape_http_result(
+                    ScrapeSource::HackerNews,
+                    ScraperHttpResponseInput::Ok(input.to_owned()),
+                ),
+            )])
+        };
+        let now = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+
+        let mut store = RawResponseStore::default();
+        store.insert(&config, ScrapeSource::HackerNews, &scrape(HN_INPUT), now)?;
+        let entries = store.entries(&config)?;
+        assert_eq!(1, entries.len());
+        assert_eq!(url, entries[0].url);
+        assert_eq!(ScrapeSource::HackerNews, entries[0].source);
+        assert_eq!(HN_INPUT.len() as u64, entries[0].length);
+
+        // The response can be read back, and replayed through the scraper from a fresh store
+        let stored = RawResponseStore::default().read(&config, &entries[0].file)?;
+        assert_eq!(HN_INPUT, stored);
+        let archive = ScrapeArchive::open(dir.path())?;
+        match scrapers.scrape_http_result(ScrapeSource::HackerNews, archive.fetch(&url)?) {
+            ScraperHttpResult::Ok(_, scrapes) => assert!(!scrapes.is_empty()),
+            _ => panic!("Expected the replayed response to scrape"),
+        }
+        assert!(matches!(
+            store.read(&config, "../../etc/passwd"),
+            Err(WebError::NotFound)
+        ));
+
+        // Only the newest responses are kept
+        for i in 1..=2 {
+            store.insert(
+                &config,
+                ScrapeSource::HackerNews,
+                &scrape(&format!("broken {}", i)),
+                StoryDate::from_seconds(now.timestamp() + i).expect("Date"),
+            )?;
+        }
+        let later = store.entries(&config)?;
+        assert_eq!(2, later.len());
+        assert!(later
+            .iter()
+            .all(|response| response.file != entries[0].file));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+//! Estimated read times for linked articles. Articles are fetched separately from scrapes, so this is opt-in and
+//! limited to a handful of fetches each time the job runs.
+use std::collections::HashSet;
+
+use progscrape_application::{Story, StoryIdentifier, StoryIndex};
+use progscrape_scrapers::{article_word_count, StoryUrl};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{index::Index, web::WebError};
+
+#[derive(Serialize, Deserialize)]
+pub struct ReadTimeConfig {
+    /// Articles are only fetched if this is set.
+    pub enabled: bool,
+    pub words_per_minute: u32,
+    /// The maximum number of articles fetched each time the job runs.
+    pub max_fetches_per_run: usize,
+}
+
+impl Default for ReadTimeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            words_per_minute: 230,
+            max_fetches_per_run: 10,
+        }
+    }
+}
+
+impl ReadTimeConfig {
+    /// The estimated read time of an article in whole minutes, rounding up. Returns `None` if the page has no text.
+    pub fn estimate(&self, html: &str) -> Option<u32> {
+        let words = article_word_count(html)? as u32;
+        Some(words.div_ceil(self.words_per_minute.max(1)))
+    }
+}
+
+/// Whether a story links back to one of the sites it was scraped from (ie: a self post or a comment thread), in which
+/// case there's no article to read.
+pub fn is_self_post<S>(story: &Story<S>) -> bool {
+    story.scrapes.keys().any(|id| {
+        StoryUrl::parse(id.comments_url()).is_some_and(|url| url.host() == story.url.host())
+    })
+}
+
+/// Stories whose articles have already been fetched, successfully or not, so that we don't fetch them again.
+pub type ReadTimeAttempts = Mutex<HashSet<StoryIdentifier>>;
+
+/// Fetch the articles of hot set stories that don't have a read time yet, returning the estimate for each URL.
+pub async fn update_read_times(
+    client: &reqwest::Client,
+    index: &Index<StoryIndex>,
+    config: &ReadTimeConfig,
+    attempts: &ReadTimeAttempts,
+) -> Result<Vec<(String, Option<u32>)>, WebError> {
+    if !config.enabled {
+        return Ok(vec![]);
+    }
+    let stories = {
+        let mut attempts = attempts.lock().await;
+        index
+            .hot_set()
+            .await?
+            .into_iter()
+            .filter(|story| story.read_time.is_none() && !is_self_post(story))
+            .filter(|story| attempts.insert(story.id.clone()))
+            .take(config.max_fetches_per_run)
+            .collect::<Vec<_>>()
+    };
+
+    // Fetch one at a time so that we don't hammer any one site
+    let mut results = vec![];
+    for story in stories {
+        let url = story.url.raw().to_owned();
+        let resp = client
+            .get(&url)
+            .header("User-Agent", "progscrape")
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        let minutes = match resp {
+            Ok(resp) => config.estimate(&resp.text().await?),
+            Err(e) => {
+                tracing::warn!("Failed to fetch article {}: {:?}", url, e);
+                None
+            }
+        };
+        if let Some(minutes) = minutes {
+            index.set_read_time(story.id, minutes).await?;
+        }
+        results.push((url, minutes));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use progscrape_scrapers::{
+        hacker_news::HackerNewsStory, reddit::RedditStory, StoryDate, TypedScrape,
+    };
+
+    #[test]
+    fn test_estimate() -> Result<(), WebError> {
+        let html = std::fs::read_to_string("../scrapers/testdata/article1.html")?;
+        let config = ReadTimeConfig::default();
+        // 503 words at 230 words per minute
+        assert_eq!(Some(3), config.estimate(&html));
+        let config = ReadTimeConfig {
+            words_per_minute: 1000,
+            ..Default::default()
+        };
+        assert_eq!(Some(1), config.estimate(&html));
+        assert_eq!(None, config.estimate("<div>No article here</div>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_self_post() {
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let story = |scrape: TypedScrape| {
+            Story::new_from_parts(
+                "Title".into(),
+                scrape.url.clone(),
+                date,
+                0.0,
+                [],
+                [(scrape.id.clone(), ())],
+            )
+        };
+        let url = |s: &str| StoryUrl::parse(s).expect("URL");
+
+        let article = HackerNewsStory::new_with_defaults(
+            "1",
+            date,
+            "Title",
+            url("https://example.com/article"),
+        );
+        assert!(!is_self_post(&story(article.into())));
+        let ask_hn = HackerNewsStory::new_with_defaults(
+            "2",
+            date,
+            "Ask HN: Title",
+            url("https://news.ycombinator.com/item?id=2"),
+        );
+        assert!(is_self_post(&story(ask_hn.into())));
+        let reddit = RedditStory::new_subsource_with_defaults(
+            "3",
+            "rust",
+            date,
+            "Title",
+            url("https://www.reddit.com/r/rust/comments/3/title/"),
+        );
+        assert!(is_self_post(&story(reddit.into())));
+    }
+}
@@ -0,0 +1,211 @@
+//! Resolves the URLs of link shorteners (ie: t.co) to the URLs they redirect to, so that stories dedup against and
+//! display the linked article rather than the shortener. Each URL costs at least one extra fetch, so this is opt-in
+//! and limited to the configured hosts.
+use std::{collections::HashMap, time::Duration};
+
+use hyper::header::LOCATION;
+use progscrape_scrapers::{ScraperHttpResult, StoryUrl};
+use serde::{Deserialize, Serialize};
+
+use crate::web::{ProxyConfig, WebError};
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedirectConfig {
+    /// URLs are only resolved if this is set.
+    pub enabled: bool,
+    /// The hosts whose URLs are resolved, matched against the host without any `www.` prefix.
+    pub hosts: Vec<String>,
+    /// The most redirects followed for a single URL. Longer chains keep the original URL.
+    pub max_hops: usize,
+    /// The time allowed to follow the redirects of a single URL, in seconds.
+    pub timeout_secs: u64,
+}
+
+impl Default for RedirectConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hosts: ["t.co", "bit.ly", "tinyurl.com", "buff.ly", "ow.ly"]
+                .map(str::to_owned)
+                .to_vec(),
+            max_hops: 5,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl RedirectConfig {
+    fn should_resolve(&self, url: &StoryUrl) -> bool {
+        self.enabled && self.hosts.iter().any(|host| host == url.host())
+    }
+}
+
+/// Build an HTTP client that reports redirects rather than following them, so that each hop can be checked.
+pub fn create_client(proxy: &ProxyConfig) -> Result<reqwest::Client, WebError> {
+    Ok(proxy
+        .client_builder()?
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?)
+}
+
+/// Follow the redirects from `url`, returning the URL of the first response that isn't a redirect. Returns `None` if
+/// the chain is longer than `max_hops`, leaves http(s), times out or fails.
+pub async fn resolve_redirects(
+    client: &reqwest::Client,
+    config: &RedirectConfig,
+    url: &StoryUrl,
+) -> Option<StoryUrl> {
+    let resolve = async {
+        let mut current = url::Url::parse(url.raw()).ok()?;
+        for _ in 0..=config.max_hops {
+            let resp = client
+                .get(current.clone())
+                .header("User-Agent", "progscrape")
+                .send()
+                .await
+                .map_err(|e| tracing::warn!("Failed to resolve redirect {}: {:?}", current, e))
+                .ok()?;
+            if !resp.status().is_redirection() {
+                return StoryUrl::parse(current);
+            }
+            let location = resp.headers().get(LOCATION)?.to_str().ok()?;
+            let next = current.join(location).ok()?;
+            if !matches!(next.scheme(), "http" | "https") {
+                tracing::warn!("Not following redirect from {} to {}", current, next);
+                return None;
+            }
+            current = next;
+        }
+        tracing::warn!("Too many redirects from {}", url);
+        None
+    };
+    match tokio::time::timeout(Duration::from_secs(config.timeout_secs), resolve).await {
+        Ok(resolved) => resolved,
+        Err(_) => {
+            tracing::warn!("Timed out resolving redirects from {}", url);
+            None
+        }
+    }
+}
+
+/// Replace the URLs of scrapes on the configured hosts with the URLs they redirect to. URLs that can't be resolved are
+/// kept as-is. Returns the number of URLs that were replaced.
+pub async fn resolve_scrape_urls(
+    client: &reqwest::Client,
+    config: &RedirectConfig,
+    scrapes: &mut HashMap<String, ScraperHttpResult>,
+) -> usize {
+    if !config.enabled {
+        return 0;
+    }
+    // The same link is often scraped from several pages, so only resolve it once
+    let mut resolved = HashMap::new();
+    let mut count = 0;
+    for result in scrapes.values_mut() {
+        let ScraperHttpResult::Ok(_, scrapes) = result else {
+            continue;
+        };
+        for scrape in scrapes.iter_mut() {
+            if !config.should_resolve(&scrape.url) {
+                continue;
+            }
+            if !resolved.contains_key(&scrape.url) {
+                let url = resolve_redirects(client, config, &scrape.url).await;
+                resolved.insert(scrape.url.clone(), url);
+            }
+            if let Some(url) = &resolved[&scrape.url] {
+                tracing::info!("Resolved {} to {}", scrape.url, url);
+                scrape.url = url.clone();
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{
+        response::{IntoResponse, Redirect},
+        routing::get,
+        Router,
+    };
+    use progscrape_scrapers::{hacker_news::HackerNewsStory, StoryDate};
+
+    #[tokio::test]
+    async fn test_resolve_redirects() -> Result<(), WebError> {
+        // A chain of redirects, including a relative one, ending at an article
+        let app = Router::new()
+            .route("/short", get(|| async { Redirect::temporary("/hop") }))
+            .route("/hop", get(|| async { Redirect::permanent("final") }))
+            .route("/final", get(|| async { "Article" }))
+            .route("/loop", get(|| async { Redirect::temporary("/loop") }))
+            .route(
+                "/ftp",
+                get(|| async { Redirect::temporary("ftp://example.com/file") }),
+            )
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "Slow".into_response()
+                }),
+            );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let base = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(axum::Server::from_tcp(listener)?.serve(app.into_make_service()));
+
+        let client = create_client(&ProxyConfig::default())?;
+        let config = RedirectConfig {
+            enabled: true,
+            hosts: vec!["127.0.0.1".into()],
+            timeout_secs: 1,
+            ..Default::default()
+        };
+        let url = |path: &str| StoryUrl::parse(format!("{}{}", base, path)).expect("URL");
+        let resolve = |path: &str| {
+            let url = url(path);
+            let (client, config) = (&client, &config);
+            async move { resolve_redirects(client, config, &url).await }
+        };
+
+        assert_eq!(Some(url("/final")), resolve("/short").await);
+        assert_eq!(Some(url("/final")), resolve("/final").await);
+        assert_eq!(None, resolve("/loop").await);
+        assert_eq!(None, resolve("/ftp").await);
+        assert_eq!(None, resolve("/slow").await);
+
+        // Chains longer than the limit aren't followed
+        let short_config = RedirectConfig {
+            max_hops: 1,
+            hosts: config.hosts.clone(),
+            ..config
+        };
+        assert_eq!(
+            None,
+            resolve_redirects(&client, &short_config, &url("/short")).await
+        );
+
+        // Scrapes get the resolved URL, falling back to the original
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let scrape =
+            |id: &str, url| HackerNewsStory::new_with_defaults(id, date, "Title", url).into();
+        let mut scrapes = HashMap::from([(
+            "page".to_owned(),
+            ScraperHttpResult::Ok(
+                "".into(),
+                vec![scrape("1", url("/short")), scrape("2", url("/loop"))],
+            ),
+        )]);
+        assert_eq!(1, resolve_scrape_urls(&client, &config, &mut scrapes).await);
+        let ScraperHttpResult::Ok(_, scrapes) = &scrapes["page"] else {
+            panic!("Unexpected result");
+        };
+        assert_eq!(url("/final"), scrapes[0].url);
+        assert_eq!(url("/loop"), scrapes[1].url);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+//! Tags each request with an ID that is logged with everything the request does and echoed back to the client, so
+//! that client-reported errors can be matched with the server logs.
+use axum::{
+    extract::State,
+    http::{header::HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+use crate::resource::Resources;
+
+/// The longest incoming request ID that we accept. Longer IDs are replaced with a generated one.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestIdConfig {
+    /// The header the request ID is read from and echoed back in.
+    pub header: String,
+    /// Whether to use the request ID provided by the client or reverse proxy. If false, one is always generated.
+    pub trust_incoming: bool,
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        Self {
+            header: "x-request-id".into(),
+            trust_incoming: true,
+        }
+    }
+}
+
+/// The incoming request ID, if it's trusted and reasonable.
+fn incoming_request_id(config: &RequestIdConfig, value: Option<&HeaderValue>) -> Option<String> {
+    if !config.trust_incoming {
+        return None;
+    }
+    let value = value?.to_str().ok()?.trim();
+    if value.is_empty()
+        || value.len() > MAX_REQUEST_ID_LENGTH
+        || !value.chars().all(|c| c.is_ascii_graphic())
+    {
+        return None;
+    }
+    Some(value.to_owned())
+}
+
+pub async fn request_id<B>(
+    State(resources): State<Resources>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let config = resources.config().request_id.clone();
+    let Ok(header) = HeaderName::try_from(config.header.as_str()) else {
+        tracing::error!("Invalid request ID header '{}'", config.header);
+        return next.run(req).await;
+    };
+    let id = incoming_request_id(&config, req.headers().get(&header))
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!("request", request_id = %id);
+    let mut response = next.run(req).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(header, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    use crate::web::WebError;
+
+    async fn request(header: Option<&str>) -> Result<Option<String>, WebError> {
+        let resources = crate::resource::start_watcher("../resource").await?;
+        let app = Router::new()
+            .route("/", get(|| async { "Hello" }))
+            .layer(middleware::from_fn_with_state(resources, request_id));
+        let mut req = Request::builder().uri("/");
+        if let Some(header) = header {
+            req = req.header("X-Request-Id", header);
+        }
+        let response = app
+            .oneshot(req.body(Body::empty()).expect("Request"))
+            .await
+            .expect("Infallible");
+        Ok(response
+            .headers()
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned))
+    }
+
+    #[tokio::test]
+    async fn test_provided_request_id() -> Result<(), WebError> {
+        assert_eq!(Some("abc-123".to_owned()), request(Some("abc-123")).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generated_request_id() -> Result<(), WebError> {
+        let id = request(None).await?.expect("Missing request ID");
+        assert!(uuid::Uuid::parse_str(&id).is_ok(), "{}", id);
+        assert_ne!(Some(id), request(None).await?);
+
+        // Unreasonable IDs are replaced
+        let long = "x".repeat(MAX_REQUEST_ID_LENGTH + 1);
+        let id = request(Some(&long)).await?.expect("Missing request ID");
+        assert!(uuid::Uuid::parse_str(&id).is_ok(), "{}", id);
+        Ok(())
+    }
+}
@@ -1,11 +1,10 @@
 use notify::RecursiveMode;
 use notify::Watcher;
 use progscrape_scrapers::Scrapers;
-use std::borrow::Borrow;
 use std::fs::File;
 use std::io::BufReader;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tera::Tera;
@@ -15,6 +14,7 @@ use progscrape_application::StoryEvaluator;
 
 use crate::config::Config;
 use crate::filters::*;
+use crate::icons::IconResolver;
 use crate::static_files::StaticFileRegistry;
 use crate::web::WebError;
 
@@ -26,6 +26,8 @@ struct ResourceHolder {
     config: Arc<Config>,
     story_evaluator: Arc<StoryEvaluator>,
     scrapers: Arc<Scrapers>,
+    http_client: reqwest::Client,
+    icons: Arc<IconResolver>,
 }
 
 #[derive(Clone)]
@@ -52,15 +54,53 @@ impl Resources {
     pub fn scrapers(&self) -> Arc<Scrapers> {
         self.rx.borrow().scrapers.clone()
     }
+    pub fn http_client(&self) -> reqwest::Client {
+        self.rx.borrow().http_client.clone()
+    }
+    pub fn icons(&self) -> Arc<IconResolver> {
+        self.rx.borrow().icons.clone()
+    }
+}
+
+/// The path of the active theme's resources, if a theme is configured and it exists on disk.
+fn theme_path(resource_path: &Path, config: &Config) -> Option<PathBuf> {
+    let theme = config.theme.as_ref()?;
+    let path = resource_path.join("themes").join(theme);
+    if path.is_dir() {
+        Some(path)
+    } else {
+        tracing::error!(
+            "Theme '{}' not found at {}, using the default theme",
+            theme,
+            path.to_string_lossy()
+        );
+        None
+    }
+}
+
+/// Registers all the files in a static file directory, if it exists.
+fn register_files_if_exists(
+    static_files: &mut StaticFileRegistry,
+    path: PathBuf,
+) -> Result<(), WebError> {
+    if path.is_dir() {
+        static_files.register_files(path)?;
+    }
+    Ok(())
 }
 
 fn create_static_files(
     resource_path: &Path,
+    theme_path: Option<&Path>,
     css: String,
     admin_css: String,
 ) -> Result<StaticFileRegistry, WebError> {
     let mut static_files = StaticFileRegistry::default();
     static_files.register_files(resource_path.join("static/"))?;
+    // Theme files override the defaults with the same name
+    if let Some(theme_path) = theme_path {
+        register_files_if_exists(&mut static_files, theme_path.join("static/"))?;
+    }
     let mut css_vars = ":root {\n".to_owned();
     for key in static_files.keys() {
         let url = static_files.lookup_key(&key).unwrap_or_default();
@@ -72,45 +112,72 @@ fn create_static_files(
     Ok(static_files)
 }
 
-fn create_static_files_root(resource_path: &Path) -> Result<StaticFileRegistry, WebError> {
+fn create_static_files_root(
+    resource_path: &Path,
+    theme_path: Option<&Path>,
+) -> Result<StaticFileRegistry, WebError> {
     let mut static_files = StaticFileRegistry::default();
     static_files.register_files(resource_path.join("static/root/"))?;
+    if let Some(theme_path) = theme_path {
+        register_files_if_exists(&mut static_files, theme_path.join("static/root/"))?;
+    }
     Ok(static_files)
 }
 
 fn create_templates(
     resource_path: &Path,
+    theme_path: Option<&Path>,
     static_files: Arc<StaticFileRegistry>,
+    compact_number: &CompactNumberConfig,
+    base_url: url::Url,
 ) -> Result<Tera, WebError> {
-    let mut tera = Tera::new(
-        resource_path
-            .join("templates/**/*")
-            .to_string_lossy()
-            .borrow(),
-    )?;
+    let glob = |path: &Path| path.join("templates/**/*").to_string_lossy().into_owned();
+    let mut tera = if let Some(theme_path) = theme_path {
+        // Theme templates take precedence, and any templates the theme doesn't provide come from the defaults
+        let mut tera = Tera::parse(&glob(theme_path))?;
+        tera.extend(&Tera::parse(&glob(resource_path))?)?;
+        tera
+    } else {
+        Tera::new(&glob(resource_path))?
+    };
     tera.register_filter("comma", CommaFilter::default());
+    tera.register_filter(
+        "compact_number",
+        CompactNumberFilter::new(compact_number.clone()),
+    );
     tera.register_filter("static", StaticFileFilter::new(static_files));
+    tera.register_filter("absolute_url", AbsoluteUrlFilter::new(base_url));
     tera.register_filter("relative_time", RelativeTimeFilter::default());
     tera.register_filter("absolute_time", AbsoluteTimeFilter::default());
     tera.register_filter("approx_time", ApproxTimeFilter::default());
+    tera.register_filter("rfc2822_time", Rfc2822TimeFilter::default());
     Ok(tera)
 }
 
-fn create_css(resource_path: &Path) -> Result<String, WebError> {
-    let opts = grass::Options::default()
-        .input_syntax(grass::InputSyntax::Scss)
-        .style(grass::OutputStyle::Expanded)
-        .load_path(resource_path.join("static/css/"));
-    let out = grass::from_string("@use 'root'".to_owned(), &opts)?;
-    Ok(out)
+fn create_css(resource_path: &Path, theme_path: Option<&Path>) -> Result<String, WebError> {
+    create_scss(resource_path, theme_path, "root")
 }
 
-fn create_admin_css(resource_path: &Path) -> Result<String, WebError> {
-    let opts = grass::Options::default()
+fn create_admin_css(resource_path: &Path, theme_path: Option<&Path>) -> Result<String, WebError> {
+    create_scss(resource_path, theme_path, "admin")
+}
+
+fn create_scss(
+    resource_path: &Path,
+    theme_path: Option<&Path>,
+    root: &str,
+) -> Result<String, WebError> {
+    let theme_css_path = theme_path.map(|path| path.join("static/css/"));
+    let mut opts = grass::Options::default()
         .input_syntax(grass::InputSyntax::Scss)
-        .style(grass::OutputStyle::Expanded)
-        .load_path(resource_path.join("static/css/"));
-    let out = grass::from_string("@use 'admin'".to_owned(), &opts)?;
+        .style(grass::OutputStyle::Expanded);
+    // Theme stylesheets are resolved before the defaults
+    if let Some(theme_css_path) = &theme_css_path {
+        opts = opts.load_path(theme_css_path);
+    }
+    let default_css_path = resource_path.join("static/css/");
+    opts = opts.load_path(&default_css_path);
+    let out = grass::from_string(format!("@use '{}'", root), &opts)?;
     Ok(out)
 }
 
@@ -121,18 +188,49 @@ fn create_config(resource_path: &Path) -> Result<Config, WebError> {
 
 fn generate<T: AsRef<Path>>(resource_path: T) -> Result<ResourceHolder, WebError> {
     let resource_path = resource_path.as_ref();
-    let css = create_css(resource_path)?;
-    let admin_css = create_admin_css(resource_path)?;
-    let static_files = Arc::new(create_static_files(resource_path, css, admin_css)?);
-    let static_files_root = Arc::new(create_static_files_root(resource_path)?);
-    let templates = Arc::new(create_templates(resource_path, static_files.clone())?);
-    let config = Arc::new(create_config(resource_path)?);
+    let config = create_config(resource_path)?;
+    generate_with_config(resource_path, config)
+}
+
+fn generate_with_config(resource_path: &Path, config: Config) -> Result<ResourceHolder, WebError> {
+    let base_url = url::Url::parse(&config.base_url).inspect_err(|e| {
+        tracing::error!("Invalid base_url '{}': {}", config.base_url, e);
+    })?;
+    config.cron.validate().map_err(|e| {
+        tracing::error!("Invalid cron config: {}", e);
+        WebError::InvalidConfig(e)
+    })?;
+    config.charset.fallback_encoding().map_err(|e| {
+        tracing::error!("Invalid charset config: {}", e);
+        e
+    })?;
+    let http_client = config.proxy.create_client()?;
+    let theme_path = theme_path(resource_path, &config);
+    let theme_path = theme_path.as_deref();
+    let css = create_css(resource_path, theme_path)?;
+    let admin_css = create_admin_css(resource_path, theme_path)?;
+    let static_files = Arc::new(create_static_files(
+        resource_path,
+        theme_path,
+        css,
+        admin_css,
+    )?);
+    let static_files_root = Arc::new(create_static_files_root(resource_path, theme_path)?);
+    let templates = Arc::new(create_templates(
+        resource_path,
+        theme_path,
+        static_files.clone(),
+        &config.compact_number,
+        base_url,
+    )?);
+    let config = Arc::new(config);
     let story_evaluator = Arc::new(StoryEvaluator::new(
         &config.tagger,
         &config.score,
         &config.scrape,
     ));
     let scrapers = Arc::new(Scrapers::new(&config.scrape));
+    let icons = Arc::new(IconResolver::new(&config.icons));
     Ok(ResourceHolder {
         templates,
         static_files,
@@ -140,6 +238,8 @@ fn generate<T: AsRef<Path>>(resource_path: T) -> Result<ResourceHolder, WebError
         config,
         story_evaluator,
         scrapers,
+        http_client,
+        icons,
     })
 }
 
@@ -182,3 +282,76 @@ pub async fn start_watcher<T: AsRef<Path>>(resource_path: T) -> Result<Resources
     });
     Ok(Resources { rx })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tera::Context;
+
+    #[test]
+    fn test_theme() -> Result<(), WebError> {
+        let resource_path = &Path::new("../resource").canonicalize()?;
+        let default = generate_with_config(resource_path, create_config(resource_path)?)?;
+        let mut config = create_config(resource_path)?;
+        config.theme = Some("dark".into());
+        let themed = generate_with_config(resource_path, config)?;
+
+        // The theme's stylesheet replaces the default one
+        let style = |holder: &ResourceHolder| {
+            holder
+                .static_files
+                .lookup_key("style.css")
+                .expect("Missing style.css")
+                .to_owned()
+        };
+        assert_ne!(style(&default), style(&themed));
+
+        // Templates not provided by the theme fall back to the defaults, and reference the theme's stylesheet
+        let mut context = Context::new();
+        context.insert("top_tags", &Vec::<String>::new());
+        context.insert("stories", &Vec::<String>::new());
+        context.insert("now", &progscrape_scrapers::StoryDate::now());
+        let html = themed.templates.render("index.html", &context)?;
+        assert!(html.contains(&style(&themed)));
+        assert!(!html.contains(&style(&default)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_theme() -> Result<(), WebError> {
+        let resource_path = &Path::new("../resource").canonicalize()?;
+        let mut config = create_config(resource_path)?;
+        config.theme = Some("this-theme-does-not-exist".into());
+        let holder = generate_with_config(resource_path, config)?;
+        assert!(holder
+            .templates
+            .get_template_names()
+            .any(|x| x == "index.html"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_absolute_url() -> Result<(), WebError> {
+        let resource_path = &Path::new("../resource").canonicalize()?;
+        let mut config = create_config(resource_path)?;
+        config.base_url = "https://example.com/".into();
+        let holder = generate_with_config(resource_path, config)?;
+        let mut tera = (*holder.templates).clone();
+        let url = tera.render_str("{{ '/tag/rust/feed.xml' | absolute_url }}", &Context::new())?;
+        assert_eq!("https://example.com/tag/rust/feed.xml", url);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_base_url() -> Result<(), WebError> {
+        let resource_path = &Path::new("../resource").canonicalize()?;
+        let mut config = create_config(resource_path)?;
+        config.base_url = "not a url".into();
+        assert!(matches!(
+            generate_with_config(resource_path, config),
+            Err(WebError::URLParseError(_))
+        ));
+        Ok(())
+    }
+}
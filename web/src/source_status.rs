@@ -0,0 +1,123 @@
+//! The outcome of the most recent scrape of each source, so that we can see why a source stopped producing stories.
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use progscrape_scrapers::{ScrapeSource, ScraperHttpResult, StoryDate, TypedScrapeMap};
+use serde::Serialize;
+
+#[derive(Clone, Serialize)]
+pub struct SourceError {
+    pub date: StoryDate,
+    pub url: String,
+    pub error: String,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct SourceStatus {
+    /// The last time that any URL for this source was scraped without error.
+    pub last_success: Option<StoryDate>,
+    /// The most recent error, cleared by a scrape where every URL succeeds.
+    pub last_error: Option<SourceError>,
+}
+
+#[derive(Default)]
+pub struct SourceStatusStore {
+    statuses: TypedScrapeMap<SourceStatus>,
+}
+
+impl SourceStatusStore {
+    /// Record the results of a scrape of `source`. Both HTTP and parse errors are recorded, and if more than one URL
+    /// failed, the error for the first URL is kept.
+    pub fn record(
+        &mut self,
+        source: ScrapeSource,
+        scrapes: &HashMap<String, ScraperHttpResult>,
+        now: StoryDate,
+    ) {
+        let mut status = self.statuses.get(source).clone();
+        let mut error = None;
+        for (url, result) in scrapes.iter().sorted_by_key(|(url, _)| *url) {
+            match result {
+                ScraperHttpResult::Ok(..) | ScraperHttpResult::NotModified => {
+                    status.last_success = Some(now)
+                }
+                ScraperHttpResult::Err(_, e) if error.is_none() => {
+                    error = Some(SourceError {
+                        date: now,
+                        url: url.clone(),
+                        error: e.clone(),
+                    })
+                }
+                ScraperHttpResult::Err(..) => {}
+            }
+        }
+        if !scrapes.is_empty() {
+            status.last_error = error;
+        }
+        self.statuses.set(source, status);
+    }
+
+    pub fn statuses(&self) -> &TypedScrapeMap<SourceStatus> {
+        &self.statuses
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use progscrape_scrapers::{ScrapeConfig, ScraperHttpResponseInput, Scrapers};
+
+    #[test]
+    fn test_record_error() {
+        let scrapers = Scrapers::new(&ScrapeConfig::default());
+        let url = "https://www.reddit.com/r/rust/.json".to_owned();
+        let scrape = |input| {
+            HashMap::from_iter([(
+                url.clone(),
+                scrapers.scrape_http_result(ScrapeSource::Reddit, input),
+            )])
+        };
+        let day = |d| StoryDate::year_month_day(2023, 1, d).expect("Date");
+        let mut store = SourceStatusStore::default();
+
+        // A scrape that fails to parse
+        store.record(
+            ScrapeSource::Reddit,
+            &scrape(ScraperHttpResponseInput::Ok("{\"broken\"".into())),
+            day(1),
+        );
+        let status = store.statuses().get(ScrapeSource::Reddit);
+        let error = status.last_error.as_ref().expect("Expected an error");
+        assert_eq!(url, error.url);
+        assert_eq!(day(1), error.date);
+        assert!(!error.error.is_empty());
+        assert!(status.last_success.is_none());
+        assert!(store
+            .statuses()
+            .get(ScrapeSource::HackerNews)
+            .last_error
+            .is_none());
+
+        // HTTP errors replace it
+        store.record(
+            ScrapeSource::Reddit,
+            &scrape(ScraperHttpResponseInput::HTTPError(500, "Error".into())),
+            day(2),
+        );
+        let status = store.statuses().get(ScrapeSource::Reddit);
+        assert_eq!(
+            Some(day(2)),
+            status.last_error.as_ref().map(|error| error.date)
+        );
+
+        // A successful scrape clears it
+        store.record(
+            ScrapeSource::Reddit,
+            &HashMap::from_iter([(url.clone(), ScraperHttpResult::NotModified)]),
+            day(3),
+        );
+        let status = store.statuses().get(ScrapeSource::Reddit);
+        assert!(status.last_error.is_none());
+        assert_eq!(Some(day(3)), status.last_success);
+    }
+}
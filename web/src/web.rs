@@ -1,14 +1,26 @@
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Instant};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use axum::{
     body::HttpBody,
+    error_handling::HandleErrorLayer,
     extract::{Path, Query, State},
     middleware::{self, Next},
     response::{Html, IntoResponse, Redirect, Response},
-    routing::{get, post},
+    routing::{delete, get, post},
     Extension, Json, Router,
 };
-use hyper::{service::Service, Body, HeaderMap, Method, Request, StatusCode};
+use encoding_rs::Encoding;
+use futures::{StreamExt, TryStreamExt};
+use hyper::{header::CONTENT_TYPE, service::Service, Body, HeaderMap, Method, Request, StatusCode};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tera::Context;
@@ -18,18 +30,25 @@ use unwrap_infallible::UnwrapInfallible;
 
 use crate::{
     auth::Auth,
-    cron::{Cron, CronHistory},
+    charset,
+    cron::{Cron, CronHistory, CronInterval},
+    dead_letter::DeadLetterStore,
+    filters::AbsoluteUrlFilter,
     index::Index,
+    raw_responses::RawResponseStore,
+    read_time::ReadTimeAttempts,
+    redirects,
     resource::{self, Resources},
     serve_static_files,
+    source_status::SourceStatusStore,
 };
 use progscrape_application::{
-    PersistError, Shard, Story, StoryEvaluator, StoryIdentifier, StoryIndex, StoryQuery,
-    StoryRender, StoryScore, TagSet,
+    EvaluatedStory, ImportResult, PersistError, Shard, Story, StoryEvaluator, StoryIdentifier,
+    StoryIndex, StoryQuery, StoryRender, StoryScore, StoryScoreConfig, StoryScorer, TagSet,
 };
 use progscrape_scrapers::{
-    ScrapeCollection, ScrapeSource, ScraperHttpResponseInput, ScraperHttpResult, StoryDate,
-    TypedScrape,
+    ScrapeArchive, ScrapeCollection, ScrapeSource, ScraperHttpResponseInput, ScraperHttpResult,
+    ScraperPossibilities, Scrapers, StoryDate, TypedScrape,
 };
 
 #[derive(Debug, Error)]
@@ -62,16 +81,95 @@ pub enum WebError {
     LogSetupError(#[from] tracing_subscriber::filter::ParseError),
     #[error("Log setup error")]
     LogSetup2Error(#[from] tracing_subscriber::filter::FromEnvError),
+    #[error("Invalid URL")]
+    URLParseError(#[from] url::ParseError),
     #[error("Item not found")]
     NotFound,
     #[error("Invalid command-line arguments")]
     ArgumentsInvalid(String),
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Request timed out")]
+    Timeout,
+    #[error("Too many queries")]
+    Overloaded,
+}
+
+impl WebError {
+    fn status(&self) -> StatusCode {
+        match self {
+            WebError::NotFound => StatusCode::NOT_FOUND,
+            WebError::BadRequest(_) | WebError::ArgumentsInvalid(_) => StatusCode::BAD_REQUEST,
+            WebError::Timeout | WebError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// A stable, machine-readable code for API clients.
+    fn code(&self) -> &'static str {
+        match self {
+            WebError::NotFound => "not_found",
+            WebError::BadRequest(_) => "bad_request",
+            WebError::ArgumentsInvalid(_) => "invalid_arguments",
+            WebError::Timeout => "timeout",
+            WebError::Overloaded => "overloaded",
+            _ => "internal_error",
+        }
+    }
 }
 
 impl IntoResponse for WebError {
     fn into_response(self) -> Response {
-        let body = format!("Error: {:?}", self);
-        (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+        let status = self.status();
+        let api_error = ApiError {
+            error: match &self {
+                WebError::BadRequest(message) | WebError::ArgumentsInvalid(message) => {
+                    message.clone()
+                }
+                _ => self.to_string(),
+            },
+            code: self.code(),
+        };
+        let body = match self {
+            WebError::BadRequest(message) => format!(">progscrape: {} ▒", message),
+            _ => format!("Error: {:?}", self),
+        };
+        let mut response = (status, body).into_response();
+        response.extensions_mut().insert(api_error);
+        response
+    }
+}
+
+/// The error body returned to JSON API clients, in place of the text body that browsers get.
+#[derive(Clone, Debug, Serialize)]
+struct ApiError {
+    error: String,
+    code: &'static str,
+}
+
+/// Whether errors for this request should be returned as JSON: either it's an API route, or the client asked for JSON.
+fn wants_json_errors<B>(req: &Request<B>) -> bool {
+    req.uri().path().starts_with("/api/")
+        || req
+            .headers()
+            .get(hyper::header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .map(|accept| accept.contains("application/json"))
+            .unwrap_or_default()
+}
+
+/// Replaces the body of error responses with an [`ApiError`] for JSON clients.
+async fn json_errors<B>(req: Request<B>, next: Next<B>) -> Response {
+    let json = wants_json_errors(&req);
+    let mut response = next.run(req).await;
+    if !json {
+        return response;
+    }
+    match response.extensions_mut().remove::<ApiError>() {
+        Some(error) => (response.status(), Json(error)).into_response(),
+        None => response,
     }
 }
 
@@ -82,6 +180,12 @@ struct AdminState {
     cron: Arc<Mutex<Cron>>,
     cron_history: Arc<Mutex<CronHistory>>,
     backup_path: Option<std::path::PathBuf>,
+    archive: Option<Arc<ScrapeArchive>>,
+    dead_letters: Arc<Mutex<DeadLetterStore>>,
+    raw_responses: Arc<Mutex<RawResponseStore>>,
+    source_status: Arc<Mutex<SourceStatusStore>>,
+    validators: Arc<HttpValidatorCache>,
+    read_time_attempts: Arc<ReadTimeAttempts>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -138,8 +242,50 @@ async fn ensure_slash<B>(req: Request<B>, next: Next<B>) -> Result<Response, Sta
     Ok(next.run(req).await)
 }
 
+/// Reports whether this server should receive traffic, which is once the index has been warmed up.
+async fn readiness(State(ready): State<Arc<AtomicBool>>) -> impl IntoResponse {
+    if ready.load(Ordering::Acquire) {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "warming up")
+    }
+}
+
 async fn handle_404() -> impl IntoResponse {
-    (StatusCode::NOT_FOUND, ">progscrape: 404 ▒")
+    let mut response = (StatusCode::NOT_FOUND, ">progscrape: 404 ▒").into_response();
+    response.extensions_mut().insert(ApiError {
+        error: WebError::NotFound.to_string(),
+        code: WebError::NotFound.code(),
+    });
+    response
+}
+
+fn request_timeout(resources: &Resources) -> Option<Duration> {
+    resources
+        .config()
+        .request_timeout_secs
+        .map(Duration::from_secs)
+}
+
+/// Cut off requests that take longer than `timeout` with a 503. Only the routes already added to `router` are
+/// affected, so long-running routes can be added afterwards.
+fn with_request_timeout<S: Clone + Send + Sync + 'static>(
+    router: Router<S>,
+    timeout: Option<Duration>,
+) -> Router<S> {
+    let Some(timeout) = timeout else {
+        return router;
+    };
+    router.layer(
+        tower::ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(
+                move |_: tower::BoxError| async move {
+                    tracing::warn!("Request timed out after {:?}", timeout);
+                    WebError::Timeout
+                },
+            ))
+            .timeout(timeout),
+    )
 }
 
 pub fn admin_routes<S: Clone + Send + Sync + 'static>(
@@ -148,26 +294,48 @@ pub fn admin_routes<S: Clone + Send + Sync + 'static>(
     cron: Arc<Mutex<Cron>>,
     cron_history: Arc<Mutex<CronHistory>>,
     backup_path: Option<std::path::PathBuf>,
+    archive: Option<Arc<ScrapeArchive>>,
     auth: Auth,
 ) -> Router<S> {
-    Router::new()
+    let timeout = request_timeout(&resources);
+    let routes = Router::new()
         .route("/", get(admin))
         .route("/cron/", get(admin_cron))
         .route("/cron/", post(admin_cron_post))
-        .route("/cron/backup", post(admin_cron_backup))
-        .route("/cron/refresh", post(admin_cron_refresh))
-        .route("/cron/scrape/:service", post(admin_cron_scrape))
         .route("/headers/", get(admin_headers))
         .route("/scrape/", get(admin_scrape))
         .route("/scrape/test", post(admin_scrape_test))
+        .route("/scrape/failures/", get(admin_scrape_failures))
+        .route("/scrape/raw/", get(admin_scrape_raw))
+        .route("/scrape/raw/:file", get(admin_scrape_raw_download))
+        .route(
+            "/scrape/possibilities.json",
+            get(admin_scrape_possibilities),
+        )
         .route("/index/", get(admin_index_status))
         .route("/index/frontpage/", get(admin_status_frontpage))
+        .route("/index/merges/", get(admin_index_merges))
+        .route("/index/recent/", get(admin_index_recent))
         .route(
             "/index/frontpage/scoretuner/",
             get(admin_index_frontpage_scoretuner),
         )
+        .route("/index/score-preview", post(admin_index_score_preview))
         .route("/index/shard/:shard/", get(admin_status_shard))
         .route("/index/story/:story/", get(admin_status_story))
+        .route("/index/story/:story", delete(admin_index_story_delete))
+        .route("/story/:story/hide", post(admin_story_hide))
+        .route("/story/:story/unhide", post(admin_story_unhide))
+        .route("/story/:story/pin", post(admin_story_pin))
+        .route("/story/:story/unpin", post(admin_story_unpin));
+    // Cron jobs and re-ingestion are expected to run for longer than any request timeout
+    with_request_timeout(routes, timeout)
+        .route("/cron/backup", post(admin_cron_backup))
+        .route("/cron/refresh", post(admin_cron_refresh))
+        .route("/cron/read_time", post(admin_cron_read_time))
+        .route("/cron/scrape/:service", post(admin_cron_scrape))
+        .route("/index/reingest/:source", post(admin_index_reingest))
+        .route("/index/import-legacy", post(admin_index_import_legacy))
         .fallback(handle_404)
         .with_state(AdminState {
             resources,
@@ -175,6 +343,12 @@ pub fn admin_routes<S: Clone + Send + Sync + 'static>(
             cron,
             cron_history,
             backup_path,
+            archive,
+            dead_letters: Default::default(),
+            raw_responses: Default::default(),
+            source_status: Default::default(),
+            validators: Default::default(),
+            read_time_attempts: Default::default(),
         })
         .route_layer(middleware::from_fn_with_state(auth, authorize))
 }
@@ -189,62 +363,103 @@ fn start_cron(
     // Router doesn't require poll_ready
     let mut router = router.into_make_service();
     tokio::spawn(async move {
-        let mut router = router.call(()).await.unwrap_infallible();
+        let router = router.call(()).await.unwrap_infallible();
+        // Woken when a task finishes, so that any waiting for a free slot can start
+        let finished = Arc::new(tokio::sync::Notify::new());
         loop {
-            let ready = cron
-                .lock()
-                .await
-                .tick(&resources.config().cron.jobs, Instant::now());
+            let ready = {
+                let mut cron = cron.lock().await;
+                cron.set_max_concurrent(Some(resources.config().cron.max_concurrent.max(1)));
+                cron.tick(&resources.config().cron.jobs, Instant::now())
+            };
 
             // Sleep if no tasks are available
             if ready.is_empty() {
-                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(10)) => {}
+                    _ = finished.notified() => {}
+                }
                 continue;
             }
 
             for ready_uri in ready {
-                let uri = match ready_uri.parse() {
-                    Ok(uri) => uri,
-                    Err(e) => {
-                        tracing::error!("Failed to parse URI: {} (error was {:?})", ready_uri, e);
-                        continue;
-                    }
-                };
-                tracing::info!("Running cron task: POST '{}'...", ready_uri);
-                let mut req = Request::<Body>::default();
-                *req.method_mut() = Method::POST;
-                *req.uri_mut() = uri;
-                (*req.extensions_mut()).insert(CronMarker {});
-                let response = router.call(req).await.unwrap_infallible();
-                let status = response.status();
-                tracing::info!("Cron task '{}' ran with status {}", ready_uri, status);
-
-                // TODO: Do we need to read data() multiple times?
-                let body = match response.into_body().data().await {
-                    Some(Ok(b)) => String::from_utf8_lossy(&b).to_string(),
-                    x => {
-                        tracing::error!("Could not retrieve body from cron response: {:?}", x);
-                        "(empty)".into()
-                    }
-                };
-
-                cron_history.lock().await.insert(
-                    resources.config().cron.history_age,
-                    resources.config().cron.history_count,
-                    ready_uri,
-                    status.as_u16(),
-                    body,
+                let (mut router, cron, cron_history, resources, finished) = (
+                    router.clone(),
+                    cron.clone(),
+                    cron_history.clone(),
+                    resources.clone(),
+                    finished.clone(),
                 );
+                tokio::spawn(async move {
+                    run_cron_task(&mut router, &cron, &cron_history, &resources, ready_uri).await;
+                    finished.notify_one();
+                });
             }
         }
     });
 }
 
+/// Run a single cron task through the router, recording its outcome.
+async fn run_cron_task(
+    router: &mut Router<()>,
+    cron: &Mutex<Cron>,
+    cron_history: &Mutex<CronHistory>,
+    resources: &Resources,
+    ready_uri: String,
+) {
+    let uri = match ready_uri.parse() {
+        Ok(uri) => uri,
+        Err(e) => {
+            tracing::error!("Failed to parse URI: {} (error was {:?})", ready_uri, e);
+            cron.lock().await.report(
+                resources.config().cron.retry_backoff,
+                &ready_uri,
+                false,
+                Instant::now(),
+            );
+            return;
+        }
+    };
+    tracing::info!("Running cron task: POST '{}'...", ready_uri);
+    let mut req = Request::<Body>::default();
+    *req.method_mut() = Method::POST;
+    *req.uri_mut() = uri;
+    (*req.extensions_mut()).insert(CronMarker {});
+    let response = router.call(req).await.unwrap_infallible();
+    let status = response.status();
+    tracing::info!("Cron task '{}' ran with status {}", ready_uri, status);
+
+    // TODO: Do we need to read data() multiple times?
+    let body = match response.into_body().data().await {
+        Some(Ok(b)) => String::from_utf8_lossy(&b).to_string(),
+        x => {
+            tracing::error!("Could not retrieve body from cron response: {:?}", x);
+            "(empty)".into()
+        }
+    };
+
+    cron.lock().await.report(
+        resources.config().cron.retry_backoff,
+        &ready_uri,
+        status.is_success(),
+        Instant::now(),
+    );
+
+    cron_history.lock().await.insert(
+        resources.config().cron.history_age,
+        resources.config().cron.history_count,
+        ready_uri,
+        status.as_u16(),
+        body,
+    );
+}
+
 pub async fn start_server<P1: AsRef<std::path::Path>, P2: Into<std::path::PathBuf>>(
     root_path: P1,
     backup_path: Option<P2>,
     address: SocketAddr,
     index: Index<StoryIndex>,
+    archive: Option<ScrapeArchive>,
     auth: Auth,
 ) -> Result<(), WebError> {
     let root_path = root_path.as_ref();
@@ -253,6 +468,63 @@ pub async fn start_server<P1: AsRef<std::path::Path>, P2: Into<std::path::PathBu
     let resource_path = root_path.join("resource");
 
     let resources = resource::start_watcher(resource_path).await?;
+    index.set_ingest_config(&resources.config().ingest);
+    index.set_query_limit(&resources.config().query_limit);
+    index
+        .set_search_weights(resources.config().score.search_weights())
+        .await?;
+    index.set_id_scheme(resources.config().id_scheme).await?;
+    index.set_durability(resources.config().durability);
+
+    // Any hot set loaded from disk is served until a fresh one has been computed
+    {
+        let (index, resources) = (index.clone(), resources.clone());
+        tokio::spawn(async move {
+            let config = resources.config();
+            if let Err(e) = index.refresh_hot_set(&config.hot_set).await {
+                tracing::error!("Failed to compute the initial hot set: {:?}", e);
+            } else if config.hot_set.persist_interval_secs.is_some() {
+                if let Err(e) = index.persist_hot_set().await {
+                    tracing::error!("Failed to save the hot set: {:?}", e);
+                }
+            }
+        });
+    }
+    {
+        let (index, resources) = (index.clone(), resources.clone());
+        tokio::spawn(async move {
+            loop {
+                let interval = resources.config().hot_set.persist_interval_secs;
+                tokio::time::sleep(std::time::Duration::from_secs(interval.unwrap_or(60))).await;
+                if interval.is_some() {
+                    if let Err(e) = index.persist_hot_set().await {
+                        tracing::error!("Failed to save the hot set: {:?}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    // Until the caches are warm, the readiness endpoint keeps traffic away
+    let ready = Arc::new(AtomicBool::new(!resources.config().warmup.enabled));
+    if resources.config().warmup.enabled {
+        let (index, resources, ready) = (index.clone(), resources.clone(), ready.clone());
+        tokio::spawn(async move {
+            let config = resources.config();
+            match index
+                .warm_up(
+                    &resources.story_evaluator(),
+                    &config.warmup,
+                    &config.hot_set,
+                )
+                .await
+            {
+                Ok(duration) => tracing::info!("Warmed up the index in {:?}", duration),
+                Err(e) => tracing::error!("Failed to warm up the index: {:?}", e),
+            }
+            ready.store(true, Ordering::Release);
+        });
+    }
 
     let cron = Arc::new(Mutex::new(Cron::new_with_jitter(-20..=20)));
     let cron_history = Arc::new(Mutex::new(CronHistory::default()));
@@ -260,9 +532,19 @@ pub async fn start_server<P1: AsRef<std::path::Path>, P2: Into<std::path::PathBu
     // build our application with a route
     let app = Router::new()
         .route("/", get(root))
+        .route("/api/stories/since", get(api_stories_since))
+        .route("/api/random", get(api_random))
+        .route("/random", get(random))
+        .route("/tag/:tag/feed.xml", get(tag_feed))
+        .route("/tags", get(tags))
+        .route("/api/tags", get(api_tags))
+        .route("/new", get(newest))
+        .route("/new/feed.xml", get(newest_feed))
         .with_state((index.clone(), resources.clone()))
         .route("/static/:file", get(serve_static_files_immutable))
-        .with_state(resources.clone())
+        .route("/robots.txt", get(robots_txt))
+        .with_state(resources.clone());
+    let app = with_request_timeout(app, request_timeout(&resources))
         .nest(
             "/admin",
             admin_routes(
@@ -271,14 +553,22 @@ pub async fn start_server<P1: AsRef<std::path::Path>, P2: Into<std::path::PathBu
                 cron.clone(),
                 cron_history.clone(),
                 backup_path.map(P2::into),
+                archive.map(Arc::new),
                 auth,
             ),
         )
         .route_layer(middleware::from_fn(ensure_slash))
+        .route("/ready", get(readiness).with_state(ready))
         .route(
             "/:file",
             get(serve_static_files_well_known).with_state(resources.clone()),
-        );
+        )
+        .fallback(handle_404)
+        .layer(middleware::from_fn(json_errors))
+        .layer(middleware::from_fn_with_state(
+            resources.clone(),
+            crate::request_id::request_id,
+        ));
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
     tracing::info!("listening on http://{}", address);
@@ -290,19 +580,44 @@ pub async fn start_server<P1: AsRef<std::path::Path>, P2: Into<std::path::PathBu
         app.clone(),
     );
 
-    axum::Server::bind(&address)
+    resources
+        .config()
+        .server
+        .server(std::net::TcpListener::bind(address)?)?
         .serve(app.into_make_service())
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            tracing::info!("Shutting down");
+        })
         .await?;
 
+    // Make sure nothing queued is lost on the way out
+    index.flush().await?;
+    if resources.config().hot_set.persist_interval_secs.is_some() {
+        index.persist_hot_set().await?;
+    }
+
     Ok(())
 }
 
 fn render_stories<'a, S: 'a>(
-    eval: &StoryEvaluator,
+    resources: &Resources,
     iter: impl Iterator<Item = &'a Story<S>>,
 ) -> Vec<StoryRender> {
+    let (eval, icons, config) = (
+        resources.story_evaluator(),
+        resources.icons(),
+        resources.config(),
+    );
     iter.enumerate()
-        .map(|(n, x)| x.render(&eval.tagger, n))
+        .map(|(n, x)| {
+            let mut render = x.render(&eval.tagger, n);
+            render.icon_url = icons.resolve(&render.domain);
+            if let Some(alias) = config.domain_aliases.get(&render.domain) {
+                render.domain = alias.clone();
+            }
+            render
+        })
         .collect::<Vec<_>>()
 }
 
@@ -320,6 +635,22 @@ async fn hot_set(
     Ok(hot_set)
 }
 
+/// Move the pinned stories to the front of `stories` in the order they were pinned, fetching any that aren't already
+/// present.
+async fn pin_stories(
+    index: &Index<StoryIndex>,
+    stories: &mut Vec<Story<Shard>>,
+) -> Result<(), PersistError> {
+    let pinned = index.pinned_stories().await?;
+    if pinned.is_empty() {
+        return Ok(());
+    }
+    let ids: HashSet<_> = pinned.iter().map(|story| story.id.clone()).collect();
+    stories.retain(|story| !ids.contains(&story.id));
+    stories.splice(0..0, pinned);
+    Ok(())
+}
+
 macro_rules! context_assign {
     ($id:ident , ,) => {};
     ($id:ident , , $typ:ty) => {
@@ -361,415 +692,3504 @@ fn render(
         .into())
 }
 
-// basic handler that responds with a static string
-async fn root(
-    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
-    query: Query<HashMap<String, String>>,
-) -> Result<Html<String>, WebError> {
-    let now = now(&index).await?;
-    let stories = if let Some(search) = query.get("search") {
-        index
-            .fetch(
-                StoryQuery::from_search(&resources.story_evaluator().tagger, search),
-                30,
-            )
-            .await?
-    } else {
-        let mut vec = hot_set(now, &index, &resources.story_evaluator()).await?;
-        vec.truncate(30);
-        vec
-    };
-    let stories = render_stories(&resources.story_evaluator(), stories.iter());
-    let top_tags = vec![
-        "github.com",
-        "rust",
-        "amazon",
-        "java",
-        "health",
-        "wsj.com",
-        "security",
-        "apple",
-        "theverge.com",
-        "python",
-        "kernel",
-        "google",
-        "arstechnica.com",
-    ];
-    render(&resources, "index.html", context!(top_tags, stories, now))
+/// The default filter for the front page, applied when no search is provided. An empty filter shows the full hot set.
+#[derive(Default, Serialize, Deserialize)]
+pub struct FrontPageConfig {
+    /// If non-empty, only stories with at least one of these tags are shown.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// If non-empty, only stories scraped from at least one of these sources are shown.
+    #[serde(default)]
+    pub sources: Vec<ScrapeSource>,
+    /// A search to show in place of the hot set.
+    #[serde(default)]
+    pub search: Option<String>,
+    /// Collapse stories from the same day with near-identical titles, keeping the highest-scored one.
+    #[serde(default)]
+    pub dedupe_titles: bool,
+    /// The order of the hot set when no `sort` is requested.
+    #[serde(default)]
+    pub sort: FrontPageSort,
+    /// Only show hot set stories scraped from at least this many distinct sources. If too few stories qualify to
+    /// fill the page, the best of the rest are kept.
+    #[serde(default = "default_min_sources")]
+    pub min_sources: usize,
+    /// The most hot set stories from a single domain shown before stories from other domains. Any beyond this are
+    /// moved below the rest rather than dropped. If unset, there is no limit.
+    #[serde(default)]
+    pub max_per_domain: Option<usize>,
 }
 
-async fn admin(
-    Extension(user): Extension<CurrentUser>,
-    State(AdminState { resources, .. }): State<AdminState>,
-) -> Result<Html<String>, WebError> {
-    render(
-        &resources,
-        "admin/admin.html",
-        context!(user, config = resources.config()),
-    )
+fn default_min_sources() -> usize {
+    1
 }
 
-async fn admin_cron(
-    Extension(user): Extension<CurrentUser>,
-    State(AdminState {
-        cron,
-        cron_history,
-        resources,
-        ..
-    }): State<AdminState>,
-) -> Result<Html<String>, WebError> {
-    render(
-        &resources,
-        "admin/cron.html",
-        context!(
-            user,
-            config = resources.config(),
-            cron = cron.lock().await.inspect(),
-            history = cron_history.lock().await.entries()
-        ),
-    )
+/// The chronological view of the newest stories, which bypasses the scorer.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct NewFeedConfig {
+    /// The number of stories shown on `/new` and in its feed.
+    pub count: usize,
 }
 
-#[derive(Deserialize)]
-struct AdminCronRunParams {
-    /// Which job do we want to trigger?
-    cron: String,
+impl Default for NewFeedConfig {
+    fn default() -> Self {
+        Self { count: 30 }
+    }
 }
 
-async fn admin_cron_post(
-    State(AdminState { cron, .. }): State<AdminState>,
-    Json(params): Json<AdminCronRunParams>,
-) -> Result<Json<bool>, WebError> {
-    let success = cron.lock().await.trigger(params.cron);
-    Ok(success.into())
+/// The index of every tag at `/tags`.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagsConfig {
+    /// Tags on fewer stories than this are left out unless a lower `min` is requested, to hide the long tail.
+    pub min_count: usize,
+    /// Only stories within this window of the most recent story are counted. Every story is counted if unset.
+    pub window: Option<(usize, CronInterval)>,
+    /// The window for the trending tags above the front page. A shorter window makes them more reactive.
+    pub trending_window: (usize, CronInterval),
+    /// The number of trending tags shown above the front page.
+    pub trending_count: usize,
 }
 
-async fn admin_cron_backup(
-    State(AdminState {
-        backup_path, index, ..
-    }): State<AdminState>,
-) -> Result<Json<impl Serialize>, WebError> {
-    let results = if let Some(backup_path) = backup_path {
-        index.backup(&backup_path)?
-    } else {
-        vec![]
+impl Default for TagsConfig {
+    fn default() -> Self {
+        Self {
+            min_count: 1,
+            window: None,
+            trending_window: (1, CronInterval::Day),
+            trending_count: 13,
+        }
     }
-    .into_iter()
-    .map(|(shard, r)| (shard, r.map_err(|e| e.to_string())))
-    .collect_vec();
+}
 
-    Ok(Json(results))
+/// The rules served from `/robots.txt`.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct RobotsConfig {
+    pub groups: Vec<RobotsGroup>,
+    /// The path of the sitemap, if any, which is made absolute with the site's `base_url`.
+    pub sitemap: Option<String>,
 }
 
-async fn admin_cron_refresh(
-    State(AdminState {
-        resources, index, ..
-    }): State<AdminState>,
-) -> Result<Html<String>, WebError> {
-    index.refresh_hot_set().await?;
-    render(
-        &resources,
-        "admin/cron_refresh.html",
-        context!(config = resources.config()),
-    )
+/// The rules for one `User-agent` in `/robots.txt`.
+#[derive(Serialize, Deserialize)]
+pub struct RobotsGroup {
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+    #[serde(default)]
+    pub crawl_delay: Option<u32>,
 }
 
-async fn admin_cron_scrape(
-    State(AdminState {
-        resources, index, ..
-    }): State<AdminState>,
-    Path(source): Path<ScrapeSource>,
-) -> Result<Html<String>, WebError> {
-    let subsources = resources.scrapers().compute_scrape_subsources(source);
-    let urls = resources
-        .scrapers()
-        .compute_scrape_url_demands(source, subsources);
-    let mut map = HashMap::new();
-    for url in urls {
-        let resp = reqwest::Client::new()
-            .get(&url)
-            .header("User-Agent", "progscrape")
-            .send()
-            .await?;
-        let status = resp.status();
-        if status == StatusCode::OK {
-            map.insert(url, ScraperHttpResponseInput::Ok(resp.text().await?));
-        } else {
-            map.insert(
-                url,
-                ScraperHttpResponseInput::HTTPError(status.as_u16(), status.as_str().to_owned()),
-            );
+impl Default for RobotsConfig {
+    fn default() -> Self {
+        Self {
+            groups: vec![RobotsGroup {
+                user_agent: "*".into(),
+                allow: vec!["/".into()],
+                disallow: vec!["/admin/".into(), "/api/".into()],
+                crawl_delay: None,
+            }],
+            sitemap: None,
         }
     }
+}
 
-    let scrapes = HashMap::from_iter(
-        map.into_iter()
-            .map(|(k, v)| (k, resources.scrapers().scrape_http_result(source, v))),
-    );
-
-    for result in scrapes.values() {
-        match result {
-            ScraperHttpResult::Ok(_, scrapes) => {
-                index
-                    .insert_scrapes(resources.story_evaluator(), scrapes.clone().into_iter())
-                    .await?
+impl RobotsConfig {
+    fn render(&self, absolute_url: &AbsoluteUrlFilter) -> String {
+        let mut robots = String::new();
+        for group in &self.groups {
+            robots += &format!("User-agent: {}\n", group.user_agent);
+            for path in &group.allow {
+                robots += &format!("Allow: {}\n", path);
+            }
+            for path in &group.disallow {
+                robots += &format!("Disallow: {}\n", path);
+            }
+            if let Some(delay) = group.crawl_delay {
+                robots += &format!("Crawl-Delay: {}\n", delay);
+            }
+            robots += "\n";
+        }
+        if let Some(sitemap) = &self.sitemap {
+            match absolute_url.absolute(sitemap) {
+                Ok(url) => robots += &format!("Sitemap: {}\n", url),
+                Err(e) => tracing::error!("Invalid sitemap path '{}': {}", sitemap, e),
             }
-            ScraperHttpResult::Err(..) => {}
         }
+        robots
     }
+}
 
-    render(
-        &resources,
-        "admin/cron_scrape_run.html",
-        context!(
-            source,
-            config = resources.config(),
-            scrapes: HashMap<String, ScraperHttpResult>,
-        ),
-    )
+/// The orders that the hot set can be shown in. Searches are always shown in relevance order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrontPageSort {
+    /// By score, decayed by age.
+    #[default]
+    Hot,
+    /// Newest first.
+    New,
+    /// By points, summed across sources.
+    Top,
+    /// By comments, summed across sources.
+    Comments,
 }
 
-async fn admin_headers(
-    Extension(user): Extension<CurrentUser>,
-    State(AdminState { resources, .. }): State<AdminState>,
-    Query(query): Query<HashMap<String, String>>,
-    raw_headers: HeaderMap,
-) -> Result<Html<String>, WebError> {
-    let mut headers: HashMap<_, Vec<String>> = HashMap::new();
-    for (header, value) in raw_headers {
-        let name = header.map(|h| h.to_string()).unwrap_or("(missing)".into());
-        headers
-            .entry(name)
-            .or_default()
-            .push(String::from_utf8_lossy(value.as_bytes()).to_string());
+impl FrontPageSort {
+    /// Parse the `sort` query parameter, falling back to the given default if it's missing.
+    fn parse(sort: Option<&String>, default: FrontPageSort) -> Result<FrontPageSort, WebError> {
+        match sort.map(String::as_str) {
+            None | Some("") => Ok(default),
+            Some("hot") => Ok(FrontPageSort::Hot),
+            Some("new") => Ok(FrontPageSort::New),
+            Some("top") => Ok(FrontPageSort::Top),
+            Some("comments") => Ok(FrontPageSort::Comments),
+            Some(sort) => Err(WebError::BadRequest(format!("unknown sort '{}'", sort))),
+        }
     }
-    render(
-        &resources,
-        "admin/headers.html",
-        context!(user, query, headers),
-    )
-}
 
-async fn admin_scrape(
-    Extension(user): Extension<CurrentUser>,
-    State(AdminState { resources, .. }): State<AdminState>,
-) -> Result<Html<String>, WebError> {
-    let config = resources.config();
-    render(
-        &resources,
-        "admin/scrape.html",
-        context!(
-            user,
-            config,
-            scrapes = resources.scrapers().compute_scrape_possibilities(),
-            endpoint = "/admin/scrape/test"
-        ),
-    )
+    /// Re-order stories that are in hot order. Ties keep their hot order.
+    fn apply(self, index: &Index<StoryIndex>, stories: &mut [Story<Shard>]) {
+        match self {
+            FrontPageSort::Hot => {}
+            FrontPageSort::New => stories.sort_by_key(|story| std::cmp::Reverse(story.date)),
+            FrontPageSort::Top | FrontPageSort::Comments => {
+                index.with_hot_set_engagement(|engagement| {
+                    stories.sort_by_cached_key(|story| {
+                        let engagement = engagement.get(&story.id);
+                        let metric = if self == FrontPageSort::Top {
+                            engagement.and_then(|e| e.points)
+                        } else {
+                            engagement.and_then(|e| e.comments)
+                        };
+                        std::cmp::Reverse(metric.unwrap_or_default())
+                    })
+                });
+            }
+        }
+    }
 }
 
-#[derive(Deserialize)]
-struct AdminScrapeTestParams {
-    /// Which source do we want to scrape?
-    source: ScrapeSource,
-    subsources: Vec<String>,
-}
+impl FrontPageConfig {
+    fn matches<S>(&self, story: &Story<S>) -> bool {
+        (self.tags.is_empty() || self.tags.iter().any(|tag| story.tags.contains(tag)))
+            && (self.sources.is_empty()
+                || story
+                    .scrapes
+                    .keys()
+                    .any(|id| self.sources.contains(&id.source)))
+    }
 
-async fn admin_scrape_test(
-    Extension(user): Extension<CurrentUser>,
-    State(AdminState { resources, .. }): State<AdminState>,
-    Json(params): Json<AdminScrapeTestParams>,
-) -> Result<Html<String>, WebError> {
-    let urls = resources
-        .scrapers()
-        .compute_scrape_url_demands(params.source, params.subsources);
-    let mut map = HashMap::new();
-    for url in urls {
-        let resp = reqwest::Client::new()
-            .get(&url)
-            .header("User-Agent", "progscrape")
-            .send()
-            .await?;
-        let status = resp.status();
-        if status == StatusCode::OK {
-            map.insert(url, ScraperHttpResponseInput::Ok(resp.text().await?));
-        } else {
-            map.insert(
-                url,
-                ScraperHttpResponseInput::HTTPError(status.as_u16(), status.as_str().to_owned()),
-            );
+    /// Drop stories from fewer than `min_sources` distinct sources, keeping enough of the highest-ranked ones that
+    /// `max` stories remain where possible.
+    fn retain_min_sources<S>(&self, stories: &mut Vec<Story<S>>, max: usize) {
+        if self.min_sources <= 1 {
+            return;
         }
+        let qualifies = |story: &Story<S>| {
+            story.scrapes.keys().map(|id| id.source).unique().count() >= self.min_sources
+        };
+        let mut fill = max.saturating_sub(stories.iter().filter(|story| qualifies(story)).count());
+        stories.retain(|story| {
+            if qualifies(story) {
+                true
+            } else if fill > 0 {
+                fill -= 1;
+                true
+            } else {
+                false
+            }
+        });
     }
 
-    let scrapes = HashMap::from_iter(
-        map.into_iter()
-            .map(|(k, v)| (k, resources.scrapers().scrape_http_result(params.source, v))),
-    );
+    /// Move stories beyond the first `max_per_domain` from each domain below all the others, keeping their order.
+    fn cap_domains<S>(&self, stories: &mut Vec<Story<S>>) {
+        let Some(max_per_domain) = self.max_per_domain else {
+            return;
+        };
+        let mut counts = HashMap::<String, usize>::new();
+        let (kept, excess): (Vec<_>, Vec<_>) =
+            std::mem::take(stories).into_iter().partition(|story| {
+                let count = counts.entry(story.url.host().to_owned()).or_default();
+                *count += 1;
+                *count <= max_per_domain
+            });
+        stories.extend(kept.into_iter().chain(excess));
+    }
+}
 
-    render(
-        &resources,
-        "admin/scrape_test.html",
-        context!(user, scrapes: HashMap<String, ScraperHttpResult>),
-    )
+/// Drop stories whose title matches a higher-scored story from the same day, ignoring case, punctuation and spacing.
+/// This is a safety net for duplicates that URL normalization misses.
+fn dedupe_titles<S>(stories: Vec<Story<S>>) -> Vec<Story<S>> {
+    let mut seen = HashMap::<_, usize>::new();
+    let mut deduped: Vec<Story<S>> = vec![];
+    for story in stories {
+        let title = story
+            .title
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .map(str::to_lowercase)
+            .join(" ");
+        let key = (
+            title,
+            story.date.year(),
+            story.date.month(),
+            story.date.day(),
+        );
+        match seen.entry(key) {
+            Entry::Occupied(entry) => {
+                let existing = &mut deduped[*entry.get()];
+                if story.score > existing.score {
+                    *existing = story;
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(deduped.len());
+                deduped.push(story);
+            }
+        }
+    }
+    deduped
 }
 
-async fn admin_index_status(
-    Extension(user): Extension<CurrentUser>,
-    State(AdminState {
-        index, resources, ..
-    }): State<AdminState>,
-) -> Result<Html<String>, WebError> {
-    render(
-        &resources,
-        "admin/status.html",
-        context!(
-            user,
-            storage = index.story_count().await?,
-            config = resources.config()
-        ),
+/// Limits on user-provided searches and queries.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// The longest search, in characters, that we'll pass to the index.
+    pub max_length: usize,
+    /// The number of domains and tags to count for the search filters, or zero to skip counting them.
+    pub facets: usize,
+    /// The most stories fetched by a single page or API query. Larger counts are clamped to this.
+    pub max_query_count: usize,
+    /// Words shorter than this, in characters, are dropped from searches as they match too many stories. Words in
+    /// double quotes and tags (like `go`) are always kept.
+    pub min_token_length: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            max_length: 200,
+            facets: 0,
+            max_query_count: 500,
+            min_token_length: 3,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Clamp a requested or configured story count to `max_query_count`.
+    fn clamp_count(&self, count: usize) -> usize {
+        if count > self.max_query_count {
+            tracing::info!("Clamping query count {} to {}", count, self.max_query_count);
+            self.max_query_count
+        } else {
+            count
+        }
+    }
+
+    /// Trims and collapses the whitespace in a search, rejecting it if it's too long. Blank searches are dropped, and
+    /// searches made up only of short words are rejected.
+    fn normalize(
+        &self,
+        search: Option<&String>,
+        is_tag: impl Fn(&str) -> bool,
+    ) -> Result<Option<String>, WebError> {
+        let Some(search) = search else {
+            return Ok(None);
+        };
+        let search = search.split_whitespace().join(" ");
+        if search.is_empty() {
+            Ok(None)
+        } else if search.chars().count() > self.max_length {
+            Err(WebError::BadRequest(format!(
+                "searches are limited to {} characters",
+                self.max_length
+            )))
+        } else {
+            let filtered = self.filter_short_tokens(&search, is_tag);
+            if filtered.is_empty() {
+                Err(WebError::BadRequest(format!(
+                    "search for words of at least {} characters, or put shorter words in quotes",
+                    self.min_token_length
+                )))
+            } else {
+                Ok(Some(filtered))
+            }
+        }
+    }
+
+    /// Drops bare words shorter than `min_token_length` that aren't tags. Quoted phrases are kept whatever their
+    /// length, without their quotes, and an unterminated quote runs to the end of the search.
+    fn filter_short_tokens(&self, search: &str, is_tag: impl Fn(&str) -> bool) -> String {
+        let mut tokens = vec![];
+        for (i, part) in search.split('"').enumerate() {
+            if i % 2 == 1 {
+                let phrase = part.trim();
+                if !phrase.is_empty() {
+                    tokens.push(phrase);
+                }
+            } else {
+                tokens.extend(part.split_whitespace().filter(|token| {
+                    token.chars().count() >= self.min_token_length || is_tag(token)
+                }));
+            }
+        }
+        tokens.join(" ")
+    }
+}
+
+/// Fetch the stories for the front page, either from an explicit search or the configured default filter.
+async fn front_page_stories(
+    index: &Index<StoryIndex>,
+    eval: &StoryEvaluator,
+    config: &FrontPageConfig,
+    search: Option<&String>,
+    sort: FrontPageSort,
+    now: StoryDate,
+    max: usize,
+) -> Result<Vec<Story<Shard>>, WebError> {
+    let mut stories = if let Some(search) = search {
+        index
+            .fetch(StoryQuery::from_search(&eval.tagger, search), max)
+            .await?
+    } else if let Some(search) = &config.search {
+        let mut stories = index
+            .fetch(StoryQuery::from_search(&eval.tagger, search), max)
+            .await?;
+        stories.retain(|story| config.matches(story));
+        stories
+    } else {
+        let mut stories = hot_set(now, index, eval).await?;
+        stories.retain(|story| config.matches(story));
+        config.retain_min_sources(&mut stories, max);
+        sort.apply(index, &mut stories);
+        config.cap_domains(&mut stories);
+        // Pinned stories stay on top regardless of the sort
+        pin_stories(index, &mut stories).await?;
+        stories
+    };
+    if config.dedupe_titles {
+        stories = dedupe_titles(stories);
+    }
+    stories.truncate(max);
+    Ok(stories)
+}
+
+/// Shown as the trending tags until there are recent stories to count them from.
+const TOP_TAGS: [&str; 13] = [
+    "github.com",
+    "rust",
+    "amazon",
+    "java",
+    "health",
+    "wsj.com",
+    "security",
+    "apple",
+    "theverge.com",
+    "python",
+    "kernel",
+    "google",
+    "arstechnica.com",
+];
+
+/// The start of a window ending at `now`.
+fn window_start(now: StoryDate, window: (usize, CronInterval)) -> Option<StoryDate> {
+    StoryDate::from_seconds(now.timestamp() - window.1.as_duration(window.0).as_secs() as i64)
+}
+
+/// The most common tags on stories within the trending window.
+async fn trending_tags(
+    index: &Index<StoryIndex>,
+    config: &TagsConfig,
+    now: StoryDate,
+) -> Result<Vec<String>, WebError> {
+    let tags = index
+        .fetch_tag_counts(1, window_start(now, config.trending_window))
+        .await?;
+    if tags.is_empty() {
+        return Ok(TOP_TAGS.map(str::to_owned).to_vec());
+    }
+    Ok(tags
+        .into_iter()
+        .take(config.trending_count)
+        .map(|(tag, _)| tag)
+        .collect())
+}
+
+/// The representations of the front page, negotiated with the `Accept` header so that it has a single canonical URL.
+/// Clients that can't set headers can pick one explicitly with `?format=`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FrontPageFormat {
+    Html,
+    Json,
+    Rss,
+}
+
+impl FrontPageFormat {
+    /// Parse the `format` query parameter, which overrides the `Accept` header if present.
+    fn parse(format: Option<&String>) -> Result<Option<FrontPageFormat>, WebError> {
+        match format.map(String::as_str) {
+            None | Some("") => Ok(None),
+            Some("html") => Ok(Some(FrontPageFormat::Html)),
+            Some("json") => Ok(Some(FrontPageFormat::Json)),
+            Some("rss") => Ok(Some(FrontPageFormat::Rss)),
+            Some(format) => Err(WebError::BadRequest(format!("unknown format '{}'", format))),
+        }
+    }
+
+    /// Pick the supported format with the highest quality from the `Accept` header, preferring exact media types
+    /// over wildcards. Anything we can't serve falls back to HTML.
+    fn negotiate(headers: &HeaderMap) -> FrontPageFormat {
+        let Some(accept) = headers
+            .get(hyper::header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+        else {
+            return FrontPageFormat::Html;
+        };
+        let mut best = (FrontPageFormat::Html, 0.0, false);
+        for range in accept.split(',') {
+            let mut params = range.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default().to_ascii_lowercase();
+            let (format, exact) = match media_type.as_str() {
+                "text/html" => (FrontPageFormat::Html, true),
+                "application/json" => (FrontPageFormat::Json, true),
+                "application/rss+xml" => (FrontPageFormat::Rss, true),
+                "text/*" | "*/*" => (FrontPageFormat::Html, false),
+                _ => continue,
+            };
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > best.1 || (quality == best.1 && exact && !best.2) {
+                best = (format, quality, exact);
+            }
+        }
+        best.0
+    }
+}
+
+async fn root(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+    headers: HeaderMap,
+    query: Query<HashMap<String, String>>,
+) -> Result<Response, WebError> {
+    let eval = resources.story_evaluator();
+    let search = resources
+        .config()
+        .search
+        .normalize(query.get("search"), |token| {
+            eval.tagger.check_tag_search(token).is_some()
+        })?;
+    let sort = FrontPageSort::parse(query.get("sort"), resources.config().front_page.sort)?;
+    let format = FrontPageFormat::parse(query.get("format"))?
+        .unwrap_or_else(|| FrontPageFormat::negotiate(&headers));
+    // Only the HTML page renders the facet counts
+    let facets = match &search {
+        Some(search) if format == FrontPageFormat::Html && resources.config().search.facets > 0 => {
+            Some(
+                index
+                    .fetch_facets(
+                        StoryQuery::from_search(&eval.tagger, search),
+                        resources.config().search.facets,
+                    )
+                    .await?,
+            )
+        }
+        _ => None,
+    };
+    let now = now(&index).await?;
+    let stories = front_page_stories(
+        &index,
+        &eval,
+        &resources.config().front_page,
+        search.as_ref(),
+        sort,
+        now,
+        resources.config().search.clamp_count(30),
     )
+    .await?;
+    let stories = render_stories(&resources, stories.iter());
+    let response = match format {
+        // A new deployment has nothing to show until the first scrape, so explain that rather than show a blank page
+        FrontPageFormat::Html
+            if stories.is_empty() && search.is_none() && index.hot_set().await?.is_empty() =>
+        {
+            render(&resources, "empty.html", Context::new())?.into_response()
+        }
+        FrontPageFormat::Html => render(
+            &resources,
+            "index.html",
+            context!(
+                top_tags = trending_tags(&index, &resources.config().tags, now).await?,
+                stories,
+                now,
+                facets,
+                sort
+            ),
+        )?
+        .into_response(),
+        FrontPageFormat::Json => Json(stories).into_response(),
+        FrontPageFormat::Rss => {
+            let Html(feed) = render(&resources, "rss.xml", context!(stories, front_page = true))?;
+            ([(CONTENT_TYPE, "application/rss+xml")], feed).into_response()
+        }
+    };
+    Ok(([(hyper::header::VARY, "Accept")], response).into_response())
 }
 
-async fn admin_status_frontpage(
-    Extension(user): Extension<CurrentUser>,
-    State(AdminState {
-        index, resources, ..
-    }): State<AdminState>,
-    sort: Query<HashMap<String, String>>,
+/// The newest stories, strictly by date.
+async fn newest(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
 ) -> Result<Html<String>, WebError> {
     let now = now(&index).await?;
-    let sort = sort.get("sort").cloned().unwrap_or_default();
+    let config = resources.config();
+    let stories = index
+        .query_newest(config.search.clamp_count(config.new_feed.count))
+        .await?;
+    let stories = render_stories(&resources, stories.iter());
     render(
         &resources,
-        "admin/frontpage.html",
+        "index.html",
         context!(
+            top_tags = trending_tags(&index, &resources.config().tags, now).await?,
+            stories,
             now,
-            user,
-            stories = render_stories(
-                &resources.story_evaluator(),
-                hot_set(now, &index, &resources.story_evaluator())
-                    .await?
-                    .iter(),
-            ),
-            sort,
+            feed = "/new/feed.xml"
         ),
     )
 }
 
-async fn admin_index_frontpage_scoretuner(
-    Extension(user): Extension<CurrentUser>,
-    State(AdminState {
-        index, resources, ..
-    }): State<AdminState>,
+/// An RSS feed of the newest stories, strictly by date.
+async fn newest_feed(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+) -> Result<impl IntoResponse, WebError> {
+    let config = resources.config();
+    let stories = index
+        .query_newest(config.search.clamp_count(config.new_feed.count))
+        .await?;
+    let stories = render_stories(&resources, stories.iter());
+    let Html(feed) = render(&resources, "rss.xml", context!(stories))?;
+    Ok(([(CONTENT_TYPE, "application/rss+xml")], feed))
+}
+
+/// An RSS feed of the most recent stories carrying a single tag.
+async fn tag_feed(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+    Path(tag): Path<String>,
+) -> Result<impl IntoResponse, WebError> {
+    let eval = resources.story_evaluator();
+    let tag = eval
+        .tagger
+        .check_tag_search(&tag)
+        .map(str::to_owned)
+        .unwrap_or(tag);
+    let stories = index
+        .fetch::<Shard>(
+            StoryQuery::TagSearch(tag.clone()),
+            resources.config().search.clamp_count(30),
+        )
+        .await?;
+    let stories = render_stories(&resources, stories.iter());
+    let Html(feed) = render(&resources, "rss.xml", context!(tag, stories))?;
+    Ok(([(CONTENT_TYPE, "application/rss+xml")], feed))
+}
+
+#[derive(Deserialize)]
+struct TagsParams {
+    /// Only list tags on at least this many stories.
+    min: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct TagCount {
+    tag: String,
+    count: usize,
+}
+
+/// Every tag in the configured window with its story count, most common first.
+async fn tag_counts(
+    index: &Index<StoryIndex>,
+    resources: &Resources,
+    params: TagsParams,
+) -> Result<Vec<TagCount>, WebError> {
+    let config = &resources.config().tags;
+    let min_count = params.min.unwrap_or(config.min_count).max(1);
+    let since = match config.window {
+        Some(window) => window_start(now(index).await?, window),
+        None => None,
+    };
+    Ok(index
+        .fetch_tag_counts(min_count, since)
+        .await?
+        .into_iter()
+        .map(|(tag, count)| TagCount { tag, count })
+        .collect())
+}
+
+async fn tags(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+    Query(params): Query<TagsParams>,
 ) -> Result<Html<String>, WebError> {
-    let now = now(&index).await?;
+    let tags = tag_counts(&index, &resources, params).await?;
+    render(&resources, "tags.html", context!(tags))
+}
 
-    #[derive(Serialize)]
-    struct StoryDetail {
-        story: StoryRender,
-        score_detail: Vec<(StoryScore, f32)>,
-    }
+async fn api_tags(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+    Query(params): Query<TagsParams>,
+) -> Result<Json<Vec<TagCount>>, WebError> {
+    Ok(Json(tag_counts(&index, &resources, params).await?))
+}
 
-    let stories: Vec<Story<TypedScrape>> = index.fetch(StoryQuery::FrontPage(), 500).await?;
-    let mut story_details = vec![];
-    let eval = resources.story_evaluator();
+#[derive(Deserialize)]
+struct StoriesSinceParams {
+    /// Return stories after this story ID.
+    id: Option<String>,
+    /// Return stories on or after this timestamp (seconds since the epoch).
+    ts: Option<i64>,
+    count: Option<usize>,
+}
 
-    for mut story in stories {
-        let scrapes = ScrapeCollection::new_from_iter(story.scrapes.values().cloned());
-        let extracted = scrapes.extract(&eval.extractor);
-        story.score = eval.scorer.score(&extracted) + eval.scorer.score_age(now - story.date);
-        let mut tags = TagSet::from_iter(extracted.tags());
-        eval.tagger.tag(extracted.title(), &mut tags);
-        story.tags = tags;
-        story_details.push(StoryDetail {
-            story: story.render(&eval.tagger, 0),
-            score_detail: eval.scorer.score_detail(&extracted, now),
-        });
-    }
+/// The high-water mark to pass back to `/api/stories/since` to fetch the next batch.
+#[derive(Serialize)]
+struct StoriesSinceMark {
+    id: Option<String>,
+    ts: i64,
+}
 
-    // Quick-and-dirty float sort
-    story_details.sort_by_cached_key(|x| (x.story.score * -1000.0) as i32);
+#[derive(Serialize)]
+struct StoriesSince {
+    stories: Vec<StoryRender>,
+    next: StoriesSinceMark,
+}
 
-    render(
-        &resources,
-        "admin/scoretuner.html",
-        context!(now, user, story_details,),
-    )
+/// Parse a story ID from a route or query. A malformed ID is the client's error, unlike a well-formed ID for a story
+/// that doesn't exist.
+fn parse_story_id(id: &str) -> Result<StoryIdentifier, WebError> {
+    StoryIdentifier::from_base64(id).ok_or_else(|| WebError::BadRequest("Invalid story ID".into()))
 }
 
-async fn admin_status_shard(
+/// Incrementally fetch stories, oldest first, from either a story ID or a timestamp. If neither is provided, stories
+/// are returned from the start of the index.
+async fn api_stories_since(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+    Query(params): Query<StoriesSinceParams>,
+) -> Result<Json<StoriesSince>, WebError> {
+    let (date, after) = if let Some(id) = params.id {
+        let id = index
+            .resolve_story_id(parse_story_id(&id)?)
+            .await?
+            .ok_or(WebError::NotFound)?;
+        let story = index
+            .fetch_one::<Shard>(StoryQuery::ById(id.clone()))
+            .await?
+            .ok_or(WebError::NotFound)?;
+        (story.date, Some(id))
+    } else if let Some(ts) = params.ts {
+        let date = StoryDate::from_seconds(ts)
+            .ok_or(WebError::ArgumentsInvalid("Invalid timestamp".into()))?;
+        (date, None)
+    } else {
+        (StoryDate::MIN, None)
+    };
+
+    let count = resources
+        .config()
+        .search
+        .clamp_count(params.count.unwrap_or(100));
+    let stories = index
+        .fetch::<Shard>(StoryQuery::Since(date, after.clone()), count)
+        .await?;
+    let next = if let Some(last) = stories.last() {
+        StoriesSinceMark {
+            id: Some(last.id.to_base64()),
+            ts: last.date.timestamp(),
+        }
+    } else {
+        StoriesSinceMark {
+            id: after.map(|id| id.to_base64()),
+            ts: date.timestamp(),
+        }
+    };
+    let stories = render_stories(&resources, stories.iter());
+    Ok(Json(StoriesSince { stories, next }))
+}
+
+/// The number of random candidates fetched, so that one can be picked if some are from blocked domains.
+const RANDOM_CANDIDATES: usize = 5;
+
+#[derive(Deserialize)]
+struct RandomParams {
+    tag: Option<String>,
+}
+
+/// A random story, optionally with the given tag.
+async fn random_story(
+    index: &Index<StoryIndex>,
+    eval: &StoryEvaluator,
+    tag: Option<String>,
+) -> Result<Story<Shard>, WebError> {
+    let tag = tag.map(|tag| {
+        eval.tagger
+            .check_tag_search(&tag)
+            .map(str::to_owned)
+            .unwrap_or(tag)
+    });
+    // Stories from domains that were blocked after they were indexed are still present, so skip them here
+    index
+        .fetch::<Shard>(StoryQuery::Random(tag), RANDOM_CANDIDATES)
+        .await?
+        .into_iter()
+        .find(|story| !eval.extractor.is_blocked(&story.url))
+        .ok_or(WebError::NotFound)
+}
+
+async fn api_random(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+    Query(params): Query<RandomParams>,
+) -> Result<Json<StoryRender>, WebError> {
+    let eval = resources.story_evaluator();
+    let story = random_story(&index, &eval, params.tag).await?;
+    let mut stories = render_stories(&resources, [story].iter());
+    Ok(Json(stories.remove(0)))
+}
+
+async fn random(
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+    Query(params): Query<RandomParams>,
+) -> Result<Redirect, WebError> {
+    let story = random_story(&index, &resources.story_evaluator(), params.tag).await?;
+    Ok(Redirect::temporary(story.url.raw()))
+}
+
+async fn admin(
     Extension(user): Extension<CurrentUser>,
-    State(AdminState {
-        index, resources, ..
-    }): State<AdminState>,
-    Path(shard): Path<String>,
-    sort: Query<HashMap<String, String>>,
+    State(AdminState { resources, .. }): State<AdminState>,
 ) -> Result<Html<String>, WebError> {
-    let sort = sort.get("sort").cloned().unwrap_or_default();
-    let shard = Shard::from_string(&shard).expect("Failed to parse shard");
     render(
         &resources,
-        "admin/shard.html",
-        context!(
-            user,
-            shard = shard,
-            stories = render_stories(
-                &resources.story_evaluator(),
-                index
-                    .fetch::<Shard>(StoryQuery::ByShard(shard), usize::MAX)
-                    .await?
-                    .iter()
-            ),
-            sort: String = sort
-        ),
+        "admin/admin.html",
+        context!(user, config = resources.config()),
     )
 }
 
-async fn admin_status_story(
+async fn admin_cron(
     Extension(user): Extension<CurrentUser>,
     State(AdminState {
-        index, resources, ..
+        cron,
+        cron_history,
+        resources,
+        ..
     }): State<AdminState>,
-    Path(id): Path<String>,
 ) -> Result<Html<String>, WebError> {
-    let id = StoryIdentifier::from_base64(id).ok_or(WebError::NotFound)?;
-    let now = now(&index).await?;
-    tracing::info!("Loading story = {:?}", id);
-    let story = index
-        .fetch_one(StoryQuery::ById(id))
-        .await?
-        .ok_or(WebError::NotFound)?;
-    let scrapes = ScrapeCollection::new_from_iter(story.scrapes.clone().into_values());
-    let eval = resources.story_evaluator();
-    let extract = scrapes.extract(&eval.extractor);
-    let score_details = eval.scorer.score_detail(&extract, now);
-    let tags = Default::default(); // _details = resources.story_evaluator().tagger.tag_detail(&story);
-
     render(
         &resources,
-        "admin/story.html",
+        "admin/cron.html",
         context!(
-            now,
             user,
-            story = story.render(&eval.tagger, 0),
-            scrapes = scrapes.scrapes,
-            tags: HashMap<String, Vec<String>>,
-            score = score_details
+            config = resources.config(),
+            running = cron.lock().await.running(),
+            cron = cron.lock().await.inspect(),
+            history = cron_history.lock().await.entries()
         ),
     )
 }
 
-pub async fn serve_static_files_immutable(
-    headers_in: HeaderMap,
-    Path(key): Path<String>,
-    State(resources): State<Resources>,
-) -> Result<impl IntoResponse, WebError> {
-    serve_static_files::immutable(headers_in, key, resources.static_files()).await
-}
-
-pub async fn serve_static_files_well_known(
-    headers_in: HeaderMap,
+#[derive(Deserialize)]
+struct AdminCronRunParams {
+    /// Which job do we want to trigger?
+    cron: String,
+}
+
+async fn admin_cron_post(
+    State(AdminState { cron, .. }): State<AdminState>,
+    Json(params): Json<AdminCronRunParams>,
+) -> Result<Json<bool>, WebError> {
+    let success = cron.lock().await.trigger(params.cron);
+    Ok(success.into())
+}
+
+async fn admin_cron_backup(
+    State(AdminState {
+        backup_path, index, ..
+    }): State<AdminState>,
+) -> Result<Json<impl Serialize>, WebError> {
+    let results = if let Some(backup_path) = backup_path {
+        index.backup(&backup_path)?
+    } else {
+        vec![]
+    }
+    .into_iter()
+    .map(|(shard, r)| (shard, r.map_err(|e| e.to_string())))
+    .collect_vec();
+
+    Ok(Json(results))
+}
+
+async fn admin_cron_refresh(
+    State(AdminState {
+        resources, index, ..
+    }): State<AdminState>,
+) -> Result<Html<String>, WebError> {
+    // Pick up any changes to the search weights along with the hot set
+    index
+        .set_search_weights(resources.config().score.search_weights())
+        .await?;
+    index.set_id_scheme(resources.config().id_scheme).await?;
+    index.set_durability(resources.config().durability);
+    index.refresh_hot_set(&resources.config().hot_set).await?;
+    render(
+        &resources,
+        "admin/cron_refresh.html",
+        context!(config = resources.config()),
+    )
+}
+
+async fn admin_cron_read_time(
+    State(AdminState {
+        resources,
+        index,
+        read_time_attempts,
+        ..
+    }): State<AdminState>,
+) -> Result<Json<impl Serialize>, WebError> {
+    let results = crate::read_time::update_read_times(
+        &resources.http_client(),
+        &index,
+        &resources.config().read_time,
+        &read_time_attempts,
+    )
+    .await?;
+    Ok(Json(results))
+}
+
+/// An outbound proxy for scrape traffic.
+#[derive(Default, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// The proxy URL, eg: `http://proxy.example.com:3128`. If unset, any proxy from the environment is used.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Hosts, domains and IP ranges that are fetched directly rather than through the proxy.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Build an HTTP client that routes requests through the configured proxy, failing if the proxy URL is invalid.
+    pub fn create_client(&self) -> Result<reqwest::Client, WebError> {
+        Ok(self.client_builder()?.build()?)
+    }
+
+    /// Start building an HTTP client that routes requests through the configured proxy.
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder, WebError> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(url) = &self.url {
+            url::Url::parse(url).inspect_err(|e| {
+                tracing::error!("Invalid proxy URL '{}': {}", url, e);
+            })?;
+            let proxy = reqwest::Proxy::all(url.as_str())?
+                .no_proxy(reqwest::NoProxy::from_string(&self.no_proxy.join(",")));
+            // The configured proxy replaces any proxy from the environment
+            builder = builder.no_proxy().proxy(proxy);
+        }
+        Ok(builder)
+    }
+}
+
+/// The protocols spoken by the HTTP server.
+#[derive(Default, Serialize, Deserialize)]
+pub struct ServerConfig {
+    /// Accept HTTP/2 as well as HTTP/1.1. There's no TLS, so this is cleartext HTTP/2 with prior knowledge (h2c), as
+    /// spoken by load balancers and proxies to their upstreams. Browsers only speak HTTP/2 over TLS.
+    #[serde(default)]
+    pub http2: bool,
+}
+
+impl ServerConfig {
+    /// Start building a server that accepts connections from `listener` using the configured protocols.
+    pub fn server(
+        &self,
+        listener: std::net::TcpListener,
+    ) -> Result<hyper::server::Builder<hyper::server::conn::AddrIncoming>, WebError> {
+        Ok(axum::Server::from_tcp(listener)?.http1_only(!self.http2))
+    }
+}
+
+/// The `ETag` and `Last-Modified` headers from a previous response, used to make a conditional request for the same
+/// URL.
+#[derive(Clone, Default)]
+struct HttpValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// The most recent `HttpValidators` for each URL we've fetched.
+type HttpValidatorCache = Mutex<HashMap<String, HttpValidators>>;
+
+/// Fetch a single URL. If a scrape archive is provided, the response is read from the archive rather than the
+/// network. If a validator cache is provided, the request is made conditional on the resource having changed since
+/// the last fetch. Responses that don't declare a charset are decoded with `fallback`.
+async fn fetch(
+    client: &reqwest::Client,
+    archive: Option<&ScrapeArchive>,
+    validators: Option<&HttpValidatorCache>,
+    fallback: &'static Encoding,
+    url: &str,
+) -> Result<ScraperHttpResponseInput, WebError> {
+    if let Some(archive) = archive {
+        return Ok(archive.fetch(url)?);
+    }
+    let mut req = client
+        .get(url)
+        .header("User-Agent", "progscrape")
+        .header("Accept-Encoding", "gzip, deflate");
+    if let Some(validators) = validators {
+        if let Some(previous) = validators.lock().await.get(url) {
+            if let Some(etag) = &previous.etag {
+                req = req.header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &previous.last_modified {
+                req = req.header("If-Modified-Since", last_modified);
+            }
+        }
+    }
+    let resp = req.send().await?;
+    let status = resp.status();
+    if status == StatusCode::NOT_MODIFIED {
+        tracing::info!("{} was not modified since the last fetch", url);
+        Ok(ScraperHttpResponseInput::NotModified)
+    } else if status == StatusCode::OK {
+        if let Some(validators) = validators {
+            let header = |name| {
+                resp.headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_owned)
+            };
+            let current = HttpValidators {
+                etag: header("ETag"),
+                last_modified: header("Last-Modified"),
+            };
+            validators.lock().await.insert(url.to_owned(), current);
+        }
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let (encoding, content_type) = (header("Content-Encoding"), header("Content-Type"));
+        let body = decompress_body(encoding.as_deref(), &resp.bytes().await?)?;
+        Ok(ScraperHttpResponseInput::Ok(charset::decode(
+            content_type.as_deref(),
+            &body,
+            fallback,
+        )))
+    } else {
+        Ok(ScraperHttpResponseInput::HTTPError(
+            status.as_u16(),
+            status.as_str().to_owned(),
+        ))
+    }
+}
+
+/// Decompress a response body if it was sent with gzip or deflate encoding. Some sources gzip their responses without
+/// saying so, so gzip is also detected by its magic number.
+fn decompress_body(encoding: Option<&str>, body: &[u8]) -> Result<Vec<u8>, WebError> {
+    use std::io::Read;
+    let mut decoded = vec![];
+    match encoding
+        .map(|encoding| encoding.trim().to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("gzip" | "x-gzip") => flate2::read::GzDecoder::new(body).read_to_end(&mut decoded)?,
+        Some("deflate") => flate2::read::ZlibDecoder::new(body).read_to_end(&mut decoded)?,
+        _ if body.starts_with(&[0x1f, 0x8b]) => {
+            flate2::read::GzDecoder::new(body).read_to_end(&mut decoded)?
+        }
+        _ => return Ok(body.to_vec()),
+    };
+    Ok(decoded)
+}
+
+/// Run `f` over each of the URLs, with no more than `limit` running at any one time.
+async fn fetch_all<F, Fut, T>(
+    urls: Vec<String>,
+    limit: usize,
+    f: F,
+) -> Result<Vec<(String, T)>, WebError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<T, WebError>>,
+{
+    futures::stream::iter(urls)
+        .map(|url| {
+            let fut = f(url.clone());
+            async move { Ok((url, fut.await?)) }
+        })
+        .buffer_unordered(limit.max(1))
+        .try_collect()
+        .await
+}
+
+/// Fetch each of the given URLs and scrape the responses, honouring the source's concurrency limit. If a scrape
+/// archive is provided, the responses are read from the archive rather than the network. Unmodified resources are
+/// not scraped.
+async fn fetch_and_scrape(
+    client: &reqwest::Client,
+    scrapers: &Scrapers,
+    archive: Option<&ScrapeArchive>,
+    validators: Option<&HttpValidatorCache>,
+    fallback: &'static Encoding,
+    source: ScrapeSource,
+    urls: Vec<String>,
+) -> Result<HashMap<String, ScraperHttpResult>, WebError> {
+    let limit = scrapers.max_concurrency(source);
+    let results = fetch_all(urls, limit, |url| async move {
+        fetch(client, archive, validators, fallback, &url).await
+    })
+    .await?;
+
+    Ok(HashMap::from_iter(
+        results
+            .into_iter()
+            .map(|(k, v)| (k, scrapers.scrape_http_result(source, v))),
+    ))
+}
+
+/// Insert all successful scrapes into the index.
+async fn ingest_scrapes(
+    index: &Index<StoryIndex>,
+    eval: Arc<StoryEvaluator>,
+    scrapes: &HashMap<String, ScraperHttpResult>,
+) -> Result<(), WebError> {
+    for result in scrapes.values() {
+        match result {
+            ScraperHttpResult::Ok(_, scrapes) => {
+                index
+                    .insert_scrapes(eval.clone(), scrapes.clone().into_iter())
+                    .await?
+            }
+            ScraperHttpResult::Err(..) | ScraperHttpResult::NotModified => {}
+        }
+    }
+    Ok(())
+}
+
+async fn admin_cron_scrape(
+    State(AdminState {
+        resources,
+        index,
+        archive,
+        dead_letters,
+        raw_responses,
+        source_status,
+        validators,
+        ..
+    }): State<AdminState>,
+    Path(source): Path<ScrapeSource>,
+) -> Result<Html<String>, WebError> {
+    if !resources.scrapers().is_enabled(source) {
+        tracing::info!("Skipping scrape of disabled source {:?}", source);
+    }
+    let subsources = resources.scrapers().compute_scrape_subsources(source);
+    let urls = resources
+        .scrapers()
+        .compute_scrape_url_demands(source, subsources);
+    let mut scrapes = fetch_and_scrape(
+        &resources.http_client(),
+        &resources.scrapers(),
+        archive.as_deref(),
+        Some(&validators),
+        resources.config().charset.fallback_encoding()?,
+        source,
+        urls,
+    )
+    .await?;
+    let config = resources.config();
+    if config.redirects.enabled {
+        let client = redirects::create_client(&config.proxy)?;
+        let count = redirects::resolve_scrape_urls(&client, &config.redirects, &mut scrapes).await;
+        tracing::info!("Resolved {} redirected URL(s) for {:?}", count, source);
+    }
+    ingest_scrapes(&index, resources.story_evaluator(), &scrapes).await?;
+    dead_letters.lock().await.insert_failures(
+        &resources.config().dead_letter,
+        source,
+        &scrapes,
+        StoryDate::now(),
+    );
+    // Failing to keep a copy of the responses shouldn't fail the scrape
+    if let Err(e) = raw_responses.lock().await.insert(
+        &resources.config().raw_responses,
+        source,
+        &scrapes,
+        StoryDate::now(),
+    ) {
+        tracing::error!("Failed to store raw responses for {:?}: {:?}", source, e);
+    }
+    source_status
+        .lock()
+        .await
+        .record(source, &scrapes, StoryDate::now());
+
+    render(
+        &resources,
+        "admin/cron_scrape_run.html",
+        context!(
+            source,
+            config = resources.config(),
+            scrapes: HashMap<String, ScraperHttpResult>,
+        ),
+    )
+}
+
+async fn admin_headers(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState { resources, .. }): State<AdminState>,
+    Query(query): Query<HashMap<String, String>>,
+    raw_headers: HeaderMap,
+) -> Result<Html<String>, WebError> {
+    let mut headers: HashMap<_, Vec<String>> = HashMap::new();
+    for (header, value) in raw_headers {
+        let name = header.map(|h| h.to_string()).unwrap_or("(missing)".into());
+        headers
+            .entry(name)
+            .or_default()
+            .push(String::from_utf8_lossy(value.as_bytes()).to_string());
+    }
+    render(
+        &resources,
+        "admin/headers.html",
+        context!(user, query, headers),
+    )
+}
+
+async fn admin_scrape(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        resources,
+        source_status,
+        ..
+    }): State<AdminState>,
+) -> Result<Html<String>, WebError> {
+    let config = resources.config();
+    let scrapers = resources.scrapers();
+    let statuses = source_status.lock().await.statuses().clone();
+    let disabled = ScrapeSource::all()
+        .iter()
+        .filter(|source| !scrapers.is_enabled(**source))
+        .collect_vec();
+    render(
+        &resources,
+        "admin/scrape.html",
+        context!(
+            user,
+            config,
+            scrapes = scrapers.compute_scrape_possibilities(),
+            disabled,
+            statuses,
+            endpoint = "/admin/scrape/test"
+        ),
+    )
+}
+
+/// Each scrape source and its available subsources, for tools that drive the scrape test form.
+async fn admin_scrape_possibilities(
+    State(AdminState { resources, .. }): State<AdminState>,
+) -> Result<Json<ScraperPossibilities>, WebError> {
+    Ok(Json(resources.scrapers().compute_scrape_possibilities()))
+}
+
+async fn admin_scrape_failures(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        resources,
+        dead_letters,
+        ..
+    }): State<AdminState>,
+) -> Result<Html<String>, WebError> {
+    render(
+        &resources,
+        "admin/failures.html",
+        context!(
+            user,
+            config = resources.config(),
+            failures = dead_letters.lock().await.entries()
+        ),
+    )
+}
+
+async fn admin_scrape_raw(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        resources,
+        raw_responses,
+        ..
+    }): State<AdminState>,
+) -> Result<Html<String>, WebError> {
+    let responses = raw_responses
+        .lock()
+        .await
+        .entries(&resources.config().raw_responses)?;
+    render(
+        &resources,
+        "admin/raw_responses.html",
+        context!(user, config = resources.config(), responses),
+    )
+}
+
+/// Download a stored raw response, to replay through the scraper offline.
+async fn admin_scrape_raw_download(
+    State(AdminState {
+        resources,
+        raw_responses,
+        ..
+    }): State<AdminState>,
     Path(file): Path<String>,
-    State(resources): State<Resources>,
 ) -> Result<impl IntoResponse, WebError> {
-    serve_static_files::well_known(headers_in, file, resources.static_files_root()).await
+    let body = raw_responses
+        .lock()
+        .await
+        .read(&resources.config().raw_responses, &file)?;
+    Ok((
+        [
+            (CONTENT_TYPE, "text/plain; charset=utf-8".to_owned()),
+            (
+                hyper::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[derive(Deserialize)]
+struct AdminScrapeTestParams {
+    /// Which source do we want to scrape?
+    source: ScrapeSource,
+    subsources: Vec<String>,
+}
+
+/// The result of scraping a single URL on the admin scrape test page.
+#[derive(Serialize)]
+struct ScrapeTestResult {
+    url: String,
+    /// The number of stories parsed from the URL.
+    count: usize,
+    result: ScraperHttpResult,
+}
+
+/// Order scrape results by URL so that repeated test runs are easy to compare.
+fn scrape_test_results(scrapes: HashMap<String, ScraperHttpResult>) -> Vec<ScrapeTestResult> {
+    scrapes
+        .into_iter()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .map(|(url, result)| ScrapeTestResult {
+            url,
+            count: match &result {
+                ScraperHttpResult::Ok(_, scrapes) => scrapes.len(),
+                ScraperHttpResult::Err(..) | ScraperHttpResult::NotModified => 0,
+            },
+            result,
+        })
+        .collect()
+}
+
+async fn admin_scrape_test(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        resources, archive, ..
+    }): State<AdminState>,
+    Json(params): Json<AdminScrapeTestParams>,
+) -> Result<Html<String>, WebError> {
+    let urls = resources
+        .scrapers()
+        .compute_scrape_url_demands(params.source, params.subsources);
+    let scrapes = fetch_and_scrape(
+        &resources.http_client(),
+        &resources.scrapers(),
+        archive.as_deref(),
+        None,
+        resources.config().charset.fallback_encoding()?,
+        params.source,
+        urls,
+    )
+    .await?;
+
+    // Show how each scrape would be tagged and scored if it were ingested
+    let eval = resources.story_evaluator();
+    let mut evaluated = HashMap::new();
+    for result in scrapes.values() {
+        if let ScraperHttpResult::Ok(_, scrapes) = result {
+            for scrape in scrapes {
+                evaluated.insert(scrape.id.to_string(), eval.evaluate(scrape));
+            }
+        }
+    }
+
+    render(
+        &resources,
+        "admin/scrape_test.html",
+        context!(
+            user,
+            scrapes = scrape_test_results(scrapes),
+            evaluated: HashMap<String, EvaluatedStory>
+        ),
+    )
+}
+
+async fn admin_index_status(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+) -> Result<Html<String>, WebError> {
+    render(
+        &resources,
+        "admin/status.html",
+        context!(
+            user,
+            storage = index.story_count().await?,
+            config = resources.config()
+        ),
+    )
+}
+
+async fn admin_status_frontpage(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+    sort: Query<HashMap<String, String>>,
+) -> Result<Html<String>, WebError> {
+    let now = now(&index).await?;
+    let sort = sort.get("sort").cloned().unwrap_or_default();
+    let mut stories = hot_set(now, &index, &resources.story_evaluator()).await?;
+    // Column sorts are applied by the template, so only re-order the hot set for sort modes
+    if !matches!(
+        sort.as_str(),
+        "" | "order" | "date" | "score" | "domain" | "title"
+    ) {
+        FrontPageSort::parse(Some(&sort), FrontPageSort::Hot)?.apply(&index, &mut stories);
+    }
+    render(
+        &resources,
+        "admin/frontpage.html",
+        context!(
+            now,
+            user,
+            stories = render_stories(&resources, stories.iter()),
+            sort,
+        ),
+    )
+}
+
+async fn admin_index_frontpage_scoretuner(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+) -> Result<Html<String>, WebError> {
+    let now = now(&index).await?;
+
+    #[derive(Serialize)]
+    struct StoryDetail {
+        story: StoryRender,
+        score_detail: Vec<(StoryScore, f32)>,
+    }
+
+    let stories: Vec<Story<TypedScrape>> = index.fetch(StoryQuery::FrontPage(), 500).await?;
+    let mut story_details = vec![];
+    let eval = resources.story_evaluator();
+
+    for mut story in stories {
+        let scrapes = ScrapeCollection::new_from_iter(story.scrapes.values().cloned());
+        let extracted = scrapes.extract(&eval.extractor);
+        story.score = eval.scorer.score(&extracted) + eval.scorer.score_age(now - story.date);
+        let mut tags = TagSet::from_iter(extracted.tags());
+        eval.tagger.tag(extracted.title(), &mut tags);
+        story.tags = tags;
+        story_details.push(StoryDetail {
+            story: story.render_with_engagement(&eval.tagger, 0),
+            score_detail: eval.scorer.score_detail(&extracted, now),
+        });
+    }
+
+    // Quick-and-dirty float sort
+    story_details.sort_by_cached_key(|x| (x.story.score * -1000.0) as i32);
+
+    render(
+        &resources,
+        "admin/scoretuner.html",
+        context!(now, user, story_details,),
+    )
+}
+
+/// A hot set story re-scored with a candidate scoring config. The story's `order` and `score` are from the candidate.
+#[derive(Serialize)]
+struct ScorePreviewStory {
+    current_rank: usize,
+    current_score: f32,
+    story: StoryRender,
+}
+
+/// Re-score the current hot set with a candidate scoring config, without persisting anything, to see how it would
+/// reorder the front page.
+async fn admin_index_score_preview(
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+    Json(config): Json<StoryScoreConfig>,
+) -> Result<Json<Vec<ScorePreviewStory>>, WebError> {
+    let now = now(&index).await?;
+    let eval = resources.story_evaluator();
+    let scorer = StoryScorer::new(&config);
+
+    let mut current = HashMap::new();
+    let mut stories = vec![];
+    for (rank, story) in hot_set(now, &index, &eval).await?.into_iter().enumerate() {
+        let Some(mut full) = index
+            .fetch_one::<TypedScrape>(StoryQuery::ById(story.id.clone()))
+            .await?
+        else {
+            continue;
+        };
+        let scrapes = ScrapeCollection::new_from_iter(full.scrapes.values().cloned());
+        full.score = scorer.score(&scrapes.extract(&eval.extractor));
+        current.insert(story.id, (rank, story.score));
+        stories.push(full);
+    }
+    scorer.resort_stories(now, &mut stories);
+
+    let preview = stories
+        .iter()
+        .zip(render_stories(&resources, stories.iter()))
+        .map(|(full, story)| {
+            let (current_rank, current_score) = current[&full.id];
+            ScorePreviewStory {
+                current_rank,
+                current_score,
+                story,
+            }
+        })
+        .collect();
+    Ok(Json(preview))
+}
+
+/// The default and maximum number of stories shown in the recently ingested view.
+const RECENT_DEFAULT_LIMIT: usize = 50;
+const RECENT_MAX_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+struct AdminRecentParams {
+    limit: Option<usize>,
+}
+
+/// The most recently ingested stories, to check that the cron is picking up new stories (and to tell old stories that
+/// were only just discovered from new ones).
+async fn admin_index_recent(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+    Query(params): Query<AdminRecentParams>,
+) -> Result<Html<String>, WebError> {
+    let now = now(&index).await?;
+    let limit = params
+        .limit
+        .unwrap_or(RECENT_DEFAULT_LIMIT)
+        .min(RECENT_MAX_LIMIT);
+    let stories = index
+        .fetch::<Shard>(StoryQuery::RecentlyIngested(), limit)
+        .await?;
+    let stories = render_stories(&resources, stories.iter());
+    render(
+        &resources,
+        "admin/recent.html",
+        context!(now, user, stories, limit),
+    )
+}
+
+/// Minimum `StoryUrl::is_same_story` confidence for a pair of stories to be suggested as a merge.
+const MERGE_SUGGESTION_CONFIDENCE: f32 = 0.75;
+
+async fn admin_index_merges(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+) -> Result<Html<String>, WebError> {
+    let now = now(&index).await?;
+
+    #[derive(Serialize)]
+    struct MergeSuggestion {
+        a: StoryRender,
+        b: StoryRender,
+        confidence: f32,
+    }
+
+    let eval = resources.story_evaluator();
+    let stories = hot_set(now, &index, &eval).await?;
+    let mut merges = vec![];
+    for (a, b) in stories.iter().tuple_combinations() {
+        let confidence = a.url.is_same_story(&a.title, &b.url, &b.title);
+        if confidence >= MERGE_SUGGESTION_CONFIDENCE {
+            merges.push(MergeSuggestion {
+                a: a.render(&eval.tagger, 0),
+                b: b.render(&eval.tagger, 0),
+                confidence,
+            });
+        }
+    }
+    merges.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+    render(&resources, "admin/merges.html", context!(now, user, merges))
+}
+
+async fn admin_status_shard(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+    Path(shard): Path<String>,
+    sort: Query<HashMap<String, String>>,
+) -> Result<Html<String>, WebError> {
+    let sort = sort.get("sort").cloned().unwrap_or_default();
+    let shard = Shard::from_string(&shard).expect("Failed to parse shard");
+    render(
+        &resources,
+        "admin/shard.html",
+        context!(
+            user,
+            shard = shard,
+            stories = render_stories(&resources, index
+                    .fetch::<Shard>(StoryQuery::ByShard(shard), usize::MAX)
+                    .await?
+                    .iter()
+            ),
+            sort: String = sort
+        ),
+    )
+}
+
+async fn admin_status_story(
+    Extension(user): Extension<CurrentUser>,
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Response, WebError> {
+    let id = parse_story_id(&id)?;
+    let now = now(&index).await?;
+    tracing::info!("Loading story = {:?}", id);
+    let story = match index.fetch_one(StoryQuery::ById(id.clone())).await? {
+        Some(story) => story,
+        None => {
+            let id = index
+                .resolve_story_id(id)
+                .await?
+                .ok_or(WebError::NotFound)?;
+            return Ok(
+                Redirect::permanent(&format!("/admin/index/story/{}/", id.to_base64()))
+                    .into_response(),
+            );
+        }
+    };
+    let scrapes = ScrapeCollection::new_from_iter(story.scrapes.clone().into_values());
+    let eval = resources.story_evaluator();
+    let extract = scrapes.extract(&eval.extractor);
+    let score_details = eval.scorer.score_detail(&extract, now);
+    let tags = Default::default(); // _details = resources.story_evaluator().tagger.tag_detail(&story);
+    let hidden = index.is_story_hidden(story.id.clone()).await?;
+    let pinned = index.is_story_pinned(story.id.clone()).await?;
+
+    Ok(render(
+        &resources,
+        "admin/story.html",
+        context!(
+            now,
+            user,
+            story = story.render_with_engagement(&eval.tagger, 0),
+            scrapes = scrapes.scrapes,
+            tags: HashMap<String, Vec<String>>,
+            score = score_details,
+            hidden,
+            pinned
+        ),
+    )?
+    .into_response())
+}
+
+async fn admin_story_hide(
+    State(AdminState { index, .. }): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<bool>, WebError> {
+    let id = parse_story_id(&id)?;
+    tracing::info!("Hiding story = {:?}", id);
+    Ok(index.hide_story(id).await?.into())
+}
+
+async fn admin_story_pin(
+    State(AdminState { index, .. }): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<bool>, WebError> {
+    let id = parse_story_id(&id)?;
+    tracing::info!("Pinning story = {:?}", id);
+    Ok(index.pin_story(id).await?.into())
+}
+
+async fn admin_story_unpin(
+    State(AdminState { index, .. }): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<bool>, WebError> {
+    let id = parse_story_id(&id)?;
+    tracing::info!("Un-pinning story = {:?}", id);
+    Ok(index.unpin_story(id).await?.into())
+}
+
+async fn admin_index_story_delete(
+    State(AdminState { index, .. }): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<bool>, WebError> {
+    let id = parse_story_id(&id)?;
+    tracing::info!("Deleting story = {:?}", id);
+    if !index.delete_story(id).await? {
+        return Err(WebError::NotFound);
+    }
+    Ok(true.into())
+}
+
+async fn admin_index_reingest(
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+    Path(source): Path<ScrapeSource>,
+) -> Result<Json<usize>, WebError> {
+    tracing::info!("Re-ingesting stories from {:?}", source);
+    let count = index
+        .reingest_source(resources.story_evaluator(), source)
+        .await?;
+    index.refresh_hot_set(&resources.config().hot_set).await?;
+    Ok(count.into())
+}
+
+/// Re-read the legacy import and merge it into the index, eg: after the legacy files have been updated. Like
+/// `initialize`, the legacy files are read relative to the working directory.
+async fn admin_index_import_legacy(
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+) -> Result<Json<ImportResult>, WebError> {
+    tracing::info!("Re-importing legacy stories");
+    let scrapes = tokio::task::spawn_blocking(|| {
+        progscrape_scrapers::import_legacy(std::path::Path::new("."))
+    })
+    .await
+    .map_err(|_| PersistError::UnexpectedError("Legacy import panicked".into()))??;
+    let result = index
+        .import_scrapes(resources.story_evaluator(), scrapes)
+        .await?;
+    index.refresh_hot_set(&resources.config().hot_set).await?;
+    Ok(Json(result))
+}
+
+async fn admin_story_unhide(
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<bool>, WebError> {
+    let id = parse_story_id(&id)?;
+    tracing::info!("Un-hiding story = {:?}", id);
+    let res = index.unhide_story(id).await?;
+    index.refresh_hot_set(&resources.config().hot_set).await?;
+    Ok(res.into())
+}
+
+pub async fn serve_static_files_immutable(
+    headers_in: HeaderMap,
+    Path(key): Path<String>,
+    State(resources): State<Resources>,
+) -> Result<impl IntoResponse, WebError> {
+    serve_static_files::immutable(headers_in, key, resources.static_files()).await
+}
+
+async fn robots_txt(State(resources): State<Resources>) -> Result<impl IntoResponse, WebError> {
+    let config = resources.config();
+    let absolute_url = AbsoluteUrlFilter::new(url::Url::parse(&config.base_url)?);
+    Ok((
+        [(CONTENT_TYPE, "text/plain")],
+        config.robots.render(&absolute_url),
+    ))
+}
+
+pub async fn serve_static_files_well_known(
+    headers_in: HeaderMap,
+    Path(file): Path<String>,
+    State(resources): State<Resources>,
+) -> Result<impl IntoResponse, WebError> {
+    serve_static_files::well_known(headers_in, file, resources.static_files_root()).await
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::File, io::BufReader};
+
+    use encoding_rs::UTF_8;
+    use progscrape_application::PersistLocation;
+    use progscrape_scrapers::{hacker_news::HackerNewsStory, lobsters::LobstersStory, StoryUrl};
+
+    use super::*;
+    use crate::config::Config;
+
+    fn config() -> Config {
+        let reader = BufReader::new(
+            File::open("../resource/config/config.json").expect("Failed to open config"),
+        );
+        serde_json::from_reader(reader).expect("Failed to parse config")
+    }
+
+    async fn body_text(response: Response) -> String {
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .expect("Body");
+        String::from_utf8_lossy(&body).into_owned()
+    }
+
+    /// The real resources, with an empty in-memory index.
+    async fn fixture() -> Result<(Index<StoryIndex>, Resources), WebError> {
+        let resources =
+            resource::start_watcher(std::path::Path::new("../resource").canonicalize()?).await?;
+        let index = Index::new(StoryIndex::new(PersistLocation::Memory)?)?;
+        Ok((index, resources))
+    }
+
+    /// Index a Hacker News story on its own domain for each title, and refresh the hot set.
+    async fn insert_stories(
+        index: &Index<StoryIndex>,
+        resources: &Resources,
+        date: StoryDate,
+        titles: &[&str],
+    ) -> Result<(), WebError> {
+        let scrapes: Vec<TypedScrape> = titles
+            .iter()
+            .enumerate()
+            .map(|(i, title)| {
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                HackerNewsStory::new_with_defaults(i.to_string(), date, title.to_string(), url)
+                    .into()
+            })
+            .collect();
+        index
+            .insert_scrapes(resources.story_evaluator(), scrapes.into_iter())
+            .await?;
+        index.refresh_hot_set(&Default::default()).await?;
+        Ok(())
+    }
+
+    fn admin_state(index: Index<StoryIndex>, resources: Resources) -> AdminState {
+        AdminState {
+            resources,
+            index,
+            cron: Arc::new(Mutex::new(Cron::new())),
+            cron_history: Arc::new(Mutex::new(CronHistory::default())),
+            backup_path: None,
+            archive: None,
+            dead_letters: Default::default(),
+            raw_responses: Default::default(),
+            source_status: Default::default(),
+            validators: Default::default(),
+            read_time_attempts: Default::default(),
+        }
+    }
+
+    /// The front page, routed as it is in the server.
+    fn front_page_app(index: Index<StoryIndex>, resources: Resources) -> Router {
+        Router::new()
+            .route("/", get(root))
+            .with_state((index, resources))
+    }
+
+    #[tokio::test]
+    async fn test_replay_archive() -> Result<(), WebError> {
+        let config = config();
+        let eval = Arc::new(StoryEvaluator::new(
+            &config.tagger,
+            &config.score,
+            &config.scrape,
+        ));
+        let scrapers = Scrapers::new(&config.scrape);
+        let client = reqwest::Client::new();
+        let archive = ScrapeArchive::open("../scrapers/testdata")?;
+        let index = Index::new(StoryIndex::new(PersistLocation::Memory)?)?;
+
+        for (source, subsources) in [
+            (ScrapeSource::HackerNews, vec![]),
+            (ScrapeSource::Lobsters, vec![]),
+            (ScrapeSource::Slashdot, vec![]),
+            (ScrapeSource::Reddit, vec!["programming".to_owned()]),
+        ] {
+            let urls = scrapers.compute_scrape_url_demands(source, subsources);
+            assert!(urls.iter().all(|url| archive.contains(url)));
+            let scrapes = fetch_and_scrape(
+                &client,
+                &scrapers,
+                Some(&archive),
+                None,
+                UTF_8,
+                source,
+                urls,
+            )
+            .await?;
+            for result in scrapes.values() {
+                assert!(
+                    matches!(result, ScraperHttpResult::Ok(_, v) if !v.is_empty()),
+                    "Replay of {:?} failed",
+                    source
+                );
+            }
+            ingest_scrapes(&index, eval.clone(), &scrapes).await?;
+        }
+
+        // Replaying the same archive is deterministic and results in the same index
+        let count = index.story_count().await?.total.story_count;
+        assert!(count > 0);
+        let scrapes = fetch_and_scrape(
+            &client,
+            &scrapers,
+            Some(&archive),
+            None,
+            UTF_8,
+            ScrapeSource::HackerNews,
+            scrapers.compute_scrape_url_demands(ScrapeSource::HackerNews, vec![]),
+        )
+        .await?;
+        ingest_scrapes(&index, eval, &scrapes).await?;
+        assert_eq!(count, index.story_count().await?.total.story_count);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tag_feed() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let titles = [
+            "Rust 1.66 released",
+            "Why I love Python",
+            "Writing a kernel in Rust",
+            "The state of Java",
+        ];
+        insert_stories(&index, &resources, date, &titles).await?;
+
+        let response = tag_feed(State((index, resources)), Path("rust".to_owned()))
+            .await?
+            .into_response();
+        assert_eq!(
+            "application/rss+xml",
+            response.headers()[CONTENT_TYPE].to_str().expect("Header")
+        );
+        let feed = body_text(response).await;
+        assert!(feed.contains("<title>progscrape: rust</title>"));
+        assert_eq!(2, feed.matches("<item>").count());
+        for title in titles {
+            assert_eq!(title.contains("Rust"), feed.contains(title), "{}", title);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_newest() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+
+        // The oldest stories have the most points and comments
+        let scrapes: Vec<TypedScrape> = (0..4)
+            .map(|i| {
+                let date = StoryDate::year_month_day(2023, 1, 1 + i).expect("Date");
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                let mut scrape = HackerNewsStory::new_with_defaults(
+                    format!("{}", i),
+                    date,
+                    format!("Story number {}", i),
+                    url,
+                );
+                scrape.data.points = 1000 - i * 100;
+                scrape.data.comments = 500 - i * 100;
+                scrape.into()
+            })
+            .collect();
+        index
+            .insert_scrapes(resources.story_evaluator(), scrapes.into_iter())
+            .await?;
+
+        let expected = ["3", "2", "1", "0"].map(|i| format!("Story number {}", i));
+        let in_order = |page: &str| {
+            let positions = expected
+                .iter()
+                .map(|title| page.find(title.as_str()).expect(title))
+                .collect_vec();
+            positions.windows(2).all(|w| w[0] < w[1])
+        };
+
+        let Html(page) = newest(State((index.clone(), resources.clone()))).await?;
+        assert!(in_order(&page), "{}", page);
+        assert!(page.contains("rel=\"alternate\" type=\"application/rss+xml\""));
+
+        let response = newest_feed(State((index, resources)))
+            .await?
+            .into_response();
+        assert_eq!(
+            "application/rss+xml",
+            response.headers()[CONTENT_TYPE].to_str().expect("Header")
+        );
+        let feed = body_text(response).await;
+        assert!(feed.contains("<title>progscrape: new</title>"));
+        assert_eq!(4, feed.matches("<item>").count());
+        assert!(in_order(&feed), "{}", feed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_facets() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let titles = ["Rust 1.66 released", "Writing a kernel in Rust"];
+        insert_stories(&index, &resources, date, &titles).await?;
+
+        let query = Query(HashMap::from_iter([("search".into(), "rust".into())]));
+        let page = body_text(root(State((index, resources)), HeaderMap::new(), query).await?).await;
+        assert!(page.contains("hacker_news (2)"), "{}", page);
+        assert!(page.contains(">domain-0.com</a> (1)"), "{}", page);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tags() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let titles = [
+            "Rust 1.66 released",
+            "Writing a kernel in Rust",
+            "Python 3.11 released",
+        ];
+        insert_stories(&index, &resources, date, &titles).await?;
+
+        let state = || State((index.clone(), resources.clone()));
+        let Json(all) = api_tags(state(), Query(TagsParams { min: None })).await?;
+        let count = |tag: &str| all.iter().find(|t| t.tag == tag).map(|t| t.count);
+        assert_eq!(Some(2), count("rust"));
+        assert_eq!(Some(1), count("python"));
+        assert!(all.windows(2).all(|pair| pair[0].count >= pair[1].count));
+
+        // The long tail can be hidden
+        let Json(common) = api_tags(state(), Query(TagsParams { min: Some(2) })).await?;
+        assert!(common.iter().all(|t| t.count >= 2));
+        assert!(common.iter().any(|t| t.tag == "rust"));
+
+        let Html(page) = tags(state(), Query(TagsParams { min: None })).await?;
+        assert!(page.contains(">rust</a> (2)"), "{}", page);
+        assert!(page.contains(">python</a> (1)"), "{}", page);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trending_window() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let now = StoryDate::year_month_day(2023, 1, 10).expect("Date");
+        let config = |days| TagsConfig {
+            trending_window: (days, CronInterval::Day),
+            trending_count: 1,
+            ..Default::default()
+        };
+        // Nothing to count yet
+        assert_eq!(
+            TOP_TAGS.to_vec(),
+            trending_tags(&index, &config(1), now).await?
+        );
+
+        // Python was popular last week, but today it's all Rust
+        let scrapes = (0..3)
+            .map(|i| ("Python tips", 3 + i))
+            .chain((0..2).map(|_| ("Rust tips", 0)))
+            .enumerate()
+            .map(move |(i, (title, days_ago))| {
+                let date = StoryDate::from_seconds(now.timestamp() - days_ago * 24 * 60 * 60)
+                    .expect("Date");
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                HackerNewsStory::new_with_defaults(format!("{}", i), date, title.to_string(), url)
+                    .into()
+            });
+        index
+            .insert_scrapes(resources.story_evaluator(), scrapes)
+            .await?;
+
+        assert_eq!(vec!["rust"], trending_tags(&index, &config(1), now).await?);
+        assert_eq!(
+            vec!["python"],
+            trending_tags(&index, &config(7), now).await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_empty_front_page() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let page = |query: &[(&str, &str)]| {
+            let query = query
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let state = State((index.clone(), resources.clone()));
+            async move {
+                let response = root(state, HeaderMap::new(), Query(query)).await;
+                body_text(response.expect("Front page")).await
+            }
+        };
+
+        let empty = page(&[]).await;
+        assert!(empty.contains("No stories yet"), "{}", empty);
+        assert!(empty.contains("/admin/scrape/"), "{}", empty);
+        assert!(!empty.contains("Trending tags"));
+
+        // A search with no results is not a new deployment
+        let search = page(&[("search", "rust")]).await;
+        assert!(!search.contains("No stories yet"));
+        assert!(search.contains("Trending tags"));
+
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        insert_stories(&index, &resources, date, &["Rust 1.66 released"]).await?;
+        let front_page = page(&[]).await;
+        assert!(!front_page.contains("No stories yet"));
+        assert!(front_page.contains("Rust 1.66 released"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admin_scrape_possibilities() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let state = admin_state(index, resources);
+        let Json(possibilities) = admin_scrape_possibilities(State(state)).await?;
+        let json = serde_json::to_value(possibilities)?;
+
+        let config: serde_json::Value = serde_json::from_reader(BufReader::new(File::open(
+            "../resource/config/config.json",
+        )?))?;
+        let subreddits = config["scrape"]["reddit"]["subreddits"]
+            .as_object()
+            .expect("Missing subreddits")
+            .keys()
+            .sorted()
+            .collect_vec();
+        assert!(!subreddits.is_empty());
+        let reddit = json["scrapes"]["reddit"]
+            .as_array()
+            .expect("Missing reddit")
+            .iter()
+            .map(|s| s.as_str().expect("Subsource"))
+            .sorted()
+            .collect_vec();
+        assert_eq!(subreddits, reddit);
+        assert!(json["scrapes"]["hacker_news"].is_array());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admin_scrape_status() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let state = admin_state(index, resources);
+        state.source_status.lock().await.record(
+            ScrapeSource::Reddit,
+            &HashMap::from_iter([(
+                "https://www.reddit.com/r/rust/.json".to_owned(),
+                ScraperHttpResult::Err(
+                    ScraperHttpResponseInput::HTTPError(503, "Unavailable".into()),
+                    "Service is down".into(),
+                ),
+            )]),
+            StoryDate::now(),
+        );
+
+        let Html(page) = admin_scrape(
+            Extension(CurrentUser {
+                user: "test".into(),
+            }),
+            State(state),
+        )
+        .await?;
+        assert!(page.contains("Service is down"));
+        // Other sources haven't failed
+        assert!(page.contains("(none)"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_concurrency_limit() -> Result<(), WebError> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = Arc::new(AtomicUsize::default());
+        let max_running = Arc::new(AtomicUsize::default());
+        let urls = (0..10)
+            .map(|i| format!("http://example.com/{}", i))
+            .collect();
+        let results = fetch_all(urls, 3, |url| {
+            let (running, max_running) = (running.clone(), max_running.clone());
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(url.len())
+            }
+        })
+        .await?;
+
+        assert_eq!(10, results.len());
+        assert_eq!(3, max_running.load(Ordering::SeqCst));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hide_story() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let eval = resources.story_evaluator();
+
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        insert_stories(&index, &resources, date, &["Rust"; 3]).await?;
+
+        let front_page = || {
+            let (index, eval) = (index.clone(), eval.clone());
+            async move {
+                let config = FrontPageConfig::default();
+                let stories =
+                    front_page_stories(&index, &eval, &config, None, config.sort, date, 30).await?;
+                Result::<_, WebError>::Ok(stories.into_iter().map(|s| s.id).collect_vec())
+            }
+        };
+
+        let stories = front_page().await?;
+        assert_eq!(3, stories.len());
+        let id = stories[0].clone();
+
+        // Hiding a story takes effect immediately
+        assert!(index.hide_story(id.clone()).await?);
+        let stories = front_page().await?;
+        assert_eq!(2, stories.len());
+        assert!(!stories.contains(&id));
+
+        // Unhiding a story takes effect after the hot set is refreshed
+        assert!(index.unhide_story(id.clone()).await?);
+        index.refresh_hot_set(&Default::default()).await?;
+        let stories = front_page().await?;
+        assert_eq!(3, stories.len());
+        assert!(stories.contains(&id));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pin_story() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let eval = resources.story_evaluator();
+
+        // Stories with a range of engagement, so that they score differently
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let scrapes: Vec<TypedScrape> = (0..5)
+            .map(|i| {
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                let mut story =
+                    HackerNewsStory::new_with_defaults(format!("{}", i), date, "Rust".into(), url);
+                story.data.points = i * 100;
+                story.data.comments = i * 10;
+                story.into()
+            })
+            .collect();
+        index
+            .insert_scrapes(eval.clone(), scrapes.into_iter())
+            .await?;
+        index.refresh_hot_set(&Default::default()).await?;
+
+        let front_page = || {
+            let (index, eval) = (index.clone(), eval.clone());
+            async move {
+                let config = FrontPageConfig::default();
+                let stories =
+                    front_page_stories(&index, &eval, &config, None, config.sort, date, 30).await?;
+                Result::<_, WebError>::Ok(stories.into_iter().map(|s| s.id).collect_vec())
+            }
+        };
+
+        let stories = front_page().await?;
+        assert_eq!(5, stories.len());
+        let (highest, lowest) = (stories[0].clone(), stories[4].clone());
+
+        // Pinned stories come first in the order they were pinned, and aren't repeated
+        assert!(index.pin_story(lowest.clone()).await?);
+        assert!(index.pin_story(highest.clone()).await?);
+        let pinned = front_page().await?;
+        assert_eq!(5, pinned.len());
+        assert_eq!(lowest, pinned[0]);
+        assert_eq!(highest, pinned[1]);
+        assert_eq!(stories[1..4], pinned[2..]);
+
+        // Pins apply to the other sorts as well
+        let config = FrontPageConfig::default();
+        let newest =
+            front_page_stories(&index, &eval, &config, None, FrontPageSort::New, date, 30).await?;
+        assert_eq!(lowest, newest[0].id);
+
+        // Hidden pinned stories aren't shown
+        index.hide_story(lowest.clone()).await?;
+        assert_eq!(highest, front_page().await?[0]);
+        index.unhide_story(lowest.clone()).await?;
+        index.refresh_hot_set(&Default::default()).await?;
+
+        assert!(index.unpin_story(lowest.clone()).await?);
+        assert!(index.unpin_story(highest.clone()).await?);
+        assert_eq!(stories, front_page().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_story() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        insert_stories(&index, &resources, date, &["Rust"]).await?;
+        let state = admin_state(index.clone(), resources);
+        let id = index.hot_set().await?[0].id.clone();
+
+        let Json(deleted) =
+            admin_index_story_delete(State(state.clone()), Path(id.to_base64())).await?;
+        assert!(deleted);
+        assert!(index
+            .fetch_one::<Shard>(StoryQuery::ById(id.clone()))
+            .await?
+            .is_none());
+        assert!(index.hot_set().await?.is_empty());
+
+        // A missing story is a 404
+        let res = admin_index_story_delete(State(state), Path(id.to_base64())).await;
+        assert!(matches!(res, Err(WebError::NotFound)));
+        assert_eq!(StatusCode::NOT_FOUND, res.into_response().status());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admin_recent() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let eval = resources.story_evaluator();
+        let state = admin_state(index.clone(), resources);
+        let recent = |limit| {
+            let state = state.clone();
+            async move {
+                let Html(page) = admin_index_recent(
+                    Extension(CurrentUser {
+                        user: "test".into(),
+                    }),
+                    State(state),
+                    Query(AdminRecentParams { limit }),
+                )
+                .await?;
+                Result::<_, WebError>::Ok(page)
+            }
+        };
+        assert!(!recent(None).await?.contains("Just discovered"));
+
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let url = StoryUrl::parse("http://example.com/").expect("URL");
+        let scrape = HackerNewsStory::new_with_defaults("1", date, "Just discovered", url);
+        index
+            .insert_scrapes(eval.clone(), [scrape.into()].into_iter())
+            .await?;
+        index.flush().await?;
+
+        let page = recent(Some(10)).await?;
+        assert!(page.contains("Just discovered"), "{}", page);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admin_score_preview() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+
+        // Two stories at the top of their sources
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let mut hn = HackerNewsStory::new_with_defaults(
+            "1",
+            date,
+            "From Hacker News",
+            StoryUrl::parse("http://example.com/hn").expect("URL"),
+        );
+        hn.data.position = 1;
+        let mut lobsters = LobstersStory::new_with_defaults(
+            "2",
+            date,
+            "From Lobsters",
+            StoryUrl::parse("http://example.com/lobsters").expect("URL"),
+        );
+        lobsters.data.position = 1;
+        index
+            .insert_scrapes(
+                resources.story_evaluator(),
+                [hn.into(), lobsters.into()].into_iter(),
+            )
+            .await?;
+        index.refresh_hot_set(&Default::default()).await?;
+
+        let state = admin_state(index.clone(), resources.clone());
+        let preview = |hacker_news: f32, lobsters: f32| {
+            let mut config = serde_json::to_value(&resources.config().score).expect("JSON");
+            config["service_rank"]["hacker_news"] = hacker_news.into();
+            config["service_rank"]["lobsters"] = lobsters.into();
+            let config = serde_json::from_value(config).expect("Config");
+            let state = state.clone();
+            async move {
+                let Json(preview) = admin_index_score_preview(State(state), Json(config)).await?;
+                Result::<_, WebError>::Ok(preview)
+            }
+        };
+
+        // Weighting one source over the other puts it first, whatever the current order
+        let titles = |preview: &[ScorePreviewStory]| {
+            preview.iter().map(|s| s.story.title.clone()).collect_vec()
+        };
+        let hn_first = preview(10.0, 0.0).await?;
+        assert_eq!(vec!["From Hacker News", "From Lobsters"], titles(&hn_first));
+        let lobsters_first = preview(0.0, 10.0).await?;
+        assert_eq!(
+            vec!["From Lobsters", "From Hacker News"],
+            titles(&lobsters_first)
+        );
+        assert_eq!(
+            vec![0, 1],
+            lobsters_first.iter().map(|s| s.story.order).collect_vec()
+        );
+
+        // Current ranks are the same in both previews, and match the hot set
+        let current = hot_set(date, &index, &resources.story_evaluator())
+            .await?
+            .into_iter()
+            .map(|s| s.title)
+            .collect_vec();
+        for preview in [hn_first, lobsters_first] {
+            for story in preview {
+                assert_eq!(current[story.current_rank], story.story.title);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_random_story() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let titles = ["Rust", "Python", "Python", "Python", "Python"];
+        insert_stories(&index, &resources, date, &titles).await?;
+
+        let state = (index, resources);
+        let mut urls = HashSet::new();
+        for _ in 0..30 {
+            let Json(story) =
+                api_random(State(state.clone()), Query(RandomParams { tag: None })).await?;
+            urls.insert(story.url);
+        }
+        assert!(urls.len() > 1, "{:?}", urls);
+
+        let redirect = random(
+            State(state.clone()),
+            Query(RandomParams {
+                tag: Some("rust".into()),
+            }),
+        )
+        .await?
+        .into_response();
+        assert_eq!(
+            "http://domain-0.com/",
+            redirect.headers()[hyper::header::LOCATION]
+        );
+
+        let res = api_random(
+            State(state),
+            Query(RandomParams {
+                tag: Some("kotlin".into()),
+            }),
+        )
+        .await;
+        assert!(matches!(res, Err(WebError::NotFound)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_front_page_filter() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let eval = resources.story_evaluator();
+
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let titles = ["Rust is great", "Python is great", "Rust and Python"];
+        insert_stories(&index, &resources, date, &titles).await?;
+
+        let front_page = |config: FrontPageConfig, search: Option<String>| {
+            let (index, eval) = (index.clone(), eval.clone());
+            async move {
+                let stories = front_page_stories(
+                    &index,
+                    &eval,
+                    &config,
+                    search.as_ref(),
+                    config.sort,
+                    date,
+                    30,
+                )
+                .await?;
+                Result::<_, WebError>::Ok(
+                    stories.into_iter().map(|s| s.title).sorted().collect_vec(),
+                )
+            }
+        };
+
+        // No filter shows everything
+        assert_eq!(3, front_page(FrontPageConfig::default(), None).await?.len());
+
+        // A tag filter restricts the front page...
+        let config = || FrontPageConfig {
+            tags: vec!["rust".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["Rust and Python", "Rust is great"],
+            front_page(config(), None).await?
+        );
+        // ... but not an explicit search
+        assert_eq!(
+            vec!["Python is great", "Rust and Python"],
+            front_page(config(), Some("python".into())).await?
+        );
+
+        // A source filter that excludes all stories
+        let config = FrontPageConfig {
+            sources: vec![ScrapeSource::Reddit],
+            ..Default::default()
+        };
+        assert!(front_page(config, None).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_front_page_min_sources() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let eval = resources.story_evaluator();
+
+        // One story from two sources, and two from a single source
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let url = |i| StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+        let scrapes: Vec<TypedScrape> = vec![
+            HackerNewsStory::new_with_defaults("1", date, "Everywhere", url(1)).into(),
+            LobstersStory::new_with_defaults("1", date, "Everywhere", url(1)).into(),
+            HackerNewsStory::new_with_defaults("2", date, "Hacker News only", url(2)).into(),
+            LobstersStory::new_with_defaults("3", date, "Lobsters only", url(3)).into(),
+        ];
+        index
+            .insert_scrapes(eval.clone(), scrapes.into_iter())
+            .await?;
+        index.refresh_hot_set(&Default::default()).await?;
+
+        let front_page = |min_sources: usize, max: usize| {
+            let (index, eval) = (index.clone(), eval.clone());
+            let config = FrontPageConfig {
+                min_sources,
+                ..Default::default()
+            };
+            async move {
+                let stories =
+                    front_page_stories(&index, &eval, &config, None, config.sort, date, max)
+                        .await?;
+                Result::<_, WebError>::Ok(stories.into_iter().map(|s| s.title).collect_vec())
+            }
+        };
+
+        // The default threshold shows everything
+        assert_eq!(3, front_page(1, 30).await?.len());
+        // Single-source stories are dropped when there are enough multi-source stories to fill the page...
+        assert_eq!(vec!["Everywhere"], front_page(2, 1).await?);
+        // ... and fill the page when there aren't
+        let stories = front_page(2, 2).await?;
+        assert_eq!(2, stories.len());
+        assert!(stories.contains(&"Everywhere".to_owned()));
+        assert_eq!(3, front_page(2, 30).await?.len());
+        // No stories qualify, but the page isn't empty
+        assert_eq!(2, front_page(3, 2).await?.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_front_page_max_per_domain() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let eval = resources.story_evaluator();
+
+        // A flood of stories from one domain, scored above the rest
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let mut scrapes: Vec<TypedScrape> = (0..5)
+            .map(|i| {
+                let url = StoryUrl::parse(format!("http://flood.com/{}", i)).expect("URL");
+                HackerNewsStory::new(
+                    format!("flood{}", i),
+                    date,
+                    format!("Flood {}", i),
+                    url,
+                    500 - i,
+                    100,
+                    1,
+                )
+                .into()
+            })
+            .collect();
+        scrapes.extend((0..2).map(|i| {
+            let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+            HackerNewsStory::new_with_defaults(
+                format!("other{}", i),
+                date,
+                format!("Other {}", i),
+                url,
+            )
+            .into()
+        }));
+        index
+            .insert_scrapes(eval.clone(), scrapes.into_iter())
+            .await?;
+        index.refresh_hot_set(&Default::default()).await?;
+
+        let front_page = |max_per_domain: Option<usize>, max: usize| {
+            let (index, eval, resources) = (index.clone(), eval.clone(), resources.clone());
+            let config = FrontPageConfig {
+                max_per_domain,
+                ..Default::default()
+            };
+            async move {
+                let stories =
+                    front_page_stories(&index, &eval, &config, None, config.sort, date, max)
+                        .await?;
+                let stories = render_stories(&resources, stories.iter());
+                let Html(page) = render(
+                    &resources,
+                    "index.html",
+                    context!(top_tags = TOP_TAGS, stories, now = date),
+                )?;
+                Result::<_, WebError>::Ok(page)
+            }
+        };
+
+        // Unlimited by default, so the flood fills the page
+        let page = front_page(None, 4).await?;
+        assert_eq!(4, page.matches(">Flood ").count(), "{}", page);
+        assert!(!page.contains(">Other "), "{}", page);
+
+        // Capped, the other domains are bumped up...
+        let page = front_page(Some(2), 4).await?;
+        assert_eq!(2, page.matches(">Flood ").count(), "{}", page);
+        assert_eq!(2, page.matches(">Other ").count(), "{}", page);
+        // ... and the excess follows them rather than being dropped
+        let page = front_page(Some(2), 30).await?;
+        assert_eq!(5, page.matches(">Flood ").count(), "{}", page);
+        let after_others = &page[page.rfind(">Other ").expect("Other stories")..];
+        assert_eq!(3, after_others.matches(">Flood ").count(), "{}", page);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_front_page_sort() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let eval = resources.story_evaluator();
+
+        // (title, day, points, comments)
+        let stories = [
+            ("Old and talkative", 1, 10, 300),
+            ("Popular", 2, 500, 5),
+            ("Newest", 3, 100, 50),
+        ];
+        let scrapes: Vec<TypedScrape> = stories
+            .iter()
+            .enumerate()
+            .map(|(i, (title, day, points, comments))| {
+                let date = StoryDate::year_month_day(2023, 1, *day).expect("Date");
+                let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                let mut scrape = HackerNewsStory::new_with_defaults(
+                    format!("{}", i),
+                    date,
+                    title.to_string(),
+                    url,
+                );
+                scrape.data.points = *points;
+                scrape.data.comments = *comments;
+                scrape.into()
+            })
+            .collect();
+        index
+            .insert_scrapes(eval.clone(), scrapes.into_iter())
+            .await?;
+        index.refresh_hot_set(&Default::default()).await?;
+
+        let now = StoryDate::year_month_day(2023, 1, 3).expect("Date");
+        let front_page = |sort: &str| {
+            let (index, eval) = (index.clone(), eval.clone());
+            let sort = FrontPageSort::parse(Some(&sort.to_owned()), FrontPageSort::Hot);
+            async move {
+                let stories = front_page_stories(
+                    &index,
+                    &eval,
+                    &FrontPageConfig::default(),
+                    None,
+                    sort?,
+                    now,
+                    30,
+                )
+                .await?;
+                Result::<_, WebError>::Ok(stories.into_iter().map(|s| s.title).collect_vec())
+            }
+        };
+
+        let hot = hot_set(now, &index, &eval)
+            .await?
+            .into_iter()
+            .map(|s| s.title)
+            .collect_vec();
+        assert_eq!(hot, front_page("").await?);
+        assert_eq!(hot, front_page("hot").await?);
+        assert_eq!(
+            vec!["Newest", "Popular", "Old and talkative"],
+            front_page("new").await?
+        );
+        assert_eq!(
+            vec!["Popular", "Newest", "Old and talkative"],
+            front_page("top").await?
+        );
+        assert_eq!(
+            vec!["Old and talkative", "Newest", "Popular"],
+            front_page("comments").await?
+        );
+
+        // Unknown modes are rejected on both the public and admin pages
+        assert!(matches!(
+            front_page("best").await,
+            Err(WebError::BadRequest(_))
+        ));
+        let query = Query(HashMap::from([("sort".to_owned(), "best".to_owned())]));
+        assert!(matches!(
+            root(
+                State((index.clone(), resources.clone())),
+                HeaderMap::new(),
+                query
+            )
+            .await,
+            Err(WebError::BadRequest(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_normalize() -> Result<(), WebError> {
+        let config = SearchConfig {
+            max_length: 10,
+            ..Default::default()
+        };
+        assert_eq!(None, config.normalize(None, |_| false)?);
+        assert_eq!(None, config.normalize(Some(&"   ".into()), |_| false)?);
+        assert_eq!(
+            Some("rust lang".to_owned()),
+            config.normalize(Some(&"  rust \t\n  lang ".into()), |_| false)?
+        );
+
+        // Over-length searches are rejected with a 400 before they reach the index
+        let err = config
+            .normalize(Some(&"rust language".into()), |_| false)
+            .expect_err("Expected the search to be rejected");
+        assert!(matches!(err, WebError::BadRequest(_)));
+        assert_eq!(StatusCode::BAD_REQUEST, err.into_response().status());
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_min_token_length() -> Result<(), WebError> {
+        let config = SearchConfig {
+            min_token_length: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            Some("rust lang".to_owned()),
+            config.normalize(Some(&"a rust is lang".into()), |_| false)?
+        );
+        // Quoted short words and phrases are honored
+        assert_eq!(
+            Some("go is fun".to_owned()),
+            config.normalize(Some(&"\"go\" a \"is fun\"".into()), |_| false)?
+        );
+        assert_eq!(
+            Some("rust c".to_owned()),
+            config.normalize(Some(&"rust \"c".into()), |_| false)?
+        );
+        // Short tags are kept
+        assert_eq!(
+            Some("go".to_owned()),
+            config.normalize(Some(&"go".into()), |tag| tag == "go")?
+        );
+
+        // A search with nothing left is rejected with a message
+        let err = config
+            .normalize(Some(&"a is \"\"".into()), |_| false)
+            .expect_err("Expected the search to be rejected");
+        assert!(matches!(err, WebError::BadRequest(message) if message.contains("quotes")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_query_count() -> Result<(), WebError> {
+        let config = SearchConfig {
+            max_query_count: 10,
+            ..Default::default()
+        };
+        assert_eq!(5, config.clamp_count(5));
+        assert_eq!(10, config.clamp_count(10));
+        assert_eq!(10, config.clamp_count(usize::MAX));
+
+        // An over-cap API request is clamped rather than rejected
+        let (index, resources) = fixture().await?;
+        let max = resources.config().search.max_query_count;
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        index
+            .insert_scrapes(
+                resources.story_evaluator(),
+                (0..max + 5).map(move |i| {
+                    let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+                    HackerNewsStory::new_with_defaults(
+                        i.to_string(),
+                        date,
+                        format!("Title {}", i),
+                        url,
+                    )
+                    .into()
+                }),
+            )
+            .await?;
+        let params = StoriesSinceParams {
+            id: None,
+            ts: None,
+            count: Some(max * 10),
+        };
+        let Json(result) = api_stories_since(State((index, resources)), Query(params)).await?;
+        assert_eq!(max, result.stories.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_front_page_dedupe_titles() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+
+        // The same article under two URLs that don't normalize together, plus the same title on another day
+        let day = |d| StoryDate::year_month_day(2023, 1, d).expect("Date");
+        let scrapes: Vec<TypedScrape> = [
+            (day(1), "http://example.com/rust", "Rust 1.66 released"),
+            (
+                day(1),
+                "http://mirror.example.org/rust",
+                "Rust 1.66 Released!",
+            ),
+            (
+                day(2),
+                "http://example.com/rust-again",
+                "Rust 1.66 released",
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (date, url, title))| {
+            let url = StoryUrl::parse(url).expect("URL");
+            HackerNewsStory::new_with_defaults(format!("{}", i), date, title.to_string(), url)
+                .into()
+        })
+        .collect();
+        index
+            .insert_scrapes(resources.story_evaluator(), scrapes.into_iter())
+            .await?;
+        index.refresh_hot_set(&Default::default()).await?;
+
+        assert!(resources.config().front_page.dedupe_titles);
+        let page = body_text(
+            root(
+                State((index, resources)),
+                HeaderMap::new(),
+                Query(HashMap::new()),
+            )
+            .await?,
+        )
+        .await;
+        assert_eq!(
+            2,
+            page.to_lowercase().matches("rust 1.66 released").count(),
+            "{}",
+            page
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_front_page_content_negotiation() -> Result<(), WebError> {
+        use tower::ServiceExt;
+
+        let (index, resources) = fixture().await?;
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        insert_stories(&index, &resources, date, &["Rust 1.66 released"]).await?;
+
+        let app = front_page_app(index, resources);
+        let request = |accept: Option<&str>| {
+            let mut request = Request::builder().uri("/");
+            if let Some(accept) = accept {
+                request = request.header("Accept", accept);
+            }
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(request.body(Body::empty()).expect("Request"))
+                    .await
+                    .unwrap_infallible();
+                assert_eq!(StatusCode::OK, response.status());
+                let content_type = response.headers()[CONTENT_TYPE]
+                    .to_str()
+                    .expect("Header")
+                    .to_owned();
+                (content_type, body_text(response).await)
+            }
+        };
+
+        for accept in [
+            None,
+            Some("text/html"),
+            Some("*/*"),
+            Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+            Some("image/png"),
+        ] {
+            let (content_type, body) = request(accept).await;
+            assert!(content_type.starts_with("text/html"), "{:?}", accept);
+            assert!(body.contains("Rust 1.66 released"));
+        }
+
+        let (content_type, body) = request(Some("application/json")).await;
+        assert_eq!("application/json", content_type);
+        let stories: Vec<serde_json::Value> = serde_json::from_str(&body)?;
+        assert_eq!(1, stories.len());
+        assert_eq!("Rust 1.66 released", stories[0]["title"]);
+
+        for accept in [
+            "application/rss+xml",
+            "text/html;q=0.5, application/rss+xml",
+        ] {
+            let (content_type, body) = request(Some(accept)).await;
+            assert_eq!("application/rss+xml", content_type);
+            assert!(body.contains("<title>progscrape</title>"), "{}", body);
+            assert_eq!(1, body.matches("<item>").count());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_front_page_format_param() -> Result<(), WebError> {
+        use tower::ServiceExt;
+
+        let (index, resources) = fixture().await?;
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        insert_stories(&index, &resources, date, &["Rust 1.66 released"]).await?;
+
+        let app = front_page_app(index, resources);
+        // The explicit format always wins over the Accept header
+        let request = |uri: &str, accept: &str| {
+            let request = Request::builder()
+                .uri(uri)
+                .header("Accept", accept)
+                .body(Body::empty())
+                .expect("Request");
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap_infallible();
+                let status = response.status();
+                let content_type = response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_owned();
+                (status, content_type, body_text(response).await)
+            }
+        };
+
+        for uri in ["/?format=html", "/?format=html&search=rust"] {
+            let (status, content_type, body) = request(uri, "application/json").await;
+            assert_eq!(StatusCode::OK, status);
+            assert!(content_type.starts_with("text/html"), "{}", uri);
+            assert!(body.contains("Rust 1.66 released"));
+        }
+
+        for uri in ["/?format=json", "/?format=json&search=rust"] {
+            let (status, content_type, body) = request(uri, "application/rss+xml").await;
+            assert_eq!(StatusCode::OK, status);
+            assert_eq!("application/json", content_type, "{}", uri);
+            let stories: Vec<serde_json::Value> = serde_json::from_str(&body)?;
+            assert_eq!(1, stories.len());
+            assert_eq!("Rust 1.66 released", stories[0]["title"]);
+        }
+
+        for uri in ["/?format=rss", "/?format=rss&search=rust"] {
+            let (status, content_type, body) = request(uri, "text/html").await;
+            assert_eq!(StatusCode::OK, status);
+            assert_eq!("application/rss+xml", content_type, "{}", uri);
+            assert_eq!(1, body.matches("<item>").count());
+        }
+
+        // An empty format falls back to the Accept header
+        let (status, content_type, _) = request("/?format=", "application/json").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!("application/json", content_type);
+
+        for uri in ["/?format=xml", "/?format=JSON&search=rust"] {
+            let (status, _, _) = request(uri, "text/html").await;
+            assert_eq!(StatusCode::BAD_REQUEST, status, "{}", uri);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_conditional_fetch() -> Result<(), WebError> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // A server that returns the same Reddit page with an ETag, and a 304 if the client already has it
+        let body = std::fs::read_to_string("../scrapers/testdata/reddit-prog1.json")?;
+        let requests = Arc::new(AtomicUsize::default());
+        let app = Router::new().route(
+            "/r/programming/.json",
+            get({
+                let requests = requests.clone();
+                move |headers: HeaderMap| async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    if headers.get("If-None-Match").map(|v| v.as_bytes()) == Some(b"\"v1\"") {
+                        StatusCode::NOT_MODIFIED.into_response()
+                    } else {
+                        ([("ETag", "\"v1\"")], body).into_response()
+                    }
+                }
+            }),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/r/programming/.json", listener.local_addr()?);
+        tokio::spawn(axum::Server::from_tcp(listener)?.serve(app.into_make_service()));
+
+        let (index, resources) = fixture().await?;
+        let validators = HttpValidatorCache::default();
+        let scrapers = resources.scrapers();
+        let client = resources.http_client();
+        let scrape = || {
+            fetch_and_scrape(
+                &client,
+                &scrapers,
+                None,
+                Some(&validators),
+                UTF_8,
+                ScrapeSource::Reddit,
+                vec![url.clone()],
+            )
+        };
+
+        let scrapes = scrape().await?;
+        assert!(matches!(&scrapes[&url], ScraperHttpResult::Ok(_, v) if !v.is_empty()));
+        ingest_scrapes(&index, resources.story_evaluator(), &scrapes).await?;
+        let count = index.story_count().await?.total.story_count;
+        assert!(count > 0);
+
+        // The second fetch is conditional, and there's nothing new to ingest
+        let scrapes = scrape().await?;
+        assert!(matches!(&scrapes[&url], ScraperHttpResult::NotModified));
+        ingest_scrapes(&index, resources.story_evaluator(), &scrapes).await?;
+        assert_eq!(count, index.story_count().await?.total.story_count);
+        assert_eq!(2, requests.load(Ordering::SeqCst));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_compressed() -> Result<(), WebError> {
+        use flate2::{write::GzEncoder, write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let body = std::fs::read_to_string("../scrapers/testdata/reddit-prog1.json")?;
+        let mut gzip = GzEncoder::new(vec![], Compression::default());
+        gzip.write_all(body.as_bytes())?;
+        let gzip = gzip.finish()?;
+        let mut deflate = ZlibEncoder::new(vec![], Compression::default());
+        deflate.write_all(body.as_bytes())?;
+        let deflate = deflate.finish()?;
+
+        // Compressed bodies are only sent to clients that accept them
+        let accepts = |headers: &HeaderMap| {
+            headers
+                .get("Accept-Encoding")
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.contains("gzip") && value.contains("deflate"))
+        };
+        let app = Router::new()
+            .route(
+                "/gzip",
+                get({
+                    let gzip = gzip.clone();
+                    move |headers: HeaderMap| async move {
+                        assert!(accepts(&headers));
+                        ([("Content-Encoding", "gzip")], gzip).into_response()
+                    }
+                }),
+            )
+            .route(
+                "/deflate",
+                get(move |headers: HeaderMap| async move {
+                    assert!(accepts(&headers));
+                    ([("Content-Encoding", "deflate")], deflate).into_response()
+                }),
+            )
+            // Gzipped, but without saying so
+            .route("/unlabelled", get(move || async move { gzip }));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let base = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(axum::Server::from_tcp(listener)?.serve(app.into_make_service()));
+
+        let scrapers = Scrapers::new(&config().scrape);
+        let client = reqwest::Client::new();
+        for path in ["/gzip", "/deflate", "/unlabelled"] {
+            let url = format!("{}{}", base, path);
+            match fetch(&client, None, None, UTF_8, &url).await? {
+                ScraperHttpResponseInput::Ok(decoded) => assert_eq!(body, decoded),
+                _ => panic!("Expected a body for {}", path),
+            }
+            let scrapes = fetch_and_scrape(
+                &client,
+                &scrapers,
+                None,
+                None,
+                UTF_8,
+                ScrapeSource::Reddit,
+                vec![url.clone()],
+            )
+            .await?;
+            assert!(
+                matches!(&scrapes[&url], ScraperHttpResult::Ok(_, v) if !v.is_empty()),
+                "Failed to scrape {}",
+                path
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_charset() -> Result<(), WebError> {
+        let app = Router::new().route(
+            "/feed",
+            get(|| async {
+                (
+                    [(CONTENT_TYPE, "text/plain; charset=windows-1252")],
+                    b"Caf\xe9 \x93quoted\x94".as_slice(),
+                )
+            }),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/feed", listener.local_addr()?);
+        tokio::spawn(axum::Server::from_tcp(listener)?.serve(app.into_make_service()));
+
+        let client = reqwest::Client::new();
+        assert!(matches!(
+            fetch(&client, None, None, UTF_8, &url).await?,
+            ScraperHttpResponseInput::Ok(body) if body == "Café “quoted”"
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_proxy() -> Result<(), WebError> {
+        // A server that echoes the request URI, which is only absolute when the request was made through a proxy
+        let app = Router::new().fallback(|uri: hyper::Uri| async move { uri.to_string() });
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(axum::Server::from_tcp(listener)?.serve(app.into_make_service()));
+
+        let config = ProxyConfig {
+            url: Some(format!("http://{}", addr)),
+            no_proxy: vec!["127.0.0.1".into()],
+        };
+        let client = config.create_client()?;
+        let body = |url: String| {
+            let client = client.clone();
+            async move {
+                match fetch(&client, None, None, UTF_8, &url).await? {
+                    ScraperHttpResponseInput::Ok(body) => Ok(body),
+                    _ => Err(WebError::NotFound),
+                }
+            }
+        };
+
+        assert_eq!(
+            "http://proxied.invalid/feed",
+            body("http://proxied.invalid/feed".into()).await?
+        );
+        // Hosts in the no-proxy list are fetched directly
+        assert_eq!("/feed", body(format!("http://{}/feed", addr)).await?);
+
+        // Invalid proxy URLs are rejected
+        let config = ProxyConfig {
+            url: Some("not a url".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.create_client(),
+            Err(WebError::URLParseError(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_http2() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let serve = |http2: bool| -> Result<String, WebError> {
+            let app = front_page_app(index.clone(), resources.clone());
+            let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+            let url = format!("http://{}/", listener.local_addr()?);
+            tokio::spawn(
+                ServerConfig { http2 }
+                    .server(listener)?
+                    .serve(app.into_make_service()),
+            );
+            Ok(url)
+        };
+        let h2c = reqwest::Client::builder().http2_prior_knowledge().build()?;
+
+        // The front page is served over h2c when enabled, and HTTP/1.1 still works
+        let url = serve(true)?;
+        let response = h2c.get(&url).send().await?;
+        assert_eq!(reqwest::Version::HTTP_2, response.version());
+        assert!(response.status().is_success());
+        assert!(response.text().await?.contains("<html"));
+        let response = reqwest::get(&url).await?;
+        assert_eq!(reqwest::Version::HTTP_11, response.version());
+        assert!(response.status().is_success());
+
+        // By default, only HTTP/1.1 is spoken
+        let url = serve(false)?;
+        assert!(h2c.get(&url).send().await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_stories_since() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+
+        let scrape = |i: i64| -> TypedScrape {
+            let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+            let date = StoryDate::from_seconds(1_672_531_200 + i * 60).expect("Date");
+            HackerNewsStory::new_with_defaults(format!("{}", i), date, format!("Title {}", i), url)
+                .into()
+        };
+        let since = |id: Option<String>, ts: Option<i64>| {
+            let (index, resources) = (index.clone(), resources.clone());
+            async move {
+                let params = StoriesSinceParams {
+                    id,
+                    ts,
+                    count: None,
+                };
+                api_stories_since(State((index, resources)), Query(params))
+                    .await
+                    .map(|Json(x)| x)
+            }
+        };
+
+        index
+            .insert_scrapes(resources.story_evaluator(), (0..10).map(scrape))
+            .await?;
+        let first = since(None, Some(0)).await?;
+        assert_eq!(10, first.stories.len());
+
+        index
+            .insert_scrapes(resources.story_evaluator(), (10..15).map(scrape))
+            .await?;
+        let second = since(first.next.id.clone(), None).await?;
+        assert_eq!(
+            (10..15).map(|i| format!("Title {}", i)).collect_vec(),
+            second.stories.iter().map(|s| s.title.clone()).collect_vec()
+        );
+
+        // Nothing new, so the high-water mark stays put
+        let third = since(second.next.id.clone(), None).await?;
+        assert!(third.stories.is_empty());
+        assert_eq!(second.next.id, third.next.id);
+        assert_eq!(second.next.ts, third.next.ts);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_robots_txt() -> Result<(), WebError> {
+        use tower::ServiceExt;
+
+        let absolute_url =
+            AbsoluteUrlFilter::new(url::Url::parse("https://example.com/").expect("URL"));
+        let robots = RobotsConfig::default().render(&absolute_url);
+        assert!(robots.contains("User-agent: *\n"));
+        assert!(robots.contains("Disallow: /admin/\n"));
+        assert!(robots.contains("Disallow: /api/\n"));
+        assert!(robots.contains("Allow: /\n"));
+
+        // The configured rules are served, rather than the static files
+        let resources = resource::start_watcher("../resource").await?;
+        let app = Router::new()
+            .route("/robots.txt", get(robots_txt))
+            .route("/:file", get(serve_static_files_well_known))
+            .with_state(resources);
+        let request = Request::get("/robots.txt")
+            .body(Body::empty())
+            .expect("Request");
+        let response = app.oneshot(request).await.unwrap_infallible();
+        assert_eq!(StatusCode::OK, response.status());
+        let robots = body_text(response).await;
+        assert!(robots.contains("Disallow: /admin/\n"));
+        assert!(robots.contains("Sitemap: https://www.progscrape.com/sitemap.xml\n"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() -> Result<(), WebError> {
+        use tower::ServiceExt;
+
+        async fn slow() -> &'static str {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            "slow"
+        }
+        let routes = Router::new()
+            .route("/fast", get(|| async { "fast" }))
+            .route("/slow", get(slow));
+        let app = with_request_timeout(routes, Some(Duration::from_millis(50)))
+            .route("/exempt", get(slow));
+        let status = |path: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::get(path).body(Body::empty()).expect("Request");
+                app.oneshot(request).await.unwrap_infallible().status()
+            }
+        };
+
+        assert_eq!(StatusCode::OK, status("/fast").await);
+        let start = Instant::now();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status("/slow").await);
+        assert!(start.elapsed() < Duration::from_millis(500));
+        // Routes added after the timeout aren't cut off
+        assert_eq!(StatusCode::OK, status("/exempt").await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_json_errors() -> Result<(), WebError> {
+        use tower::ServiceExt;
+
+        let (index, resources) = fixture().await?;
+        let app = Router::new()
+            .route("/api/random", get(api_random))
+            .with_state((index, resources))
+            .route("/page", get(|| async { Err::<(), _>(WebError::NotFound) }))
+            .fallback(handle_404)
+            .layer(middleware::from_fn(json_errors));
+        let request = |uri: &str, accept: Option<&str>| {
+            let mut req = Request::builder().uri(uri);
+            if let Some(accept) = accept {
+                req = req.header("Accept", accept);
+            }
+            app.clone()
+                .oneshot(req.body(Body::empty()).expect("Request"))
+        };
+        let json = |response: Response| async move {
+            assert_eq!(
+                Some("application/json"),
+                response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+            );
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .expect("Body");
+            Result::<_, WebError>::Ok(serde_json::from_slice::<serde_json::Value>(&body)?)
+        };
+
+        // Both an API error and an unknown API route are JSON
+        for uri in ["/api/random", "/api/missing"] {
+            let response = request(uri, None).await.unwrap_infallible();
+            assert_eq!(StatusCode::NOT_FOUND, response.status());
+            let body = json(response).await?;
+            assert_eq!("not_found", body["code"], "{}", uri);
+            assert_eq!("Item not found", body["error"], "{}", uri);
+        }
+
+        // Other routes are only JSON if the client asks for it
+        let response = request("/page", None).await.unwrap_infallible();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+        assert_ne!(
+            Some("application/json"),
+            response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+        );
+        let response = request("/page", Some("application/json"))
+            .await
+            .unwrap_infallible();
+        assert_eq!("not_found", json(response).await?["code"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admin_story_invalid_id() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let state = admin_state(index, resources);
+        let story = |id: String| {
+            admin_status_story(
+                Extension(CurrentUser {
+                    user: "test".into(),
+                }),
+                State(state.clone()),
+                Path(id),
+            )
+        };
+
+        // Garbage is a bad request
+        let res = story("!!not-an-id!!".into()).await;
+        assert!(matches!(res, Err(WebError::BadRequest(_))));
+        assert_eq!(StatusCode::BAD_REQUEST, res.into_response().status());
+
+        // A well-formed ID for a story that isn't indexed is missing
+        let url = StoryUrl::parse("http://example.com/").expect("URL");
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let id = StoryIdentifier::new(date, url.normalization());
+        let res = story(id.to_base64()).await;
+        assert!(matches!(res, Err(WebError::NotFound)));
+        assert_eq!(StatusCode::NOT_FOUND, res.into_response().status());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_domain_alias() -> Result<(), WebError> {
+        let (index, resources) = fixture().await?;
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let scrapes = [
+            "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+            "https://www.example.com/rust",
+        ]
+        .into_iter()
+        .enumerate()
+        .map(move |(i, url)| {
+            let url = StoryUrl::parse(url).expect("URL");
+            HackerNewsStory::new_with_defaults(i.to_string(), date, "Rust".into(), url).into()
+        });
+        index
+            .insert_scrapes(resources.story_evaluator(), scrapes)
+            .await?;
+        let stories = index.fetch::<Shard>(StoryQuery::FrontPage(), 10).await?;
+        let stories = render_stories(&resources, stories.iter())
+            .into_iter()
+            .map(|story| (story.domain.clone(), story))
+            .collect::<HashMap<_, _>>();
+
+        // The alias is only a label: the URL and domain tag are unchanged
+        let wikipedia = &stories["wikipedia"];
+        assert_eq!(
+            "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+            wikipedia.url
+        );
+        assert_eq!("en.wikipedia.org", wikipedia.tags[0]);
+
+        // Unaliased domains are displayed without the www prefix
+        assert_eq!("https://www.example.com/rust", stories["example.com"].url);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scrape_test_results_order() {
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date");
+        let scrape = |i: usize| {
+            let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+            HackerNewsStory::new_with_defaults(i.to_string(), date, "Title".into(), url).into()
+        };
+        let urls = [
+            "http://c.com/",
+            "http://a.com/",
+            "http://d.com/",
+            "http://b.com/",
+        ];
+        let scrapes = urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| {
+                let result = if i == 2 {
+                    ScraperHttpResult::NotModified
+                } else {
+                    ScraperHttpResult::Ok("".into(), (0..i).map(scrape).collect())
+                };
+                (url.to_string(), result)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let results = scrape_test_results(scrapes);
+        assert_eq!(
+            vec![
+                "http://a.com/",
+                "http://b.com/",
+                "http://c.com/",
+                "http://d.com/"
+            ],
+            results.iter().map(|r| r.url.as_str()).collect_vec()
+        );
+        assert_eq!(
+            vec![1, 3, 0, 0],
+            results.iter().map(|r| r.count).collect_vec()
+        );
+    }
 }